@@ -0,0 +1,66 @@
+//! Benchmarks of the generator on large meshes. Compare the results with and without `parallel`
+//! feature to see how the generation scales with the amount of CPU cores:
+//!
+//! ```text
+//! cargo bench --bench generate
+//! cargo bench --bench generate --features parallel
+//! ```
+
+use criterion::{criterion_group, criterion_main, BenchmarkId, Criterion, Throughput};
+use uvgen::{test_meshes, MeshInput, UvGenOptions};
+
+fn generate_uvs(c: &mut Criterion) {
+    let mut group = c.benchmark_group("generate_uvs");
+    group.sample_size(10);
+    for (name, mesh) in [
+        ("uv_sphere", test_meshes::uv_sphere(256, 128)),
+        ("torus", test_meshes::torus(1.0, 0.3, 256, 128)),
+        ("cylinder", test_meshes::cylinder(0.5, 2.0, 16384)),
+    ] {
+        group.throughput(Throughput::Elements(mesh.triangles.len() as u64));
+        for deterministic in [false, true] {
+            let options = UvGenOptions {
+                deterministic,
+                ..Default::default()
+            };
+            let mode = if deterministic {
+                "deterministic"
+            } else {
+                "default"
+            };
+            group.bench_with_input(BenchmarkId::new(name, mode), &mesh, |b, mesh| {
+                b.iter(|| {
+                    uvgen::generate_uvs_with_options(
+                        mesh.vertices.iter().cloned(),
+                        mesh.triangles.iter().cloned(),
+                        &options,
+                    )
+                    .unwrap()
+                })
+            });
+        }
+    }
+    group.finish();
+}
+
+fn generate_uvs_batch(c: &mut Criterion) {
+    let inputs = (0..64)
+        .map(|i| {
+            let mesh = test_meshes::uv_sphere(16 + i, 8 + i / 2);
+            MeshInput {
+                vertices: mesh.vertices,
+                triangles: mesh.triangles,
+            }
+        })
+        .collect::<Vec<_>>();
+    let mut group = c.benchmark_group("generate_uvs_batch");
+    group.sample_size(10);
+    group.throughput(Throughput::Elements(inputs.len() as u64));
+    group.bench_function("uv_spheres", |b| {
+        b.iter(|| uvgen::generate_uvs_batch(inputs.clone(), &Default::default()))
+    });
+    group.finish();
+}
+
+criterion_group!(benches, generate_uvs, generate_uvs_batch);
+criterion_main!(benches);
@@ -0,0 +1,306 @@
+//! Runs the same meshes through uvgen and xatlas and reports time, amount of charts and quality
+//! metrics (see [`uvgen::uv_metrics`]) of both, so the generators could be compared objectively.
+//! Requires `xatlas` feature and a shared library of xatlas with its C API (`xatlas_c.h`), which
+//! is loaded at runtime, so nothing has to be linked at build time:
+//!
+//! ```text
+//! cargo run --release --features xatlas --example compare_xatlas -- libxatlas.so [mesh.obj ...]
+//! ```
+//!
+//! A few test meshes (see [`uvgen::test_meshes`]) are compared, if no meshes are given. Both
+//! generators run with their default options, except that uvgen uses the spacing of one texel of
+//! the xatlas atlas for every mesh.
+
+use libloading::Library;
+use nalgebra::{Vector2, Vector3};
+use std::{
+    ffi::c_void,
+    path::Path,
+    time::{Duration, Instant},
+};
+
+/// `xatlasVertex`.
+#[repr(C)]
+#[allow(dead_code)] // Every field of the C declaration must be present.
+struct XatlasVertex {
+    atlas_index: i32,
+    chart_index: i32,
+    uv: [f32; 2],
+    xref: u32,
+}
+
+/// `xatlasMesh`.
+#[repr(C)]
+#[allow(dead_code)] // Every field of the C declaration must be present.
+struct XatlasMesh {
+    chart_array: *mut c_void,
+    index_array: *mut u32,
+    vertex_array: *mut XatlasVertex,
+    chart_count: u32,
+    index_count: u32,
+    vertex_count: u32,
+}
+
+/// `xatlasAtlas`.
+#[repr(C)]
+#[allow(dead_code)] // Every field of the C declaration must be present.
+struct XatlasAtlas {
+    image: *mut u32,
+    meshes: *mut XatlasMesh,
+    utilization: *mut f32,
+    width: u32,
+    height: u32,
+    atlas_count: u32,
+    chart_count: u32,
+    mesh_count: u32,
+    texels_per_unit: f32,
+}
+
+/// `xatlasMeshDecl`.
+#[repr(C)]
+#[allow(dead_code)] // Every field of the C declaration must be present.
+struct XatlasMeshDecl {
+    vertex_position_data: *const c_void,
+    vertex_normal_data: *const c_void,
+    vertex_uv_data: *const c_void,
+    index_data: *const c_void,
+    face_ignore_data: *const bool,
+    face_material_data: *const u32,
+    face_vertex_count: *const u8,
+    vertex_count: u32,
+    vertex_position_stride: u32,
+    vertex_normal_stride: u32,
+    vertex_uv_stride: u32,
+    index_count: u32,
+    index_offset: i32,
+    face_count: u32,
+    index_format: u32,
+    epsilon: f32,
+}
+
+/// `XATLAS_INDEX_FORMAT_UINT32`.
+const XATLAS_INDEX_FORMAT_UINT32: u32 = 1;
+
+/// `XATLAS_ADD_MESH_ERROR_SUCCESS`.
+const XATLAS_ADD_MESH_ERROR_SUCCESS: u32 = 0;
+
+type CreateFn = unsafe extern "C" fn() -> *mut XatlasAtlas;
+type DestroyFn = unsafe extern "C" fn(*mut XatlasAtlas);
+type MeshDeclInitFn = unsafe extern "C" fn(*mut XatlasMeshDecl);
+type AddMeshFn = unsafe extern "C" fn(*mut XatlasAtlas, *const XatlasMeshDecl, u32) -> u32;
+// Null options make xatlas use its default ones.
+type GenerateFn = unsafe extern "C" fn(*mut XatlasAtlas, *const c_void, *const c_void);
+
+struct Mesh {
+    name: String,
+    vertices: Vec<Vector3<f32>>,
+    triangles: Vec<[u32; 3]>,
+}
+
+/// Texture coordinates of a mesh in the layout of [`uvgen::uv_metrics`].
+struct Unwrap {
+    sources: Vec<u32>,
+    triangles: Vec<[u32; 3]>,
+    uvs: Vec<Vector2<f32>>,
+    charts: usize,
+    time: Duration,
+}
+
+/// Loads every model of the file as a separate mesh.
+fn load_obj(path: &Path) -> Result<Vec<Mesh>, String> {
+    let options = tobj::LoadOptions {
+        triangulate: true,
+        ..Default::default()
+    };
+    let (models, _) =
+        tobj::load_obj(path, &options).map_err(|e| format!("unable to load {path:?}: {e}"))?;
+    Ok(models
+        .into_iter()
+        .map(|model| Mesh {
+            name: format!("{}/{}", path.display(), model.name),
+            vertices: model
+                .mesh
+                .positions
+                .chunks_exact(3)
+                .map(|p| Vector3::new(p[0], p[1], p[2]))
+                .collect(),
+            triangles: model
+                .mesh
+                .indices
+                .chunks_exact(3)
+                .map(|t| [t[0], t[1], t[2]])
+                .collect(),
+        })
+        .collect())
+}
+
+fn test_meshes() -> Vec<Mesh> {
+    use uvgen::test_meshes::*;
+    [
+        ("cube", cube()),
+        ("uv_sphere", uv_sphere(32, 16)),
+        ("torus", torus(1.0, 0.3, 32, 16)),
+        ("cylinder", cylinder(0.5, 2.0, 32)),
+        ("stairs", stairs(16)),
+    ]
+    .into_iter()
+    .map(|(name, mesh)| Mesh {
+        name: name.to_string(),
+        vertices: mesh.vertices,
+        triangles: mesh.triangles,
+    })
+    .collect()
+}
+
+/// Unwraps the mesh with xatlas. Returns the result along with the size of the atlas.
+fn run_xatlas(library: &Library, mesh: &Mesh) -> Result<(Unwrap, Vector2<u32>), String> {
+    let symbol_error = |e: libloading::Error| format!("xatlas C API is not found: {e}");
+    // SAFETY: the signatures match the declarations of `xatlas_c.h`, the declaration of the mesh
+    // references buffers that outlive the call to `xatlasAddMesh`, which copies them, and the
+    // output of the atlas is copied before the atlas is destroyed.
+    unsafe {
+        let create = library
+            .get::<CreateFn>(b"xatlasCreate\0")
+            .map_err(symbol_error)?;
+        let destroy = library
+            .get::<DestroyFn>(b"xatlasDestroy\0")
+            .map_err(symbol_error)?;
+        let mesh_decl_init = library
+            .get::<MeshDeclInitFn>(b"xatlasMeshDeclInit\0")
+            .map_err(symbol_error)?;
+        let add_mesh = library
+            .get::<AddMeshFn>(b"xatlasAddMesh\0")
+            .map_err(symbol_error)?;
+        let generate = library
+            .get::<GenerateFn>(b"xatlasGenerate\0")
+            .map_err(symbol_error)?;
+
+        let mut decl = std::mem::MaybeUninit::<XatlasMeshDecl>::zeroed();
+        mesh_decl_init(decl.as_mut_ptr());
+        let mut decl = decl.assume_init();
+        decl.vertex_position_data = mesh.vertices.as_ptr().cast();
+        decl.vertex_count = mesh.vertices.len() as u32;
+        decl.vertex_position_stride = std::mem::size_of::<Vector3<f32>>() as u32;
+        decl.index_data = mesh.triangles.as_ptr().cast();
+        decl.index_count = 3 * mesh.triangles.len() as u32;
+        decl.index_format = XATLAS_INDEX_FORMAT_UINT32;
+
+        let start = Instant::now();
+        let atlas = create();
+        let error = add_mesh(atlas, &decl, 1);
+        if error != XATLAS_ADD_MESH_ERROR_SUCCESS {
+            destroy(atlas);
+            return Err(format!("xatlas rejected the mesh, error {error}"));
+        }
+        generate(atlas, std::ptr::null(), std::ptr::null());
+        let time = start.elapsed();
+
+        let result = &*atlas;
+        let size = Vector2::new(result.width, result.height);
+        let output = &*result.meshes;
+        let vertices =
+            std::slice::from_raw_parts(output.vertex_array, output.vertex_count as usize);
+        let indices = std::slice::from_raw_parts(output.index_array, output.index_count as usize);
+        let scale = size.map(|v| 1.0 / v.max(1) as f32);
+        let unwrap = Unwrap {
+            sources: vertices.iter().map(|v| v.xref).collect(),
+            triangles: indices
+                .chunks_exact(3)
+                .map(|t| [t[0], t[1], t[2]])
+                .collect(),
+            uvs: vertices
+                .iter()
+                .map(|v| Vector2::from(v.uv).component_mul(&scale))
+                .collect(),
+            charts: result.chart_count as usize,
+            time,
+        };
+        destroy(atlas);
+        Ok((unwrap, size))
+    }
+}
+
+/// Unwraps the mesh with uvgen, using spacing of one texel of the atlas of the given size.
+fn run_uvgen(mesh: &Mesh, atlas_size: Vector2<u32>) -> Result<Unwrap, String> {
+    let start = Instant::now();
+    let patch = uvgen::try_generate_uvs_with_options(
+        mesh.vertices.iter().cloned(),
+        mesh.triangles.iter().cloned(),
+        &uvgen::UvGenOptions {
+            spacing: 1.0 / atlas_size.max().max(1) as f32,
+            ..Default::default()
+        },
+    )
+    .map_err(|e| e.to_string())?;
+    let time = start.elapsed();
+    Ok(Unwrap {
+        sources: (0..patch.second_tex_coords.len() as u32)
+            .map(|index| patch.source_vertex(index).unwrap_or(u32::MAX))
+            .collect(),
+        triangles: patch.triangles,
+        uvs: patch.second_tex_coords,
+        charts: patch.pack_stats.chart_count,
+        time,
+    })
+}
+
+fn print_row(mesh: &Mesh, generator: &str, unwrap: &Unwrap) {
+    let metrics = uvgen::uv_metrics(
+        &mesh.vertices,
+        &unwrap.sources,
+        &unwrap.triangles,
+        &unwrap.uvs,
+    );
+    println!(
+        "{:<24} {:<8} {:>10.2} {:>7} {:>10.3} {:>7} {:>12.3} {:>8.3} {:>11.3}",
+        mesh.name,
+        generator,
+        unwrap.time.as_secs_f64() * 1000.0,
+        unwrap.charts,
+        metrics.occupancy,
+        metrics.seam_count,
+        metrics.seam_length,
+        metrics.stretch,
+        metrics.max_anisotropy
+    );
+}
+
+fn main() -> Result<(), String> {
+    let mut args = std::env::args().skip(1);
+    let library_path = args
+        .next()
+        .ok_or("usage: compare_xatlas <xatlas shared library> [mesh.obj ...]")?;
+    // SAFETY: loading a library runs its initialization code, xatlas has no special one.
+    let library = unsafe { Library::new(&library_path) }
+        .map_err(|e| format!("unable to load {library_path}: {e}"))?;
+
+    let mut meshes = Vec::new();
+    for path in args {
+        meshes.extend(load_obj(Path::new(&path))?);
+    }
+    if meshes.is_empty() {
+        meshes = test_meshes();
+    }
+
+    println!(
+        "{:<24} {:<8} {:>10} {:>7} {:>10} {:>7} {:>12} {:>8} {:>11}",
+        "mesh",
+        "unwrap",
+        "time (ms)",
+        "charts",
+        "occupancy",
+        "seams",
+        "seam length",
+        "stretch",
+        "anisotropy"
+    );
+    for mesh in meshes.iter() {
+        let (xatlas, atlas_size) = run_xatlas(&library, mesh)?;
+        match run_uvgen(mesh, atlas_size) {
+            Ok(uvgen) => print_row(mesh, "uvgen", &uvgen),
+            Err(error) => println!("{:<24} {:<8} failed: {error}", mesh.name, "uvgen"),
+        }
+        print_row(mesh, "xatlas", &xatlas);
+    }
+    Ok(())
+}
@@ -0,0 +1,264 @@
+//! Loads a Wavefront OBJ mesh, generates UVs for it and shows the mesh (colored by charts) and the
+//! atlas side by side in a window. This is the fastest way to evaluate quality of the generator on
+//! your own meshes. Requires `viewer` feature:
+//!
+//! ```text
+//! cargo run --release --features viewer --example viewer -- mesh.obj [spacing]
+//! ```
+//!
+//! A cube (see [`uvgen::test_meshes::cube`]) is shown, if no mesh is given. Drag the mesh with the
+//! left mouse button (or use the arrow keys) to rotate it, use the mouse wheel to zoom, press
+//! `Escape` to quit. Everything is drawn by a tiny software rasterizer into a `minifb` window.
+
+use minifb::{Key, KeyRepeat, MouseButton, MouseMode, Window, WindowOptions};
+use nalgebra::{Rotation3, Vector2, Vector3};
+use std::path::Path;
+
+/// Size of each half of the window.
+const SIZE: usize = 512;
+
+/// Color of the background, `0RGB`.
+const BACKGROUND: u32 = 0x202020;
+
+struct Mesh {
+    vertices: Vec<Vector3<f32>>,
+    triangles: Vec<[u32; 3]>,
+}
+
+/// Loads positions and triangles of every model of the file. Polygons are triangulated,
+/// everything except positions and faces is ignored.
+fn load_obj(path: &Path) -> Result<Mesh, String> {
+    let options = tobj::LoadOptions {
+        triangulate: true,
+        ..Default::default()
+    };
+    let (models, _) =
+        tobj::load_obj(path, &options).map_err(|e| format!("unable to load {path:?}: {e}"))?;
+    let mut mesh = Mesh {
+        vertices: Vec::new(),
+        triangles: Vec::new(),
+    };
+    for model in models {
+        let offset = mesh.vertices.len() as u32;
+        mesh.vertices.extend(
+            model
+                .mesh
+                .positions
+                .chunks_exact(3)
+                .map(|p| Vector3::new(p[0], p[1], p[2])),
+        );
+        mesh.triangles.extend(
+            model
+                .mesh
+                .indices
+                .chunks_exact(3)
+                .map(|t| [t[0] + offset, t[1] + offset, t[2] + offset]),
+        );
+    }
+    Ok(mesh)
+}
+
+/// Frame buffer of the window with a depth buffer.
+struct Frame {
+    pixels: Vec<u32>,
+    depth: Vec<f32>,
+}
+
+impl Frame {
+    fn new() -> Self {
+        Self {
+            pixels: vec![BACKGROUND; 2 * SIZE * SIZE],
+            depth: vec![f32::INFINITY; 2 * SIZE * SIZE],
+        }
+    }
+
+    /// Clears the given half of the frame.
+    fn clear(&mut self, half: usize) {
+        for y in 0..SIZE {
+            let row = y * 2 * SIZE + half * SIZE;
+            self.pixels[row..row + SIZE].fill(BACKGROUND);
+            self.depth[row..row + SIZE].fill(f32::INFINITY);
+        }
+    }
+
+    /// Fills the triangle given in pixel coordinates of the given half of the frame, `depth` of
+    /// the corners is used to hide occluded triangles.
+    fn fill(&mut self, half: usize, points: [Vector2<f32>; 3], depth: [f32; 3], color: u32) {
+        let [a, b, c] = points;
+        let area = (b - a).perp(&(c - a));
+        if area.abs() <= f32::EPSILON {
+            return;
+        }
+        let min = a.inf(&b).inf(&c).map(|v| v.floor().max(0.0) as usize);
+        let max = a
+            .sup(&b)
+            .sup(&c)
+            .map(|v| (v.ceil().max(0.0) as usize).min(SIZE));
+        for y in min.y..max.y {
+            for x in min.x..max.x {
+                let p = Vector2::new(x as f32 + 0.5, y as f32 + 0.5);
+                let wa = (c - b).perp(&(p - b)) / area;
+                let wb = (a - c).perp(&(p - c)) / area;
+                let wc = 1.0 - wa - wb;
+                if wa < 0.0 || wb < 0.0 || wc < 0.0 {
+                    continue;
+                }
+                let z = wa * depth[0] + wb * depth[1] + wc * depth[2];
+                let index = y * 2 * SIZE + half * SIZE + x;
+                if z < self.depth[index] {
+                    self.depth[index] = z;
+                    self.pixels[index] = color;
+                }
+            }
+        }
+    }
+}
+
+/// Packs the color into `0RGB` format, multiplying it by the given brightness.
+fn pack_color([r, g, b, _]: [u8; 4], brightness: f32) -> u32 {
+    let channel = |c: u8| (c as f32 * brightness).clamp(0.0, 255.0) as u32;
+    (channel(r) << 16) | (channel(g) << 8) | channel(b)
+}
+
+fn main() -> Result<(), String> {
+    let args = std::env::args().skip(1).collect::<Vec<_>>();
+    let Mesh {
+        vertices,
+        triangles,
+    } = match args.first() {
+        Some(path) => load_obj(Path::new(path))?,
+        None => {
+            let cube = uvgen::test_meshes::cube();
+            Mesh {
+                vertices: cube.vertices,
+                triangles: cube.triangles,
+            }
+        }
+    };
+    let spacing = match args.get(1) {
+        Some(spacing) => spacing.parse().map_err(|_| "invalid spacing")?,
+        None => 0.005,
+    };
+
+    let patch = uvgen::generate_uvs_with_options(
+        vertices.iter().cloned(),
+        triangles.iter().cloned(),
+        &uvgen::UvGenOptions {
+            spacing,
+            chart_colors: true,
+            ..Default::default()
+        },
+    )
+    .ok_or("unable to generate UVs")?;
+    println!(
+        "{} triangles, {} charts, {} added vertices, scale {}",
+        patch.triangles.len(),
+        patch.pack_stats.chart_count,
+        patch.additional_vertices.len(),
+        patch.pack_stats.scale
+    );
+    println!("{:?}", patch.uv_metrics(&vertices));
+
+    let position = |index: u32| {
+        patch
+            .source_vertex(index)
+            .and_then(|source| vertices.get(source as usize))
+            .cloned()
+            .unwrap_or_default()
+    };
+    let color = |triangle: &[u32; 3]| patch.chart_colors[triangle[0] as usize];
+
+    // The mesh is rotated around the center of its bounds and fitted by its bounding sphere.
+    let (min, max) = vertices.iter().fold(
+        (Vector3::repeat(f32::MAX), Vector3::repeat(-f32::MAX)),
+        |(min, max), v| (min.inf(v), max.sup(v)),
+    );
+    let center = (min + max).scale(0.5);
+    let radius = vertices
+        .iter()
+        .map(|v| (v - center).norm())
+        .fold(f32::EPSILON, f32::max);
+
+    let mut frame = Frame::new();
+
+    // The atlas is static, it goes to the right half.
+    for triangle in patch.triangles.iter() {
+        let uvs = triangle.map(|index| patch.second_tex_coords[index as usize]);
+        frame.fill(
+            1,
+            uvs.map(|uv| uv * SIZE as f32),
+            [0.0; 3],
+            pack_color(color(triangle), 1.0),
+        );
+    }
+
+    let mut window = Window::new("uvgen viewer", 2 * SIZE, SIZE, WindowOptions::default())
+        .map_err(|e| format!("unable to open a window: {e}"))?;
+    window.set_target_fps(60);
+
+    let (mut yaw, mut pitch, mut zoom) = (std::f32::consts::FRAC_PI_4, -0.6f32, 1.0f32);
+    let mut last_mouse = None;
+    let mut dirty = true;
+    while window.is_open() && !window.is_key_down(Key::Escape) {
+        let mouse = window.get_mouse_pos(MouseMode::Discard);
+        if window.get_mouse_down(MouseButton::Left) {
+            if let (Some((x, y)), Some((last_x, last_y))) = (mouse, last_mouse) {
+                yaw += (x - last_x) * 0.01;
+                pitch += (y - last_y) * 0.01;
+                dirty |= x != last_x || y != last_y;
+            }
+        }
+        last_mouse = mouse;
+        for (key, yaw_delta, pitch_delta) in [
+            (Key::Left, -0.1, 0.0),
+            (Key::Right, 0.1, 0.0),
+            (Key::Up, 0.0, -0.1),
+            (Key::Down, 0.0, 0.1),
+        ] {
+            if window.is_key_pressed(key, KeyRepeat::Yes) {
+                yaw += yaw_delta;
+                pitch += pitch_delta;
+                dirty = true;
+            }
+        }
+        if let Some((_, scroll)) = window.get_scroll_wheel() {
+            zoom = (zoom * (1.0 + scroll * 0.05)).clamp(0.1, 20.0);
+            dirty = true;
+        }
+        pitch = pitch.clamp(-1.5, 1.5);
+
+        if dirty {
+            dirty = false;
+            let rotation = Rotation3::from_axis_angle(&Vector3::x_axis(), pitch)
+                * Rotation3::from_axis_angle(&Vector3::y_axis(), yaw);
+            let scale = zoom * 0.45 * SIZE as f32 / radius;
+            let transform = |p: Vector3<f32>| rotation * (p - center);
+            let to_pixels = |p: &Vector3<f32>| {
+                Vector2::new(p.x, -p.y) * scale + Vector2::repeat(SIZE as f32 * 0.5)
+            };
+
+            frame.clear(0);
+            for triangle in patch.triangles.iter() {
+                let corners = triangle.map(|index| transform(position(index)));
+                let normal = (corners[1] - corners[0]).cross(&(corners[2] - corners[0]));
+                // Simple two-sided lighting from the viewer.
+                let brightness = 0.3
+                    + 0.7
+                        * normal
+                            .try_normalize(f32::EPSILON)
+                            .map_or(1.0, |normal| normal.z.abs());
+                frame.fill(
+                    0,
+                    corners.map(|p| to_pixels(&p)),
+                    corners.map(|p| -p.z),
+                    pack_color(color(triangle), brightness),
+                );
+            }
+        }
+
+        window
+            .update_with_buffer(&frame.pixels, 2 * SIZE, SIZE)
+            .map_err(|e| format!("unable to update the window: {e}"))?;
+    }
+    Ok(())
+}
@@ -0,0 +1,127 @@
+//! Triangle adjacency.
+
+use crate::{SurfaceDataPatch, UvGenOptions};
+use std::collections::HashMap;
+
+/// Calculates adjacency of the triangles. For every triangle it returns indices of the triangles,
+/// that share its edges, where `i`-th edge goes from `i`-th corner to `(i + 1) % 3`-th corner.
+/// An edge without a neighbour has `None`. If an edge is shared by more than two triangles, the
+/// neighbour is the next triangle (in the order of the triangles) that uses the edge, so all such
+/// triangles form a cycle.
+///
+/// ```rust
+/// let triangles = [[0, 1, 2], [2, 1, 3]];
+/// let adjacency = uvgen::triangle_adjacency(&triangles);
+/// assert_eq!(adjacency, [[None, Some(1), None], [Some(0), None, None]]);
+/// ```
+pub fn triangle_adjacency(triangles: &[[u32; 3]]) -> Vec<[Option<u32>; 3]> {
+    adjacency_by(triangles, |index| index)
+}
+
+/// Calculates adjacency of the triangles, where vertices are compared by their keys.
+pub(crate) fn adjacency_by(
+    triangles: &[[u32; 3]],
+    key: impl Fn(u32) -> u32,
+) -> Vec<[Option<u32>; 3]> {
+    let mut edges = HashMap::<(u32, u32), Vec<(u32, usize)>>::new();
+    for (triangle_index, triangle) in triangles.iter().enumerate() {
+        for corner in 0..3 {
+            let (a, b) = (key(triangle[corner]), key(triangle[(corner + 1) % 3]));
+            edges
+                .entry((a.min(b), a.max(b)))
+                .or_default()
+                .push((triangle_index as u32, corner));
+        }
+    }
+
+    let mut adjacency = vec![[None; 3]; triangles.len()];
+    for users in edges.values() {
+        if users.len() < 2 {
+            continue;
+        }
+        for (i, &(triangle_index, corner)) in users.iter().enumerate() {
+            let (neighbour, _) = users[(i + 1) % users.len()];
+            if neighbour != triangle_index {
+                adjacency[triangle_index as usize][corner] = Some(neighbour);
+            }
+        }
+    }
+    adjacency
+}
+
+/// Calculates adjacency of the patched triangles across seams, if it was requested.
+pub(crate) fn fill_adjacency(patch: &mut SurfaceDataPatch, options: &UvGenOptions) {
+    if options.adjacency {
+        patch.adjacency = adjacency_by(&patch.triangles, |index| {
+            patch.source_vertex(index).unwrap_or(index)
+        });
+    }
+}
+
+/// Triangles around every vertex, stored in a single buffer. It is used to grow the charts:
+/// every vertex is expanded only once (see [`Self::expand`]), so the charts are gathered in
+/// linear time, since every corner of every triangle is visited once.
+#[derive(Default)]
+pub(crate) struct VertexTriangles {
+    offsets: Vec<usize>,
+    triangles: Vec<usize>,
+    expanded: Vec<bool>,
+}
+
+impl VertexTriangles {
+    /// Builds the index for the given triangles. Indices out of range are ignored.
+    pub fn new(vertex_count: usize, triangles: &[[u32; 3]]) -> Self {
+        let mut offsets = vec![0; vertex_count + 1];
+        for &index in triangles.iter().flatten() {
+            if let Some(count) = offsets.get_mut(index as usize + 1) {
+                *count += 1;
+            }
+        }
+        for i in 1..offsets.len() {
+            offsets[i] += offsets[i - 1];
+        }
+        let mut cursors = offsets.clone();
+        let mut vertex_triangles = vec![0; offsets[vertex_count]];
+        for (triangle_index, triangle) in triangles.iter().enumerate() {
+            for &index in triangle {
+                if (index as usize) < vertex_count {
+                    let cursor = &mut cursors[index as usize];
+                    vertex_triangles[*cursor] = triangle_index;
+                    *cursor += 1;
+                }
+            }
+        }
+        Self {
+            offsets,
+            triangles: vertex_triangles,
+            expanded: vec![false; vertex_count],
+        }
+    }
+
+    /// Returns the triangles around the given vertex, if it wasn't expanded before. Returns an
+    /// empty slice otherwise, since every triangle around the vertex is gathered already.
+    pub fn expand(&mut self, index: u32) -> &[usize] {
+        match self.expanded.get_mut(index as usize) {
+            Some(expanded) if !*expanded => {
+                *expanded = true;
+                &self.triangles[self.offsets[index as usize]..self.offsets[index as usize + 1]]
+            }
+            _ => &[],
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    #[test]
+    fn test_vertex_triangles() {
+        let triangles = [[0, 1, 2], [2, 1, 3], [3, 4, 2], [5, 6, 7]];
+        let mut vertex_triangles = super::VertexTriangles::new(8, &triangles);
+        assert_eq!(vertex_triangles.expand(2), [0, 1, 2]);
+        assert_eq!(vertex_triangles.expand(1), [0, 1]);
+        // Every vertex is expanded only once.
+        assert!(vertex_triangles.expand(2).is_empty());
+        assert_eq!(vertex_triangles.expand(7), [3]);
+        assert!(vertex_triangles.expand(8).is_empty());
+    }
+}
@@ -0,0 +1,230 @@
+//! Chart anchors - stable placement of charts across re-runs. See [`UvGenOptions::stable_anchors`]
+//! for more info.
+
+use crate::{
+    hash::Fnv1a,
+    pack::{self, Packing},
+    Chart, Rect, UvGenOptions,
+};
+use nalgebra::Vector3;
+use std::collections::HashMap;
+
+/// Location of a chart in the atlas, keyed by a hash of the chart content.
+#[derive(Copy, Clone, Debug, PartialEq)]
+#[cfg_attr(
+    feature = "rkyv",
+    derive(rkyv::Archive, rkyv::Serialize, rkyv::Deserialize),
+    archive(check_bytes)
+)]
+pub struct ChartAnchor {
+    /// Hash of positions of the triangles of the chart. It does not depend on the order of the
+    /// triangles or on indices of their vertices.
+    pub hash: u64,
+    /// Rectangle of the atlas occupied by the chart (including spacing).
+    #[cfg_attr(feature = "rkyv", with(crate::archive::RectAsArray))]
+    pub rect: Rect<f32>,
+}
+
+/// Locations of charts in the atlas, that could be passed to the next run of the generator (see
+/// [`UvGenOptions::previous_layout`]).
+#[derive(Clone, Debug, Default, PartialEq)]
+#[cfg_attr(
+    feature = "rkyv",
+    derive(rkyv::Archive, rkyv::Serialize, rkyv::Deserialize),
+    archive(check_bytes)
+)]
+pub struct AnchorLayout {
+    /// Scale that was applied to the charts.
+    pub scale: f32,
+    /// Locations of the charts.
+    pub anchors: Vec<ChartAnchor>,
+}
+
+/// Calculates a hash of the chart content. `position` must return a position of a vertex with
+/// the given index.
+pub(crate) fn chart_hash(
+    mesh: &Chart,
+    triangles: &[[u32; 3]],
+    position: impl Fn(u32) -> Option<Vector3<f32>>,
+) -> Option<u64> {
+    let mut triangle_hashes = mesh
+        .triangles
+        .iter()
+        .map(|&triangle_index| {
+            let triangle = triangles.get(triangle_index)?;
+            let points = [
+                position(triangle[0])?,
+                position(triangle[1])?,
+                position(triangle[2])?,
+            ];
+            // Start from the smallest point, so the hash does not depend on the rotation of
+            // indices of the triangle.
+            let first = (0..3)
+                .min_by(|&a, &b| {
+                    let (a, b) = (&points[a], &points[b]);
+                    a.x.total_cmp(&b.x)
+                        .then(a.y.total_cmp(&b.y))
+                        .then(a.z.total_cmp(&b.z))
+                })
+                .unwrap_or_default();
+            let mut hasher = Fnv1a::default();
+            for i in 0..3 {
+                for component in points[(first + i) % 3].iter() {
+                    hasher.write_f32(*component);
+                }
+            }
+            Some(hasher.finish())
+        })
+        .collect::<Option<Vec<_>>>()?;
+    triangle_hashes.sort_unstable();
+
+    let mut hasher = Fnv1a::default();
+    for triangle_hash in triangle_hashes {
+        hasher.write_u64(triangle_hash);
+    }
+    Some(hasher.finish())
+}
+
+/// Subtracts the rectangle from every free rectangle. Free rectangles never overlap each other.
+fn subtract(free_rects: &mut Vec<Rect<f32>>, rect: &Rect<f32>) {
+    let mut result = Vec::with_capacity(free_rects.len());
+    for free in free_rects.drain(..) {
+        let left = free.x().max(rect.x());
+        let right = (free.x() + free.w()).min(rect.x() + rect.w());
+        let bottom = free.y().max(rect.y());
+        let top = (free.y() + free.h()).min(rect.y() + rect.h());
+        if left >= right || bottom >= top {
+            result.push(free);
+            continue;
+        }
+
+        // Split the rest of the free rectangle into up to four non-overlapping pieces: full
+        // height strips to the left and to the right, and the pieces above and below.
+        result.extend(
+            [
+                Rect::new(free.x(), free.y(), left - free.x(), free.h()),
+                Rect::new(right, free.y(), free.x() + free.w() - right, free.h()),
+                Rect::new(left, free.y(), right - left, bottom - free.y()),
+                Rect::new(left, top, right - left, free.y() + free.h() - top),
+            ]
+            .into_iter()
+            .filter(|piece| piece.w() > 0.0 && piece.h() > 0.0),
+        );
+    }
+    *free_rects = result;
+}
+
+/// Packs the charts, keeping the ones that were not changed since the previous run in their
+/// previous locations. Returns `None` if the rest of the charts do not fit into the remaining
+/// space. Meshes must be sorted by [`pack::sort_meshes`] first.
+pub(crate) fn pack_anchored(
+    meshes: &[Chart],
+    hashes: &[u64],
+    layout: &AnchorLayout,
+    options: &UvGenOptions,
+) -> Option<Packing> {
+    let scale = layout.scale;
+    let twice_spacing = 2.0 * options.spacing;
+
+    let mut anchors = HashMap::<u64, Vec<Rect<f32>>>::new();
+    for anchor in layout.anchors.iter().rev() {
+        anchors.entry(anchor.hash).or_default().push(anchor.rect);
+    }
+
+    let mut rects = vec![None; meshes.len()];
+    let mut free_rects = vec![Rect::new(0.0, 0.0, 1.0, 1.0)];
+    for (mesh_index, (mesh, hash)) in meshes.iter().zip(hashes).enumerate() {
+        let w = mesh.width() * scale + twice_spacing;
+        let h = mesh.height() * scale + twice_spacing;
+        let Some(anchor) = anchors.get_mut(hash).and_then(|rects| rects.pop()) else {
+            continue;
+        };
+        // Allow some tolerance, since the size is recalculated from the same data.
+        if w <= anchor.w() + f32::EPSILON && h <= anchor.h() + f32::EPSILON {
+            subtract(&mut free_rects, &anchor);
+            rects[mesh_index] = Some(Rect::new(anchor.x(), anchor.y(), w, h));
+        }
+    }
+
+    let unanchored = (0..meshes.len())
+        .filter(|&mesh_index| rects[mesh_index].is_none())
+        .collect::<Vec<_>>();
+    let packing = pack::pack_into_free_rects(
+        unanchored.iter().map(|&mesh_index| &meshes[mesh_index]),
+        &free_rects,
+        scale,
+        options.spacing,
+    )?;
+    for (&mesh_index, rect) in unanchored.iter().zip(packing.rects) {
+        rects[mesh_index] = Some(rect);
+    }
+
+    Some(Packing {
+        rects: rects.into_iter().collect::<Option<Vec<_>>>()?,
+        scale,
+        free_rects: packing.free_rects,
+        ..Default::default()
+    })
+}
+
+/// Returns anchors of the packed charts.
+pub(crate) fn anchor_layout(packing: &Packing, hashes: &[u64]) -> AnchorLayout {
+    AnchorLayout {
+        scale: packing.scale,
+        anchors: hashes
+            .iter()
+            .zip(packing.rects.iter())
+            .map(|(&hash, &rect)| ChartAnchor { hash, rect })
+            .collect(),
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::UvGenOptions;
+    use nalgebra::Vector3;
+
+    #[test]
+    fn test_stable_anchors() {
+        // Two separate quads: a floor and a wall.
+        let mut vertices = vec![
+            Vector3::new(0.0, 0.0, 0.0),
+            Vector3::new(1.0, 0.0, 0.0),
+            Vector3::new(1.0, 0.0, 1.0),
+            Vector3::new(0.0, 0.0, 1.0),
+            Vector3::new(0.0, 1.0, 2.0),
+            Vector3::new(2.0, 1.0, 2.0),
+            Vector3::new(2.0, 3.0, 2.0),
+            Vector3::new(0.0, 3.0, 2.0),
+        ];
+        let triangles: [[u32; 3]; 4] = [[0, 2, 1], [0, 3, 2], [4, 5, 6], [4, 6, 7]];
+        let generate = |vertices: &[Vector3<f32>], previous_layout| {
+            crate::generate_uvs_with_options(
+                vertices.iter().cloned(),
+                triangles.iter().cloned(),
+                &UvGenOptions {
+                    stable_anchors: true,
+                    previous_layout,
+                    ..Default::default()
+                },
+            )
+            .unwrap()
+        };
+
+        let first = generate(&vertices, None);
+        let layout = first.pack_stats.anchor_layout.clone().unwrap();
+        assert_eq!(layout.anchors.len(), 2);
+
+        // Slightly shrink the wall, the floor must stay at the same place.
+        vertices[6].y = 2.9;
+        vertices[7].y = 2.9;
+        let second = generate(&vertices, Some(layout.clone()));
+        assert_eq!(second.pack_stats.scale, layout.scale);
+        for &index in second.triangles[..2].iter().flatten() {
+            assert_eq!(
+                second.second_tex_coords[index as usize],
+                first.second_tex_coords[index as usize]
+            );
+        }
+    }
+}
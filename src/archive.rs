@@ -0,0 +1,91 @@
+//! Zero-copy archiving of patches with `rkyv`. Available only with `rkyv` feature. See
+//! [`crate::SurfaceDataPatch`] docs for more info.
+
+use crate::Rect;
+use rkyv::{
+    with::{ArchiveWith, DeserializeWith, SerializeWith},
+    Archive, Archived, Fallible, Serialize,
+};
+
+/// Archives a rectangle as `[x, y, w, h]` array, because `rectutils` knows nothing about `rkyv`.
+#[derive(Debug)]
+pub struct RectAsArray;
+
+fn to_array(rect: &Rect<f32>) -> [f32; 4] {
+    [rect.x(), rect.y(), rect.w(), rect.h()]
+}
+
+impl ArchiveWith<Rect<f32>> for RectAsArray {
+    type Archived = Archived<[f32; 4]>;
+    type Resolver = <[f32; 4] as Archive>::Resolver;
+
+    unsafe fn resolve_with(
+        field: &Rect<f32>,
+        pos: usize,
+        resolver: Self::Resolver,
+        out: *mut Self::Archived,
+    ) {
+        to_array(field).resolve(pos, resolver, out);
+    }
+}
+
+impl<S: Fallible + ?Sized> SerializeWith<Rect<f32>, S> for RectAsArray {
+    fn serialize_with(field: &Rect<f32>, serializer: &mut S) -> Result<Self::Resolver, S::Error> {
+        to_array(field).serialize(serializer)
+    }
+}
+
+impl<D: Fallible + ?Sized> DeserializeWith<Archived<[f32; 4]>, Rect<f32>, D> for RectAsArray {
+    fn deserialize_with(field: &Archived<[f32; 4]>, _: &mut D) -> Result<Rect<f32>, D::Error> {
+        Ok(Rect::new(field[0], field[1], field[2], field[3]))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::{SurfaceDataPatch, UvGenOptions};
+    use rkyv::Deserialize;
+
+    #[test]
+    fn test_archive() {
+        let mesh = crate::test_meshes::uv_sphere(16, 8);
+        let patch = crate::generate_uvs_with_options(
+            mesh.vertices.iter().cloned(),
+            mesh.triangles.iter().cloned(),
+            &UvGenOptions {
+                resolution: 256,
+                page_count: 2,
+                chart_uvs: true,
+                chart_colors: true,
+                adjacency: true,
+                stage_timings: true,
+                stable_anchors: true,
+                ..Default::default()
+            },
+        )
+        .unwrap();
+        assert!(!patch.pack_stats.stage_timings.is_empty());
+
+        let bytes = rkyv::to_bytes::<_, 4096>(&patch).unwrap();
+        let archived = rkyv::check_archived_root::<SurfaceDataPatch>(&bytes).unwrap();
+        assert_eq!(archived.triangles.len(), patch.triangles.len());
+        assert_eq!(
+            archived.second_tex_coords.as_slice(),
+            patch.second_tex_coords.as_slice()
+        );
+        assert_eq!(
+            archived.pack_stats.charts.len(),
+            patch.pack_stats.charts.len()
+        );
+
+        let deserialized: SurfaceDataPatch = archived.deserialize(&mut rkyv::Infallible).unwrap();
+        assert_eq!(deserialized.to_golden_string(), patch.to_golden_string());
+        assert_eq!(deserialized.pack_stats, patch.pack_stats);
+        assert_eq!(deserialized.chart_uvs, patch.chart_uvs);
+        assert_eq!(deserialized.adjacency, patch.adjacency);
+        assert_eq!(deserialized.triangle_pages, patch.triangle_pages);
+
+        // Corrupted archives are rejected instead of being read.
+        assert!(rkyv::check_archived_root::<SurfaceDataPatch>(&bytes[..bytes.len() / 2]).is_err());
+    }
+}
@@ -0,0 +1,195 @@
+//! Description of a partially filled atlas, that is shared between multiple meshes. See
+//! [`pack_into_atlas`] for more info.
+
+use crate::{extend::unwrap_into_free_space, PackStats, Rect, SurfaceDataPatch, UvGenOptions};
+use nalgebra::Vector3;
+use std::fmt::{Display, Formatter};
+
+/// Free space of an atlas and the scale of the charts in it. The description could be saved as
+/// text (using [`Display`]) and loaded back using [`AtlasDescription::parse`], which allows to
+/// fill the atlas in multiple passes (for example, when meshes of a level arrive incrementally).
+#[derive(Clone, Debug, PartialEq)]
+pub struct AtlasDescription {
+    /// Scale of the charts in the atlas. New charts use the same scale, so texel density is the
+    /// same for every mesh.
+    pub scale: f32,
+    /// Rectangles (in [0;1] range) of the atlas, that are not occupied by any chart.
+    pub free_rects: Vec<Rect<f32>>,
+}
+
+impl AtlasDescription {
+    const HEADER: &'static str = "uvgen-atlas 1";
+
+    /// Creates a description of an empty atlas with the given scale of the charts.
+    pub fn new(scale: f32) -> Self {
+        Self {
+            scale,
+            free_rects: vec![Rect::new(0.0, 0.0, 1.0, 1.0)],
+        }
+    }
+
+    /// Creates a description of the atlas from the statistics of a generated patch.
+    pub fn from_stats(stats: &PackStats) -> Self {
+        Self {
+            scale: stats.scale,
+            free_rects: stats.free_rects.clone(),
+        }
+    }
+
+    /// Parses the description from the text, that was produced by [`Display`] implementation.
+    /// Returns `None` if the text is malformed.
+    pub fn parse(text: &str) -> Option<Self> {
+        let mut lines = text.lines().map(str::trim).filter(|line| !line.is_empty());
+        if lines.next()? != Self::HEADER {
+            return None;
+        }
+
+        let mut scale = None;
+        let mut free_rects = Vec::new();
+        for line in lines {
+            let mut tokens = line.split_whitespace();
+            match tokens.next()? {
+                "scale" => scale = Some(tokens.next()?.parse().ok()?),
+                "rect" => {
+                    let mut next = || tokens.next()?.parse::<f32>().ok();
+                    free_rects.push(Rect::new(next()?, next()?, next()?, next()?));
+                }
+                _ => return None,
+            }
+        }
+
+        Some(Self {
+            scale: scale?,
+            free_rects,
+        })
+    }
+}
+
+impl Display for AtlasDescription {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        // Default formatting of floats gives the shortest representation, that is parsed back
+        // into exactly the same value.
+        writeln!(f, "{}", Self::HEADER)?;
+        writeln!(f, "scale {}", self.scale)?;
+        for rect in self.free_rects.iter() {
+            writeln!(
+                f,
+                "rect {} {} {} {}",
+                rect.x(),
+                rect.y(),
+                rect.w(),
+                rect.h()
+            )?;
+        }
+        Ok(())
+    }
+}
+
+/// Generates UVs for the mesh and packs its charts only into the free space of the given atlas.
+/// Returns the patch for the mesh and the updated description of the atlas:
+///
+/// ```rust
+/// # use nalgebra::Vector3;
+/// use uvgen::AtlasDescription;
+///
+/// let vertices = [
+///     Vector3::new(0.0, 0.0, 0.0),
+///     Vector3::new(1.0, 0.0, 0.0),
+///     Vector3::new(0.0, 1.0, 0.0),
+/// ];
+/// let triangles = [[0, 1, 2]];
+///
+/// let mut atlas = AtlasDescription::new(0.25);
+/// for _ in 0..2 {
+///     let (patch, new_atlas) = uvgen::pack_into_atlas(
+///         vertices.iter().cloned(),
+///         triangles.iter().cloned(),
+///         &atlas,
+///         &Default::default(),
+///     )
+///     .unwrap();
+///     assert_eq!(patch.second_tex_coords.len(), 3);
+///
+///     // Save the description between the passes.
+///     atlas = AtlasDescription::parse(&new_atlas.to_string()).unwrap();
+/// }
+/// ```
+///
+/// New charts use the scale of the atlas multiplied by [`UvGenOptions::scale_in_lightmap`]. If
+/// they do not fit into the free space, they're scaled down until they do. Returns `None` if the
+/// input data is invalid or if there's no free space left in the atlas.
+pub fn pack_into_atlas(
+    vertices: impl Iterator<Item = Vector3<f32>>,
+    triangles: impl Iterator<Item = [u32; 3]>,
+    atlas: &AtlasDescription,
+    options: &UvGenOptions,
+) -> Option<(SurfaceDataPatch, AtlasDescription)> {
+    trace_span!("pack_into_atlas");
+
+    let vertices = vertices.collect::<Vec<_>>();
+    let patch = unwrap_into_free_space(
+        &vertices,
+        triangles,
+        atlas.scale,
+        &atlas.free_rects,
+        0,
+        options,
+    )?;
+    let atlas = AtlasDescription {
+        scale: atlas.scale,
+        free_rects: patch.pack_stats.free_rects.clone(),
+    };
+    Some((patch, atlas))
+}
+
+#[cfg(test)]
+mod test {
+    use super::AtlasDescription;
+    use crate::{Rect, UvGenOptions};
+
+    #[test]
+    fn test_atlas_description_round_trip() {
+        let atlas = AtlasDescription {
+            scale: 0.123_456_79,
+            free_rects: vec![
+                Rect::new(0.1, 0.2, 0.3, 0.4),
+                Rect::new(1.0 / 3.0, 0.0, 2.0 / 3.0, 1.0e-7),
+            ],
+        };
+        assert_eq!(AtlasDescription::parse(&atlas.to_string()), Some(atlas));
+        assert_eq!(AtlasDescription::parse("scale 1.0"), None);
+        assert_eq!(AtlasDescription::parse("uvgen-atlas 1\nrect 0 0 1"), None);
+    }
+
+    #[test]
+    fn test_scale_in_lightmap() {
+        let mesh = crate::test_meshes::cube();
+        let pack = |atlas: &AtlasDescription, scale_in_lightmap| {
+            crate::pack_into_atlas(
+                mesh.vertices.iter().cloned(),
+                mesh.triangles.iter().cloned(),
+                atlas,
+                &UvGenOptions {
+                    scale_in_lightmap,
+                    ..Default::default()
+                },
+            )
+            .unwrap()
+        };
+
+        let atlas = AtlasDescription::new(0.05);
+        let (regular, atlas) = pack(&atlas, 1.0);
+        let (doubled, atlas) = pack(&atlas, 2.0);
+        // The scale of the shared atlas must not be affected by the multiplier.
+        assert_eq!(atlas.scale, 0.05);
+        assert_eq!(doubled.pack_stats.scale, 2.0 * regular.pack_stats.scale);
+        for (regular, doubled) in regular
+            .pack_stats
+            .charts
+            .iter()
+            .zip(doubled.pack_stats.charts.iter())
+        {
+            assert!((doubled.uv_area - 4.0 * regular.uv_area).abs() < 1.0e-6);
+        }
+    }
+}
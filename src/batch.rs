@@ -0,0 +1,106 @@
+//! Generation for many meshes at once. See [`generate_uvs_batch`] for more info.
+
+use crate::{
+    parallel, try_generate_uvs_with_options, SurfaceDataPatch, UvGenError, UvGenOptions, UvIndex,
+};
+use nalgebra::Vector3;
+
+/// Vertices and triangles of a mesh for [`generate_uvs_batch`].
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct MeshInput<I = u32> {
+    /// Positions of the vertices of the mesh.
+    pub vertices: Vec<Vector3<f32>>,
+    /// Triangles of the mesh.
+    pub triangles: Vec<[I; 3]>,
+}
+
+/// Generates UV maps for every mesh using the same options, like
+/// [`crate::try_generate_uvs_with_options`] does for a single mesh. Meshes are spread across all
+/// available CPU cores if `parallel` feature is enabled, otherwise they are processed one by one
+/// on the current thread. This is useful for level-baking tools, which process hundreds of
+/// surfaces at once:
+///
+/// ```rust
+/// let meshes = [uvgen::test_meshes::cube(), uvgen::test_meshes::uv_sphere(8, 4)];
+/// let inputs = meshes
+///     .iter()
+///     .map(|mesh| uvgen::MeshInput {
+///         vertices: mesh.vertices.clone(),
+///         triangles: mesh.triangles.clone(),
+///     })
+///     .collect();
+/// let patches = uvgen::generate_uvs_batch(inputs, &Default::default());
+/// assert_eq!(patches.len(), 2);
+/// assert!(patches.iter().all(|patch| patch.is_ok()));
+/// ```
+///
+/// Results are returned in the order of the meshes and they are exactly the same as the ones of
+/// separate calls. Failure of one mesh does not affect the others. Meshes are handed out to the
+/// threads one by one, so a few large meshes do not hold up the small ones.
+pub fn generate_uvs_batch<I: UvIndex + Sync>(
+    inputs: Vec<MeshInput<I>>,
+    options: &UvGenOptions,
+) -> Vec<Result<SurfaceDataPatch, UvGenError>> {
+    trace_span!("generate_uvs_batch");
+
+    parallel::map_balanced(&inputs, |input| {
+        try_generate_uvs_with_options(
+            input.vertices.iter().cloned(),
+            input.triangles.iter().cloned(),
+            options,
+        )
+    })
+}
+
+#[cfg(test)]
+mod test {
+    use super::MeshInput;
+    use crate::UvGenError;
+
+    #[test]
+    fn test_batch() {
+        let meshes = [
+            crate::test_meshes::cube(),
+            crate::test_meshes::uv_sphere(16, 8),
+            crate::test_meshes::torus(1.0, 0.25, 16, 8),
+            crate::test_meshes::stairs(4),
+        ];
+        let mut inputs = meshes
+            .iter()
+            .map(|mesh| MeshInput {
+                vertices: mesh.vertices.clone(),
+                triangles: mesh.triangles.clone(),
+            })
+            .collect::<Vec<_>>();
+        // A broken mesh does not affect the others.
+        inputs.insert(
+            1,
+            MeshInput {
+                vertices: Vec::new(),
+                triangles: vec![[0, 1, 2]],
+            },
+        );
+
+        let patches = crate::generate_uvs_batch(inputs, &Default::default());
+        assert_eq!(patches.len(), meshes.len() + 1);
+        assert_eq!(
+            patches[1].as_ref().unwrap_err(),
+            &UvGenError::OutOfBoundsIndex {
+                triangle: 0,
+                index: 0
+            }
+        );
+        for (patch, mesh) in patches.iter().take(1).chain(&patches[2..]).zip(&meshes) {
+            let expected = crate::generate_uvs(
+                mesh.vertices.iter().cloned(),
+                mesh.triangles.iter().cloned(),
+                0.005,
+            )
+            .unwrap();
+            assert_eq!(
+                patch.as_ref().unwrap().to_golden_string(),
+                expected.to_golden_string()
+            );
+        }
+    }
+}
@@ -0,0 +1,291 @@
+//! Splitting of charts, that are too large to be packed as a whole. See
+//! [`crate::UvGenOptions::texel_density`] and [`crate::UvGenOptions::max_chart_size`] for more
+//! info.
+
+use crate::{locks, new_vertex_index, virtual_pages, Chart, SurfaceDataPatch, UvGenOptions};
+use nalgebra::{Vector2, Vector3};
+use std::collections::{BTreeMap, HashMap};
+
+/// Two tiles of a chart, that was split into multiple charts (see
+/// [`UvGenOptions::max_chart_size`]), which share a part of the cut. See
+/// [`crate::PackStats::tile_adjacency`].
+#[derive(Clone, Debug, Default, PartialEq, Eq, Hash)]
+#[cfg_attr(
+    feature = "rkyv",
+    derive(rkyv::Archive, rkyv::Serialize, rkyv::Deserialize),
+    archive(check_bytes)
+)]
+pub struct TileAdjacency {
+    /// Indices of the tiles in [`crate::PackStats::charts`], the first one is smaller.
+    pub charts: [usize; 2],
+    /// Pairs of vertices of the tiles along the cut, the first vertex of every pair belongs to
+    /// the first tile. Vertices of a pair are copies of the same source vertex, they have the
+    /// same position, but different texture coordinates. Bakers could stitch the tiles by making
+    /// the texels at these pairs match.
+    pub vertices: Vec<[u32; 2]>,
+}
+
+/// Returns the maximal extent of a chart in the projection space, which is the smallest one of
+/// [`UvGenOptions::max_chart_size`] and the extent, at which the chart fits into a virtual page at
+/// [`UvGenOptions::texel_density`]. Returns `None` if charts must not be split.
+pub(crate) fn max_chart_extent(options: &UvGenOptions) -> Option<f32> {
+    let size = options.max_chart_size;
+    let size = (size > 0.0 && size.is_finite()).then_some(size);
+    match (size, page_chart_extent(options)) {
+        (Some(size), Some(page_extent)) => Some(size.min(page_extent)),
+        (size, page_extent) => size.or(page_extent),
+    }
+}
+
+/// Returns the maximal extent of a chart in the projection space, at which the chart fits into a
+/// virtual page at [`UvGenOptions::texel_density`], or `None` if it is not limited.
+fn page_chart_extent(options: &UvGenOptions) -> Option<f32> {
+    let page_extent = virtual_pages::page_extent(options)?;
+    if !(options.texel_density > 0.0 && options.texel_density.is_finite()) {
+        return None;
+    }
+    // Page extent and spacing are in UV units, while the density is in texels per world unit.
+    let texels = (page_extent - 2.0 * options.spacing) * options.resolution as f32;
+    let extent = texels / options.texel_density;
+    (extent > 0.0).then_some(extent)
+}
+
+/// Splits every chart, that is wider or higher than the given extent, by a uniform grid of cells,
+/// that are not larger than the extent. Triangles go to the cell of their center, so every
+/// triangle stays intact. Triangles with locked vertices (see
+/// [`UvGenOptions::locked_vertices`]) follow the first triangle with the same locked vertex
+/// instead, so such vertices are not split. Vertices shared between the cells are cloned, the
+/// indices of the original vertices of the clones are pushed to `additional_vertices` and pairs
+/// of indices of the original vertices and their clones are pushed to `cut_vertices`. Returns
+/// `None` if vertex indices overflow or are out of range.
+#[allow(clippy::too_many_arguments)]
+pub(crate) fn split_large_charts(
+    meshes: &mut Vec<Chart>,
+    vertices: &mut Vec<Vector3<f32>>,
+    triangles: &mut [[u32; 3]],
+    additional_vertices: &mut Vec<u32>,
+    cut_vertices: &mut Vec<[u32; 2]>,
+    projections: &[[Vector2<f32>; 3]],
+    locked_vertices: &[bool],
+    max_extent: f32,
+) -> Option<()> {
+    // Clones of the vertices are never locked.
+    let source_count = vertices.len().saturating_sub(additional_vertices.len());
+    let is_locked =
+        |index: u32| (index as usize) < source_count && locks::is_locked(locked_vertices, index);
+    let mut split = Vec::with_capacity(meshes.len());
+    for mesh in meshes.drain(..) {
+        let extent = Vector2::new(mesh.width(), mesh.height());
+        // Charts of meaningless geometry (with infinite or NaN bounds) can't be split.
+        if mesh.extra_rect.is_some() || extent.max() <= max_extent || !extent.max().is_finite() {
+            split.push(mesh);
+            continue;
+        }
+
+        // Cells of the same size, that cover the chart exactly.
+        let cell_counts = extent.map(|e| (e / max_extent).ceil().max(1.0));
+        let cell_size = extent.component_div(&cell_counts);
+        let mut cells = HashMap::<(u32, u32), Vec<usize>>::new();
+        let mut order = Vec::new();
+        let mut locked_cells = HashMap::new();
+        for &triangle_index in mesh.triangles.iter() {
+            let [a, b, c] = *projections.get(triangle_index)?;
+            let triangle = triangles.get(triangle_index)?;
+            let locked = triangle.iter().filter(|&&index| is_locked(index));
+            let key = match locked.clone().find_map(|index| locked_cells.get(index)) {
+                Some(&key) => key,
+                None => {
+                    let center = (a + b + c) / 3.0 - mesh.uv_min;
+                    let cell = center
+                        .component_div(&cell_size)
+                        .zip_map(&cell_counts, |c, count| c.min(count - 1.0).max(0.0) as u32);
+                    (cell.x, cell.y)
+                }
+            };
+            for &index in locked {
+                locked_cells.entry(index).or_insert(key);
+            }
+            cells
+                .entry(key)
+                .or_insert_with(|| {
+                    order.push(key);
+                    Vec::new()
+                })
+                .push(triangle_index);
+        }
+
+        // Vertices belong to the first cell, that uses them, other cells get their own clones.
+        let mut owners = HashMap::new();
+        for (cell_index, key) in order.iter().enumerate() {
+            let mut clones = HashMap::new();
+            for &triangle_index in cells[key].iter() {
+                for vertex_index in triangles.get_mut(triangle_index)?.iter_mut() {
+                    let owner = *owners.entry(*vertex_index).or_insert(cell_index);
+                    if owner == cell_index {
+                        continue;
+                    }
+                    *vertex_index = match clones.get(vertex_index) {
+                        Some(&clone) => clone,
+                        None => {
+                            let clone = new_vertex_index(vertices.len())?;
+                            vertices.push(*vertices.get(*vertex_index as usize)?);
+                            additional_vertices.push(*vertex_index);
+                            cut_vertices.push([*vertex_index, clone]);
+                            clones.insert(*vertex_index, clone);
+                            clone
+                        }
+                    };
+                }
+            }
+        }
+
+        for key in order {
+            if let Some(cell_triangles) = cells.remove(&key) {
+                split.push(Chart::from_triangles(cell_triangles, projections));
+            }
+        }
+    }
+    *meshes = split;
+    Some(())
+}
+
+/// Fills [`crate::PackStats::tile_adjacency`] of the patch using the pairs of the original vertices
+/// and their clones, that were made by [`split_large_charts`]. Charts and triangles of the patch
+/// must be set already.
+pub(crate) fn fill_tile_adjacency(patch: &mut SurfaceDataPatch, cut_vertices: &[[u32; 2]]) {
+    if cut_vertices.is_empty() {
+        return;
+    }
+    let mut vertex_charts = HashMap::new();
+    for (chart_index, chart) in patch.pack_stats.charts.iter().enumerate() {
+        for triangle in chart.triangles.iter() {
+            for &index in patch.triangles.get(*triangle).into_iter().flatten() {
+                vertex_charts.insert(index, chart_index);
+            }
+        }
+    }
+
+    // Every original vertex with all of its clones, a vertex at a corner of a cell could be
+    // shared by up to four tiles.
+    let mut copies = BTreeMap::<u32, Vec<u32>>::new();
+    for &[original, clone] in cut_vertices {
+        copies
+            .entry(original)
+            .or_insert_with(|| vec![original])
+            .push(clone);
+    }
+    let mut tiles = BTreeMap::<[usize; 2], Vec<[u32; 2]>>::new();
+    for copies in copies.values() {
+        for (i, &a) in copies.iter().enumerate() {
+            for &b in copies[i + 1..].iter() {
+                let (Some(&chart_a), Some(&chart_b)) =
+                    (vertex_charts.get(&a), vertex_charts.get(&b))
+                else {
+                    continue;
+                };
+                if chart_a < chart_b {
+                    tiles.entry([chart_a, chart_b]).or_default().push([a, b]);
+                } else if chart_b < chart_a {
+                    tiles.entry([chart_b, chart_a]).or_default().push([b, a]);
+                }
+            }
+        }
+    }
+    patch.pack_stats.tile_adjacency = tiles
+        .into_iter()
+        .map(|(charts, vertices)| TileAdjacency { charts, vertices })
+        .collect();
+}
+
+#[cfg(test)]
+mod test {
+    use crate::UvGenOptions;
+    use nalgebra::Vector3;
+
+    /// A large floor, that consists of 8x8 quads.
+    fn floor() -> (Vec<Vector3<f32>>, Vec<[u32; 3]>) {
+        let mut vertices = Vec::new();
+        let mut triangles = Vec::new();
+        for z in 0..9 {
+            for x in 0..9 {
+                vertices.push(Vector3::new(x as f32, 0.0, z as f32));
+            }
+        }
+        for z in 0..8 {
+            for x in 0..8 {
+                let (a, b) = (z * 9 + x, z * 9 + x + 1);
+                let (c, d) = (b + 9, a + 9);
+                triangles.extend([[a, d, c], [a, c, b]]);
+            }
+        }
+        (vertices, triangles)
+    }
+
+    #[test]
+    fn test_split_large_charts() {
+        let (vertices, triangles) = floor();
+        let options = UvGenOptions {
+            spacing: 0.0,
+            resolution: 1024,
+            virtual_page_size: 128,
+            // Page covers 2x2 quads.
+            texel_density: 64.0,
+            ..Default::default()
+        };
+        let patch = crate::generate_uvs_with_options(
+            vertices.iter().cloned(),
+            triangles.iter().cloned(),
+            &options,
+        )
+        .unwrap();
+
+        assert_eq!(patch.pack_stats.chart_count, 16);
+        let max_extent = super::max_chart_extent(&options).unwrap();
+        for chart in patch.pack_stats.charts.iter() {
+            let (mut min, mut max) = (Vector3::repeat(f32::MAX), Vector3::repeat(-f32::MAX));
+            for &triangle in chart.triangles.iter() {
+                for &index in patch.triangles[triangle].iter() {
+                    let source = patch.source_vertex(index).unwrap() as usize;
+                    min = min.inf(&vertices[source]);
+                    max = max.sup(&vertices[source]);
+                }
+            }
+            assert!((max - min).max() <= max_extent + 1.0e-5);
+        }
+    }
+
+    #[test]
+    fn test_tile_adjacency() {
+        let (vertices, triangles) = floor();
+        let patch = crate::generate_uvs_with_options(
+            vertices.iter().cloned(),
+            triangles.iter().cloned(),
+            &UvGenOptions {
+                max_chart_size: 2.0,
+                ..Default::default()
+            },
+        )
+        .unwrap();
+        assert_eq!(patch.pack_stats.chart_count, 16);
+
+        // Tiles of a 4x4 grid share 24 edges.
+        let tiles = &patch.pack_stats.tile_adjacency;
+        let edges = tiles.iter().filter(|tiles| tiles.vertices.len() == 3);
+        assert_eq!(edges.count(), 24);
+        for tiles in tiles.iter() {
+            for &[a, b] in tiles.vertices.iter() {
+                let chart = |index| {
+                    patch.pack_stats.charts.iter().position(|chart| {
+                        chart
+                            .triangles
+                            .iter()
+                            .any(|&triangle| patch.triangles[triangle].contains(&index))
+                    })
+                };
+                assert_eq!(chart(a), Some(tiles.charts[0]));
+                assert_eq!(chart(b), Some(tiles.charts[1]));
+                assert_eq!(patch.source_vertex(a), patch.source_vertex(b));
+            }
+        }
+    }
+}
@@ -0,0 +1,91 @@
+//! Texture coordinates relative to chart rectangles. See [`crate::UvGenOptions::chart_uvs`] for
+//! more info.
+
+use crate::{SurfaceDataPatch, UvGenOptions};
+use nalgebra::Vector2;
+
+/// Texture coordinates of a vertex relative to the rectangle of its chart, see
+/// [`SurfaceDataPatch::chart_uvs`].
+#[derive(Copy, Clone, Debug, Default, PartialEq)]
+#[cfg_attr(
+    feature = "rkyv",
+    derive(rkyv::Archive, rkyv::Serialize, rkyv::Deserialize),
+    archive(check_bytes)
+)]
+pub struct ChartUv {
+    /// Index of the chart in [`crate::PackStats::charts`], or `None` if the vertex does not
+    /// belong to any chart.
+    pub chart: Option<u32>,
+    /// Texture coordinates in the unit square, that is mapped to the rectangle of the chart
+    /// (including its spacing). Zero for vertices without a chart.
+    pub uv: Vector2<f32>,
+}
+
+impl ChartUv {
+    /// Returns atlas texture coordinates for the chart, that was moved to the given rectangle
+    /// (in atlas UV units). With the original rectangle of the chart it gives the texture
+    /// coordinates of the patch (see [`SurfaceDataPatch::second_tex_coords`]).
+    pub fn relocate(&self, position: Vector2<f32>, size: Vector2<f32>) -> Vector2<f32> {
+        position + self.uv.component_mul(&size)
+    }
+}
+
+/// Calculates texture coordinates relative to chart rectangles, if it was requested. Texture
+/// coordinates, charts and triangles of the patch must be set already.
+pub(crate) fn fill_chart_uvs(patch: &mut SurfaceDataPatch, options: &UvGenOptions) {
+    if !options.chart_uvs {
+        return;
+    }
+    patch.chart_uvs = vec![ChartUv::default(); patch.second_tex_coords.len()];
+    for (chart_index, chart) in patch.pack_stats.charts.iter().enumerate() {
+        // Degenerate rectangles have nothing to scale.
+        let inverse_size = chart
+            .rect
+            .size
+            .map(|size| if size > 0.0 { 1.0 / size } else { 0.0 });
+        for triangle in chart.triangles.iter() {
+            let Some(triangle) = patch.triangles.get(*triangle) else {
+                continue;
+            };
+            for &index in triangle {
+                if let (Some(chart_uv), Some(tex_coord)) = (
+                    patch.chart_uvs.get_mut(index as usize),
+                    patch.second_tex_coords.get(index as usize),
+                ) {
+                    *chart_uv = ChartUv {
+                        chart: Some(chart_index as u32),
+                        uv: (tex_coord - chart.rect.position).component_mul(&inverse_size),
+                    };
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::UvGenOptions;
+
+    #[test]
+    fn test_chart_uvs() {
+        let mesh = crate::test_meshes::cube();
+        let patch = crate::generate_uvs_with_options(
+            mesh.vertices.iter().cloned(),
+            mesh.triangles.iter().cloned(),
+            &UvGenOptions {
+                chart_uvs: true,
+                ..Default::default()
+            },
+        )
+        .unwrap();
+        assert_eq!(patch.chart_uvs.len(), patch.second_tex_coords.len());
+
+        for &index in patch.triangles.iter().flatten() {
+            let chart_uv = patch.chart_uvs[index as usize];
+            let chart = &patch.pack_stats.charts[chart_uv.chart.unwrap() as usize];
+            assert!((0.0..=1.0).contains(&chart_uv.uv.x) && (0.0..=1.0).contains(&chart_uv.uv.y));
+            let uv = chart_uv.relocate(chart.rect.position, chart.rect.size);
+            assert!((uv - patch.second_tex_coords[index as usize]).norm() < 1.0e-5);
+        }
+    }
+}
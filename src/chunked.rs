@@ -0,0 +1,378 @@
+//! Chunked generation for very large meshes. The mesh is split into spatially coherent chunks,
+//! every chunk is unwrapped independently and then all the charts are packed together into a
+//! single atlas. Intermediate data of the generator exists only for one chunk at a time (or for
+//! one chunk per thread, with `parallel` feature), which bounds peak memory usage.
+
+use crate::{
+    adjacency, anchor, assign_sentinel_uvs, assign_uvs, chart_split, chart_uvs, double_sided,
+    locality, locks, new_vertex_index, options, pack, padding, pages, parallel, prepare_patch,
+    stats, task::TaskCharts, vertex_position, virtual_pages, weld, winding, Chart, Stage,
+    SurfaceDataPatch, UnwrapTask, UvGenError, UvGenOptions,
+};
+use nalgebra::{Vector2, Vector3};
+use std::time::Instant;
+
+/// Splits the triangles into spatially coherent chunks, each having at most `max_triangles`
+/// triangles. Chunks are formed by recursive median splits along the longest axis of the bounds
+/// of triangle centers.
+fn partition(
+    vertices: &[Vector3<f32>],
+    triangles: &[[u32; 3]],
+    max_triangles: usize,
+) -> Option<Vec<Vec<usize>>> {
+    let centers = triangles
+        .iter()
+        .map(|triangle| {
+            let a = vertices.get(triangle[0] as usize)?;
+            let b = vertices.get(triangle[1] as usize)?;
+            let c = vertices.get(triangle[2] as usize)?;
+            Some((a + b + c).scale(1.0 / 3.0))
+        })
+        .collect::<Option<Vec<_>>>()?;
+    Some(partition_centers(&centers, max_triangles))
+}
+
+/// Splits the triangles with the given centers into spatially coherent chunks, each having at
+/// most `max_triangles` triangles, see [`partition`].
+pub(crate) fn partition_centers(centers: &[Vector3<f32>], max_triangles: usize) -> Vec<Vec<usize>> {
+    let mut chunks = Vec::new();
+    let mut stack = vec![(0..centers.len()).collect::<Vec<_>>()];
+    while let Some(mut chunk) = stack.pop() {
+        if chunk.len() <= max_triangles.max(1) {
+            chunks.push(chunk);
+            continue;
+        }
+
+        let (min, max) = chunk.iter().fold(
+            (Vector3::repeat(f32::MAX), Vector3::repeat(-f32::MAX)),
+            |(min, max), &i| (min.inf(&centers[i]), max.sup(&centers[i])),
+        );
+        let axis = (max - min).imax();
+        let median = chunk.len() / 2;
+        chunk.select_nth_unstable_by(median, |&a, &b| {
+            centers[a][axis].total_cmp(&centers[b][axis])
+        });
+        let second_half = chunk.split_off(median);
+        // Keep the order of chunks stable: first half is processed first.
+        stack.push(second_half);
+        stack.push(chunk);
+    }
+
+    // Keep original order of triangles inside of each chunk, so the results do not depend on
+    // the order of partitioning.
+    for chunk in chunks.iter_mut() {
+        chunk.sort_unstable();
+    }
+
+    chunks
+}
+
+/// Unwraps a single chunk of the mesh.
+fn unwrap_chunk(
+    vertices: &[Vector3<f32>],
+    triangles: &[[u32; 3]],
+    chunk: &[usize],
+    options: &UvGenOptions,
+) -> Result<(Vec<u32>, TaskCharts), UvGenError> {
+    // Map global vertex indices to local ones, local vertices are stored in order of their first
+    // appearance.
+    let mut local_to_global = Vec::new();
+    let mut global_to_local = std::collections::HashMap::new();
+    let local_triangles = chunk
+        .iter()
+        .map(|&triangle_index| {
+            triangles[triangle_index].map(|index| {
+                *global_to_local.entry(index).or_insert_with(|| {
+                    local_to_global.push(index);
+                    local_to_global.len() as u32 - 1
+                })
+            })
+        })
+        .collect::<Vec<_>>();
+
+    let local_vertices = local_to_global
+        .iter()
+        .map(|&index| vertices.get(index as usize).cloned())
+        .collect::<Option<Vec<_>>>()
+        .ok_or(UvGenError::DegenerateInput)?;
+
+    // Locked vertices, normals and importance of triangles must be addressed by local indices as
+    // well.
+    let options = UvGenOptions {
+        triangle_normals: if options.triangle_normals.is_empty() {
+            Vec::new()
+        } else {
+            // Zero normals are replaced by the calculated ones.
+            chunk
+                .iter()
+                .map(|&triangle_index| {
+                    options
+                        .triangle_normals
+                        .get(triangle_index)
+                        .cloned()
+                        .unwrap_or_default()
+                })
+                .collect()
+        },
+        triangle_importance: if options.triangle_importance.is_empty() {
+            Vec::new()
+        } else {
+            // Missing values are replaced by the regular importance.
+            chunk
+                .iter()
+                .map(|&triangle_index| {
+                    options
+                        .triangle_importance
+                        .get(triangle_index)
+                        .cloned()
+                        .unwrap_or(1.0)
+                })
+                .collect()
+        },
+        locked_vertices: if options.locked_vertices.is_empty() {
+            Vec::new()
+        } else {
+            local_to_global
+                .iter()
+                .map(|&index| locks::is_locked(&options.locked_vertices, index))
+                .collect()
+        },
+        ..options.clone()
+    };
+    let mut task = UnwrapTask::new(
+        local_vertices.into_iter(),
+        local_triangles.into_iter(),
+        options,
+    );
+    if !task.run_until(Stage::Packing) {
+        return Err(task.failure().unwrap_or(UvGenError::DegenerateInput));
+    }
+
+    Ok((local_to_global, task.into_charts()))
+}
+
+/// Generates UVs for the mesh by splitting it into chunks with at most
+/// [`UvGenOptions::max_chunk_triangles`] triangles. Returns the reason of the failure, if it
+/// fails.
+pub(crate) fn generate_uvs_chunked(
+    vertices: Vec<Vector3<f32>>,
+    triangles: Vec<[u32; 3]>,
+    options: &UvGenOptions,
+) -> Result<SurfaceDataPatch, UvGenError> {
+    trace_span!("chunked");
+
+    let mut triangles = triangles;
+    if let Some(tolerance) = options.weld {
+        weld::weld(&vertices, &mut triangles, tolerance);
+    }
+    // Welding is done for the whole mesh, otherwise vertices at the chunk boundaries won't be
+    // welded.
+    let options = &UvGenOptions {
+        weld: None,
+        ..options.clone()
+    };
+    let chunks = partition(&vertices, &triangles, options.max_chunk_triangles)
+        .ok_or(UvGenError::DegenerateInput)?;
+
+    let mut patch = SurfaceDataPatch::default();
+    let mut projections = vec![[Vector2::default(); 3]; triangles.len()];
+    let mut meshes = Vec::<Chart>::new();
+    let mut claimed = vec![false; vertices.len()];
+    let mut cut_vertices = Vec::new();
+    let mut stage_timings = Vec::new();
+
+    // Chunks are unwrapped in batches of one chunk per thread and every batch is merged before
+    // the next one starts, so intermediate data exists only for the chunks of a single batch.
+    // Triangles are remapped in place: every triangle belongs to a single chunk, which reads only
+    // its own triangles, and no chunk is merged before it is unwrapped.
+    for batch in chunks.chunks(parallel::thread_count()) {
+        let results = parallel::map_balanced(batch, |chunk| {
+            unwrap_chunk(&vertices, &triangles, chunk, options)
+        });
+        for (chunk, result) in batch.iter().zip(results) {
+            let (local_to_global, charts) = result?;
+            stats::merge_stages(&mut stage_timings, &charts.stage_timings);
+
+            // Every local vertex (including the ones that were added at seams) is mapped to
+            // either its source vertex, if it is not used by any other chunk, or to a new clone.
+            let mut local_vertex_map = Vec::with_capacity(charts.vertex_count);
+            for local_index in 0..charts.vertex_count {
+                let source = match local_index.checked_sub(local_to_global.len()) {
+                    Some(added) => {
+                        let source = charts.additional_vertices.get(added);
+                        local_to_global[*source.ok_or(UvGenError::DegenerateInput)? as usize]
+                    }
+                    None => local_to_global[local_index],
+                };
+                let claimed = claimed
+                    .get_mut(source as usize)
+                    .ok_or(UvGenError::DegenerateInput)?;
+                if *claimed {
+                    local_vertex_map.push(
+                        new_vertex_index(vertices.len() + patch.additional_vertices.len())
+                            .ok_or(UvGenError::TooManyVertices)?,
+                    );
+                    patch.additional_vertices.push(source);
+                } else {
+                    *claimed = true;
+                    local_vertex_map.push(source);
+                }
+            }
+
+            for pair in charts.cut_vertices.iter() {
+                let clone = |index: u32| local_vertex_map.get(index as usize).cloned();
+                cut_vertices.push([
+                    clone(pair[0]).ok_or(UvGenError::DegenerateInput)?,
+                    clone(pair[1]).ok_or(UvGenError::DegenerateInput)?,
+                ]);
+            }
+
+            for (&global_triangle, (local_triangle, projection)) in chunk
+                .iter()
+                .zip(charts.triangles.iter().zip(charts.projections))
+            {
+                triangles[global_triangle] =
+                    local_triangle.map(|index| local_vertex_map[index as usize]);
+                projections[global_triangle] = projection;
+            }
+
+            meshes.extend(charts.meshes.into_iter().map(|mut mesh| {
+                for triangle in mesh.triangles.iter_mut() {
+                    *triangle = chunk[*triangle];
+                }
+                mesh
+            }));
+        }
+    }
+
+    // Pack all charts together.
+    let packing_start = Instant::now();
+    let mut packing_attempts = 0;
+    let mut upscale_passes = 0;
+    let (packing, hashes) = loop {
+        if options::is_cancelled(options) {
+            return Err(UvGenError::Cancelled);
+        }
+        pack::add_extra_charts(&mut meshes, options);
+        if let Err(error) = pack::validate_spacing(meshes.len(), options.spacing) {
+            return Err(UvGenError::PackingFailed(error));
+        }
+        pack::sort_meshes(&mut meshes);
+        if options.spatial_locality {
+            locality::sort_by_locality(&mut meshes, &triangles, |index| {
+                vertex_position(&vertices, &patch.additional_vertices, index)
+            });
+        }
+        // Pages reorder meshes, so they must be packed before hashing.
+        let page_packing = if options.page_count > 1 {
+            Some(pages::pack_pages(
+                &mut meshes,
+                &triangles,
+                |index| vertex_position(&vertices, &patch.additional_vertices, index),
+                options,
+            ))
+        } else {
+            virtual_pages::page_extent(options)
+                .map(|extent| virtual_pages::pack_virtual_pages(&meshes, extent, options))
+        };
+        let mut hashes = if options.stable_anchors {
+            let position = |index| vertex_position(&vertices, &patch.additional_vertices, index);
+            meshes
+                .iter()
+                .map(|mesh| anchor::chart_hash(mesh, &triangles, position))
+                .collect::<Option<Vec<_>>>()
+                .ok_or(UvGenError::DegenerateInput)?
+        } else {
+            Vec::new()
+        };
+        let mut packing = page_packing.unwrap_or_else(|| {
+            options
+                .previous_layout
+                .as_ref()
+                .filter(|_| options.stable_anchors)
+                .and_then(|layout| anchor::pack_anchored(&meshes, &hashes, layout, options))
+                .unwrap_or_else(|| pack::pack_meshes(&meshes, options))
+        });
+        pack::validate_packing(&packing, &meshes).map_err(UvGenError::PackingFailed)?;
+        pack::take_extra_rects(&mut meshes, &mut hashes, &mut packing, options.spacing);
+        packing_attempts += packing.attempts;
+
+        if pack::collapse_tiny_charts(&mut meshes, &mut projections, packing.scale, options) {
+            continue;
+        }
+        if upscale_passes < pack::MAX_UPSCALE_PASSES
+            && pack::upscale_thin_charts(&mut meshes, &mut projections, packing.scale, options)
+        {
+            upscale_passes += 1;
+        } else {
+            pack::sort_by_key(&mut meshes, &mut hashes, &mut packing);
+            pack::fill_atlas(&meshes, &mut packing, options);
+            break (packing, hashes);
+        }
+    };
+
+    let assignment_start = Instant::now();
+    let packing_time = assignment_start - packing_start;
+    let vertex_count = vertices.len() + patch.additional_vertices.len();
+    prepare_patch(&mut patch, vertex_count, options);
+    for (mesh_index, (mesh, rect)) in meshes.iter().zip(packing.rects.iter()).enumerate() {
+        assign_uvs(
+            &mut patch,
+            mesh_index,
+            mesh,
+            rect,
+            &packing,
+            &triangles,
+            &projections,
+            options,
+        )
+        .ok_or(UvGenError::DegenerateInput)?;
+    }
+
+    patch.pack_stats = packing.stats(&meshes, &triangles, &projections, |index| {
+        vertex_position(&vertices, &patch.additional_vertices, index)
+    });
+    stats::flag_thin_charts(&mut patch.pack_stats.charts, options);
+    stats::fill_texel_rects(&mut patch.pack_stats.charts, options);
+    patch.pack_stats.needle_triangles = stats::needle_triangles(&triangles, options, |index| {
+        vertex_position(&vertices, &patch.additional_vertices, index)
+    });
+    (patch.pack_stats.seam_count, patch.pack_stats.seam_length) =
+        stats::seam_stats(&vertices, &patch.additional_vertices, &triangles);
+    if options.stable_anchors {
+        patch.pack_stats.anchor_layout = Some(anchor::anchor_layout(&packing, &hashes));
+    }
+    patch.triangle_pages = pages::triangle_pages(&meshes, &packing, triangles.len());
+    patch.triangles = triangles;
+    if options.triangle_projections {
+        patch.triangle_projections = projections;
+    }
+    assign_sentinel_uvs(&mut patch, options);
+    chart_uvs::fill_chart_uvs(&mut patch, options);
+    double_sided::handle_double_sided(&mut patch, &vertices, options);
+    adjacency::fill_adjacency(&mut patch, options);
+    padding::verify_padding(&mut patch, options);
+    winding::find_flipped_triangles(&mut patch);
+    patch.pack_stats.min_texel_density = stats::min_texel_density(&patch, options, |index| {
+        vertex_position(&vertices, &patch.additional_vertices, index)
+    });
+    locks::find_split_locked_vertices(&mut patch, &options.locked_vertices);
+    chart_split::fill_tile_adjacency(&mut patch, &cut_vertices);
+    if options.stage_timings {
+        stats::record_stage(
+            &mut stage_timings,
+            Stage::Packing,
+            packing_time,
+            packing_attempts,
+        );
+        stats::record_stage(
+            &mut stage_timings,
+            Stage::Assignment,
+            assignment_start.elapsed(),
+            meshes.len(),
+        );
+        patch.pack_stats.stage_timings = stage_timings;
+    }
+
+    Ok(patch)
+}
@@ -0,0 +1,116 @@
+//! Reusable buffers of the generator. See [`UvGenContext`] docs for more info.
+
+use crate::{
+    chunked, index, mips, realtime, task::TaskBuffers, SurfaceDataPatch, UnwrapTask, UvGenOptions,
+    UvIndex,
+};
+use nalgebra::Vector3;
+use std::{task::Poll, time::Duration};
+
+/// Scratch buffers of the generator (vertices with their clones, projections of the triangles,
+/// charts, buffers of the packer and so on), that are kept between generations. Generating UVs
+/// for lots of meshes (for example, on level load) with the same context avoids most of the
+/// allocations, that [`crate::generate_uvs_with_options`] makes on every call:
+///
+/// ```rust
+/// let mut context = uvgen::UvGenContext::new();
+/// for mesh in [uvgen::test_meshes::cube(), uvgen::test_meshes::uv_sphere(8, 4)] {
+///     let patch = context
+///         .generate_uvs(
+///             mesh.vertices.iter().cloned(),
+///             mesh.triangles.iter().cloned(),
+///             &Default::default(),
+///         )
+///         .unwrap();
+///     assert!(patch.pack_stats.chart_count > 0);
+/// }
+/// ```
+///
+/// The results are exactly the same as the ones of [`crate::generate_uvs_with_options`]. The
+/// buffers grow up to the size of the largest mesh and are never shrunk. Chunked mode (see
+/// [`UvGenOptions::max_chunk_triangles`]) does not use the buffers.
+#[derive(Default)]
+pub struct UvGenContext {
+    buffers: TaskBuffers,
+}
+
+impl UvGenContext {
+    /// Creates new context with empty buffers.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Generates UV map for the given vertices and triangles using the given options, like
+    /// [`crate::generate_uvs_with_options`], but reuses the buffers of the context.
+    pub fn generate_uvs<I: UvIndex>(
+        &mut self,
+        vertices: impl Iterator<Item = Vector3<f32>>,
+        triangles: impl Iterator<Item = [I; 3]>,
+        options: &UvGenOptions,
+    ) -> Option<SurfaceDataPatch> {
+        trace_span!("generate_uvs");
+
+        let options = realtime::bounded_options(mips::with_mip_padding(options.clone()));
+        let triangles = index::widen(triangles).ok()?;
+        let patch =
+            if options.max_chunk_triangles > 0 && triangles.len() > options.max_chunk_triangles {
+                chunked::generate_uvs_chunked(vertices.collect(), triangles, &options).ok()
+            } else {
+                self.run(vertices, triangles.into_iter(), options)
+            };
+        index::check_vertex_count::<I>(patch?).ok()
+    }
+
+    fn run(
+        &mut self,
+        vertices: impl Iterator<Item = Vector3<f32>>,
+        triangles: impl Iterator<Item = [u32; 3]>,
+        options: UvGenOptions,
+    ) -> Option<SurfaceDataPatch> {
+        let buffers = std::mem::take(&mut self.buffers);
+        let mut task = UnwrapTask::with_buffers(vertices, triangles, options, buffers);
+        let patch = loop {
+            if let Poll::Ready(patch) = task.step(Duration::MAX) {
+                break patch;
+            }
+        };
+        self.buffers = task.into_buffers();
+        patch
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::UvGenOptions;
+
+    #[test]
+    fn test_context() {
+        let meshes = [
+            crate::test_meshes::uv_sphere(8, 4),
+            crate::test_meshes::cube(),
+            crate::test_meshes::uv_sphere(16, 8),
+        ];
+        let options = UvGenOptions {
+            pack_restarts: 2,
+            ..Default::default()
+        };
+        let mut context = super::UvGenContext::new();
+        for mesh in meshes.iter() {
+            let expected = crate::generate_uvs_with_options(
+                mesh.vertices.iter().cloned(),
+                mesh.triangles.iter().cloned(),
+                &options,
+            )
+            .unwrap();
+            let patch = context
+                .generate_uvs(
+                    mesh.vertices.iter().cloned(),
+                    mesh.triangles.iter().cloned(),
+                    &options,
+                )
+                .unwrap();
+            assert_eq!(patch.to_golden_string(), expected.to_golden_string());
+            assert_eq!(patch.pack_stats.charts, expected.pack_stats.charts);
+        }
+    }
+}
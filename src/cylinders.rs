@@ -0,0 +1,267 @@
+//! Cylindrical unwrapping of pipe-like parts of a mesh. See
+//! [`crate::UvGenOptions::cylinder_unwrap`] for more info.
+
+use crate::{frames, UvBox};
+use nalgebra::{Matrix3, SymmetricEigen, Vector2, Vector3};
+use std::f32::consts::{PI, TAU};
+
+/// Maximal absolute cosine between the normal of a side triangle and the axis of a cylinder.
+const SIDE_COSINE: f32 = 0.25;
+
+/// Minimal absolute cosine between the normal of a cap triangle and the axis of a cylinder.
+const CAP_COSINE: f32 = 0.95;
+
+/// Side of a cylinder must cover at least this fraction of its area.
+const MIN_SIDE_FRACTION: f32 = 0.5;
+
+/// Normals of the side must point in every one of this amount of sectors around the axis,
+/// otherwise the side does not wrap around the axis (for example, it is flat) or it is a prism
+/// with just a few sides (for example, a box), that is better handled by box mapping.
+const SIDE_SECTORS: usize = 8;
+
+/// Side of the box, that a triangle of a cylinder is put on, and its projection.
+pub(crate) type CylinderMapping = (usize, [Vector2<f32>; 3]);
+
+/// A triangle of a connected part of a mesh.
+#[derive(Copy, Clone)]
+struct PartTriangle {
+    index: usize,
+    points: [Vector3<f32>; 3],
+    /// Unit normal, or zero for degenerate triangles.
+    normal: Vector3<f32>,
+    /// Doubled area.
+    area: f32,
+}
+
+impl PartTriangle {
+    fn center(&self) -> Vector3<f32> {
+        (self.points[0] + self.points[1] + self.points[2]) / 3.0
+    }
+}
+
+/// Cylinder, that a connected part of a mesh was recognized as.
+struct Cylinder {
+    center: Vector3<f32>,
+    axis: Vector3<f32>,
+    tangent: Vector3<f32>,
+    bitangent: Vector3<f32>,
+    radius: f32,
+}
+
+impl Cylinder {
+    fn angle(&self, point: &Vector3<f32>) -> f32 {
+        let offset = point - self.center;
+        offset.dot(&self.bitangent).atan2(offset.dot(&self.tangent))
+    }
+
+    /// Unwraps the side triangle. Triangles with the center in the upper half (by angle) go to
+    /// one side of the box and the rest of them to the other side, so the side of a cylinder
+    /// becomes two strips, which are split along two lines parallel to the axis.
+    fn map_side(&self, points: [Vector3<f32>; 3]) -> CylinderMapping {
+        let center_angle = self.angle(&((points[0] + points[1] + points[2]) / 3.0));
+        let (face, reference) = if center_angle >= 0.0 {
+            (0, 0.5 * PI)
+        } else {
+            (1, -0.5 * PI)
+        };
+        let projection = points.map(|point| {
+            let mut angle = self.angle(&point);
+            // Every angle of a strip must be close to its middle, otherwise triangles that
+            // cross the `-PI` angle would be torn apart.
+            if angle - reference > PI {
+                angle -= TAU;
+            } else if angle - reference < -PI {
+                angle += TAU;
+            }
+            Vector2::new(angle * self.radius, (point - self.center).dot(&self.axis))
+        });
+        (face, projection)
+    }
+
+    /// Projects the cap triangle on the plane, that is perpendicular to the axis. Caps facing the
+    /// opposite directions go to different sides of the box.
+    fn map_cap(&self, points: [Vector3<f32>; 3], normal: &Vector3<f32>) -> CylinderMapping {
+        let project = |point: &Vector3<f32>| {
+            let offset = point - self.center;
+            Vector2::new(offset.dot(&self.tangent), offset.dot(&self.bitangent))
+        };
+        if normal.dot(&self.axis) >= 0.0 {
+            (2, points.map(|point| project(&point)))
+        } else {
+            // Mirror the projection, so the triangles keep their winding.
+            (3, points.map(|point| project(&point).yx()))
+        }
+    }
+}
+
+/// Tries to recognize a cylinder in the given triangles of a connected part of a mesh. Every
+/// triangle must be either on the side of the cylinder (its normal is perpendicular to the axis)
+/// or on a cap (its normal is parallel to the axis).
+fn recognize(triangles: &[PartTriangle]) -> Option<Cylinder> {
+    // The axis is the direction, that is the least aligned with the normals.
+    let covariance = triangles.iter().fold(Matrix3::zeros(), |covariance, t| {
+        covariance + t.normal * t.normal.transpose() * t.area
+    });
+    let eigen = SymmetricEigen::new(covariance);
+    let axis = eigen
+        .eigenvectors
+        .column(eigen.eigenvalues.imin())
+        .into_owned()
+        .try_normalize(f32::EPSILON)?;
+
+    let mut tangent = Vector3::zeros();
+    tangent[axis.iamin()] = 1.0;
+    let tangent = (tangent - axis * tangent.dot(&axis)).try_normalize(f32::EPSILON)?;
+    let bitangent = axis.cross(&tangent);
+
+    let (mut side_area, mut total_area) = (0.0, 0.0);
+    let mut side_centers = Vector3::zeros();
+    let mut sectors = [false; SIDE_SECTORS];
+    for triangle in triangles {
+        let cosine = triangle.normal.dot(&axis).abs();
+        total_area += triangle.area;
+        if cosine <= SIDE_COSINE {
+            side_area += triangle.area;
+            side_centers += triangle.center() * triangle.area;
+            let normal = triangle.normal;
+            let angle = normal.dot(&bitangent).atan2(normal.dot(&tangent)) + PI;
+            let sector = (angle / TAU * SIDE_SECTORS as f32) as usize;
+            sectors[sector.min(SIDE_SECTORS - 1)] = true;
+        } else if cosine < CAP_COSINE {
+            return None;
+        }
+    }
+    if side_area <= 0.0 || side_area < MIN_SIDE_FRACTION * total_area || sectors.contains(&false) {
+        return None;
+    }
+
+    let center = side_centers / side_area;
+    let radius = triangles
+        .iter()
+        .filter(|triangle| triangle.normal.dot(&axis).abs() <= SIDE_COSINE)
+        .map(|triangle| {
+            let offset = triangle.center() - center;
+            (offset - axis * offset.dot(&axis)).norm() * triangle.area
+        })
+        .sum::<f32>()
+        / side_area;
+    (radius > f32::EPSILON).then_some(Cylinder {
+        center,
+        axis,
+        tangent,
+        bitangent,
+        radius,
+    })
+}
+
+/// Finds connected parts of the mesh, that are (nearly) cylinders, and unwraps them. Returns the
+/// side of the box and the projection of every triangle of such parts, or `None` for the
+/// triangles of the other parts. Returns `None` if a triangle references a vertex that does not
+/// exist.
+pub(crate) fn cylinder_mappings(
+    vertices: &[Vector3<f32>],
+    triangles: &[[u32; 3]],
+) -> Option<Vec<Option<CylinderMapping>>> {
+    let (triangle_parts, part_count) = frames::connected_parts(vertices, triangles)?;
+    let mut parts = vec![Vec::new(); part_count];
+    for (index, (triangle, &part)) in triangles.iter().zip(&triangle_parts).enumerate() {
+        let points = triangle.map(|index| vertices[index as usize]);
+        let normal = (points[1] - points[0]).cross(&(points[2] - points[0]));
+        parts[part as usize].push(PartTriangle {
+            index,
+            points,
+            // Degenerate triangles have no direction, they follow the side.
+            normal: normal.try_normalize(f32::EPSILON).unwrap_or_default(),
+            area: normal.norm(),
+        });
+    }
+
+    let mut mappings = vec![None; triangles.len()];
+    for part in parts {
+        let Some(cylinder) = recognize(&part) else {
+            continue;
+        };
+        for triangle in part {
+            let normal = triangle.normal;
+            mappings[triangle.index] = Some(if normal.dot(&cylinder.axis).abs() <= SIDE_COSINE {
+                cylinder.map_side(triangle.points)
+            } else {
+                cylinder.map_cap(triangle.points, &normal)
+            });
+        }
+    }
+    Some(mappings)
+}
+
+/// Moves the triangles of the cylinders to their sides of the box and replaces their
+/// projections. Triangles, that do not belong to any chart, are left untouched.
+pub(crate) fn apply(uv_box: &mut UvBox, mappings: &[Option<CylinderMapping>]) {
+    let mut moved = mappings.iter().map(Option::is_some).collect::<Vec<_>>();
+    for &triangle_index in uv_box.excluded.iter() {
+        if let Some(moved) = moved.get_mut(triangle_index) {
+            *moved = false;
+        }
+    }
+    let is_moved = |triangle_index: &usize| moved.get(*triangle_index).cloned().unwrap_or(false);
+    for face_triangles in uv_box.faces.iter_mut() {
+        face_triangles.retain(|triangle_index| !is_moved(triangle_index));
+    }
+    uv_box
+        .isolated
+        .retain(|triangle_index| !is_moved(triangle_index));
+    for (triangle_index, mapping) in mappings.iter().enumerate() {
+        if let (Some((face, projection)), true) = (mapping, is_moved(&triangle_index)) {
+            uv_box.faces[*face].push(triangle_index);
+            uv_box.projections[triangle_index] = *projection;
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::UvGenOptions;
+    use nalgebra::{UnitQuaternion, Vector3};
+
+    #[test]
+    fn test_cylinder_unwrap() {
+        // A long pipe, that is not aligned with any axis.
+        let mut mesh = crate::test_meshes::cylinder(0.1, 4.0, 16);
+        let rotation = UnitQuaternion::from_euler_angles(0.6, 0.3, 0.8);
+        for vertex in mesh.vertices.iter_mut() {
+            *vertex = rotation * *vertex + Vector3::new(1.0, 2.0, 3.0);
+        }
+        let generate = |cylinder_unwrap| {
+            crate::generate_uvs_with_options(
+                mesh.vertices.iter().cloned(),
+                mesh.triangles.iter().cloned(),
+                &UvGenOptions {
+                    cylinder_unwrap,
+                    ..Default::default()
+                },
+            )
+            .unwrap()
+        };
+
+        let fragmented = generate(false);
+        let patch = generate(true);
+        // Two strips of the side and two caps, which have their own vertices.
+        assert_eq!(patch.pack_stats.chart_count, 4);
+        assert!(fragmented.pack_stats.chart_count > patch.pack_stats.chart_count);
+
+        // Triangles of the strips must keep their area (up to the uniform scale).
+        let strips = patch
+            .pack_stats
+            .charts
+            .iter()
+            .filter(|chart| chart.triangles.iter().all(|triangle| triangle % 4 < 2))
+            .map(|chart| chart.world_to_uv_ratio())
+            .collect::<Vec<_>>();
+        assert_eq!(strips.len(), 2);
+        assert!((strips[0] - strips[1]).abs() < 0.05 * strips[0]);
+
+        // A cube is not a cylinder.
+        let cube = crate::test_meshes::cube();
+        let mappings = super::cylinder_mappings(&cube.vertices, &cube.triangles).unwrap();
+        assert!(mappings.iter().all(Option::is_none));
+    }
+}
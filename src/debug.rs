@@ -0,0 +1,48 @@
+//! Debug data of the generator, that could be used to find out why unwrap results look wrong.
+
+use crate::{FacePlane, Stage, UnwrapTask, UvGenOptions};
+use nalgebra::Vector3;
+
+/// Returns a side of the box for every triangle of the mesh, exactly as the generator would
+/// classify it with the given options. Triangles, that were split into their own charts (see
+/// [`UvGenOptions::max_stretch`]), do not belong to any side and have `None` plane:
+///
+/// ```rust
+/// # use nalgebra::Vector3;
+/// use uvgen::FacePlane;
+///
+/// let vertices = [
+///     Vector3::new(0.0, 0.0, 0.0),
+///     Vector3::new(1.0, 0.0, 0.0),
+///     Vector3::new(0.0, 1.0, 0.0),
+/// ];
+/// let triangles = [[0, 1, 2]];
+/// let planes = uvgen::face_planes(
+///     vertices.iter().cloned(),
+///     triangles.iter().cloned(),
+///     &Default::default(),
+/// )
+/// .unwrap();
+/// assert_eq!(planes, [Some(FacePlane::PositiveZ)]);
+/// ```
+///
+/// Returns `None` if the input data is invalid (for example, a triangle references a vertex that
+/// does not exist).
+pub fn face_planes(
+    vertices: impl Iterator<Item = Vector3<f32>>,
+    triangles: impl Iterator<Item = [u32; 3]>,
+    options: &UvGenOptions,
+) -> Option<Vec<Option<FacePlane>>> {
+    let mut task = UnwrapTask::new(vertices, triangles, options.clone());
+    if !task.run_until(Stage::Seams) {
+        return None;
+    }
+
+    let mut planes = vec![None; task.triangle_count()];
+    for plane in FacePlane::ALL {
+        for &triangle_index in task.face_triangles(plane) {
+            planes[triangle_index] = Some(plane);
+        }
+    }
+    Some(planes)
+}
@@ -0,0 +1,186 @@
+//! Detection of double-sided geometry. See [`crate::UvGenOptions::double_sided_handling`] for more
+//! info.
+
+use crate::{vertex_position, DoubleSidedHandling, SurfaceDataPatch, UvGenOptions};
+use nalgebra::Vector3;
+use std::collections::HashMap;
+
+/// Returns a key of a position, that is the same for positions that compare equal.
+fn position_key(position: &Vector3<f32>) -> [u32; 3] {
+    // Adding zero turns negative zeros into positive ones.
+    [position.x, position.y, position.z].map(|c| (c + 0.0).to_bits())
+}
+
+/// Returns pairs of back-to-back triangles: triangles with the same corner positions, but the
+/// opposite winding. The first triangle of every pair precedes the second one, every triangle is
+/// in at most one pair. `position` must return a position of a vertex with the given index.
+pub(crate) fn find_twins(
+    triangles: &[[u32; 3]],
+    position: impl Fn(u32) -> Option<Vector3<f32>>,
+) -> Vec<[usize; 2]> {
+    // Triangles without a twin (yet) keyed by sorted positions of their corners.
+    let mut unpaired = HashMap::<[[u32; 3]; 3], Vec<(usize, bool)>>::new();
+    let mut twins = Vec::new();
+    for (triangle_index, triangle) in triangles.iter().enumerate() {
+        let Some(keys) = triangle
+            .iter()
+            .map(|&index| position(index).map(|p| position_key(&p)))
+            .collect::<Option<Vec<_>>>()
+        else {
+            continue;
+        };
+        if keys[0] == keys[1] || keys[1] == keys[2] || keys[0] == keys[2] {
+            continue;
+        }
+        // Triangles with the opposite winding have the opposite order of the corners, that
+        // follow the smallest one.
+        let first = (0..3).min_by_key(|&i| keys[i]).unwrap_or_default();
+        let winding = keys[(first + 1) % 3] < keys[(first + 2) % 3];
+        let mut sorted = [keys[0], keys[1], keys[2]];
+        sorted.sort_unstable();
+
+        let candidates = unpaired.entry(sorted).or_default();
+        if let Some(candidate) = candidates.iter().position(|&(_, w)| w != winding) {
+            let (other, _) = candidates.remove(candidate);
+            twins.push([other, triangle_index]);
+        } else {
+            candidates.push((triangle_index, winding));
+        }
+    }
+    twins.sort_unstable();
+    twins
+}
+
+/// Returns a flag for every triangle, that tells whether the triangle is the second triangle of a
+/// pair of back-to-back triangles, which shares texture coordinates with the first one. It is
+/// empty, unless the sharing is enabled.
+pub(crate) fn shared_twins(
+    vertices: &[Vector3<f32>],
+    triangles: &[[u32; 3]],
+    options: &UvGenOptions,
+) -> Vec<bool> {
+    if options.double_sided_handling != DoubleSidedHandling::Share {
+        return Vec::new();
+    }
+    let mut flags = vec![false; triangles.len()];
+    for [_, twin] in find_twins(triangles, |index| vertices.get(index as usize).cloned()) {
+        flags[twin] = true;
+    }
+    flags
+}
+
+/// Reports back-to-back triangles and copies texture coordinates (and chart colors) of the first
+/// triangle of every pair to the second one, if it was requested. Triangles of the patch must be
+/// set already. Texture coordinates are copied only if the vertices of the second triangle are
+/// not used by any other triangle, so charts of other triangles are never affected. `vertices`
+/// are the source vertices of the mesh.
+pub(crate) fn handle_double_sided(
+    patch: &mut SurfaceDataPatch,
+    vertices: &[Vector3<f32>],
+    options: &UvGenOptions,
+) {
+    if options.double_sided_handling == DoubleSidedHandling::Ignore {
+        return;
+    }
+    let additional_vertices = &patch.additional_vertices;
+    let position = |index| vertex_position(vertices, additional_vertices, index);
+    let twins = find_twins(&patch.triangles, position);
+    if options.double_sided_handling == DoubleSidedHandling::Share {
+        let mut usage = vec![0usize; patch.second_tex_coords.len()];
+        for &index in patch.triangles.iter().flatten() {
+            if let Some(usage) = usage.get_mut(index as usize) {
+                *usage += 1;
+            }
+        }
+        for &[first, second] in twins.iter() {
+            let (first, second) = (patch.triangles[first], patch.triangles[second]);
+            if second
+                .iter()
+                .any(|&index| usage.get(index as usize) != Some(&1))
+            {
+                continue;
+            }
+            for index in second {
+                let key = position(index).map(|p| position_key(&p));
+                let Some(source) = first
+                    .iter()
+                    .find(|&&other| position(other).map(|p| position_key(&p)) == key)
+                else {
+                    continue;
+                };
+                if let Some(&tex_coord) = patch.second_tex_coords.get(*source as usize) {
+                    patch.second_tex_coords[index as usize] = tex_coord;
+                }
+                if let Some(&color) = patch.chart_colors.get(*source as usize) {
+                    patch.chart_colors[index as usize] = color;
+                }
+                if let Some(&chart_uv) = patch.chart_uvs.get(*source as usize) {
+                    patch.chart_uvs[index as usize] = chart_uv;
+                }
+            }
+        }
+    }
+    patch.pack_stats.double_sided_triangles = twins;
+}
+
+#[cfg(test)]
+mod test {
+    use crate::{DoubleSidedHandling, UvGenOptions};
+    use nalgebra::Vector3;
+
+    #[test]
+    fn test_double_sided() {
+        // A quad, that is visible from both sides. The back side has its own vertices, as it
+        // usually is for foliage cards, which need flipped normals.
+        let front = [
+            Vector3::new(0.0, 0.0, 0.0),
+            Vector3::new(1.0, 0.0, 0.0),
+            Vector3::new(1.0, 1.0, 0.0),
+            Vector3::new(0.0, 1.0, 0.0),
+        ];
+        let vertices = front
+            .iter()
+            .chain(front.iter())
+            .cloned()
+            .collect::<Vec<_>>();
+        let triangles: [[u32; 3]; 4] = [[0, 1, 2], [0, 2, 3], [4, 6, 5], [4, 7, 6]];
+        let generate = |double_sided_handling| {
+            crate::generate_uvs_with_options(
+                vertices.iter().cloned(),
+                triangles.iter().cloned(),
+                &UvGenOptions {
+                    double_sided_handling,
+                    ..Default::default()
+                },
+            )
+            .unwrap()
+        };
+
+        let patch = generate(DoubleSidedHandling::Ignore);
+        assert!(patch.pack_stats.double_sided_triangles.is_empty());
+        assert_eq!(patch.pack_stats.chart_count, 2);
+
+        let patch = generate(DoubleSidedHandling::Report);
+        assert_eq!(patch.pack_stats.double_sided_triangles, [[0, 2], [1, 3]]);
+        assert_eq!(patch.pack_stats.chart_count, 2);
+
+        let patch = generate(DoubleSidedHandling::Share);
+        assert_eq!(patch.pack_stats.double_sided_triangles, [[0, 2], [1, 3]]);
+        assert_eq!(patch.pack_stats.chart_count, 1);
+        for [first, second] in [[0, 2], [1, 3]] {
+            for &index in patch.triangles[second].iter() {
+                let source = patch.source_vertex(index).unwrap() as usize;
+                let twin = patch.triangles[first]
+                    .iter()
+                    .find(|&&other| {
+                        vertices[patch.source_vertex(other).unwrap() as usize] == vertices[source]
+                    })
+                    .unwrap();
+                assert_eq!(
+                    patch.second_tex_coords[index as usize],
+                    patch.second_tex_coords[*twin as usize]
+                );
+            }
+        }
+    }
+}
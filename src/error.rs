@@ -0,0 +1,359 @@
+//! Reasons of failures of the generator. See [`crate::try_generate_uvs_with_options`] for more
+//! info.
+
+use crate::{
+    has_repeated_indices, weld, Chart, PackingError, RepeatedIndexHandling, Stage, UvGenOptions,
+};
+use nalgebra::{Vector2, Vector3};
+use std::fmt::{Display, Formatter};
+
+/// Reason why UV generation failed.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum UvGenError {
+    /// A triangle references a vertex, that does not exist.
+    OutOfBoundsIndex {
+        /// Index of the triangle.
+        triangle: usize,
+        /// Index of the vertex, that is out of bounds.
+        index: u64,
+    },
+    /// A triangle references the same vertex more than once (after welding, if it is enabled),
+    /// while [`UvGenOptions::repeated_index_handling`] is [`RepeatedIndexHandling::Fail`].
+    DegenerateTriangle {
+        /// Index of the triangle.
+        triangle: usize,
+    },
+    /// The input can't be unwrapped, because a triangle references a vertex, that does not exist,
+    /// or the same vertex more than once. Validated input reports such triangles as
+    /// [`Self::OutOfBoundsIndex`] or [`Self::DegenerateTriangle`] before the generation starts,
+    /// so this error comes only from the input, that wasn't validated (for example, the input of
+    /// [`crate::UnwrapTask`]).
+    DegenerateInput,
+    /// The mesh has more vertices, than indices of the triangles could address: either the
+    /// patched mesh does not fit into the index type of the input, or an index of the input does
+    /// not fit into `u32` (see the limits of [`crate::generate_uvs`] and [`crate::UvIndex`]).
+    TooManyVertices,
+    /// Some charts could not be packed into the atlas: the spacing is too large for them, or the
+    /// packer gave up before it found a scale, at which every chart fits. Such packings are never
+    /// returned as results, so every chart of a successful result has its place in the atlas.
+    PackingFailed(PackingError),
+    /// The generation was cancelled, see [`UvGenOptions::cancellation`].
+    Cancelled,
+}
+
+impl Display for UvGenError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::OutOfBoundsIndex { triangle, index } => {
+                write!(f, "triangle {triangle} references missing vertex {index}")
+            }
+            Self::DegenerateTriangle { triangle } => {
+                write!(
+                    f,
+                    "triangle {triangle} references the same vertex more than once"
+                )
+            }
+            Self::DegenerateInput => write!(f, "input mesh is degenerate or invalid"),
+            Self::TooManyVertices => {
+                write!(f, "mesh has more vertices than its index type can address")
+            }
+            Self::PackingFailed(error) => write!(f, "packing failed: {error}"),
+            Self::Cancelled => write!(f, "generation was cancelled"),
+        }
+    }
+}
+
+impl std::error::Error for UvGenError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Self::PackingFailed(error) => Some(error),
+            _ => None,
+        }
+    }
+}
+
+/// Data, that was calculated before the generation has failed. It allows tools to show what the
+/// generator managed to do (for example, to draw the charts over the mesh), instead of just
+/// reporting the failure. See [`crate::try_generate_uvs_partial`] and
+/// [`crate::UnwrapTask::take_partial_result`].
+#[derive(Clone, Debug)]
+pub struct PartialResult {
+    /// Stage, at which the generation has failed.
+    pub stage: Stage,
+    /// Vertices of the mesh, including the clones, that were made at seams so far.
+    pub vertices: Vec<Vector3<f32>>,
+    /// Triangles of the mesh, which reference the clones of the vertices, if any.
+    pub triangles: Vec<[u32; 3]>,
+    /// Projections of the triangles, indexed by triangle index. Only the triangles, that were
+    /// mapped before the failure, have projections, so the list could be shorter than the list of
+    /// triangles.
+    pub projections: Vec<[Vector2<f32>; 3]>,
+    /// Charts, that were gathered before the failure. Empty if the generation has failed before
+    /// [`Stage::Charts`] stage.
+    pub charts: Vec<Chart>,
+}
+
+/// Reason why UV generation failed along with the data, that was calculated before the failure.
+#[derive(Clone, Debug)]
+pub struct PartialError {
+    /// Reason of the failure.
+    pub error: UvGenError,
+    /// Data, that was calculated before the failure. `None` if the input is invalid, so the
+    /// generation hasn't started at all, or if the mesh was unwrapped in chunked mode (see
+    /// [`UvGenOptions::max_chunk_triangles`]).
+    pub partial: Option<Box<PartialResult>>,
+}
+
+impl Display for PartialError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match &self.partial {
+            Some(partial) => write!(f, "{} at {:?} stage", self.error, partial.stage),
+            None => self.error.fmt(f),
+        }
+    }
+}
+
+impl std::error::Error for PartialError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        Some(&self.error)
+    }
+}
+
+/// Checks the input of the generator for errors, that make the generation fail regardless of the
+/// geometry. Returns the first error found.
+pub(crate) fn validate(
+    vertices: &[Vector3<f32>],
+    triangles: &[[u32; 3]],
+    options: &UvGenOptions,
+) -> Result<(), UvGenError> {
+    for (triangle_index, triangle) in triangles.iter().enumerate() {
+        if let Some(&index) = triangle
+            .iter()
+            .find(|&&index| index as usize >= vertices.len())
+        {
+            return Err(UvGenError::OutOfBoundsIndex {
+                triangle: triangle_index,
+                index: index.into(),
+            });
+        }
+    }
+
+    if options.repeated_index_handling == RepeatedIndexHandling::Fail {
+        // Welding could make indices of a triangle repeat.
+        let mut welded;
+        let triangles = match options.weld {
+            Some(tolerance) => {
+                welded = triangles.to_vec();
+                weld::weld(vertices, &mut welded, tolerance);
+                &welded
+            }
+            None => triangles,
+        };
+        if let Some(triangle_index) = triangles.iter().position(has_repeated_indices) {
+            return Err(UvGenError::DegenerateTriangle {
+                triangle: triangle_index,
+            });
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod test {
+    use crate::{PackingError, RepeatedIndexHandling, Stage, UvGenError, UvGenOptions};
+
+    #[test]
+    fn test_errors() {
+        let mesh = crate::test_meshes::cube();
+        let generate = |triangles: &[[u32; 3]], options: &UvGenOptions| {
+            crate::try_generate_uvs_with_options(
+                mesh.vertices.iter().cloned(),
+                triangles.iter().cloned(),
+                options,
+            )
+        };
+
+        let patch = generate(&mesh.triangles, &Default::default()).unwrap();
+        assert_eq!(patch.pack_stats.chart_count, 6);
+
+        let mut triangles = mesh.triangles.clone();
+        triangles[3][1] = 100;
+        assert_eq!(
+            generate(&triangles, &Default::default()).unwrap_err(),
+            UvGenError::OutOfBoundsIndex {
+                triangle: 3,
+                index: 100
+            }
+        );
+
+        triangles[3] = [1, 1, 2];
+        let options = UvGenOptions {
+            repeated_index_handling: RepeatedIndexHandling::Fail,
+            ..Default::default()
+        };
+        assert_eq!(
+            generate(&triangles, &options).unwrap_err(),
+            UvGenError::DegenerateTriangle { triangle: 3 }
+        );
+
+        for max_chunk_triangles in [0, 4] {
+            let options = UvGenOptions {
+                spacing: 0.4,
+                max_chunk_triangles,
+                ..Default::default()
+            };
+            assert!(matches!(
+                generate(&mesh.triangles, &options),
+                Err(UvGenError::PackingFailed(
+                    PackingError::SpacingTooLarge { .. }
+                ))
+            ));
+        }
+
+        // The spacing passes the upfront check, but the packer gives up before it finds a scale,
+        // at which every chart fits.
+        for max_chunk_triangles in [0, 4] {
+            let options = UvGenOptions {
+                spacing: 0.15,
+                max_packing_iterations: 1,
+                tiny_mesh_threshold: 0,
+                max_chunk_triangles,
+                ..Default::default()
+            };
+            assert_eq!(
+                generate(&mesh.triangles, &options).unwrap_err(),
+                UvGenError::PackingFailed(PackingError::Exhausted { attempts: 1 })
+            );
+        }
+
+        // 64 charts with this spacing pass the upfront check as well, but the packer bails out
+        // once the charts are tiny compared to the spacing, and suggests a smaller spacing.
+        let soup = crate::test_meshes::random_soup(1, 64);
+        for max_chunk_triangles in [0, 16] {
+            let options = UvGenOptions {
+                spacing: 0.0624,
+                tiny_mesh_threshold: 0,
+                max_chunk_triangles,
+                ..Default::default()
+            };
+            let error = crate::try_generate_uvs_with_options(
+                soup.vertices.iter().cloned(),
+                soup.triangles.iter().cloned(),
+                &options,
+            )
+            .unwrap_err();
+            let UvGenError::PackingFailed(PackingError::SpacingTooLarge { max_spacing, .. }) =
+                error
+            else {
+                panic!("unexpected error {error:?}");
+            };
+            assert!(max_spacing > 0.0 && max_spacing < options.spacing);
+        }
+
+        let cancellation = crate::CancellationToken::new();
+        cancellation.cancel();
+        for max_chunk_triangles in [0, 4] {
+            let options = UvGenOptions {
+                cancellation: Some(cancellation.clone()),
+                max_chunk_triangles,
+                ..Default::default()
+            };
+            assert_eq!(
+                generate(&mesh.triangles, &options).unwrap_err(),
+                UvGenError::Cancelled
+            );
+        }
+    }
+
+    #[test]
+    fn test_partial_results() {
+        let mesh = crate::test_meshes::cube();
+        let generate = |options: &UvGenOptions| {
+            crate::try_generate_uvs_partial(
+                mesh.vertices.iter().cloned(),
+                mesh.triangles.iter().cloned(),
+                options,
+            )
+            .unwrap_err()
+        };
+
+        let options = UvGenOptions {
+            spacing: 0.4,
+            ..Default::default()
+        };
+        let error = generate(&options);
+        assert!(matches!(error.error, UvGenError::PackingFailed(_)));
+        let partial = error.partial.unwrap();
+        assert_eq!(partial.stage, Stage::Packing);
+        assert_eq!(partial.projections.len(), mesh.triangles.len());
+        assert_eq!(partial.triangles.len(), mesh.triangles.len());
+        assert_eq!(partial.vertices.len(), mesh.vertices.len() + 16);
+        assert_eq!(partial.charts.len(), 6);
+
+        // Nothing was calculated before the cancellation.
+        let cancellation = crate::CancellationToken::new();
+        cancellation.cancel();
+        let options = UvGenOptions {
+            cancellation: Some(cancellation),
+            ..Default::default()
+        };
+        let error = generate(&options);
+        assert_eq!(error.error, UvGenError::Cancelled);
+        let partial = error.partial.unwrap();
+        assert_eq!(partial.stage, Stage::BoxMapping);
+        assert!(partial.projections.is_empty() && partial.charts.is_empty());
+
+        // Chunked mode does not keep partial results.
+        let options = UvGenOptions {
+            spacing: 0.4,
+            max_chunk_triangles: 4,
+            ..Default::default()
+        };
+        assert!(generate(&options).partial.is_none());
+    }
+
+    #[test]
+    fn test_task_failures() {
+        let mesh = crate::test_meshes::cube();
+        let run = |triangles: &[[u32; 3]], options: UvGenOptions| {
+            let mut task = crate::UnwrapTask::new(
+                mesh.vertices.iter().cloned(),
+                triangles.iter().cloned(),
+                options,
+            );
+            let failed = !task.run_until(Stage::Assignment);
+            assert_eq!(failed, task.failure().is_some());
+            task.failure()
+        };
+
+        assert_eq!(run(&mesh.triangles, Default::default()), None);
+
+        // Tasks do not validate the input.
+        let mut triangles = mesh.triangles.clone();
+        triangles[3][1] = 100;
+        for options in [
+            UvGenOptions::default(),
+            UvGenOptions {
+                local_frames: true,
+                ..Default::default()
+            },
+            UvGenOptions {
+                cylinder_unwrap: true,
+                ..Default::default()
+            },
+        ] {
+            assert_eq!(run(&triangles, options), Some(UvGenError::DegenerateInput));
+        }
+
+        assert!(matches!(
+            run(
+                &mesh.triangles,
+                UvGenOptions {
+                    spacing: 0.4,
+                    ..Default::default()
+                }
+            ),
+            Some(UvGenError::PackingFailed(_))
+        ));
+    }
+}
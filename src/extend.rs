@@ -0,0 +1,419 @@
+//! Extension of an existing patch with new geometry.
+
+use crate::{
+    adjacency, assign_sentinel_uvs, assign_uvs, chart_split, chart_uvs, double_sided, locality,
+    locks, mips, new_vertex_index, pack, padding, prepare_patch, stats, vertex_position, winding,
+    ChartUv, Rect, Stage, SurfaceDataPatch, TexelDensity, TileAdjacency, UnwrapTask, UvGenOptions,
+};
+use nalgebra::Vector3;
+use std::time::Instant;
+
+/// Generates UVs for new geometry, that was appended to an already unwrapped mesh, and packs them
+/// into the space of the atlas, that was left unoccupied by the existing patch (see
+/// [`crate::PackStats::free_rects`]). UVs of the existing geometry are left untouched. This is
+/// useful for procedural generation, which adds triangles to an already baked surface.
+///
+/// `new_triangles` must reference `new_vertices` starting from zero. The returned patch must be
+/// applied to the source vertices of the existing mesh **followed** by `new_vertices`:
+///
+/// ```rust
+/// # use nalgebra::Vector3;
+/// // An elongated triangle leaves a half of the atlas free.
+/// let mut vertices = vec![
+///     Vector3::new(0.0, 0.0, 0.0),
+///     Vector3::new(2.0, 0.0, 0.0),
+///     Vector3::new(0.0, 1.0, 0.0),
+/// ];
+/// let patch = uvgen::generate_uvs(vertices.iter().cloned(), [[0u32, 1, 2]].into_iter(), 0.005)
+///     .unwrap();
+///
+/// let new_vertices = [
+///     Vector3::new(0.0, 0.0, 1.0),
+///     Vector3::new(0.0, 1.0, 1.0),
+///     Vector3::new(1.0, 0.0, 1.0),
+/// ];
+/// let patch = uvgen::extend_patch(
+///     &patch,
+///     new_vertices.iter().cloned(),
+///     [[0, 1, 2]].into_iter(),
+///     &Default::default(),
+/// )
+/// .unwrap();
+///
+/// vertices.extend_from_slice(&new_vertices);
+/// for &vertex_index in &patch.additional_vertices {
+///     vertices.push(vertices[vertex_index as usize]);
+/// }
+/// assert_eq!(vertices.len(), patch.second_tex_coords.len());
+/// assert_eq!(patch.triangles.len(), 2);
+/// ```
+///
+/// New charts use the same scale as the existing ones, so texel density is preserved. If they do
+/// not fit into the free space, they're scaled down until they do. Returns `None` if the input
+/// data is invalid or if there's no free space left in the atlas.
+pub fn extend_patch(
+    patch: &SurfaceDataPatch,
+    new_vertices: impl Iterator<Item = Vector3<f32>>,
+    new_triangles: impl Iterator<Item = [u32; 3]>,
+    options: &UvGenOptions,
+) -> Option<SurfaceDataPatch> {
+    trace_span!("extend");
+
+    let new_vertices = new_vertices.collect::<Vec<_>>();
+    let new_patch = unwrap_into_free_space(
+        &new_vertices,
+        new_triangles,
+        patch.pack_stats.scale,
+        &patch.pack_stats.free_rects,
+        patch.pack_stats.chart_count,
+        options,
+    )?;
+
+    // Vertices of the combined patch are laid out as: old source vertices, new source vertices,
+    // old additional vertices, new additional vertices.
+    let old_source_count = patch
+        .second_tex_coords
+        .len()
+        .checked_sub(patch.additional_vertices.len())?;
+    let new_source_count = new_vertices.len();
+    let old_additional_count = patch.additional_vertices.len();
+    // Make sure that every index of the combined patch fits into u32.
+    new_vertex_index(new_patch.second_tex_coords.len() + patch.second_tex_coords.len())?;
+    let map_old = |index: u32| {
+        if (index as usize) < old_source_count {
+            index
+        } else {
+            index + new_source_count as u32
+        }
+    };
+    let map_new = |index: u32| {
+        if (index as usize) < new_source_count {
+            index + old_source_count as u32
+        } else {
+            index + (old_source_count + old_additional_count) as u32
+        }
+    };
+
+    let mut combined = SurfaceDataPatch {
+        data_id: patch.data_id,
+        ..Default::default()
+    };
+    combined.additional_vertices = patch
+        .additional_vertices
+        .iter()
+        .map(|&index| map_old(index))
+        .chain(
+            new_patch
+                .additional_vertices
+                .iter()
+                .map(|&index| map_new(index)),
+        )
+        .collect();
+    combined.triangles = patch
+        .triangles
+        .iter()
+        .map(|triangle| triangle.map(map_old))
+        .chain(
+            new_patch
+                .triangles
+                .iter()
+                .map(|triangle| triangle.map(map_new)),
+        )
+        .collect();
+    combined.second_tex_coords = interleave(
+        &patch.second_tex_coords,
+        &new_patch.second_tex_coords,
+        old_source_count,
+        new_source_count,
+    );
+    if !patch.chart_colors.is_empty() && !new_patch.chart_colors.is_empty() {
+        combined.chart_colors = interleave(
+            &patch.chart_colors,
+            &new_patch.chart_colors,
+            old_source_count,
+            new_source_count,
+        );
+    }
+
+    if !patch.chart_uvs.is_empty() && !new_patch.chart_uvs.is_empty() {
+        // Charts of the new patch follow the charts of the old one.
+        let first_chart = patch.pack_stats.charts.len() as u32;
+        let new_chart_uvs = new_patch
+            .chart_uvs
+            .iter()
+            .map(|chart_uv| ChartUv {
+                chart: chart_uv.chart.map(|chart| chart + first_chart),
+                ..*chart_uv
+            })
+            .collect::<Vec<_>>();
+        combined.chart_uvs = interleave(
+            &patch.chart_uvs,
+            &new_chart_uvs,
+            old_source_count,
+            new_source_count,
+        );
+    }
+
+    let new_stats = new_patch.pack_stats;
+    combined.pack_stats = patch.pack_stats.clone();
+    combined.pack_stats.free_rects = new_stats.free_rects;
+    combined.pack_stats.chart_count += new_stats.chart_count;
+    // Only the new part was generated.
+    combined.pack_stats.stage_timings = new_stats.stage_timings;
+    combined
+        .pack_stats
+        .charts
+        .extend(new_stats.charts.into_iter().map(|mut chart| {
+            for triangle in chart.triangles.iter_mut() {
+                *triangle += patch.triangles.len();
+            }
+            chart.key = chart.key.saturating_add(patch.triangles.len());
+            chart
+        }));
+    combined
+        .pack_stats
+        .area_histogram
+        .merge(&new_stats.area_histogram);
+    combined
+        .pack_stats
+        .perimeter_ratio_histogram
+        .merge(&new_stats.perimeter_ratio_histogram);
+    if !patch.triangle_projections.is_empty() && !new_patch.triangle_projections.is_empty() {
+        combined.triangle_projections = patch
+            .triangle_projections
+            .iter()
+            .chain(new_patch.triangle_projections.iter())
+            .cloned()
+            .collect();
+    }
+    if !patch.triangle_pages.is_empty() {
+        // New charts are put into the free space of the first page.
+        combined.triangle_pages = patch.triangle_pages.clone();
+        combined.triangle_pages.resize(combined.triangles.len(), 0);
+    }
+    combined.pack_stats.needle_triangles.extend(
+        new_stats
+            .needle_triangles
+            .iter()
+            .map(|triangle| triangle + patch.triangles.len()),
+    );
+    combined.pack_stats.double_sided_triangles.extend(
+        new_stats
+            .double_sided_triangles
+            .iter()
+            .map(|pair| pair.map(|triangle| triangle + patch.triangles.len())),
+    );
+    combined
+        .pack_stats
+        .tile_adjacency
+        .extend(new_stats.tile_adjacency.iter().map(|tiles| {
+            TileAdjacency {
+                charts: tiles
+                    .charts
+                    .map(|chart| chart + patch.pack_stats.charts.len()),
+                vertices: tiles
+                    .vertices
+                    .iter()
+                    .map(|pair| pair.map(map_new))
+                    .collect(),
+            }
+        }));
+    combined.pack_stats.split_locked_vertices.extend(
+        new_stats
+            .split_locked_vertices
+            .iter()
+            .map(|&index| index + old_source_count as u32),
+    );
+    if let Some(density) = new_stats.min_texel_density.filter(|density| {
+        patch
+            .pack_stats
+            .min_texel_density
+            .map_or(true, |min| density.texels_per_unit < min.texels_per_unit)
+    }) {
+        combined.pack_stats.min_texel_density = Some(TexelDensity {
+            triangle: density.triangle + patch.triangles.len(),
+            chart: density.chart + patch.pack_stats.charts.len(),
+            ..density
+        });
+    }
+    combined.pack_stats.seam_count += new_stats.seam_count;
+    combined.pack_stats.seam_length += new_stats.seam_length;
+    adjacency::fill_adjacency(&mut combined, options);
+    // New charts could come too close to the old ones.
+    padding::verify_padding(&mut combined, options);
+    winding::find_flipped_triangles(&mut combined);
+
+    Some(combined)
+}
+
+/// Generates UVs for the mesh and packs its charts into the given free rectangles of an atlas with
+/// the given scale (multiplied by [`UvGenOptions::scale_in_lightmap`]). If the charts do not fit,
+/// they're scaled down until they do. Indices of the charts (used for chart colors) start from
+/// `first_chart`. Scale of the returned patch is the scale that was actually used.
+pub(crate) fn unwrap_into_free_space(
+    vertices: &[Vector3<f32>],
+    triangles: impl Iterator<Item = [u32; 3]>,
+    scale: f32,
+    free_rects: &[Rect<f32>],
+    first_chart: usize,
+    options: &UvGenOptions,
+) -> Option<SurfaceDataPatch> {
+    let options = &mips::with_mip_padding(options.clone());
+    let mut task = UnwrapTask::new(vertices.iter().cloned(), triangles, options.clone());
+    if !task.run_until(Stage::Packing) {
+        return None;
+    }
+    let mut charts = task.into_charts();
+    let packing_start = Instant::now();
+    pack::sort_meshes(&mut charts.meshes);
+    if options.spatial_locality {
+        locality::sort_by_locality(&mut charts.meshes, &charts.triangles, |index| {
+            vertex_position(vertices, &charts.additional_vertices, index)
+        });
+    }
+
+    // Try to keep the requested texel density, shrink charts only if they do not fit.
+    let mut scale = scale * options.scale_in_lightmap;
+    let mut packing = loop {
+        // NaN and infinite scales would never get small enough.
+        if !(scale > f32::EPSILON && scale.is_finite()) {
+            return None;
+        }
+        if let Some(packing) =
+            pack::pack_into_free_rects(&charts.meshes, free_rects, scale, options.spacing)
+        {
+            break packing;
+        }
+        scale /= pack::scale_growth_factor(options);
+    };
+    pack::sort_by_key(&mut charts.meshes, &mut Vec::new(), &mut packing);
+
+    let assignment_start = Instant::now();
+    let packing_time = assignment_start - packing_start;
+    let mut stage_timings = std::mem::take(&mut charts.stage_timings);
+    let mut patch = SurfaceDataPatch::default();
+    prepare_patch(&mut patch, charts.vertex_count, options);
+    for (mesh_index, (mesh, rect)) in charts.meshes.iter().zip(packing.rects.iter()).enumerate() {
+        assign_uvs(
+            &mut patch,
+            first_chart + mesh_index,
+            mesh,
+            rect,
+            &packing,
+            &charts.triangles,
+            &charts.projections,
+            options,
+        )?;
+    }
+
+    patch.pack_stats = packing.stats(
+        &charts.meshes,
+        &charts.triangles,
+        &charts.projections,
+        |index| vertex_position(vertices, &charts.additional_vertices, index),
+    );
+    stats::flag_thin_charts(&mut patch.pack_stats.charts, options);
+    stats::fill_texel_rects(&mut patch.pack_stats.charts, options);
+    patch.pack_stats.needle_triangles =
+        stats::needle_triangles(&charts.triangles, options, |index| {
+            vertex_position(vertices, &charts.additional_vertices, index)
+        });
+    (patch.pack_stats.seam_count, patch.pack_stats.seam_length) =
+        stats::seam_stats(vertices, &charts.additional_vertices, &charts.triangles);
+    patch.additional_vertices = charts.additional_vertices;
+    patch.triangles = charts.triangles;
+    if options.triangle_projections {
+        patch.triangle_projections = charts.projections;
+    }
+    chart_split::fill_tile_adjacency(&mut patch, &charts.cut_vertices);
+    assign_sentinel_uvs(&mut patch, options);
+    chart_uvs::fill_chart_uvs(&mut patch, options);
+    double_sided::handle_double_sided(&mut patch, vertices, options);
+    adjacency::fill_adjacency(&mut patch, options);
+    padding::verify_padding(&mut patch, options);
+    winding::find_flipped_triangles(&mut patch);
+    patch.pack_stats.min_texel_density = stats::min_texel_density(&patch, options, |index| {
+        vertex_position(vertices, &patch.additional_vertices, index)
+    });
+    locks::find_split_locked_vertices(&mut patch, &options.locked_vertices);
+    if options.stage_timings {
+        // Charts are placed into the free rectangles without the iterative packer.
+        stats::record_stage(&mut stage_timings, Stage::Packing, packing_time, 0);
+        stats::record_stage(
+            &mut stage_timings,
+            Stage::Assignment,
+            assignment_start.elapsed(),
+            patch.pack_stats.charts.len(),
+        );
+        patch.pack_stats.stage_timings = stage_timings;
+    }
+
+    Some(patch)
+}
+
+/// Combines per-vertex data of two patches in the following order: old source vertices, new
+/// source vertices, old additional vertices, new additional vertices.
+fn interleave<T: Copy>(old: &[T], new: &[T], old_source: usize, new_source: usize) -> Vec<T> {
+    old[..old_source]
+        .iter()
+        .chain(&new[..new_source])
+        .chain(&old[old_source..])
+        .chain(&new[new_source..])
+        .cloned()
+        .collect()
+}
+
+#[cfg(test)]
+mod test {
+    use nalgebra::{Vector2, Vector3};
+
+    #[test]
+    fn test_extend_patch() {
+        // An elongated quad leaves a half of the atlas free.
+        let vertices = [
+            Vector3::new(0.0, 0.0, 0.0),
+            Vector3::new(2.0, 0.0, 0.0),
+            Vector3::new(2.0, 1.0, 0.0),
+            Vector3::new(0.0, 1.0, 0.0),
+        ];
+        let patch = crate::generate_uvs(
+            vertices.iter().cloned(),
+            [[0u32, 1, 2], [0, 2, 3]].into_iter(),
+            0.005,
+        )
+        .unwrap();
+
+        let new_vertices = [
+            Vector3::new(0.0, 0.0, 1.0),
+            Vector3::new(0.0, 0.5, 1.0),
+            Vector3::new(0.5, 0.0, 1.0),
+        ];
+        let extended = super::extend_patch(
+            &patch,
+            new_vertices.iter().cloned(),
+            [[0, 1, 2]].into_iter(),
+            &Default::default(),
+        )
+        .unwrap();
+
+        let old_source_count = patch.second_tex_coords.len() - patch.additional_vertices.len();
+        assert_eq!(
+            extended.second_tex_coords[..old_source_count],
+            patch.second_tex_coords[..old_source_count]
+        );
+        assert_eq!(extended.triangles[..2], patch.triangles[..]);
+        assert_eq!(extended.pack_stats.scale, patch.pack_stats.scale);
+        assert_eq!(extended.pack_stats.chart_count, 2);
+
+        // New UVs must be in the atlas, but outside of the old chart.
+        let (min, max) = patch.second_tex_coords.iter().fold(
+            (Vector2::repeat(f32::MAX), Vector2::repeat(-f32::MAX)),
+            |(min, max), uv| (min.inf(uv), max.sup(uv)),
+        );
+        for &index in extended.triangles[2].iter() {
+            let uv = extended.second_tex_coords[index as usize];
+            assert!(uv.x >= 0.0 && uv.x <= 1.0 && uv.y >= 0.0 && uv.y <= 1.0);
+            assert!(uv.x < min.x || uv.x > max.x || uv.y < min.y || uv.y > max.y);
+        }
+    }
+}
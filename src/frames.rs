@@ -0,0 +1,170 @@
+//! Local frames of connected parts of a mesh. See [`crate::UvGenOptions::local_frames`] and
+//! [`crate::UvGenOptions::coordinate_system`] for more info.
+
+use crate::CoordinateSystem;
+use nalgebra::{Matrix3, Vector3};
+
+/// Rotation of every connected part of a mesh into its own local frame.
+pub(crate) struct LocalFrames {
+    /// Rotations from world space to local frames.
+    rotations: Vec<Matrix3<f32>>,
+    /// Index of a frame of every triangle.
+    triangle_frames: Vec<u32>,
+    /// Whether the frames change handedness, see [`Self::is_mirrored`].
+    mirrored: bool,
+}
+
+impl LocalFrames {
+    /// Transforms the point from world space to the local frame of the given triangle.
+    pub fn to_local(&self, triangle: usize, point: &Vector3<f32>) -> Vector3<f32> {
+        self.rotations[self.triangle_frames[triangle] as usize] * point
+    }
+
+    /// Returns `true` if the frames change handedness (see [`CoordinateSystem`]). Cross products
+    /// of transformed vectors point in the opposite direction in this case.
+    pub fn is_mirrored(&self) -> bool {
+        self.mirrored
+    }
+}
+
+/// Returns the change of basis from the given coordinate system to the one of the generator
+/// (Y-up, right-handed).
+fn basis(coordinate_system: CoordinateSystem) -> Matrix3<f32> {
+    // Axes of the coordinate system of the generator in the given one.
+    let (x, y, z) = (Vector3::x(), Vector3::y(), Vector3::z());
+    let [right, up, back] = match coordinate_system {
+        CoordinateSystem::YUpRightHanded => [x, y, z],
+        CoordinateSystem::YUpLeftHanded => [x, y, -z],
+        CoordinateSystem::ZUpRightHanded => [x, z, -y],
+        CoordinateSystem::ZUpLeftHanded => [x, z, y],
+    };
+    Matrix3::from_rows(&[right.transpose(), up.transpose(), back.transpose()])
+}
+
+/// Returns frames, that only convert every one of the given amount of triangles from the given
+/// coordinate system to the one of the generator.
+pub(crate) fn convention_frames(
+    triangle_count: usize,
+    coordinate_system: CoordinateSystem,
+) -> LocalFrames {
+    let basis = basis(coordinate_system);
+    LocalFrames {
+        rotations: vec![basis],
+        triangle_frames: vec![0; triangle_count],
+        mirrored: basis.determinant() < 0.0,
+    }
+}
+
+fn find(parents: &mut [u32], mut index: u32) -> u32 {
+    while parents[index as usize] != index {
+        let parent = parents[index as usize];
+        // Path halving.
+        parents[index as usize] = parents[parent as usize];
+        index = parent;
+    }
+    index
+}
+
+/// Calculates an orthonormal frame, which axes are aligned with the dominant orientations of the
+/// given triangles. The first axis is the normal of the largest triangle, the second one is the
+/// normal of the largest triangle, that is at least 45 degrees away from the first axis.
+fn dominant_frame(normals: &[(f32, Vector3<f32>)]) -> Matrix3<f32> {
+    let largest = |filter: &dyn Fn(&Vector3<f32>) -> bool| {
+        normals
+            .iter()
+            .filter(|(_, normal)| filter(normal))
+            .fold(
+                None,
+                |best: Option<(f32, Vector3<f32>)>, &(area, normal)| match best {
+                    Some((best_area, _)) if best_area >= area => best,
+                    _ => Some((area, normal)),
+                },
+            )
+            .map(|(_, normal)| normal)
+    };
+
+    let Some(first) = largest(&|_| true) else {
+        return Matrix3::identity();
+    };
+    let second = largest(&|normal| normal.dot(&first).abs() < std::f32::consts::FRAC_1_SQRT_2)
+        .unwrap_or_else(|| {
+            // Any axis, that is least aligned with the first one.
+            let mut axis = Vector3::zeros();
+            axis[first.iamin()] = 1.0;
+            axis
+        });
+    let Some(second) = (second - first.scale(second.dot(&first))).try_normalize(f32::EPSILON)
+    else {
+        return Matrix3::identity();
+    };
+    let third = first.cross(&second);
+
+    Matrix3::from_rows(&[first.transpose(), second.transpose(), third.transpose()])
+}
+
+/// Splits the mesh into connected parts. Returns index of a part of every triangle and the amount
+/// of parts, parts are numbered in the order of their first triangle, so the result is
+/// deterministic. Returns `None` if a triangle references a vertex that does not exist.
+pub(crate) fn connected_parts(
+    vertices: &[Vector3<f32>],
+    triangles: &[[u32; 3]],
+) -> Option<(Vec<u32>, usize)> {
+    let mut parents = (0..vertices.len() as u32).collect::<Vec<_>>();
+    for triangle in triangles {
+        if triangle
+            .iter()
+            .any(|&index| index as usize >= vertices.len())
+        {
+            return None;
+        }
+        let root = find(&mut parents, triangle[0]);
+        for &index in &triangle[1..] {
+            let other = find(&mut parents, index);
+            parents[other as usize] = root;
+        }
+    }
+
+    let mut part_indices = vec![u32::MAX; vertices.len()];
+    let mut part_count = 0;
+    let triangle_parts = triangles
+        .iter()
+        .map(|triangle| {
+            let root = find(&mut parents, triangle[0]) as usize;
+            if part_indices[root] == u32::MAX {
+                part_indices[root] = part_count;
+                part_count += 1;
+            }
+            part_indices[root]
+        })
+        .collect();
+    Some((triangle_parts, part_count as usize))
+}
+
+/// Splits the mesh into connected parts and calculates a local frame for each of them. Frames are
+/// relative to the coordinate system of the generator, the mesh is converted from the given
+/// coordinate system first. Returns `None` if a triangle references a vertex that does not exist.
+pub(crate) fn local_frames(
+    vertices: &[Vector3<f32>],
+    triangles: &[[u32; 3]],
+    coordinate_system: CoordinateSystem,
+) -> Option<LocalFrames> {
+    let basis = basis(coordinate_system);
+    let (triangle_frames, part_count) = connected_parts(vertices, triangles)?;
+    let mut part_normals = vec![Vec::<(f32, Vector3<f32>)>::new(); part_count];
+    for (triangle, &part) in triangles.iter().zip(triangle_frames.iter()) {
+        let [a, b, c] = triangle.map(|index| basis * vertices[index as usize]);
+        let normal = (b - a).cross(&(c - a));
+        if let Some(unit) = normal.try_normalize(f32::EPSILON) {
+            part_normals[part as usize].push((normal.norm(), unit));
+        }
+    }
+
+    Some(LocalFrames {
+        rotations: part_normals
+            .iter()
+            .map(|normals| dominant_frame(normals) * basis)
+            .collect(),
+        triangle_frames,
+        mirrored: basis.determinant() < 0.0,
+    })
+}
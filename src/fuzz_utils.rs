@@ -0,0 +1,298 @@
+//! Generation of inputs for fuzzing of the generator. The generator never panics, whatever
+//! vertices, triangles and options it is given: invalid input makes it return `None` (or produce
+//! meaningless texture coordinates for meaningless geometry, such as triangles with NaN
+//! vertices). This module helps to check this contract in downstream projects:
+//!
+//! - [`FuzzCase::from_bytes`] turns arbitrary bytes into a mesh and options, so it could be used
+//!   as a target of coverage-guided fuzzers (such as `cargo fuzz`).
+//! - [`fuzz_corpus`] generates a reproducible set of cases, that could be used as a seed corpus
+//!   or directly in tests.
+//!
+//! ```rust
+//! for case in uvgen::fuzz_utils::fuzz_corpus(0, 16) {
+//!     // Must not panic.
+//!     let _ = case.run();
+//! }
+//! ```
+
+use crate::{
+    rng::Rng, AnchorLayout, ChartAnchor, CoordinateSystem, DoubleSidedHandling, NeedleHandling,
+    Rect, RepeatedIndexHandling, SurfaceDataPatch, TexelRounding, UvGenOptions, WeldTolerance,
+};
+use nalgebra::{Vector2, Vector3};
+
+/// Mesh and options to run the generator with.
+#[derive(Clone, Debug, PartialEq)]
+pub struct FuzzCase {
+    /// Vertices of the mesh.
+    pub vertices: Vec<Vector3<f32>>,
+    /// Triangles of the mesh. Indices could point outside of the vertices.
+    pub triangles: Vec<[u32; 3]>,
+    /// Options of the generator.
+    pub options: UvGenOptions,
+}
+
+/// Reads values from a byte slice, missing bytes are zeros.
+struct Reader<'a> {
+    bytes: &'a [u8],
+}
+
+impl Reader<'_> {
+    fn u8(&mut self) -> u8 {
+        match self.bytes.split_first() {
+            Some((&byte, rest)) => {
+                self.bytes = rest;
+                byte
+            }
+            None => 0,
+        }
+    }
+
+    fn u32(&mut self) -> u32 {
+        u32::from_le_bytes([self.u8(), self.u8(), self.u8(), self.u8()])
+    }
+
+    fn below(&mut self, n: u8) -> u8 {
+        self.u8() % n
+    }
+
+    /// Reads either one of "interesting" values or arbitrary bits.
+    fn f32(&mut self, scale: f32) -> f32 {
+        match self.u8() {
+            0 => 0.0,
+            1 => -0.0,
+            2 => f32::NAN,
+            3 => f32::INFINITY,
+            4 => -f32::INFINITY,
+            5 => f32::MAX,
+            6 => f32::MIN_POSITIVE,
+            7 => f32::from_bits(self.u32()),
+            // Mostly regular values, so the generator gets past validation.
+            _ => (self.u8() as f32 - 128.0) / 128.0 * scale,
+        }
+    }
+
+    fn vector2(&mut self, scale: f32) -> Vector2<f32> {
+        Vector2::new(self.f32(scale), self.f32(scale))
+    }
+
+    fn vector3(&mut self, scale: f32) -> Vector3<f32> {
+        Vector3::new(self.f32(scale), self.f32(scale), self.f32(scale))
+    }
+}
+
+impl FuzzCase {
+    /// Maximum amount of vertices and triangles of a case. It is kept small, so a fuzzer could
+    /// make lots of runs per second.
+    pub const MAX_ELEMENTS: usize = 64;
+
+    /// Decodes a case from arbitrary bytes. Every byte sequence gives a valid case and the same
+    /// bytes always give the same case. Values of options are limited only to the extent, that
+    /// keeps the amount of work (but not the validity of the values) bounded, for example, there
+    /// are at most 4 atlas pages.
+    pub fn from_bytes(bytes: &[u8]) -> Self {
+        let mut reader = Reader { bytes };
+
+        let options = Self::options(&mut reader);
+
+        let vertex_count = reader.u8() as usize % (Self::MAX_ELEMENTS + 1);
+        let scale = [1.0, 1.0e-6, 1.0e6][reader.below(3) as usize];
+        let vertices = (0..vertex_count)
+            .map(|_| reader.vector3(scale))
+            .collect::<Vec<_>>();
+
+        let triangle_count = reader.u8() as usize % (Self::MAX_ELEMENTS + 1);
+        let triangles = (0..triangle_count)
+            .map(|_| {
+                [(); 3].map(|_| {
+                    if reader.below(128) == 0 {
+                        // Mostly out of range.
+                        reader.u32()
+                    } else {
+                        reader.u8() as u32 % (vertex_count as u32).max(1)
+                    }
+                })
+            })
+            .collect();
+
+        Self {
+            vertices,
+            triangles,
+            options,
+        }
+    }
+
+    fn options(reader: &mut Reader) -> UvGenOptions {
+        let default = UvGenOptions::default();
+        let flags = reader.u32();
+        let flag = |bit: u32| flags & (1 << bit) != 0;
+        UvGenOptions {
+            spacing: if flag(0) {
+                reader.f32(0.1)
+            } else {
+                default.spacing
+            },
+            chart_colors: flag(1),
+            tiny_mesh_threshold: reader.u8() as usize,
+            deterministic: flag(2),
+            max_chunk_triangles: if flag(3) {
+                reader.u8() as usize
+            } else {
+                default.max_chunk_triangles
+            },
+            max_stretch: if flag(4) {
+                reader.f32(4.0)
+            } else {
+                default.max_stretch
+            },
+            seam_bias: if flag(5) {
+                reader.f32(1.0)
+            } else {
+                default.seam_bias
+            },
+            density: if flag(6) {
+                reader.vector3(2.0)
+            } else {
+                default.density
+            },
+            stable_anchors: flag(7),
+            previous_layout: flag(8).then(|| AnchorLayout {
+                anchors: (0..reader.below(8))
+                    .map(|_| ChartAnchor {
+                        hash: reader.below(4) as u64,
+                        rect: Rect::new(
+                            reader.f32(1.0),
+                            reader.f32(1.0),
+                            reader.f32(1.0),
+                            reader.f32(1.0),
+                        ),
+                    })
+                    .collect(),
+                scale: reader.f32(1.0),
+            }),
+            adjacency: flag(9),
+            pack_restarts: reader.below(4) as usize,
+            max_packing_iterations: reader.below(200) as usize,
+            scale_growth_factor: if flag(30) { reader.f32(4.0) } else { 1.33 },
+            seed: reader.u32() as u64,
+            texel_snap_resolution: if flag(10) { reader.u32() % 4097 } else { 0 },
+            local_frames: flag(11),
+            spatial_locality: flag(12),
+            page_count: reader.below(5) as usize,
+            extra_rects: (0..reader.below(4)).map(|_| reader.vector2(0.5)).collect(),
+            resolution: if flag(13) { reader.u32() % 4097 } else { 0 },
+            min_chart_texel_width: reader.f32(4.0),
+            upscale_thin_charts: flag(14),
+            weld: match reader.below(8) {
+                0 => Some(WeldTolerance::Absolute(reader.f32(0.1))),
+                1 => Some(WeldTolerance::Relative(reader.f32(0.1))),
+                2 => Some(WeldTolerance::Ulps(reader.u32())),
+                _ => None,
+            },
+            needle_ratio: if flag(15) {
+                reader.f32(100.0)
+            } else {
+                default.needle_ratio
+            },
+            needle_handling: match reader.below(3) {
+                0 => NeedleHandling::Keep,
+                1 => NeedleHandling::Isolate,
+                _ => NeedleHandling::Reproject,
+            },
+            repeated_index_handling: match reader.below(4) {
+                0 => RepeatedIndexHandling::Keep,
+                1 => RepeatedIndexHandling::Skip,
+                2 => RepeatedIndexHandling::Sentinel(reader.vector2(1.0)),
+                _ => RepeatedIndexHandling::Fail,
+            },
+            scale_in_lightmap: if flag(16) {
+                reader.f32(4.0)
+            } else {
+                default.scale_in_lightmap
+            },
+            mip_padding_levels: reader.below(4) as u32,
+            double_sided_handling: match reader.below(3) {
+                0 => DoubleSidedHandling::Ignore,
+                1 => DoubleSidedHandling::Report,
+                _ => DoubleSidedHandling::Share,
+            },
+            cylinder_unwrap: flag(17),
+            chart_uvs: flag(18),
+            virtual_page_size: if flag(19) { 32 << reader.below(8) } else { 0 },
+            texel_density: reader.f32(256.0),
+            verify_padding: flag(20),
+            locked_vertices: (0..reader.u8()).map(|_| reader.below(2) == 1).collect(),
+            max_chart_size: if flag(21) {
+                reader.f32(4.0)
+            } else {
+                default.max_chart_size
+            },
+            texel_rounding: match reader.below(3) {
+                0 => TexelRounding::Outward,
+                1 => TexelRounding::Inward,
+                _ => TexelRounding::Nearest,
+            },
+            triangle_normals: if flag(22) {
+                (0..reader.u8()).map(|_| reader.vector3(1.0)).collect()
+            } else {
+                Vec::new()
+            },
+            coordinate_system: match reader.below(4) {
+                0 => CoordinateSystem::YUpRightHanded,
+                1 => CoordinateSystem::YUpLeftHanded,
+                2 => CoordinateSystem::ZUpRightHanded,
+                _ => CoordinateSystem::ZUpLeftHanded,
+            },
+            fill_atlas: flag(23),
+            triangle_projections: flag(24),
+            min_chart_texels: if flag(25) { reader.f32(4.0) } else { 0.0 },
+            cancellation: None,
+            real_time: flag(26),
+            stage_timings: flag(27),
+            tiling_size: if flag(28) { reader.f32(4.0) } else { 0.0 },
+            triangle_importance: if flag(29) {
+                (0..reader.u8()).map(|_| reader.f32(4.0)).collect()
+            } else {
+                Vec::new()
+            },
+        }
+    }
+
+    /// Runs the generator for the case.
+    pub fn run(&self) -> Option<SurfaceDataPatch> {
+        crate::generate_uvs_with_options(
+            self.vertices.iter().cloned(),
+            self.triangles.iter().cloned(),
+            &self.options,
+        )
+    }
+}
+
+/// Generates the given amount of pseudo-random cases. The same seed always gives the same cases.
+pub fn fuzz_corpus(seed: u64, count: usize) -> Vec<FuzzCase> {
+    let mut rng = Rng::new(seed);
+    (0..count)
+        .map(|_| {
+            let len = rng.below(1024);
+            let bytes = (0..len).map(|_| rng.next_u64() as u8).collect::<Vec<_>>();
+            FuzzCase::from_bytes(&bytes)
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod test {
+    #[test]
+    fn test_fuzz_corpus() {
+        let corpus = super::fuzz_corpus(0, 500);
+        // Cases contain NaNs, so compare them by their text representation.
+        assert_eq!(
+            format!("{corpus:?}"),
+            format!("{:?}", super::fuzz_corpus(0, 500))
+        );
+        // A panic fails the test.
+        let succeeded = corpus.iter().filter(|case| case.run().is_some()).count();
+        // Most of the cases must get past validation, otherwise the corpus is useless.
+        assert!(succeeded > corpus.len() / 2);
+    }
+}
@@ -0,0 +1,107 @@
+//! Export of patches in the layout of Godot meshes. Available only with `godot` feature.
+//!
+//! Godot stores every attribute of a surface in a separate array (see `Mesh::ArrayType`), all
+//! the arrays must have the same length and the vertices are referenced by 32-bit signed indices.
+//! Lightmap coordinates go to `Mesh::ARRAY_TEX_UV2`, which is an array of 32-bit float pairs.
+//! [`godot_uv2_arrays`] converts a patch into this layout:
+//!
+//! ```rust
+//! # use nalgebra::Vector3;
+//! # let vertices = [
+//! #     Vector3::new(0.0, 0.0, 0.0),
+//! #     Vector3::new(1.0, 0.0, 0.0),
+//! #     Vector3::new(0.0, 1.0, 0.0),
+//! # ];
+//! # let normals = [Vector3::new(0.0, 0.0, 1.0); 3];
+//! // Arrays of the surface, as they were fetched from Godot.
+//! let indices = [0, 2, 1];
+//! let patch = uvgen::generate_uvs(
+//!     vertices.iter().cloned(),
+//!     indices.chunks(3).map(|t| [t[0] as u32, t[1] as u32, t[2] as u32]),
+//!     0.005,
+//! )
+//! .unwrap();
+//!
+//! let arrays = uvgen::godot::godot_uv2_arrays(&patch).unwrap();
+//! // Every other attribute of the surface must be rebuilt from the source vertices.
+//! let new_normals = arrays
+//!     .source_vertices
+//!     .iter()
+//!     .map(|&source| normals[source as usize])
+//!     .collect::<Vec<_>>();
+//! assert_eq!(new_normals.len(), arrays.uv2.len());
+//! assert_eq!(arrays.indices.len(), 3);
+//! ```
+
+use crate::SurfaceDataPatch;
+
+/// Arrays of a Godot surface with lightmap coordinates, see [`godot_uv2_arrays`].
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct GodotUv2Arrays {
+    /// Index of the source vertex for every vertex of the new surface. Every per-vertex array of
+    /// the source surface (`ARRAY_VERTEX`, `ARRAY_NORMAL`, `ARRAY_TEX_UV`, etc.) must be rebuilt
+    /// using these indices, so all the arrays have the same length as [`Self::uv2`].
+    pub source_vertices: Vec<i32>,
+    /// Lightmap coordinates for `ARRAY_TEX_UV2`.
+    pub uv2: Vec<[f32; 2]>,
+    /// New indices for `ARRAY_INDEX`, three per triangle. Triangles keep the winding of the
+    /// source triangles, so the clockwise front faces of Godot stay front faces.
+    pub indices: Vec<i32>,
+}
+
+/// Converts the patch into arrays, that could be passed to Godot directly (see
+/// [`GodotUv2Arrays`]). Unreferenced vertices (see
+/// [`SurfaceDataPatch::unreferenced_vertices`]) are removed, since Godot does not allow to leave
+/// holes in the arrays. Returns `None` if the amount of vertices does not fit into `i32` indices.
+pub fn godot_uv2_arrays(patch: &SurfaceDataPatch) -> Option<GodotUv2Arrays> {
+    let compacted = patch.compacted();
+    Some(GodotUv2Arrays {
+        source_vertices: compacted
+            .vertices
+            .iter()
+            .map(|&source| i32::try_from(source).ok())
+            .collect::<Option<_>>()?,
+        uv2: compacted
+            .second_tex_coords
+            .iter()
+            .map(|uv| [uv.x, uv.y])
+            .collect(),
+        indices: compacted
+            .triangles
+            .iter()
+            .flatten()
+            .map(|&index| i32::try_from(index).ok())
+            .collect::<Option<_>>()?,
+    })
+}
+
+#[cfg(test)]
+mod test {
+    #[test]
+    fn test_godot_uv2_arrays() {
+        let mesh = crate::test_meshes::cube();
+        let patch = crate::generate_uvs(
+            mesh.vertices.iter().cloned(),
+            mesh.triangles.iter().cloned(),
+            0.005,
+        )
+        .unwrap();
+        let arrays = super::godot_uv2_arrays(&patch).unwrap();
+
+        assert_eq!(arrays.source_vertices.len(), arrays.uv2.len());
+        assert_eq!(arrays.indices.len(), mesh.triangles.len() * 3);
+        for (triangle, indices) in mesh.triangles.iter().zip(arrays.indices.chunks(3)) {
+            // Corners must keep their positions and order, so the winding is the same.
+            for (&source, &index) in triangle.iter().zip(indices) {
+                let vertex = arrays.source_vertices[index as usize];
+                assert_eq!(
+                    mesh.vertices[vertex as usize],
+                    mesh.vertices[source as usize]
+                );
+            }
+        }
+        for uv in arrays.uv2.iter() {
+            assert!((0.0..=1.0).contains(&uv[0]) && (0.0..=1.0).contains(&uv[1]));
+        }
+    }
+}
@@ -0,0 +1,203 @@
+//! Text snapshots of patches for golden tests. See [`SurfaceDataPatch::to_golden_string`] for
+//! more info.
+
+use crate::SurfaceDataPatch;
+use std::fmt::{Display, Formatter, Write};
+
+/// Version of the snapshot format. It is increased whenever the format changes, so snapshots made
+/// by different versions of the format are never compared to each other.
+const HEADER: &str = "uvgen-golden 1";
+
+/// Keywords of the lines, which values are floats and compared with a tolerance.
+const FLOAT_KEYWORDS: [&str; 2] = ["scale", "uv"];
+
+impl SurfaceDataPatch {
+    /// Returns a versioned text snapshot of the patch, which could be stored next to a mesh and
+    /// compared with a snapshot of a patch produced later (possibly by a newer version of the
+    /// crate) using [`compare_golden`]. The snapshot contains the scale of the charts, additional
+    /// vertices, triangles and texture coordinates, one item per line:
+    ///
+    /// ```text
+    /// uvgen-golden 1
+    /// scale 0.25
+    /// vertices 8
+    /// additional 3
+    /// triangle 0 1 2
+    /// uv 0.005 0.5
+    /// ```
+    ///
+    /// Floats are written in their shortest form, that is parsed back into exactly the same value.
+    pub fn to_golden_string(&self) -> String {
+        let mut text = String::new();
+        // Writing into a string never fails.
+        let _ = writeln!(text, "{HEADER}");
+        let _ = writeln!(text, "scale {}", self.pack_stats.scale);
+        let _ = writeln!(text, "vertices {}", self.source_vertex_count());
+        for source in self.additional_vertices.iter() {
+            let _ = writeln!(text, "additional {source}");
+        }
+        for [a, b, c] in self.triangles.iter() {
+            let _ = writeln!(text, "triangle {a} {b} {c}");
+        }
+        for uv in self.second_tex_coords.iter() {
+            let _ = writeln!(text, "uv {} {}", uv.x, uv.y);
+        }
+        text
+    }
+}
+
+/// The first difference between two golden snapshots, see [`compare_golden`].
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct GoldenMismatch {
+    /// Index of the differing line (the header is line `0`).
+    pub line: usize,
+    /// The line of the expected snapshot, or an empty string if the snapshot is shorter.
+    pub expected: String,
+    /// The line of the actual snapshot, or an empty string if the snapshot is shorter.
+    pub actual: String,
+}
+
+impl Display for GoldenMismatch {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "golden mismatch at line {}: expected `{}`, got `{}`",
+            self.line, self.expected, self.actual
+        )
+    }
+}
+
+impl std::error::Error for GoldenMismatch {}
+
+/// Checks whether two values of a line match. NaNs match each other.
+fn values_match(keyword: &str, expected: &str, actual: &str, tolerance: f32) -> bool {
+    if !FLOAT_KEYWORDS.contains(&keyword) {
+        return expected == actual;
+    }
+    match (expected.parse::<f32>(), actual.parse::<f32>()) {
+        (Ok(expected), Ok(actual)) => {
+            expected == actual
+                || (expected.is_nan() && actual.is_nan())
+                || (expected - actual).abs() <= tolerance
+        }
+        _ => false,
+    }
+}
+
+/// Checks whether two lines match.
+fn lines_match(expected: &str, actual: &str, tolerance: f32) -> bool {
+    let mut expected = expected.split_whitespace();
+    let mut actual = actual.split_whitespace();
+    let keyword = expected.next();
+    if keyword != actual.next() {
+        return false;
+    }
+    let keyword = keyword.unwrap_or_default();
+    loop {
+        match (expected.next(), actual.next()) {
+            (Some(expected), Some(actual)) => {
+                if !values_match(keyword, expected, actual, tolerance) {
+                    return false;
+                }
+            }
+            (None, None) => return true,
+            _ => return false,
+        }
+    }
+}
+
+/// Compares two golden snapshots (see [`SurfaceDataPatch::to_golden_string`]) and returns the
+/// first difference. Texture coordinates and the scale are compared with the given absolute
+/// tolerance, everything else must match exactly. Snapshots of different versions of the format
+/// never match.
+///
+/// ```rust
+/// # use nalgebra::Vector3;
+/// let vertices = [
+///     Vector3::new(0.0, 0.0, 0.0),
+///     Vector3::new(1.0, 0.0, 0.0),
+///     Vector3::new(0.0, 1.0, 0.0),
+/// ];
+/// let patch =
+///     uvgen::generate_uvs(vertices.into_iter(), [[0u32, 1, 2]].into_iter(), 0.005).unwrap();
+/// let golden = patch.to_golden_string();
+/// assert_eq!(uvgen::compare_golden(&golden, &golden, 0.0), Ok(()));
+/// ```
+pub fn compare_golden(expected: &str, actual: &str, tolerance: f32) -> Result<(), GoldenMismatch> {
+    let mut expected_lines = expected
+        .lines()
+        .map(str::trim)
+        .filter(|line| !line.is_empty());
+    let mut actual_lines = actual
+        .lines()
+        .map(str::trim)
+        .filter(|line| !line.is_empty());
+    for line in 0.. {
+        let (expected, actual) = match (expected_lines.next(), actual_lines.next()) {
+            (None, None) => return Ok(()),
+            (expected, actual) => (expected.unwrap_or_default(), actual.unwrap_or_default()),
+        };
+        let matches = if line == 0 {
+            expected == HEADER && actual == HEADER
+        } else {
+            lines_match(expected, actual, tolerance)
+        };
+        if !matches {
+            return Err(GoldenMismatch {
+                line,
+                expected: expected.to_string(),
+                actual: actual.to_string(),
+            });
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod test {
+    use super::GoldenMismatch;
+    use nalgebra::{Vector2, Vector3};
+
+    #[test]
+    fn test_golden() {
+        let vertices = [
+            Vector3::new(0.0, 0.0, 0.0),
+            Vector3::new(1.0, 0.0, 0.0),
+            Vector3::new(1.0, 1.0, 0.0),
+            Vector3::new(0.0, 1.0, 1.0),
+        ];
+        let triangles: [[u32; 3]; 2] = [[0, 1, 2], [0, 2, 3]];
+        let mut patch =
+            crate::generate_uvs(vertices.into_iter(), triangles.into_iter(), 0.005).unwrap();
+        let golden = patch.to_golden_string();
+        assert!(golden.starts_with("uvgen-golden 1\n"));
+        assert_eq!(super::compare_golden(&golden, &golden, 0.0), Ok(()));
+
+        // Small differences of texture coordinates are tolerated.
+        patch.second_tex_coords[0] += Vector2::new(1.0e-4, 0.0);
+        let changed = patch.to_golden_string();
+        assert_eq!(super::compare_golden(&golden, &changed, 1.0e-3), Ok(()));
+        let mismatch = super::compare_golden(&golden, &changed, 1.0e-5).unwrap_err();
+        assert!(mismatch.expected.starts_with("uv ") && mismatch.actual.starts_with("uv "));
+
+        // Indices must match exactly.
+        patch.triangles[0] = [0, 2, 1];
+        let changed = patch.to_golden_string();
+        let mismatch = super::compare_golden(&golden, &changed, 1.0).unwrap_err();
+        assert!(mismatch.expected.starts_with("triangle "));
+
+        // Missing lines are reported too.
+        let truncated = golden.lines().take(3).collect::<Vec<_>>().join("\n");
+        assert_eq!(
+            super::compare_golden(&golden, &truncated, 0.0),
+            Err(GoldenMismatch {
+                line: 3,
+                expected: golden.lines().nth(3).unwrap().to_string(),
+                actual: String::new(),
+            })
+        );
+        assert!(
+            super::compare_golden(&golden, &golden.replace("golden 1", "golden 2"), 0.0).is_err()
+        );
+    }
+}
@@ -0,0 +1,156 @@
+//! Gutter texels around charts for dilation of baked maps. See [`SurfaceDataPatch::gutter_texels`]
+//! for more info.
+
+use crate::{
+    padding::{page_count, rasterize_page},
+    SurfaceDataPatch,
+};
+
+/// An empty texel near a chart, see [`SurfaceDataPatch::gutter_texels`].
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq, Hash)]
+pub struct GutterTexel {
+    /// Index of the atlas page of the texel.
+    pub page: usize,
+    /// Coordinates of the empty texel.
+    pub texel: [u32; 2],
+    /// Coordinates of the closest texel of a chart on the same page, its value should be copied
+    /// to the empty texel.
+    pub source: [u32; 2],
+    /// Index of the chart of the source texel in [`crate::PackStats::charts`].
+    pub chart: usize,
+    /// Distance between the texels, it is the largest of the distances along the axes, `1` means
+    /// that the texels touch each other.
+    pub distance: u32,
+}
+
+impl SurfaceDataPatch {
+    /// Rasterizes every chart conservatively (see [`Self::padding_violations`]) at the given
+    /// resolution of the (square) atlas and returns every empty texel, that is at most `width`
+    /// texels away from a chart, along with the closest texel of a chart. Baked maps could be
+    /// dilated with a single pass, that copies the sources to the empty texels, since the sources
+    /// are never empty. Empty texels between charts are split between them halfway. Texels are
+    /// sorted by page and then row by row:
+    ///
+    /// ```rust
+    /// let mesh = uvgen::test_meshes::cube();
+    /// let patch = uvgen::generate_uvs(
+    ///     mesh.vertices.iter().cloned(),
+    ///     mesh.triangles.iter().cloned(),
+    ///     0.005,
+    /// )
+    /// .unwrap();
+    /// let mut lightmap = vec![[0.0f32; 3]; 256 * 256];
+    /// for gutter in patch.gutter_texels(256, 2) {
+    ///     let [x, y] = gutter.texel.map(|c| c as usize);
+    ///     let [sx, sy] = gutter.source.map(|c| c as usize);
+    ///     lightmap[y * 256 + x] = lightmap[sy * 256 + sx];
+    /// }
+    /// ```
+    pub fn gutter_texels(&self, resolution: u32, width: u32) -> Vec<GutterTexel> {
+        let size = resolution as usize;
+        if size.checked_mul(size).is_none() || width == 0 {
+            return Vec::new();
+        }
+        let mut gutter = Vec::new();
+        for page in 0..page_count(self) {
+            let mut owners = rasterize_page(self, page, size, |_, _, _| ());
+            // Closest chart texel of every texel, that was reached by the growth fronts.
+            let mut sources = vec![usize::MAX; owners.len()];
+
+            // Charts grow one texel per step in every direction (including diagonals), like in
+            // `padding_violations`, but every texel keeps the source texel, that it was reached
+            // from, instead of the amount of steps.
+            let mut layer = Vec::new();
+            for y in 0..size {
+                for x in 0..size {
+                    let index = y * size + x;
+                    if owners[index] == u32::MAX {
+                        continue;
+                    }
+                    sources[index] = index;
+                    let empty_neighbour = (y.saturating_sub(1)..(y + 2).min(size)).any(|ny| {
+                        (x.saturating_sub(1)..(x + 2).min(size))
+                            .any(|nx| owners[ny * size + nx] == u32::MAX)
+                    });
+                    if empty_neighbour {
+                        layer.push(index);
+                    }
+                }
+            }
+            let mut step = 0;
+            while !layer.is_empty() && step < width {
+                let mut next = Vec::new();
+                for &index in layer.iter() {
+                    let (x, y) = (index % size, index / size);
+                    for ny in y.saturating_sub(1)..(y + 2).min(size) {
+                        for nx in x.saturating_sub(1)..(x + 2).min(size) {
+                            let neighbour = ny * size + nx;
+                            if owners[neighbour] == u32::MAX {
+                                owners[neighbour] = owners[index];
+                                sources[neighbour] = sources[index];
+                                next.push(neighbour);
+                            }
+                        }
+                    }
+                }
+                for &index in next.iter() {
+                    let source = sources[index];
+                    gutter.push(GutterTexel {
+                        page,
+                        texel: [(index % size) as u32, (index / size) as u32],
+                        source: [(source % size) as u32, (source / size) as u32],
+                        chart: owners[index] as usize,
+                        distance: step + 1,
+                    });
+                }
+                layer = next;
+                step += 1;
+            }
+        }
+        gutter.sort_unstable_by_key(|gutter| (gutter.page, gutter.texel[1], gutter.texel[0]));
+        gutter
+    }
+}
+
+#[cfg(test)]
+mod test {
+    #[test]
+    fn test_gutter_texels() {
+        let mesh = crate::test_meshes::cube();
+        let patch = crate::generate_uvs(
+            mesh.vertices.iter().cloned(),
+            mesh.triangles.iter().cloned(),
+            4.0 / 64.0,
+        )
+        .unwrap();
+        let gutter = patch.gutter_texels(64, 2);
+        assert!(!gutter.is_empty());
+
+        let occupied = crate::padding::rasterize_page(&patch, 0, 64, |_, _, _| ());
+        let mut seen = std::collections::HashMap::new();
+        for texel in gutter.iter() {
+            let [x, y] = texel.texel.map(|c| c as usize);
+            let [sx, sy] = texel.source.map(|c| c as usize);
+            assert_eq!(occupied[y * 64 + x], u32::MAX);
+            assert_eq!(occupied[sy * 64 + sx], texel.chart as u32);
+            let distance = x.abs_diff(sx).max(y.abs_diff(sy));
+            assert!((1..=2).contains(&distance));
+            assert_eq!(distance as u32, texel.distance);
+            assert!(seen.insert(texel.texel, texel.distance).is_none());
+        }
+
+        // Every empty texel next to a chart is a gutter texel at distance 1.
+        for y in 0..64 {
+            for x in 0..64 {
+                if occupied[y * 64 + x] != u32::MAX {
+                    continue;
+                }
+                let near_chart = (y.saturating_sub(1)..(y + 2).min(64)).any(|ny| {
+                    (x.saturating_sub(1)..(x + 2).min(64))
+                        .any(|nx| occupied[ny * 64 + nx] != u32::MAX)
+                });
+                assert_eq!(near_chart, seen.get(&[x as u32, y as u32]) == Some(&1));
+            }
+        }
+    }
+}
@@ -0,0 +1,198 @@
+//! Stable hashing, that does not depend on the platform or the version of the standard library.
+
+use nalgebra::Vector3;
+use std::collections::HashMap;
+
+/// 64-bit FNV-1a hasher.
+pub(crate) struct Fnv1a(u64);
+
+impl Default for Fnv1a {
+    fn default() -> Self {
+        Self(0xcbf2_9ce4_8422_2325)
+    }
+}
+
+impl Fnv1a {
+    pub fn write(&mut self, bytes: &[u8]) {
+        for &byte in bytes {
+            self.0 ^= byte as u64;
+            self.0 = self.0.wrapping_mul(0x0100_0000_01b3);
+        }
+    }
+
+    pub fn write_u64(&mut self, value: u64) {
+        self.write(&value.to_le_bytes());
+    }
+
+    pub fn write_f32(&mut self, value: f32) {
+        self.write(&normalized_bits(value).to_le_bytes());
+    }
+
+    pub fn finish(&self) -> u64 {
+        self.0
+    }
+}
+
+/// Returns bits of the value, with 0.0 and -0.0 having the same bits.
+fn normalized_bits(value: f32) -> u32 {
+    if value == 0.0 {
+        0
+    } else {
+        value.to_bits()
+    }
+}
+
+/// Calculates a content hash of a mesh, that could be used as [`crate::SurfaceDataPatch::data_id`].
+/// The hash is stable across runs, platforms and versions of Rust, so it could be saved along with
+/// a patch to find out whether the patch is still valid for the mesh.
+///
+/// ```rust
+/// # use nalgebra::Vector3;
+/// let vertices = [
+///     Vector3::new(0.0, 0.0, 0.0),
+///     Vector3::new(1.0, 0.0, 0.0),
+///     Vector3::new(0.0, 1.0, 0.0),
+/// ];
+/// let hash = uvgen::hash_mesh(vertices.iter().cloned(), [[0, 1, 2]].into_iter());
+/// assert_ne!(hash, uvgen::hash_mesh(vertices.iter().cloned(), [[0, 2, 1]].into_iter()));
+/// ```
+pub fn hash_mesh(
+    vertices: impl Iterator<Item = Vector3<f32>>,
+    triangles: impl Iterator<Item = [u32; 3]>,
+) -> u64 {
+    let mut hasher = Fnv1a::default();
+    let mut vertex_count = 0u64;
+    for vertex in vertices {
+        hasher.write_f32(vertex.x);
+        hasher.write_f32(vertex.y);
+        hasher.write_f32(vertex.z);
+        vertex_count += 1;
+    }
+    // Make sure that the boundary between vertices and triangles affects the hash.
+    hasher.write_u64(vertex_count);
+    for triangle in triangles {
+        for index in triangle {
+            hasher.write(&index.to_le_bytes());
+        }
+    }
+    hasher.finish()
+}
+
+/// Two meshes with different content, but with the same hash. See [`hash_meshes`].
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub struct HashCollision {
+    /// Index of the first mesh.
+    pub first: usize,
+    /// Index of the second mesh.
+    pub second: usize,
+    /// The hash of both meshes.
+    pub hash: u64,
+}
+
+/// Hashes of a set of meshes. See [`hash_meshes`].
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct MeshHashes {
+    /// Hash of every mesh (see [`hash_mesh`]), in the same order as the meshes.
+    pub hashes: Vec<u64>,
+    /// Pairs of different meshes with the same hash. Identical meshes have the same hash too, but
+    /// they're not considered as collisions.
+    pub collisions: Vec<HashCollision>,
+}
+
+/// Calculates hashes (see [`hash_mesh`]) of the given meshes (pairs of vertices and triangles) and
+/// detects collisions between them. Meshes with colliding hashes must not share
+/// [`crate::SurfaceDataPatch::data_id`], so a different id must be assigned to them.
+pub fn hash_meshes<V, T>(meshes: impl IntoIterator<Item = (V, T)>) -> MeshHashes
+where
+    V: AsRef<[Vector3<f32>]>,
+    T: AsRef<[[u32; 3]]>,
+{
+    let meshes = meshes.into_iter().collect::<Vec<_>>();
+    let hashes = meshes
+        .iter()
+        .map(|(vertices, triangles)| {
+            hash_mesh(
+                vertices.as_ref().iter().cloned(),
+                triangles.as_ref().iter().cloned(),
+            )
+        })
+        .collect::<Vec<_>>();
+
+    let same_content = |a: usize, b: usize| {
+        let (a_vertices, a_triangles) = (meshes[a].0.as_ref(), meshes[a].1.as_ref());
+        let (b_vertices, b_triangles) = (meshes[b].0.as_ref(), meshes[b].1.as_ref());
+        a_triangles == b_triangles
+            && a_vertices.len() == b_vertices.len()
+            && a_vertices.iter().zip(b_vertices).all(|(a, b)| {
+                a.iter()
+                    .zip(b.iter())
+                    .all(|(&a, &b)| normalized_bits(a) == normalized_bits(b))
+            })
+    };
+
+    let collisions = find_collisions(&hashes, same_content);
+    MeshHashes { hashes, collisions }
+}
+
+/// Finds pairs of items with the same hash, but with different content.
+fn find_collisions(
+    hashes: &[u64],
+    same_content: impl Fn(usize, usize) -> bool,
+) -> Vec<HashCollision> {
+    // Items with distinct content for every hash.
+    let mut groups = HashMap::<u64, Vec<usize>>::new();
+    let mut collisions = Vec::new();
+    for (index, &hash) in hashes.iter().enumerate() {
+        let group = groups.entry(hash).or_default();
+        if group.iter().any(|&other| same_content(other, index)) {
+            continue;
+        }
+        collisions.extend(group.iter().map(|&other| HashCollision {
+            first: other,
+            second: index,
+            hash,
+        }));
+        group.push(index);
+    }
+    collisions
+}
+
+#[cfg(test)]
+mod test {
+    use nalgebra::Vector3;
+
+    #[test]
+    fn test_hash_meshes() {
+        let vertices = [
+            Vector3::new(0.0, 0.0, 0.0),
+            Vector3::new(1.0, 0.0, 0.0),
+            Vector3::new(0.0, 1.0, 0.0),
+        ];
+        let negative_zero = [
+            Vector3::new(-0.0, 0.0, 0.0),
+            Vector3::new(1.0, -0.0, 0.0),
+            Vector3::new(0.0, 1.0, -0.0),
+        ];
+        let triangles = [[0, 1, 2]];
+        let flipped = [[0, 2, 1]];
+        let result = super::hash_meshes([
+            (&vertices[..], &triangles[..]),
+            (&vertices[..], &flipped[..]),
+            (&negative_zero[..], &triangles[..]),
+        ]);
+        assert_eq!(result.hashes[0], result.hashes[2]);
+        assert_ne!(result.hashes[0], result.hashes[1]);
+        assert!(result.collisions.is_empty());
+
+        // Collisions of the hash are hard to find, so check the detection with fake hashes.
+        let collisions = super::find_collisions(&[1, 2, 1, 1], |a, b| a == b || a + b == 3);
+        assert_eq!(
+            collisions,
+            [super::HashCollision {
+                first: 0,
+                second: 2,
+                hash: 1,
+            }]
+        );
+    }
+}
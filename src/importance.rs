@@ -0,0 +1,96 @@
+//! Importance-weighted sizing of charts. See [`crate::UvGenOptions::triangle_importance`] for
+//! more info.
+
+use crate::{Chart, UvGenOptions};
+use nalgebra::{Matrix3, Vector2};
+
+/// Returns importance of the triangle. Missing and invalid values mean regular importance.
+fn importance(options: &UvGenOptions, triangle_index: usize) -> f32 {
+    options
+        .triangle_importance
+        .get(triangle_index)
+        .cloned()
+        .filter(|importance| *importance > 0.0 && importance.is_finite())
+        .unwrap_or(1.0)
+}
+
+/// Scales every chart by the square root of the average importance of its triangles (weighted
+/// by their projected area), so the area of the chart in the atlas is proportional to the total
+/// importance-weighted area of its triangles. Does nothing, if there's no importance.
+pub(crate) fn weight_charts(
+    meshes: &mut [Chart],
+    projections: &mut [[Vector2<f32>; 3]],
+    options: &UvGenOptions,
+) {
+    if options.triangle_importance.is_empty() {
+        return;
+    }
+    for mesh in meshes.iter_mut() {
+        let (mut area, mut weighted_area) = (0.0, 0.0);
+        for &triangle_index in mesh.triangles.iter() {
+            if let Some([a, b, c]) = projections.get(triangle_index) {
+                let triangle_area = (b - a).perp(&(c - a)).abs() * 0.5;
+                area += triangle_area;
+                weighted_area += triangle_area * importance(options, triangle_index);
+            }
+        }
+        if area <= 0.0 {
+            continue;
+        }
+        let factor = (weighted_area / area).sqrt();
+        if factor == 1.0 || !factor.is_finite() {
+            continue;
+        }
+        let origin = mesh.uv_min();
+        let transform = Matrix3::new_translation(&origin)
+            * Matrix3::new_scaling(factor)
+            * Matrix3::new_translation(&-origin);
+        mesh.transform(projections, &transform);
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::UvGenOptions;
+
+    #[test]
+    fn test_triangle_importance() {
+        let mesh = crate::test_meshes::cube();
+        let generate = |triangle_importance: Vec<f32>| {
+            crate::generate_uvs_with_options(
+                mesh.vertices.iter().cloned(),
+                mesh.triangles.iter().cloned(),
+                &UvGenOptions {
+                    triangle_importance,
+                    ..Default::default()
+                },
+            )
+            .unwrap()
+        };
+        let chart_area = |patch: &crate::SurfaceDataPatch, triangle: usize| {
+            patch
+                .pack_stats
+                .charts
+                .iter()
+                .find(|chart| chart.triangles.contains(&triangle))
+                .unwrap()
+                .uv_area
+        };
+
+        // Invalid values and triangles past the end of the list have regular importance.
+        let regular = generate(Vec::new());
+        let invalid = generate(vec![1.0, 1.0, -1.0, 0.0, f32::NAN, f32::INFINITY]);
+        assert_eq!(regular.second_tex_coords, invalid.second_tex_coords);
+
+        // The first side of the cube is four times as important as the others.
+        let mut importance = vec![1.0; mesh.triangles.len()];
+        importance[0] = 4.0;
+        importance[1] = 4.0;
+        let weighted = generate(importance);
+        let ratio = chart_area(&weighted, 0) / chart_area(&weighted, 2);
+        assert!((ratio - 4.0).abs() < 1.0e-3, "{ratio}");
+        for triangle in 2..mesh.triangles.len() {
+            assert!((chart_area(&weighted, triangle) - chart_area(&weighted, 2)).abs() < 1.0e-6);
+        }
+    }
+}
@@ -0,0 +1,187 @@
+//! Index types of the triangles of the input meshes. See [`UvIndex`] for more info.
+
+use crate::{SurfaceDataPatch, UvGenError};
+
+/// Type of the vertex indices of the triangles, that could be passed to the generator. The
+/// generator works with `u32` indices internally, so indices of other types are converted when
+/// the triangles are read. Indices of the patched mesh are returned as `u32` in
+/// [`SurfaceDataPatch::triangles`], use [`SurfaceDataPatch::triangles_as`] to convert them back:
+///
+/// ```rust
+/// let mesh = uvgen::test_meshes::cube();
+/// let triangles = mesh
+///     .triangles
+///     .iter()
+///     .map(|triangle| triangle.map(|index| index as u16))
+///     .collect::<Vec<_>>();
+/// let patch = uvgen::generate_uvs(mesh.vertices.iter().cloned(), triangles.into_iter(), 0.005)
+///     .unwrap();
+/// let triangles = patch.triangles_as::<u16>().unwrap();
+/// assert_eq!(triangles.len(), mesh.triangles.len());
+/// ```
+///
+/// The generator duplicates vertices at seams, so the patched mesh could have more vertices than
+/// the index type could address. In this case the generation fails with
+/// [`UvGenError::TooManyVertices`] (or `None`). The generation fails the same way, if an index of
+/// the input does not fit into `u32`.
+pub trait UvIndex: Copy {
+    /// Converts the index to `u32`. Returns `None` if it does not fit into `u32`.
+    fn to_u32(self) -> Option<u32>;
+
+    /// Converts the index from `u32`. Returns `None` if it does not fit into the type.
+    fn from_u32(index: u32) -> Option<Self>;
+}
+
+impl UvIndex for u16 {
+    fn to_u32(self) -> Option<u32> {
+        Some(self.into())
+    }
+
+    fn from_u32(index: u32) -> Option<Self> {
+        index.try_into().ok()
+    }
+}
+
+impl UvIndex for u32 {
+    fn to_u32(self) -> Option<u32> {
+        Some(self)
+    }
+
+    fn from_u32(index: u32) -> Option<Self> {
+        Some(index)
+    }
+}
+
+impl UvIndex for usize {
+    fn to_u32(self) -> Option<u32> {
+        self.try_into().ok()
+    }
+
+    fn from_u32(index: u32) -> Option<Self> {
+        index.try_into().ok()
+    }
+}
+
+/// Converts triangles with indices of the given type to `u32` indices. Fails with
+/// [`UvGenError::TooManyVertices`] if some index does not fit into `u32`.
+pub(crate) fn widen<I: UvIndex>(
+    triangles: impl Iterator<Item = [I; 3]>,
+) -> Result<Vec<[u32; 3]>, UvGenError> {
+    triangles
+        .map(|triangle| {
+            Some([
+                triangle[0].to_u32()?,
+                triangle[1].to_u32()?,
+                triangle[2].to_u32()?,
+            ])
+        })
+        .collect::<Option<Vec<_>>>()
+        .ok_or(UvGenError::TooManyVertices)
+}
+
+/// Checks whether every vertex of the patched mesh could be addressed by indices of the given
+/// type.
+pub(crate) fn check_vertex_count<I: UvIndex>(
+    patch: SurfaceDataPatch,
+) -> Result<SurfaceDataPatch, UvGenError> {
+    let fits = match u32::try_from(patch.second_tex_coords.len()) {
+        Ok(0) => true,
+        Ok(count) => I::from_u32(count - 1).is_some(),
+        // `u32` indices are checked by the generator itself.
+        Err(_) => false,
+    };
+    if fits {
+        Ok(patch)
+    } else {
+        Err(UvGenError::TooManyVertices)
+    }
+}
+
+impl SurfaceDataPatch {
+    /// Returns the triangles of the patched mesh with indices of the given type. Returns `None`
+    /// if some index does not fit into the type.
+    pub fn triangles_as<I: UvIndex>(&self) -> Option<Vec<[I; 3]>> {
+        self.triangles
+            .iter()
+            .map(|triangle| {
+                Some([
+                    I::from_u32(triangle[0])?,
+                    I::from_u32(triangle[1])?,
+                    I::from_u32(triangle[2])?,
+                ])
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::{UvGenError, UvGenOptions, UvIndex};
+
+    #[test]
+    fn test_index_types() {
+        let mesh = crate::test_meshes::uv_sphere(16, 8);
+        let expected = crate::generate_uvs(
+            mesh.vertices.iter().cloned(),
+            mesh.triangles.iter().cloned(),
+            0.005,
+        )
+        .unwrap();
+
+        let narrow = |triangle: &[u32; 3]| triangle.map(|index| index as u16);
+        let patch = crate::generate_uvs(
+            mesh.vertices.iter().cloned(),
+            mesh.triangles.iter().map(narrow),
+            0.005,
+        )
+        .unwrap();
+        assert_eq!(patch.to_golden_string(), expected.to_golden_string());
+        let wide = |triangle: &[u32; 3]| triangle.map(|index| index as usize);
+        let mut triangles = mesh.triangles.iter().map(wide).collect::<Vec<_>>();
+        let mut vertices = mesh.vertices.clone();
+        crate::generate_uvs_inplace(&mut vertices, &mut triangles, &Default::default()).unwrap();
+        assert_eq!(triangles, expected.triangles_as::<usize>().unwrap());
+
+        // The patched mesh does not fit into `u16` indices, if it has too many vertices.
+        assert!(super::check_vertex_count::<u16>(expected.clone()).is_ok());
+        let mut large = expected;
+        large
+            .second_tex_coords
+            .resize(u16::MAX as usize + 2, Default::default());
+        assert_eq!(
+            super::check_vertex_count::<u16>(large.clone()).unwrap_err(),
+            UvGenError::TooManyVertices
+        );
+        assert!(super::check_vertex_count::<u32>(large.clone()).is_ok());
+        large.triangles[0][0] = u16::MAX as u32 + 1;
+        assert!(large.triangles_as::<u16>().is_none());
+
+        let error = crate::try_generate_uvs_with_options(
+            mesh.vertices.iter().cloned(),
+            [[0, 1, mesh.vertices.len() as u16]].into_iter(),
+            &UvGenOptions::default(),
+        )
+        .unwrap_err();
+        assert_eq!(
+            error,
+            UvGenError::OutOfBoundsIndex {
+                triangle: 0,
+                index: mesh.vertices.len() as u64
+            }
+        );
+
+        // Indices, that do not fit into `u32`, are not wrapped around.
+        assert_eq!(u16::MAX.to_u32(), Some(u16::MAX as u32));
+        assert_eq!((u32::MAX as usize).to_u32(), Some(u32::MAX));
+        if let Some(index) = (u32::MAX as usize).checked_add(1) {
+            assert_eq!(index.to_u32(), None);
+            let error = crate::try_generate_uvs_with_options(
+                mesh.vertices.iter().cloned(),
+                [[0, 1, index]].into_iter(),
+                &UvGenOptions::default(),
+            )
+            .unwrap_err();
+            assert_eq!(error, UvGenError::TooManyVertices);
+        }
+    }
+}
@@ -0,0 +1,310 @@
+//! Manual editing of the layout of the charts in the atlas. See [`SurfaceDataPatch::move_chart`]
+//! for more info.
+
+use crate::{ChartUv, Rect, SurfaceDataPatch};
+use nalgebra::Vector2;
+use std::fmt::{Display, Formatter};
+
+/// Tolerance of the placement checks, so rectangles of the packer, that touch each other or the
+/// border of the atlas with rounding errors, are still valid.
+const EPSILON: f32 = 1.0e-5;
+
+/// Reason why a chart could not be moved or scaled, see [`SurfaceDataPatch::move_chart`].
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum LayoutError {
+    /// There's no chart with the given index.
+    InvalidChart(usize),
+    /// The scale factor is not a positive finite number.
+    InvalidScale(f32),
+    /// The new rectangle of the chart is outside of the atlas.
+    OutOfAtlas,
+    /// The new rectangle of the chart overlaps with the rectangle of the other chart.
+    Overlap {
+        /// Index of the other chart in [`crate::PackStats::charts`].
+        chart: usize,
+    },
+    /// The new rectangle of the chart overlaps with an extra rectangle (see
+    /// [`crate::PackStats::extra_rects`]).
+    ExtraRectOverlap {
+        /// Index of the extra rectangle.
+        index: usize,
+    },
+}
+
+impl Display for LayoutError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::InvalidChart(chart) => write!(f, "chart {chart} does not exist"),
+            Self::InvalidScale(factor) => write!(f, "invalid scale factor {factor}"),
+            Self::OutOfAtlas => write!(f, "chart is outside of the atlas"),
+            Self::Overlap { chart } => write!(f, "chart overlaps with chart {chart}"),
+            Self::ExtraRectOverlap { index } => {
+                write!(f, "chart overlaps with extra rectangle {index}")
+            }
+        }
+    }
+}
+
+impl std::error::Error for LayoutError {}
+
+/// Checks whether the rectangles overlap by more than the tolerance.
+fn overlaps(a: &Rect<f32>, b: &Rect<f32>) -> bool {
+    a.x() < b.x() + b.w() - EPSILON
+        && b.x() < a.x() + a.w() - EPSILON
+        && a.y() < b.y() + b.h() - EPSILON
+        && b.y() < a.y() + a.h() - EPSILON
+}
+
+impl SurfaceDataPatch {
+    /// Moves the chart with the given index (see [`crate::PackStats::charts`]), so its rectangle
+    /// (including spacing) starts at the given position of its atlas page. This allows to build
+    /// a manual layout editor on top of the generated layout:
+    ///
+    /// ```rust
+    /// # use nalgebra::Vector2;
+    /// let mesh = uvgen::test_meshes::cube();
+    /// let mut patch = uvgen::generate_uvs(
+    ///     mesh.vertices.iter().cloned(),
+    ///     mesh.triangles.iter().cloned(),
+    ///     0.005,
+    /// )
+    /// .unwrap();
+    /// // Charts can't overlap.
+    /// let other = patch.pack_stats.charts[1].rect.position;
+    /// assert!(patch.move_chart(0, other).is_err());
+    /// // Shrink the chart and move it within its old place.
+    /// let position = patch.pack_stats.charts[0].rect.position;
+    /// patch.scale_chart(0, 0.5).unwrap();
+    /// patch.move_chart(0, position).unwrap();
+    /// ```
+    ///
+    /// The move is rejected, if the chart gets outside of the atlas or overlaps with other charts
+    /// on the same page or with the extra rectangles, the patch is left untouched in this case.
+    /// Texture coordinates of the chart (and [`Self::chart_uvs`], if any) are updated along with
+    /// its rectangle, while its rectangle in texels is reset to `None`. Other statistics, that
+    /// depend on the layout (such as [`crate::PackStats::free_rects`]), are not updated.
+    pub fn move_chart(&mut self, chart: usize, position: Vector2<f32>) -> Result<(), LayoutError> {
+        let rect = self
+            .pack_stats
+            .charts
+            .get(chart)
+            .ok_or(LayoutError::InvalidChart(chart))?
+            .rect;
+        let offset = position - rect.position;
+        let new_rect = Rect::new(position.x, position.y, rect.w(), rect.h());
+        self.check_placement(chart, &new_rect)?;
+        self.transform_chart(chart, new_rect, |uv| uv + offset);
+        Ok(())
+    }
+
+    /// Scales the chart with the given index (see [`crate::PackStats::charts`]) around the center
+    /// of its texture coordinates by the given factor. Spacing around the chart stays the same.
+    /// Texel density of the chart changes proportionally to the factor. The scaling is rejected
+    /// the same way as [`Self::move_chart`] and it also fails if the factor is not a positive
+    /// finite number.
+    pub fn scale_chart(&mut self, chart: usize, factor: f32) -> Result<(), LayoutError> {
+        if !(factor > 0.0 && factor.is_finite()) {
+            return Err(LayoutError::InvalidScale(factor));
+        }
+        let rect = self
+            .pack_stats
+            .charts
+            .get(chart)
+            .ok_or(LayoutError::InvalidChart(chart))?
+            .rect;
+        let (min, max) = self
+            .chart_vertices(chart)
+            .into_iter()
+            .filter_map(|index| self.second_tex_coords.get(index as usize))
+            .fold(
+                (Vector2::repeat(f32::MAX), Vector2::repeat(-f32::MAX)),
+                |(min, max), uv| (min.inf(uv), max.sup(uv)),
+            );
+        let (min, max) = if min.x <= max.x && min.y <= max.y {
+            (min, max)
+        } else {
+            // A chart without triangles is scaled around the center of its rectangle.
+            let center = rect.position + rect.size * 0.5;
+            (center, center)
+        };
+        let center = (min + max) * 0.5;
+        let (margin_min, margin_max) = (min - rect.position, rect.position + rect.size - max);
+        let new_min = center + (min - center) * factor - margin_min;
+        let new_max = center + (max - center) * factor + margin_max;
+        let new_rect = Rect::new(
+            new_min.x,
+            new_min.y,
+            new_max.x - new_min.x,
+            new_max.y - new_min.y,
+        );
+        self.check_placement(chart, &new_rect)?;
+        self.transform_chart(chart, new_rect, |uv| center + (uv - center) * factor);
+        self.pack_stats.charts[chart].uv_area *= factor * factor;
+        Ok(())
+    }
+
+    /// Checks whether the chart could be put into the given rectangle of its page.
+    fn check_placement(&self, chart: usize, rect: &Rect<f32>) -> Result<(), LayoutError> {
+        if !(rect.x() >= -EPSILON
+            && rect.y() >= -EPSILON
+            && rect.x() + rect.w() <= 1.0 + EPSILON
+            && rect.y() + rect.h() <= 1.0 + EPSILON)
+        {
+            return Err(LayoutError::OutOfAtlas);
+        }
+        let page = self.pack_stats.charts[chart].page;
+        for (other_index, other) in self.pack_stats.charts.iter().enumerate() {
+            if other_index != chart && other.page == page && overlaps(rect, &other.rect) {
+                return Err(LayoutError::Overlap { chart: other_index });
+            }
+        }
+        if page == 0 {
+            if let Some(index) = self
+                .pack_stats
+                .extra_rects
+                .iter()
+                .position(|extra| overlaps(rect, extra))
+            {
+                return Err(LayoutError::ExtraRectOverlap { index });
+            }
+        }
+        Ok(())
+    }
+
+    /// Returns unique indices of the vertices of the triangles of the chart.
+    fn chart_vertices(&self, chart: usize) -> Vec<u32> {
+        let mut visited = vec![false; self.second_tex_coords.len()];
+        let mut vertices = Vec::new();
+        for &triangle_index in self.pack_stats.charts[chart].triangles.iter() {
+            for &index in self.triangles.get(triangle_index).into_iter().flatten() {
+                if let Some(visited) = visited.get_mut(index as usize) {
+                    if !*visited {
+                        *visited = true;
+                        vertices.push(index);
+                    }
+                }
+            }
+        }
+        vertices
+    }
+
+    /// Transforms texture coordinates of the vertices of the chart and puts it into the given
+    /// rectangle.
+    fn transform_chart(
+        &mut self,
+        chart: usize,
+        rect: Rect<f32>,
+        transform: impl Fn(Vector2<f32>) -> Vector2<f32>,
+    ) {
+        let inverse_size = rect
+            .size
+            .map(|size| if size > 0.0 { 1.0 / size } else { 0.0 });
+        for index in self.chart_vertices(chart) {
+            let uv = &mut self.second_tex_coords[index as usize];
+            *uv = transform(*uv);
+            if let Some(chart_uv) = self.chart_uvs.get_mut(index as usize) {
+                *chart_uv = ChartUv {
+                    chart: Some(chart as u32),
+                    uv: (*uv - rect.position).component_mul(&inverse_size),
+                };
+            }
+        }
+        let info = &mut self.pack_stats.charts[chart];
+        info.rect = rect;
+        info.texel_rect = None;
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::LayoutError;
+    use crate::UvGenOptions;
+    use nalgebra::Vector2;
+
+    #[test]
+    fn test_chart_layout_editing() {
+        let mesh = crate::test_meshes::cube();
+        let mut patch = crate::generate_uvs_with_options(
+            mesh.vertices.iter().cloned(),
+            mesh.triangles.iter().cloned(),
+            &UvGenOptions {
+                chart_uvs: true,
+                ..Default::default()
+            },
+        )
+        .unwrap();
+        let original = patch.clone();
+        let chart_vertices = |patch: &crate::SurfaceDataPatch, chart: usize| {
+            patch.pack_stats.charts[chart]
+                .triangles
+                .iter()
+                .flat_map(|&triangle| patch.triangles[triangle])
+                .collect::<Vec<_>>()
+        };
+
+        let other = patch.pack_stats.charts[1].rect.position;
+        assert_eq!(
+            patch.move_chart(0, other),
+            Err(LayoutError::Overlap { chart: 1 })
+        );
+        assert_eq!(
+            patch.move_chart(0, Vector2::new(0.9, 0.0)),
+            Err(LayoutError::OutOfAtlas)
+        );
+        // Charts are packed tightly, so there's no room to grow.
+        assert!(patch.scale_chart(0, 2.0).is_err());
+        assert_eq!(
+            patch.scale_chart(0, -1.0),
+            Err(LayoutError::InvalidScale(-1.0))
+        );
+        assert_eq!(
+            patch.move_chart(6, Vector2::zeros()),
+            Err(LayoutError::InvalidChart(6))
+        );
+        assert_eq!(patch.second_tex_coords, original.second_tex_coords);
+        assert_eq!(patch.pack_stats, original.pack_stats);
+
+        // A shrunk chart keeps its spacing and stays inside of its old rectangle.
+        let old_rect = patch.pack_stats.charts[0].rect;
+        patch.scale_chart(0, 0.5).unwrap();
+        let rect = patch.pack_stats.charts[0].rect;
+        let spacing = 0.005;
+        assert!((rect.w() - (old_rect.w() - 2.0 * spacing) * 0.5 - 2.0 * spacing).abs() < 1.0e-5);
+        let uv_area = original.pack_stats.charts[0].uv_area;
+        assert!((patch.pack_stats.charts[0].uv_area - uv_area * 0.25).abs() < 1.0e-6);
+        for index in chart_vertices(&patch, 0) {
+            let uv = patch.second_tex_coords[index as usize];
+            assert!(uv.x >= rect.x() + spacing - 1.0e-5 && uv.y >= rect.y() + spacing - 1.0e-5);
+            assert!(uv.x <= rect.x() + rect.w() - spacing + 1.0e-5);
+            assert!(uv.y <= rect.y() + rect.h() - spacing + 1.0e-5);
+        }
+
+        // Moving the shrunk chart to the corner of its old rectangle shifts its texture
+        // coordinates and chart UVs stay relative to the rectangle.
+        patch.move_chart(0, old_rect.position).unwrap();
+        let offset = old_rect.position - rect.position;
+        for index in chart_vertices(&original, 0) {
+            let index = index as usize;
+            let expected = (original.second_tex_coords[index] - old_rect.position) * 0.5
+                + old_rect.position
+                + Vector2::repeat(spacing * 0.5);
+            assert!((patch.second_tex_coords[index] - expected).norm() < 1.0e-5);
+            let chart_uv = patch.chart_uvs[index];
+            assert_eq!(chart_uv.chart, Some(0));
+            let relocated = chart_uv.relocate(
+                patch.pack_stats.charts[0].rect.position,
+                patch.pack_stats.charts[0].rect.size,
+            );
+            assert!((relocated - patch.second_tex_coords[index]).norm() < 1.0e-5);
+        }
+        assert!(offset.norm() > 0.0);
+        // Other charts are untouched.
+        for index in chart_vertices(&original, 1) {
+            let index = index as usize;
+            assert_eq!(
+                patch.second_tex_coords[index],
+                original.second_tex_coords[index]
+            );
+        }
+    }
+}
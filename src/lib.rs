@@ -74,9 +74,123 @@
 //! }
 //! ```
 
-use nalgebra::{Vector2, Vector3};
-use rectutils::pack::RectPacker;
-use std::cmp::Ordering;
+// Emits a `tracing` debug event, if `trace` feature is enabled. Does nothing otherwise.
+macro_rules! trace_event {
+    ($($arg:tt)*) => {
+        #[cfg(feature = "trace")]
+        tracing::debug!($($arg)*);
+    };
+}
+
+// Opens a `tracing` debug span that lasts until the end of the current scope, if `trace` feature
+// is enabled. Does nothing otherwise.
+macro_rules! trace_span {
+    ($($arg:tt)*) => {
+        #[cfg(feature = "trace")]
+        let _span = tracing::debug_span!($($arg)*).entered();
+    };
+}
+
+mod adjacency;
+mod anchor;
+#[cfg(feature = "rkyv")]
+mod archive;
+mod atlas;
+mod batch;
+mod chart_split;
+mod chart_uvs;
+mod chunked;
+mod context;
+mod cylinders;
+mod debug;
+mod double_sided;
+mod error;
+mod extend;
+mod frames;
+pub mod fuzz_utils;
+#[cfg(feature = "godot")]
+pub mod godot;
+mod golden;
+mod gutter;
+mod hash;
+mod importance;
+mod index;
+mod layout;
+mod locality;
+mod locks;
+mod lod;
+mod measure;
+mod metrics;
+mod mips;
+mod neighbors;
+mod options;
+mod pack;
+mod padding;
+mod pages;
+mod parallel;
+mod plane;
+mod precision;
+mod proxy;
+mod realtime;
+mod rng;
+mod seams;
+#[cfg(feature = "serde")]
+mod serialization;
+pub mod spatial;
+mod split;
+mod stats;
+mod sweep;
+mod task;
+pub mod test_meshes;
+mod tiling;
+mod topology;
+mod vertex_buffer;
+mod virtual_pages;
+mod weld;
+mod winding;
+
+pub use adjacency::triangle_adjacency;
+pub use anchor::{AnchorLayout, ChartAnchor};
+pub use atlas::{pack_into_atlas, AtlasDescription};
+pub use batch::{generate_uvs_batch, MeshInput};
+pub use chart_split::TileAdjacency;
+pub use chart_uvs::ChartUv;
+pub use context::UvGenContext;
+pub use debug::face_planes;
+pub use error::{PartialError, PartialResult, UvGenError};
+pub use extend::extend_patch;
+pub use golden::{compare_golden, GoldenMismatch};
+pub use gutter::GutterTexel;
+pub use hash::{hash_mesh, hash_meshes, HashCollision, MeshHashes};
+pub use index::UvIndex;
+pub use layout::LayoutError;
+pub use lod::ChartLod;
+pub use measure::{measure, measure_with_options, SizeHints};
+pub use metrics::{uv_metrics, UvMetrics};
+pub use mips::{mip_paddings, MipPadding};
+pub use neighbors::ChartNeighbors;
+pub use options::{
+    CancellationToken, CoordinateSystem, DoubleSidedHandling, NeedleHandling,
+    RepeatedIndexHandling, TexelRounding, UvGenOptions,
+};
+pub use pack::PackingError;
+pub use padding::PaddingViolation;
+pub use plane::{project_triangle, triangle_plane, FacePlane};
+pub use precision::generate_uvs_f64;
+pub use proxy::{generate_uvs_with_proxy, transfer_uvs};
+pub use rectutils::Rect;
+pub use split::{generate_uvs_split, MeshPart};
+pub use stats::{
+    ChartCluster, ChartInfo, Histogram, HistogramBin, PackStats, StageTiming, TexelDensity,
+    TexelRect,
+};
+pub use sweep::{resolution_sweep, ResolutionLayout};
+pub use task::{ChartsMut, Stage, UnwrapTask};
+pub use topology::TopologyEdit;
+pub use weld::WeldTolerance;
+
+use nalgebra::{Matrix3, Point2, Vector2, Vector3};
+use std::collections::HashMap;
 
 #[derive(Copy, Clone)]
 enum PlaneClass {
@@ -108,97 +222,420 @@ fn classify_plane(normal: Vector3<f32>) -> PlaneClass {
     class
 }
 
-#[derive(Debug)]
-struct UvMesh {
+/// A chart - a set of connected triangles, that is packed into the atlas as a whole. Texture
+/// coordinates of the triangles of a chart are defined by their projections (see
+/// [`UnwrapTask::charts_mut`]), the chart itself stores only the indices of its triangles and
+/// bounds of their projections. Bounds must be updated (see [`Self::recompute_bounds`]) every time
+/// the projections are changed, otherwise the chart could overlap with other charts in the atlas.
+#[derive(Clone, Debug)]
+pub struct Chart {
     // Array of indices of triangles.
     triangles: Vec<usize>,
     uv_max: Vector2<f32>,
     uv_min: Vector2<f32>,
+    // Index of an extra rectangle (see `UvGenOptions::extra_rects`), that this chart is a
+    // placeholder for.
+    extra_rect: Option<usize>,
+    // Whether this chart consists of collapsed tiny charts, see `UvGenOptions::min_chart_texels`.
+    collapsed: bool,
 }
 
-impl UvMesh {
+impl Chart {
     fn new(first_triangle: usize) -> Self {
         Self {
             triangles: vec![first_triangle],
             uv_max: Vector2::new(-f32::MAX, -f32::MAX),
             uv_min: Vector2::new(f32::MAX, f32::MAX),
+            extra_rect: None,
+            collapsed: false,
         }
     }
 
-    // Returns total width of the mesh.
-    fn width(&self) -> f32 {
+    /// Creates a new chart from the given triangles and calculates its bounds using the given
+    /// projections of the triangles.
+    pub fn from_triangles(triangles: Vec<usize>, projections: &[[Vector2<f32>; 3]]) -> Self {
+        let mut chart = Self {
+            triangles,
+            uv_max: Default::default(),
+            uv_min: Default::default(),
+            extra_rect: None,
+            collapsed: false,
+        };
+        chart.recompute_bounds(projections);
+        chart
+    }
+
+    /// Returns indices of the triangles of the chart.
+    pub fn triangles(&self) -> &[usize] {
+        &self.triangles
+    }
+
+    /// Returns the stable key of the chart, which is the lowest index of its triangles, or
+    /// [`usize::MAX`] if the chart has no triangles. See [`ChartInfo::key`].
+    pub fn key(&self) -> usize {
+        self.triangles.iter().min().cloned().unwrap_or(usize::MAX)
+    }
+
+    /// Returns the bottom-left corner of the bounds of the chart in the projection space.
+    pub fn uv_min(&self) -> Vector2<f32> {
+        self.uv_min
+    }
+
+    /// Returns the top-right corner of the bounds of the chart in the projection space.
+    pub fn uv_max(&self) -> Vector2<f32> {
+        self.uv_max
+    }
+
+    /// Returns total width of the chart.
+    pub fn width(&self) -> f32 {
         self.uv_max.x - self.uv_min.x
     }
 
-    // Returns total height of the mesh.
-    fn height(&self) -> f32 {
+    /// Returns total height of the chart.
+    pub fn height(&self) -> f32 {
         self.uv_max.y - self.uv_min.y
     }
 
-    // Returns total area of the mesh.
-    fn area(&self) -> f32 {
+    /// Returns area of the bounds of the chart.
+    pub fn area(&self) -> f32 {
         self.width() * self.height()
     }
+
+    /// Calculates bounds of the chart using the given projections of the triangles. Triangles
+    /// without projections are ignored.
+    pub fn recompute_bounds(&mut self, projections: &[[Vector2<f32>; 3]]) {
+        (self.uv_min, self.uv_max) = self.projection_bounds(projections);
+    }
+
+    /// Returns the bounds (minimum and maximum) of the projections of the triangles of the chart,
+    /// without updating the chart itself.
+    fn projection_bounds(&self, projections: &[[Vector2<f32>; 3]]) -> (Vector2<f32>, Vector2<f32>) {
+        let mut uv_max = Vector2::new(-f32::MAX, -f32::MAX);
+        let mut uv_min = Vector2::new(f32::MAX, f32::MAX);
+        for &triangle_index in self.triangles.iter() {
+            if let Some([a, b, c]) = projections.get(triangle_index) {
+                uv_min = a.inf(b).inf(c).inf(&uv_min);
+                uv_max = a.sup(b).sup(c).sup(&uv_max);
+            }
+        }
+        (uv_min, uv_max)
+    }
+
+    /// Applies the given 2D affine transform (in homogeneous coordinates) to the projections of
+    /// the triangles of the chart and updates the bounds. This could be used to rotate, scale or
+    /// move a chart.
+    pub fn transform(&mut self, projections: &mut [[Vector2<f32>; 3]], transform: &Matrix3<f32>) {
+        for &triangle_index in self.triangles.iter() {
+            if let Some(projection) = projections.get_mut(triangle_index) {
+                for point in projection.iter_mut() {
+                    *point = transform.transform_point(&Point2::from(*point)).coords;
+                }
+            }
+        }
+        self.recompute_bounds(projections);
+    }
+
+    /// Moves the triangles of the other chart to this chart. Projections are left untouched, so
+    /// the charts should be transformed first to not overlap.
+    pub fn merge(&mut self, other: Chart) {
+        self.triangles.extend(other.triangles);
+        self.uv_min = self.uv_min.inf(&other.uv_min);
+        self.uv_max = self.uv_max.sup(&other.uv_max);
+    }
 }
 
 /// A set of faces with triangles belonging to faces.
-#[derive(Default, Debug)]
+#[derive(Clone, Default, Debug)]
 struct UvBox {
-    px: Vec<usize>,
-    nx: Vec<usize>,
-    py: Vec<usize>,
-    ny: Vec<usize>,
-    pz: Vec<usize>,
-    nz: Vec<usize>,
+    // Triangles of each face of the box in +X, -X, +Y, -Y, +Z, -Z order.
+    faces: [Vec<usize>; 6],
     projections: Vec<[Vector2<f32>; 3]>,
+    // Triangles that do not belong to any face and form their own charts.
+    isolated: Vec<usize>,
+    // Triangles that do not belong to any chart, see `UvGenOptions::repeated_index_handling`.
+    excluded: Vec<usize>,
 }
 
-fn face_vs_face(
-    vertices: &mut Vec<Vector3<f32>>,
-    triangles: &mut Vec<[u32; 3]>,
-    face_triangles: &[usize],
-    other_face_triangles: &[usize],
-    patch: &mut SurfaceDataPatch,
+impl UvBox {
+    /// Removes every triangle, but keeps the allocated memory.
+    fn clear(&mut self) {
+        for face in self.faces.iter_mut() {
+            face.clear();
+        }
+        self.projections.clear();
+        self.isolated.clear();
+        self.excluded.clear();
+    }
+
+    /// Adds the triangle with the given index to the face with the given index.
+    fn push(&mut self, index: usize, face: usize, projection: [Vector2<f32>; 3]) {
+        self.faces[face].push(index);
+        self.projections.push(projection);
+    }
+}
+
+/// Maps the triangle to appropriate side of box. This is so called box mapping. Returns index of
+/// the side of the box (see [`UvBox::faces`]) and projection of the triangle on this side.
+fn box_map(a: &Vector3<f32>, b: &Vector3<f32>, c: &Vector3<f32>) -> (usize, [Vector2<f32>; 3]) {
+    let face = box_face(&(b - a).cross(&(c - a)));
+    (face, face_projection(face, a, b, c))
+}
+
+/// Returns index of the side of the box (see [`UvBox::faces`]), that is aligned with the given
+/// normal the best.
+fn box_face(normal: &Vector3<f32>) -> usize {
+    let class = classify_plane(*normal);
+    match class {
+        PlaneClass::XY => {
+            if normal.z < 0.0 {
+                5
+            } else {
+                4
+            }
+        }
+        PlaneClass::XZ => {
+            if normal.y < 0.0 {
+                3
+            } else {
+                2
+            }
+        }
+        PlaneClass::YZ => {
+            if normal.x < 0.0 {
+                1
+            } else {
+                0
+            }
+        }
+    }
+}
+
+/// Returns the normal of the triangle with the given index, which is either the precomputed one
+/// (see [`UvGenOptions::triangle_normals`]) or the one calculated from the given corners of the
+/// triangle. Corners and the result are in the local frame of the triangle, if `frames` are set.
+fn triangle_normal(
+    triangle_index: usize,
+    [a, b, c]: &[Vector3<f32>; 3],
+    normals: &[Vector3<f32>],
+    frames: Option<&frames::LocalFrames>,
+) -> Vector3<f32> {
+    match normals.get(triangle_index) {
+        Some(normal) if normal.iter().all(|c| c.is_finite()) && *normal != Vector3::zeros() => {
+            match frames {
+                Some(frames) => frames.to_local(triangle_index, normal),
+                None => *normal,
+            }
+        }
+        _ => {
+            let normal = (b - a).cross(&(c - a));
+            match frames {
+                Some(frames) if frames.is_mirrored() => -normal,
+                _ => normal,
+            }
+        }
+    }
+}
+
+/// Projects the triangle on the given side of the box.
+fn face_projection(
+    face: usize,
+    a: &Vector3<f32>,
+    b: &Vector3<f32>,
+    c: &Vector3<f32>,
+) -> [Vector2<f32>; 3] {
+    match face {
+        0 => [a.yz(), b.yz(), c.yz()],
+        1 => [a.zy(), b.zy(), c.zy()],
+        2 => [a.zx(), b.zx(), c.zx()],
+        3 => [a.xz(), b.xz(), c.xz()],
+        4 => [a.xy(), b.xy(), c.xy()],
+        _ => [a.yx(), b.yx(), c.yx()],
+    }
+}
+
+/// Returns a signed component of the normal along the axis of the given side of the box. It is
+/// positive if the normal faces the same direction as the side.
+fn face_alignment(face: usize, normal: &Vector3<f32>) -> f32 {
+    let component = normal[face / 2];
+    if face % 2 == 0 {
+        component
+    } else {
+        -component
+    }
+}
+
+/// Moves triangles, that are almost equally well aligned with two sides of the box, to the side
+/// that most of their neighbours belong to. This reduces the amount of short, ragged seams, that
+/// are typical for curved surfaces. `bias` is in `[0; 1]` range, it defines how much worse the
+/// new side could be aligned with a triangle compared to the best one. `density` is a per-axis
+/// scale of the projections (see [`UvGenOptions::density`]), `normals` are optional precomputed
+/// normals of the triangles (see [`UvGenOptions::triangle_normals`]) and `frames` are optional
+/// local frames of the triangles (see [`UvGenOptions::local_frames`]).
+fn reduce_seams(
+    vertices: &[Vector3<f32>],
+    triangles: &[[u32; 3]],
+    uv_box: &mut UvBox,
+    bias: f32,
+    density: &Vector3<f32>,
+    normals: &[Vector3<f32>],
+    frames: Option<&frames::LocalFrames>,
 ) {
-    for other_triangle_index in other_face_triangles.iter() {
-        let other_triangle = triangles[*other_triangle_index];
-        for triangle_index in face_triangles.iter() {
-            'outer_loop: for vertex_index in triangles[*triangle_index].iter_mut() {
-                for other_vertex_index in other_triangle {
-                    if *vertex_index == other_vertex_index {
-                        // We have adjacency, add new vertex and fix current index.
-                        patch.additional_vertices.push(other_vertex_index);
-                        *vertex_index = vertices.len() as u32;
-                        let vertex = vertices[other_vertex_index as usize];
-                        vertices.push(vertex);
-                        continue 'outer_loop;
+    let mut triangle_faces = vec![None; triangles.len()];
+    for (face, face_triangles) in uv_box.faces.iter().enumerate() {
+        for &triangle_index in face_triangles {
+            triangle_faces[triangle_index] = Some(face);
+        }
+    }
+
+    let mut edges = HashMap::<(u32, u32), Vec<usize>>::new();
+    for (triangle_index, triangle) in triangles.iter().enumerate() {
+        for (i, j) in [(0, 1), (1, 2), (2, 0)] {
+            let key = (triangle[i].min(triangle[j]), triangle[i].max(triangle[j]));
+            edges.entry(key).or_default().push(triangle_index);
+        }
+    }
+
+    for (triangle_index, triangle) in triangles.iter().enumerate() {
+        let Some(face) = triangle_faces[triangle_index] else {
+            continue;
+        };
+
+        let mut neighbour_faces = [0usize; 6];
+        for (i, j) in [(0, 1), (1, 2), (2, 0)] {
+            let key = (triangle[i].min(triangle[j]), triangle[i].max(triangle[j]));
+            for &neighbour in edges[&key].iter() {
+                if neighbour != triangle_index {
+                    if let Some(neighbour_face) = triangle_faces[neighbour] {
+                        neighbour_faces[neighbour_face] += 1;
                     }
                 }
             }
         }
+
+        // Pick the side with the most neighbours, preferring the current one.
+        let mut best_face = face;
+        for (other_face, &count) in neighbour_faces.iter().enumerate() {
+            if count > neighbour_faces[best_face] {
+                best_face = other_face;
+            }
+        }
+        if best_face == face {
+            continue;
+        }
+
+        let [a, b, c] = triangle.map(|index| {
+            let vertex = &vertices[index as usize];
+            match frames {
+                Some(frames) => frames.to_local(triangle_index, vertex),
+                None => *vertex,
+            }
+        });
+        let normal = triangle_normal(triangle_index, &[a, b, c], normals, frames);
+        let alignment = face_alignment(best_face, &normal);
+        if alignment > 0.0 && alignment >= (1.0 - bias) * face_alignment(face, &normal) {
+            triangle_faces[triangle_index] = Some(best_face);
+            let [a, b, c] = [a, b, c].map(|p| p.component_mul(density));
+            uv_box.projections[triangle_index] = face_projection(best_face, &a, &b, &c);
+        }
+    }
+
+    for face_triangles in uv_box.faces.iter_mut() {
+        face_triangles.clear();
+    }
+    for (triangle_index, face) in triangle_faces.into_iter().enumerate() {
+        if let Some(face) = face {
+            uv_box.faces[face].push(triangle_index);
+        }
+    }
+}
+
+/// Projects the triangle on its own plane, which gives a projection without any stretch.
+fn planar_map(a: &Vector3<f32>, b: &Vector3<f32>, c: &Vector3<f32>) -> [Vector2<f32>; 3] {
+    let normal = (b - a).cross(&(c - a));
+    let (Some(u), Some(v)) = (
+        (b - a).try_normalize(f32::EPSILON),
+        normal.cross(&(b - a)).try_normalize(f32::EPSILON),
+    ) else {
+        return [Vector2::default(); 3];
+    };
+    [a, b, c].map(|p| Vector2::new((p - a).dot(&u), (p - a).dot(&v)))
+}
+
+/// Returns the ratio of the longest edge of the triangle to the height of the triangle, that is
+/// perpendicular to that edge. Needle triangles have large ratios, while the ratio of an
+/// equilateral triangle is `~1.15`. Degenerate triangles with non-zero edges have infinite ratio.
+fn needle_ratio(a: &Vector3<f32>, b: &Vector3<f32>, c: &Vector3<f32>) -> f32 {
+    let longest = [b - a, c - b, a - c]
+        .iter()
+        .fold(0.0f32, |longest, edge| longest.max(edge.norm_squared()));
+    let twice_area = (b - a).cross(&(c - a)).norm();
+    if longest == 0.0 {
+        1.0
+    } else if twice_area == 0.0 {
+        f32::INFINITY
+    } else {
+        longest / twice_area
     }
 }
 
-fn make_seam(
+/// Projects the triangle on its own plane, so its longest edge is aligned with the U axis. This
+/// gives the tightest bounds for needle triangles.
+fn edge_aligned_map(a: &Vector3<f32>, b: &Vector3<f32>, c: &Vector3<f32>) -> [Vector2<f32>; 3] {
+    let points = [a, b, c];
+    let first = (0..3)
+        .max_by(|&i, &j| {
+            let edge_i = (points[(i + 1) % 3] - points[i]).norm_squared();
+            let edge_j = (points[(j + 1) % 3] - points[j]).norm_squared();
+            edge_i.total_cmp(&edge_j)
+        })
+        .unwrap_or_default();
+    let projection = planar_map(
+        points[first],
+        points[(first + 1) % 3],
+        points[(first + 2) % 3],
+    );
+    std::array::from_fn(|corner| projection[(corner + 3 - first) % 3])
+}
+
+/// Returns the ratio of the area of the triangle to the area of its projection.
+fn projection_stretch(
+    a: &Vector3<f32>,
+    b: &Vector3<f32>,
+    c: &Vector3<f32>,
+    [pa, pb, pc]: &[Vector2<f32>; 3],
+) -> f32 {
+    let area = (b - a).cross(&(c - a)).norm();
+    let projected_area = (pb - pa).perp(&(pc - pa)).abs();
+    if area == 0.0 {
+        1.0
+    } else if projected_area == 0.0 {
+        f32::INFINITY
+    } else {
+        area / projected_area
+    }
+}
+
+/// Returns an index for a new vertex, that will be added to the given amount of vertices. Returns
+/// `None` if the index does not fit into `u32`, the generator fails in this case instead of
+/// silently wrapping indices around.
+fn new_vertex_index(vertex_count: usize) -> Option<u32> {
+    u32::try_from(vertex_count).ok()
+}
+
+/// Replaces every vertex of the triangle with a new copy, so it is not connected with any other
+/// triangle. Returns `None` if vertex indices overflow or are out of range.
+fn isolate_triangle(
     vertices: &mut Vec<Vector3<f32>>,
-    triangles: &mut Vec<[u32; 3]>,
-    current_face: usize,
-    faces: &[&[usize]],
+    triangle: &mut [u32; 3],
     patch: &mut SurfaceDataPatch,
-) {
-    for (face_index, &other_face_triangles) in faces.iter().enumerate() {
-        if face_index == current_face {
-            continue;
-        }
-
-        face_vs_face(
-            vertices,
-            triangles,
-            &faces[current_face],
-            other_face_triangles,
-            patch,
-        );
+) -> Option<()> {
+    for vertex_index in triangle.iter_mut() {
+        patch.additional_vertices.push(*vertex_index);
+        let vertex = *vertices.get(*vertex_index as usize)?;
+        *vertex_index = new_vertex_index(vertices.len())?;
+        vertices.push(vertex);
     }
+    Some(())
 }
 
 /// A patch for surface data that contains secondary texture coordinates and new topology for data.
@@ -208,10 +645,35 @@ fn make_seam(
 /// deserialization. But freshly loaded resource is not suitable for generated lightmap - in most
 /// cases it just does not have secondary texture coordinates. So we have to patch data after loading
 /// somehow with required data, this is where `SurfaceDataPatch` comes into play.
+///
+/// With `rkyv` feature, patches (including their statistics) could be archived with `rkyv` and
+/// then accessed in place without deserialization, for example, straight from a memory-mapped
+/// asset pack:
+///
+/// ```rust
+/// ##[cfg(feature = "rkyv")]
+/// {
+///     let mesh = uvgen::test_meshes::cube();
+///     let patch = uvgen::generate_uvs(
+///         mesh.vertices.iter().cloned(),
+///         mesh.triangles.iter().cloned(),
+///         0.005,
+///     )
+///     .unwrap();
+///     let bytes = rkyv::to_bytes::<_, 1024>(&patch).unwrap();
+///     let archived = rkyv::check_archived_root::<uvgen::SurfaceDataPatch>(&bytes).unwrap();
+///     assert_eq!(archived.triangles.len(), patch.triangles.len());
+/// }
+/// ```
 #[derive(Clone, Debug, Default)]
+#[cfg_attr(
+    feature = "rkyv",
+    derive(rkyv::Archive, rkyv::Serialize, rkyv::Deserialize),
+    archive(check_bytes)
+)]
 pub struct SurfaceDataPatch {
-    /// A surface data id. Usually it is just a hash of surface data. Can be ignored completely, if
-    /// you don't need to save patches.
+    /// A surface data id. Usually it is just a hash of surface data (see [`hash_mesh`]). Can be
+    /// ignored completely, if you don't need to save patches.
     pub data_id: u64,
     /// List of indices of vertices, that must be cloned and pushed into vertices array one by one at
     /// the end.
@@ -222,126 +684,415 @@ pub struct SurfaceDataPatch {
     /// List of second texture coordinates used for light maps. This list includes all the vertices
     /// **added** by the generation step.
     pub second_tex_coords: Vec<Vector2<f32>>,
+    /// Packing statistics. It is not needed to apply the patch, but could be used to find out
+    /// which parts of the atlas are left unoccupied.
+    pub pack_stats: PackStats,
+    /// Optional per-vertex RGBA colors, where each chart has its own distinct color. This list
+    /// has the same length as [`Self::second_tex_coords`]. It is empty, unless
+    /// [`UvGenOptions::chart_colors`] is set.
+    pub chart_colors: Vec<[u8; 4]>,
+    /// Optional adjacency of the new triangles (see [`triangle_adjacency`] for the layout). Seams
+    /// are ignored, so triangles on the opposite sides of a seam are neighbours. It is empty,
+    /// unless [`UvGenOptions::adjacency`] is set.
+    pub adjacency: Vec<[Option<u32>; 3]>,
+    /// Optional indices of atlas pages of the new triangles. Texture coordinates of a triangle
+    /// are relative to its page. It is empty, unless [`UvGenOptions::page_count`] is greater than
+    /// one.
+    pub triangle_pages: Vec<u32>,
+    /// Optional texture coordinates of every vertex relative to the rectangle of its chart. This
+    /// list has the same length as [`Self::second_tex_coords`]. It is empty, unless
+    /// [`UvGenOptions::chart_uvs`] is set.
+    pub chart_uvs: Vec<ChartUv>,
+    /// Optional projections of every new triangle (see [`UvGenOptions::triangle_projections`]),
+    /// indexed by triangle index. It is empty, unless [`UvGenOptions::triangle_projections`] is
+    /// set.
+    pub triangle_projections: Vec<[Vector2<f32>; 3]>,
 }
 
-/// Maps each triangle from surface to appropriate side of box. This is so called
-/// box mapping.
-fn generate_uv_box(vertices: &[Vector3<f32>], triangles: &[[u32; 3]]) -> Option<UvBox> {
-    let mut uv_box = UvBox::default();
-    for (i, triangle) in triangles.iter().enumerate() {
-        let a = vertices.get(triangle[0] as usize)?;
-        let b = vertices.get(triangle[1] as usize)?;
-        let c = vertices.get(triangle[2] as usize)?;
-        let normal = (b - a).cross(&(c - a));
-        let class = classify_plane(normal);
-        match class {
-            PlaneClass::XY => {
-                if normal.z < 0.0 {
-                    uv_box.nz.push(i);
-                    uv_box.projections.push([a.yx(), b.yx(), c.yx()])
-                } else {
-                    uv_box.pz.push(i);
-                    uv_box.projections.push([a.xy(), b.xy(), c.xy()]);
-                }
+/// A corner of a triangle, that was moved to a cloned vertex by the generator. See
+/// [`SurfaceDataPatch::corner_remaps`] for more info.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub struct CornerRemap {
+    /// Index of the triangle.
+    pub triangle: usize,
+    /// Index of the corner of the triangle (in `0..3` range).
+    pub corner: usize,
+    /// Index of the source vertex, that the corner used originally.
+    pub source: u32,
+    /// Index of the vertex, that the corner uses now. It is a clone of the source vertex.
+    pub clone: u32,
+}
+
+/// Relation of a vertex of a patched mesh to the seams, see [`SurfaceDataPatch::seam_flags`].
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq, Hash)]
+pub struct SeamFlags {
+    /// Whether the vertex lies on a seam: it is used by at least one triangle and there's some
+    /// other used vertex, that is a copy of the same source vertex.
+    pub on_seam: bool,
+    /// Whether the vertex was added by the generator (see
+    /// [`SurfaceDataPatch::additional_vertices`]).
+    pub added: bool,
+}
+
+impl SurfaceDataPatch {
+    /// Returns amount of source vertices of the mesh, that the patch was generated for.
+    pub fn source_vertex_count(&self) -> usize {
+        self.second_tex_coords
+            .len()
+            .saturating_sub(self.additional_vertices.len())
+    }
+
+    /// Returns an index of the source vertex for the given vertex index of the patched mesh.
+    /// Returns `None` if the index is out of bounds.
+    pub fn source_vertex(&self, mut index: u32) -> Option<u32> {
+        let source_count = self.source_vertex_count();
+        // Additional vertices could be clones of other (earlier) additional vertices.
+        while let Some(added) = (index as usize).checked_sub(source_count) {
+            let source = *self.additional_vertices.get(added)?;
+            if source >= index {
+                return None;
             }
-            PlaneClass::XZ => {
-                if normal.y < 0.0 {
-                    uv_box.ny.push(i);
-                    uv_box.projections.push([a.xz(), b.xz(), c.xz()])
-                } else {
-                    uv_box.py.push(i);
-                    uv_box.projections.push([a.zx(), b.zx(), c.zx()])
-                }
+            index = source;
+        }
+        Some(index)
+    }
+
+    /// Returns seam flags for every vertex of the patched mesh. Unlike
+    /// [`Self::additional_vertices`], the flags take into account that source vertices could become
+    /// unreferenced (so their only used clone is not on a seam) and that both sides of a seam are
+    /// on the seam, not only the added one:
+    ///
+    /// ```rust
+    /// let mesh = uvgen::test_meshes::cube();
+    /// let patch = uvgen::generate_uvs(
+    ///     mesh.vertices.iter().cloned(),
+    ///     mesh.triangles.iter().cloned(),
+    ///     0.005,
+    /// )
+    /// .unwrap();
+    /// // Every vertex of a cube lies on a seam.
+    /// let flags = patch.seam_flags();
+    /// for vertex in patch.triangles.iter().flatten() {
+    ///     assert!(flags[*vertex as usize].on_seam);
+    /// }
+    /// ```
+    pub fn seam_flags(&self) -> Vec<SeamFlags> {
+        let source_count = self.source_vertex_count();
+        let referenced = self.referenced_vertices();
+        let sources = (0..referenced.len() as u32)
+            .map(|index| self.source_vertex(index).unwrap_or(index) as usize)
+            .collect::<Vec<_>>();
+        let mut copies = vec![0usize; source_count];
+        for (&source, _) in sources.iter().zip(&referenced).filter(|(_, r)| **r) {
+            if let Some(copies) = copies.get_mut(source) {
+                *copies += 1;
             }
-            PlaneClass::YZ => {
-                if normal.x < 0.0 {
-                    uv_box.nx.push(i);
-                    uv_box.projections.push([a.zy(), b.zy(), c.zy()])
-                } else {
-                    uv_box.px.push(i);
-                    uv_box.projections.push([a.yz(), b.yz(), c.yz()])
+        }
+        sources
+            .iter()
+            .zip(&referenced)
+            .enumerate()
+            .map(|(index, (&source, &referenced))| SeamFlags {
+                on_seam: referenced && copies.get(source).is_some_and(|&c| c > 1),
+                added: index >= source_count,
+            })
+            .collect()
+    }
+
+    /// Returns a "relative" view of the new topology: a list of triangle corners, that were moved
+    /// to cloned vertices. Every other corner references the same vertex as in the source
+    /// topology. This is useful for engines, that keep source index buffers immutable and build
+    /// their own derived buffers:
+    ///
+    /// ```rust
+    /// # use nalgebra::Vector3;
+    /// # let vertices = [
+    /// #     Vector3::new(0.0, 0.0, 0.0),
+    /// #     Vector3::new(1.0, 0.0, 0.0),
+    /// #     Vector3::new(0.0, 1.0, 0.0),
+    /// #     Vector3::new(0.0, 0.0, 1.0),
+    /// # ];
+    /// let source_triangles = [[0, 1, 2], [0, 3, 1]];
+    /// let patch =
+    ///     uvgen::generate_uvs(vertices.iter().cloned(), source_triangles.iter().cloned(), 0.005)
+    ///         .unwrap();
+    ///
+    /// let mut triangles = source_triangles.to_vec();
+    /// for remap in patch.corner_remaps() {
+    ///     assert_eq!(triangles[remap.triangle][remap.corner], remap.source);
+    ///     triangles[remap.triangle][remap.corner] = remap.clone;
+    /// }
+    /// assert_eq!(triangles, patch.triangles);
+    /// ```
+    pub fn corner_remaps(&self) -> Vec<CornerRemap> {
+        let source_count = self.source_vertex_count();
+        let mut remaps = Vec::new();
+        for (triangle_index, triangle) in self.triangles.iter().enumerate() {
+            for (corner, &index) in triangle.iter().enumerate() {
+                if index as usize >= source_count {
+                    if let Some(source) = self.source_vertex(index) {
+                        remaps.push(CornerRemap {
+                            triangle: triangle_index,
+                            corner,
+                            source,
+                            clone: index,
+                        });
+                    }
                 }
             }
         }
+        remaps
     }
-    Some(uv_box)
 }
 
-// Generates a set of UV meshes.
-fn generate_uv_meshes(
-    uv_box: &UvBox,
-    data_id: u64,
-    vertices: &mut Vec<Vector3<f32>>,
-    triangles: &mut Vec<[u32; 3]>,
-) -> (Vec<UvMesh>, SurfaceDataPatch) {
-    let mut mesh_patch = SurfaceDataPatch {
-        data_id,
-        ..Default::default()
-    };
+/// A patch without unreferenced vertices. See [`SurfaceDataPatch::compacted`] for more info.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct CompactedPatch {
+    /// Index of a source vertex for every vertex of the compacted mesh.
+    pub vertices: Vec<u32>,
+    /// Topology of the compacted mesh.
+    pub triangles: Vec<[u32; 3]>,
+    /// Second texture coordinates for every vertex of the compacted mesh.
+    pub second_tex_coords: Vec<Vector2<f32>>,
+    /// Chart colors for every vertex of the compacted mesh. It is empty, if the patch does not
+    /// have chart colors.
+    pub chart_colors: Vec<[u8; 4]>,
+    /// Texture coordinates relative to chart rectangles for every vertex of the compacted mesh.
+    /// It is empty, if the patch does not have them.
+    pub chart_uvs: Vec<ChartUv>,
+}
 
-    // Step 1. Split vertices at boundary between each face. This step multiplies the
-    // number of vertices at boundary so we'll get separate texture coordinates at
-    // seams.
-    for face_index in 0..6 {
-        make_seam(
-            vertices,
-            triangles,
-            face_index,
-            &[
-                &uv_box.px, &uv_box.nx, &uv_box.py, &uv_box.ny, &uv_box.pz, &uv_box.nz,
-            ],
-            &mut mesh_patch,
-        );
+impl SurfaceDataPatch {
+    /// Returns indices of vertices of the patched mesh, that are not referenced by any triangle.
+    /// Source vertices could become unreferenced when every triangle that used them was moved to
+    /// clones of them.
+    pub fn unreferenced_vertices(&self) -> Vec<u32> {
+        let referenced = self.referenced_vertices();
+        (0..referenced.len() as u32)
+            .filter(|&index| !referenced[index as usize])
+            .collect()
     }
 
-    // Step 2. Find separate "meshes" on uv map. After box mapping we will most likely
-    // end up with set of faces, some of them may form meshes and each such mesh must
-    // be moved with all faces it has.
-    let mut meshes = Vec::new();
-    let mut removed_triangles = vec![false; triangles.len()];
-    for triangle_index in 0..triangles.len() {
-        if !removed_triangles[triangle_index] {
-            // Start off random triangle and continue gather adjacent triangles one by one.
-            let mut mesh = UvMesh::new(triangle_index);
-            removed_triangles[triangle_index] = true;
-
-            let mut last_triangle = 1;
-            let mut i = 0;
-            while i < last_triangle {
-                let triangle = &triangles[mesh.triangles[i]];
-                // Push all adjacent triangles into mesh. This is brute force implementation.
-                for (other_triangle_index, other_triangle) in triangles.iter().enumerate() {
-                    if !removed_triangles[other_triangle_index] {
-                        'vertex_loop: for &vertex_index in triangle {
-                            for &other_vertex_index in other_triangle {
-                                if vertex_index == other_vertex_index {
-                                    mesh.triangles.push(other_triangle_index);
-                                    removed_triangles[other_triangle_index] = true;
-                                    // Push border further to continue iterating from added
-                                    // triangle. This is needed because we checking one triangle
-                                    // after another and we must continue if new triangles have
-                                    // some adjacent ones.
-                                    last_triangle += 1;
-                                    break 'vertex_loop;
-                                }
-                            }
+    /// Returns the tight bounding rectangle of the texture coordinates of the charts on the given
+    /// atlas page (always zero, unless [`UvGenOptions::page_count`] is greater than one), or
+    /// `None` if there are no charts on the page. Packing is conservative, so the rectangle is
+    /// usually smaller than the whole atlas. Bakers could crop the lightmap to it or rescale the
+    /// texture coordinates to reclaim the unused border:
+    ///
+    /// ```rust
+    /// let mesh = uvgen::test_meshes::cube();
+    /// let mut patch = uvgen::generate_uvs(
+    ///     mesh.vertices.iter().cloned(),
+    ///     mesh.triangles.iter().cloned(),
+    ///     0.005,
+    /// )
+    /// .unwrap();
+    /// let bounds = patch.uv_bounds(0).unwrap();
+    /// assert!(bounds.w() <= 1.0 && bounds.h() <= 1.0);
+    /// for uv in patch.second_tex_coords.iter_mut() {
+    ///     *uv = (*uv - bounds.position).component_div(&bounds.size);
+    /// }
+    /// ```
+    pub fn uv_bounds(&self, page: usize) -> Option<Rect<f32>> {
+        let (mut min, mut max) = (Vector2::repeat(f32::MAX), Vector2::repeat(-f32::MAX));
+        for chart in self.pack_stats.charts.iter() {
+            if chart.page != page {
+                continue;
+            }
+            for &triangle_index in chart.triangles.iter() {
+                for &index in self.triangles.get(triangle_index).into_iter().flatten() {
+                    if let Some(uv) = self.second_tex_coords.get(index as usize) {
+                        if uv.iter().all(|c| c.is_finite()) {
+                            min = min.inf(uv);
+                            max = max.sup(uv);
                         }
                     }
                 }
-                i += 1;
             }
+        }
+        (min.x <= max.x && min.y <= max.y)
+            .then(|| Rect::new(min.x, min.y, max.x - min.x, max.y - min.y))
+    }
 
-            // Calculate bounds.
-            for &triangle_index in mesh.triangles.iter() {
-                let [a, b, c] = uv_box.projections[triangle_index];
-                mesh.uv_min = a.inf(&b).inf(&c).inf(&mesh.uv_min);
-                mesh.uv_max = a.sup(&b).sup(&c).sup(&mesh.uv_max);
+    fn referenced_vertices(&self) -> Vec<bool> {
+        let mut referenced = vec![false; self.second_tex_coords.len()];
+        for &index in self.triangles.iter().flatten() {
+            if let Some(referenced) = referenced.get_mut(index as usize) {
+                *referenced = true;
             }
-            meshes.push(mesh);
         }
+        referenced
     }
 
-    (meshes, mesh_patch)
+    /// Returns the patched mesh without unreferenced vertices (see
+    /// [`Self::unreferenced_vertices`]), which keeps vertex buffers tight. Vertices keep their
+    /// relative order. Unlike the patch itself, the result describes the entire vertex buffer:
+    ///
+    /// ```rust
+    /// # use nalgebra::Vector3;
+    /// # let source_vertices = [
+    /// #     Vector3::new(0.0, 0.0, 0.0),
+    /// #     Vector3::new(1.0, 0.0, 0.0),
+    /// #     Vector3::new(0.0, 1.0, 0.0),
+    /// #     Vector3::new(0.0, 0.0, 1.0),
+    /// # ];
+    /// # let source_triangles = [[0u32, 1, 2], [0, 3, 1]];
+    /// let patch = uvgen::generate_uvs(
+    ///     source_vertices.iter().cloned(),
+    ///     source_triangles.iter().cloned(),
+    ///     0.005,
+    /// )
+    /// .unwrap();
+    /// let compacted = patch.compacted();
+    ///
+    /// let vertices = compacted
+    ///     .vertices
+    ///     .iter()
+    ///     .map(|&source| source_vertices[source as usize])
+    ///     .collect::<Vec<_>>();
+    /// assert_eq!(vertices.len(), compacted.second_tex_coords.len());
+    /// ```
+    pub fn compacted(&self) -> CompactedPatch {
+        let referenced = self.referenced_vertices();
+        let mut new_indices = vec![u32::MAX; referenced.len()];
+        let mut compacted = CompactedPatch::default();
+        for (index, _) in referenced.iter().enumerate().filter(|(_, r)| **r) {
+            new_indices[index] = compacted.vertices.len() as u32;
+            compacted
+                .vertices
+                .push(self.source_vertex(index as u32).unwrap_or(index as u32));
+            compacted
+                .second_tex_coords
+                .push(self.second_tex_coords[index]);
+            if let Some(color) = self.chart_colors.get(index) {
+                compacted.chart_colors.push(*color);
+            }
+            if let Some(chart_uv) = self.chart_uvs.get(index) {
+                compacted.chart_uvs.push(*chart_uv);
+            }
+        }
+        compacted.triangles = self
+            .triangles
+            .iter()
+            .map(|triangle| {
+                triangle.map(|index| new_indices.get(index as usize).cloned().unwrap_or(index))
+            })
+            .collect();
+        compacted
+    }
+}
+
+/// Returns a position of a vertex with the given index of a patched mesh. `vertices` are the
+/// source vertices and `additional_vertices` are the vertices added by the generator.
+fn vertex_position(
+    vertices: &[Vector3<f32>],
+    additional_vertices: &[u32],
+    mut index: u32,
+) -> Option<Vector3<f32>> {
+    // Additional vertices could be clones of other (earlier) additional vertices.
+    while let Some(added) = (index as usize).checked_sub(vertices.len()) {
+        let source = *additional_vertices.get(added)?;
+        if source >= index {
+            return None;
+        }
+        index = source;
+    }
+    vertices.get(index as usize).cloned()
+}
+
+/// Returns a distinct color for a chart with the given index. Hue of colors of subsequent charts
+/// is shifted by the golden ratio, which gives well distinguishable colors for any number of
+/// charts.
+fn chart_color(index: usize) -> [u8; 4] {
+    let hue = (index as f32 * 0.618_034).fract() * 6.0;
+    let x = 1.0 - (hue % 2.0 - 1.0).abs();
+    let (r, g, b) = match hue as u32 {
+        0 => (1.0, x, 0.0),
+        1 => (x, 1.0, 0.0),
+        2 => (0.0, 1.0, x),
+        3 => (0.0, x, 1.0),
+        4 => (x, 0.0, 1.0),
+        _ => (1.0, 0.0, x),
+    };
+    // Keep colors a bit desaturated so they're not too bright.
+    let to_byte = |c: f32| (64.0 + c * 191.0) as u8;
+    [to_byte(r), to_byte(g), to_byte(b), 255]
+}
+
+/// Allocates per-vertex data of the patch for the given amount of vertices.
+fn prepare_patch(patch: &mut SurfaceDataPatch, vertex_count: usize, options: &UvGenOptions) {
+    patch.second_tex_coords = vec![Vector2::default(); vertex_count];
+    if options.chart_colors {
+        patch.chart_colors = vec![[0, 0, 0, 255]; vertex_count];
+    }
+}
+
+/// Checks whether the triangle references the same vertex more than once.
+fn has_repeated_indices(triangle: &[u32; 3]) -> bool {
+    triangle[0] == triangle[1] || triangle[1] == triangle[2] || triangle[0] == triangle[2]
+}
+
+/// Assigns sentinel texture coordinates to the vertices of triangles with repeated indices, if it
+/// was requested. Triangles of the patch must be set already.
+fn assign_sentinel_uvs(patch: &mut SurfaceDataPatch, options: &UvGenOptions) {
+    let RepeatedIndexHandling::Sentinel(sentinel) = options.repeated_index_handling else {
+        return;
+    };
+    for triangle in patch.triangles.iter() {
+        // Vertices of such triangles were split, so compare the source vertices.
+        if has_repeated_indices(&triangle.map(|index| patch.source_vertex(index).unwrap_or(index)))
+        {
+            for &index in triangle {
+                if let Some(tex_coord) = patch.second_tex_coords.get_mut(index as usize) {
+                    *tex_coord = sentinel;
+                }
+            }
+        }
+    }
+}
+
+/// Returns an offset, that moves the given point to the nearest texel center of a texture with the
+/// given resolution.
+fn texel_snap_offset(point: Vector2<f32>, resolution: f32) -> Vector2<f32> {
+    point.map(|c| ((c * resolution - 0.5).round() + 0.5) / resolution - c)
+}
+
+/// Calculates final texture coordinates for every vertex of the given UV mesh, that was packed
+/// into the given rectangle. Returns `None` if the mesh references a vertex that does not exist.
+#[allow(clippy::too_many_arguments)]
+fn assign_uvs(
+    patch: &mut SurfaceDataPatch,
+    mesh_index: usize,
+    mesh: &Chart,
+    rect: &Rect<f32>,
+    packing: &pack::Packing,
+    triangles: &[[u32; 3]],
+    projections: &[[Vector2<f32>; 3]],
+    options: &UvGenOptions,
+) -> Option<()> {
+    let color = chart_color(mesh_index);
+    let offset = if options.texel_snap_resolution > 0 {
+        let origin = rect.position + Vector2::repeat(options.spacing);
+        texel_snap_offset(origin, options.texel_snap_resolution as f32)
+    } else {
+        Vector2::default()
+    };
+    for &triangle_index in mesh.triangles.iter() {
+        for (&vertex_index, &projection) in triangles
+            .get(triangle_index)?
+            .iter()
+            .zip(projections.get(triangle_index)?)
+        {
+            let second_tex_coord = patch.second_tex_coords.get_mut(vertex_index as usize)?;
+
+            *second_tex_coord = packing.map(mesh, rect, projection, options.spacing) + offset;
+
+            if let Some(chart_color) = patch.chart_colors.get_mut(vertex_index as usize) {
+                *chart_color = color;
+            }
+        }
+    }
+    Some(())
 }
 
 /// Generates UV map for the given vertices and triangles.
@@ -350,82 +1101,275 @@ fn generate_uv_meshes(
 ///
 /// This method utilizes lots of "brute force" algorithms, so it is not fast as it could be in
 /// ideal case. It also allocates some memory for internal needs.
-pub fn generate_uvs(
+///
+/// # Limits
+///
+/// The generator duplicates vertices at seams, so the patched mesh could have more vertices than
+/// the source one. If the amount of vertices of the patched mesh exceeds `u32::MAX + 1`, so they
+/// can't be addressed by `u32` indices, the generation fails and `None` is returned. The same
+/// applies to narrower index types of the triangles (see [`UvIndex`]), for example, `u16` indices
+/// could address up to `65536` vertices. Indices of the input, that do not fit into `u32`, make the
+/// generation fail as well.
+/// Meshes, that are too large for `u32` indices, could be unwrapped part by part with
+/// [`generate_uvs_split`].
+///
+/// # Panics
+///
+/// Never. Invalid input (out-of-range indices, non-finite positions, invalid options) makes the
+/// generation fail with `None` or gives meaningless texture coordinates for meaningless geometry.
+/// See [`fuzz_utils`] module to check this for your inputs.
+pub fn generate_uvs<I: UvIndex>(
     vertices: impl Iterator<Item = Vector3<f32>>,
-    triangles: impl Iterator<Item = [u32; 3]>,
+    triangles: impl Iterator<Item = [I; 3]>,
     spacing: f32,
 ) -> Option<SurfaceDataPatch> {
-    let mut vertices = vertices.collect::<Vec<_>>();
-    let mut triangles = triangles.collect::<Vec<_>>();
-
-    let uv_box = generate_uv_box(&vertices, &triangles)?;
-
-    let (mut meshes, mut patch) = generate_uv_meshes(&uv_box, 0, &mut vertices, &mut triangles);
-
-    // Step 4. Arrange and scale all meshes on uv map so it fits into [0;1] range.
-    let area = meshes.iter().fold(0.0, |area, mesh| area + mesh.area());
-    let square_side = area.sqrt() + spacing * meshes.len() as f32;
+    generate_uvs_with_options(
+        vertices,
+        triangles,
+        &UvGenOptions {
+            spacing,
+            ..Default::default()
+        },
+    )
+}
 
-    meshes.sort_unstable_by(|a, b| b.area().partial_cmp(&a.area()).unwrap_or(Ordering::Equal));
+/// Generates UV map for the given vertices and triangles using the given options. See
+/// [`UvGenOptions`] docs for more info.
+///
+/// # Performance
+///
+/// See [`generate_uvs`] docs.
+pub fn generate_uvs_with_options<I: UvIndex>(
+    vertices: impl Iterator<Item = Vector3<f32>>,
+    triangles: impl Iterator<Item = [I; 3]>,
+    options: &UvGenOptions,
+) -> Option<SurfaceDataPatch> {
+    trace_span!("generate_uvs");
 
-    let mut rects = Vec::new();
+    let options = &tiling::tiling_options(realtime::bounded_options(mips::with_mip_padding(
+        options.clone(),
+    )));
+    let triangles = index::widen(triangles).ok()?;
+    let patch = if options.max_chunk_triangles > 0 && triangles.len() > options.max_chunk_triangles
+    {
+        chunked::generate_uvs_chunked(vertices.collect(), triangles, options).ok()
+    } else {
+        UnwrapTask::new(vertices, triangles.into_iter(), options.clone()).run()
+    };
+    index::check_vertex_count::<I>(patch?).ok()
+}
 
-    let twice_spacing = spacing * 2.0;
+/// Generates UV map for the given vertices and triangles using the given options, like
+/// [`generate_uvs_with_options`], but returns the reason of the failure, if it fails:
+///
+/// ```rust
+/// let mesh = uvgen::test_meshes::cube();
+/// let error = uvgen::try_generate_uvs_with_options(
+///     mesh.vertices[..4].iter().cloned(),
+///     mesh.triangles.iter().cloned(),
+///     &Default::default(),
+/// )
+/// .unwrap_err();
+/// assert!(matches!(error, uvgen::UvGenError::OutOfBoundsIndex { .. }));
+/// ```
+///
+/// Indices of the triangles are validated before the generation, which takes an additional pass
+/// over the triangles. If the packer gives up, the generation fails with
+/// [`UvGenError::PackingFailed`] instead of returning charts, that have no place in the atlas.
+pub fn try_generate_uvs_with_options<I: UvIndex>(
+    vertices: impl Iterator<Item = Vector3<f32>>,
+    triangles: impl Iterator<Item = [I; 3]>,
+    options: &UvGenOptions,
+) -> Result<SurfaceDataPatch, UvGenError> {
+    try_generate_uvs_partial(vertices, triangles, options).map_err(|error| error.error)
+}
 
-    // Some empiric coefficient that large enough to make size big enough for all meshes.
-    // This should be large enough to fit all meshes, but small to prevent losing of space.
-    // We'll use iterative approach to pack everything as tight as possible: at each iteration
-    // scale will be increased until packer is able to pack everything.
-    let mut empiric_scale = 1.1;
-    let mut scale = 1.0;
-    let mut packer = RectPacker::new(1.0, 1.0);
-    'try_loop: for _ in 0..100 {
-        rects.clear();
+/// Generates UV map for the given vertices and triangles using the given options, like
+/// [`try_generate_uvs_with_options`], but also returns the data, that was calculated before the
+/// failure (see [`PartialResult`]), if it fails:
+///
+/// ```rust
+/// let mesh = uvgen::test_meshes::cube();
+/// let options = uvgen::UvGenOptions {
+///     spacing: 0.4,
+///     ..Default::default()
+/// };
+/// let error = uvgen::try_generate_uvs_partial(
+///     mesh.vertices.iter().cloned(),
+///     mesh.triangles.iter().cloned(),
+///     &options,
+/// )
+/// .unwrap_err();
+/// let partial = error.partial.unwrap();
+/// assert_eq!(partial.stage, uvgen::Stage::Packing);
+/// assert_eq!(partial.charts.len(), 6);
+/// ```
+pub fn try_generate_uvs_partial<I: UvIndex>(
+    vertices: impl Iterator<Item = Vector3<f32>>,
+    triangles: impl Iterator<Item = [I; 3]>,
+    options: &UvGenOptions,
+) -> Result<SurfaceDataPatch, PartialError> {
+    trace_span!("generate_uvs");
 
-        // Calculate size of atlas for packer, we'll scale it later on.
-        scale = 1.0 / (square_side * empiric_scale);
+    let fail = |error, partial| PartialError { error, partial };
+    let options = &tiling::tiling_options(realtime::bounded_options(mips::with_mip_padding(
+        options.clone(),
+    )));
+    let vertices = vertices.collect::<Vec<_>>();
+    let triangles = index::widen(triangles).map_err(|error| fail(error, None))?;
+    error::validate(&vertices, &triangles, options).map_err(|error| fail(error, None))?;
 
-        // We'll pack into 1.0 square, our UVs must be in [0;1] range, no wrapping is allowed.
-        packer.clear();
-        for mesh in meshes.iter() {
-            if let Some(rect) = packer.find_free(
-                mesh.width() * scale + twice_spacing,
-                mesh.height() * scale + twice_spacing,
-            ) {
-                rects.push(rect);
-            } else {
-                // I don't know how to pass this by without iterative approach :(
-                empiric_scale *= 1.33;
-                continue 'try_loop;
+    let patch = if options.max_chunk_triangles > 0 && triangles.len() > options.max_chunk_triangles
+    {
+        chunked::generate_uvs_chunked(vertices, triangles, options)
+            .map_err(|error| fail(error, None))?
+    } else {
+        let mut task =
+            UnwrapTask::new(vertices.into_iter(), triangles.into_iter(), options.clone());
+        // Unlike `run`, stepping keeps the task, so the reason of its failure is still available.
+        let patch = loop {
+            if let std::task::Poll::Ready(result) = task.step(std::time::Duration::MAX) {
+                break result;
             }
-        }
-    }
+        };
+        let Some(patch) = patch else {
+            // Every failure of the task has a reason.
+            let error = task.failure().unwrap_or(UvGenError::DegenerateInput);
+            return Err(fail(error, task.take_partial_result().map(Box::new)));
+        };
+        patch
+    };
+    index::check_vertex_count::<I>(patch).map_err(|error| fail(error, None))
+}
 
-    patch.second_tex_coords = vec![Vector2::default(); vertices.len()];
-    for (i, rect) in rects.into_iter().enumerate() {
-        let mesh = &meshes[i];
+/// Generates UV map only for the given subset of the triangles (for example, for the static part
+/// of a mesh, that receives lightmaps), using the given options. Other triangles do not belong to
+/// any chart and keep their original vertex indices in [`SurfaceDataPatch::triangles`], their
+/// vertices are never split. Vertices, that are used only by such triangles, get zero texture
+/// coordinates:
+///
+/// ```rust
+/// let mesh = uvgen::test_meshes::cube();
+/// let patch = uvgen::generate_uvs_for_subset(
+///     mesh.vertices.iter().cloned(),
+///     mesh.triangles.iter().cloned(),
+///     [0, 1].into_iter(),
+///     &Default::default(),
+/// )
+/// .unwrap();
+/// assert_eq!(patch.pack_stats.chart_count, 1);
+/// assert_eq!(patch.triangles[2..], mesh.triangles[2..]);
+/// ```
+///
+/// Indices of triangles, that are out of range, are ignored. Chunked mode (see
+/// [`UvGenOptions::max_chunk_triangles`]) is not supported, the mesh is always unwrapped as a
+/// whole.
+pub fn generate_uvs_for_subset<I: UvIndex>(
+    vertices: impl Iterator<Item = Vector3<f32>>,
+    triangles: impl Iterator<Item = [I; 3]>,
+    subset: impl Iterator<Item = usize>,
+    options: &UvGenOptions,
+) -> Option<SurfaceDataPatch> {
+    trace_span!("generate_uvs_for_subset");
 
-        for &triangle_index in mesh.triangles.iter() {
-            for (&vertex_index, &projection) in triangles[triangle_index]
-                .iter()
-                .zip(&uv_box.projections[triangle_index])
-            {
-                let second_tex_coord = patch.second_tex_coords.get_mut(vertex_index as usize)?;
+    let mut task = UnwrapTask::new(
+        vertices,
+        index::widen(triangles).ok()?.into_iter(),
+        options.clone(),
+    );
+    task.set_subset(subset);
+    index::check_vertex_count::<I>(task.run()?).ok()
+}
 
-                *second_tex_coord = (projection - mesh.uv_min).scale(scale)
-                    + Vector2::new(spacing, spacing)
-                    + rect.position;
-            }
+/// Generates UV map for the given vertices and triangles using the given options, like
+/// [`generate_uvs_with_options`], and reports the progress of the generation, so editors could
+/// show a progress bar. The callback receives the current stage and its progress in `[0; 1]`
+/// range (see [`UnwrapTask::progress`]), it is called when a new stage starts and then every
+/// time the progress of the stage grows by at least a percent:
+///
+/// ```rust
+/// let mesh = uvgen::test_meshes::uv_sphere(16, 8);
+/// let mut stages = Vec::new();
+/// let patch = uvgen::generate_uvs_with_progress(
+///     mesh.vertices.iter().cloned(),
+///     mesh.triangles.iter().cloned(),
+///     &Default::default(),
+///     |stage, progress| {
+///         if stages.last() != Some(&stage) {
+///             stages.push(stage);
+///         }
+///         println!("{stage:?}: {:.0}%", progress * 100.0);
+///     },
+/// );
+/// assert!(patch.is_some());
+/// assert_eq!(stages.last(), Some(&uvgen::Stage::Done));
+/// ```
+///
+/// Chunked mode (see [`UvGenOptions::max_chunk_triangles`]) is not supported, the mesh is always
+/// unwrapped as a whole.
+pub fn generate_uvs_with_progress<I: UvIndex>(
+    vertices: impl Iterator<Item = Vector3<f32>>,
+    triangles: impl Iterator<Item = [I; 3]>,
+    options: &UvGenOptions,
+    mut progress: impl FnMut(Stage, f32),
+) -> Option<SurfaceDataPatch> {
+    trace_span!("generate_uvs");
+
+    let mut task = UnwrapTask::new(
+        vertices,
+        index::widen(triangles).ok()?.into_iter(),
+        options.clone(),
+    );
+    let mut reported = (task.stage(), task.progress());
+    progress(reported.0, reported.1);
+    loop {
+        // Zero budget makes the task do a single chunk of work.
+        let result = task.step(std::time::Duration::ZERO);
+        let current = (task.stage(), task.progress());
+        if current.0 != reported.0 || current.1 >= reported.1 + 0.01 {
+            progress(current.0, current.1);
+            reported = current;
+        }
+        if let std::task::Poll::Ready(patch) = result {
+            return index::check_vertex_count::<I>(patch?).ok();
         }
     }
+}
 
-    patch.triangles = triangles;
-
-    Some(patch)
+/// Generates UV map for the given vertices and triangles using the given options, like
+/// [`generate_uvs_with_options`], and applies the patch to the buffers in place: clones of the
+/// vertices (see [`SurfaceDataPatch::additional_vertices`]) are pushed to the end of `vertices`
+/// and `triangles` are replaced with the new topology. Returns the texture coordinates of every
+/// vertex of the patched mesh:
+///
+/// ```rust
+/// let mesh = uvgen::test_meshes::cube();
+/// let (mut vertices, mut triangles) = (mesh.vertices, mesh.triangles);
+/// let tex_coords =
+///     uvgen::generate_uvs_inplace(&mut vertices, &mut triangles, &Default::default()).unwrap();
+/// assert_eq!(tex_coords.len(), vertices.len());
+/// ```
+///
+/// The buffers are left intact if the generation fails. Use [`generate_uvs_with_options`], if
+/// other parts of the patch (statistics, colors and so on) are needed.
+pub fn generate_uvs_inplace<I: UvIndex>(
+    vertices: &mut Vec<Vector3<f32>>,
+    triangles: &mut Vec<[I; 3]>,
+    options: &UvGenOptions,
+) -> Option<Vec<Vector2<f32>>> {
+    let patch =
+        generate_uvs_with_options(vertices.iter().cloned(), triangles.iter().cloned(), options)?;
+    let patched_triangles = patch.triangles_as()?;
+    if !patch.topology_edit().apply_to_vertices(vertices) {
+        return None;
+    }
+    *triangles = patched_triangles;
+    Some(patch.second_tex_coords)
 }
 
 #[cfg(test)]
 mod test {
+    use crate::RepeatedIndexHandling;
     use nalgebra::{Vector2, Vector3};
 
     #[derive(Copy, Clone, Debug, Default, PartialEq)]
@@ -443,6 +1387,460 @@ mod test {
         }
     }
 
+    fn cube() -> (Vec<Vector3<f32>>, Vec<[u32; 3]>) {
+        let mesh = crate::test_meshes::cube();
+        (mesh.vertices, mesh.triangles)
+    }
+
+    #[test]
+    fn test_chart_colors() {
+        let (vertices, triangles) = cube();
+        let patch = super::generate_uvs_with_options(
+            vertices.into_iter(),
+            triangles.into_iter(),
+            &super::UvGenOptions {
+                chart_colors: true,
+                ..Default::default()
+            },
+        )
+        .unwrap();
+
+        assert_eq!(patch.chart_colors.len(), patch.second_tex_coords.len());
+        let mut colors = Vec::new();
+        for triangle in patch.triangles.iter() {
+            let color = patch.chart_colors[triangle[0] as usize];
+            assert!(triangle
+                .iter()
+                .all(|&i| patch.chart_colors[i as usize] == color));
+            if !colors.contains(&color) {
+                colors.push(color);
+            }
+        }
+        // Each side of the cube is at least one separate chart.
+        assert!(colors.len() >= 6);
+    }
+
+    #[test]
+    fn test_unwrap_task() {
+        let (vertices, triangles) = cube();
+        let expected =
+            super::generate_uvs(vertices.iter().cloned(), triangles.iter().cloned(), 0.005)
+                .unwrap();
+
+        let mut task = super::UnwrapTask::new(
+            vertices.into_iter(),
+            triangles.into_iter(),
+            Default::default(),
+        );
+        let mut steps = 0;
+        let patch = loop {
+            steps += 1;
+            if let std::task::Poll::Ready(patch) = task.step(std::time::Duration::ZERO) {
+                break patch.unwrap();
+            }
+        };
+
+        assert!(steps > 1);
+        assert_eq!(task.stage(), super::Stage::Done);
+        assert_eq!(patch.triangles, expected.triangles);
+        assert_eq!(patch.second_tex_coords, expected.second_tex_coords);
+    }
+
+    #[test]
+    fn test_spacing_validation() {
+        let (vertices, triangles) = cube();
+        let options = super::UvGenOptions {
+            spacing: 0.25,
+            ..Default::default()
+        };
+        let mut task = super::UnwrapTask::new(
+            vertices.iter().cloned(),
+            triangles.iter().cloned(),
+            options.clone(),
+        );
+        let patch = loop {
+            if let std::task::Poll::Ready(patch) = task.step(std::time::Duration::MAX) {
+                break patch;
+            }
+        };
+        assert!(patch.is_none());
+        let Some(super::PackingError::SpacingTooLarge { max_spacing, .. }) = task.packing_error()
+        else {
+            panic!("unexpected error {:?}", task.packing_error());
+        };
+        assert!(max_spacing < options.spacing);
+
+        let options = super::UvGenOptions {
+            spacing: max_spacing * 0.5,
+            ..options
+        };
+        let patch = super::generate_uvs_with_options(
+            vertices.iter().cloned(),
+            triangles.iter().cloned(),
+            &options,
+        )
+        .unwrap();
+        assert_eq!(patch.pack_stats.packing_error, None);
+    }
+
+    #[test]
+    fn test_tiny_mesh_fast_path() {
+        let (vertices, triangles) = cube();
+        let patch = super::generate_uvs_with_options(
+            vertices.into_iter(),
+            triangles.into_iter(),
+            &super::UvGenOptions {
+                tiny_mesh_threshold: 100,
+                ..Default::default()
+            },
+        )
+        .unwrap();
+
+        assert!(patch
+            .second_tex_coords
+            .iter()
+            .all(|uv| uv.x >= 0.0 && uv.x <= 1.0 && uv.y >= 0.0 && uv.y <= 1.0));
+        assert!(!patch.pack_stats.free_rects.is_empty());
+    }
+
+    #[test]
+    fn test_seam_bias() {
+        // A strip on a slope, where the first quad has a bit steeper triangle, that is mapped to
+        // +Y side instead of +Z.
+        let mut vertices = Vec::new();
+        for i in 0..4 {
+            for j in 0..2 {
+                vertices.push(Vector3::new(i as f32, j as f32, -0.98 * j as f32));
+            }
+        }
+        vertices[1].z = -1.05;
+        let mut triangles = Vec::new();
+        for i in 0..3 {
+            let (a, b, c, d): (u32, u32, u32, u32) = (i * 2, i * 2 + 1, i * 2 + 2, i * 2 + 3);
+            triangles.push([a, c, d]);
+            triangles.push([a, d, b]);
+        }
+        let generate = |seam_bias| {
+            super::generate_uvs_with_options(
+                vertices.iter().cloned(),
+                triangles.iter().cloned(),
+                &super::UvGenOptions {
+                    seam_bias,
+                    ..Default::default()
+                },
+            )
+            .unwrap()
+            .pack_stats
+        };
+
+        let stats = generate(0.0);
+        assert_eq!(stats.chart_count, 2);
+        assert_eq!(stats.seam_count, 1);
+        assert_eq!(stats.seam_length, vertices[0].metric_distance(&vertices[3]));
+
+        let stats = generate(0.1);
+        assert_eq!(stats.chart_count, 1);
+        assert_eq!(stats.seam_count, 0);
+        assert_eq!(stats.seam_length, 0.0);
+    }
+
+    #[test]
+    fn test_adjacency() {
+        let (vertices, triangles) = cube();
+        let patch = super::generate_uvs_with_options(
+            vertices.into_iter(),
+            triangles.into_iter(),
+            &super::UvGenOptions {
+                adjacency: true,
+                ..Default::default()
+            },
+        )
+        .unwrap();
+
+        // Cube is closed, so every edge has a neighbour even across seams.
+        assert_eq!(patch.adjacency.len(), patch.triangles.len());
+        for (triangle_index, neighbours) in patch.adjacency.iter().enumerate() {
+            for neighbour in neighbours {
+                let neighbour = neighbour.unwrap() as usize;
+                assert!(patch.adjacency[neighbour].contains(&Some(triangle_index as u32)));
+            }
+        }
+    }
+
+    #[test]
+    fn test_chart_info() {
+        let (vertices, triangles) = cube();
+        let patch =
+            super::generate_uvs(vertices.into_iter(), triangles.into_iter(), 0.005).unwrap();
+        let stats = &patch.pack_stats;
+
+        assert_eq!(stats.charts.len(), stats.chart_count);
+        assert_eq!(
+            stats
+                .charts
+                .iter()
+                .map(|chart| chart.triangles.len())
+                .sum::<usize>(),
+            12
+        );
+        let world_area = stats
+            .charts
+            .iter()
+            .map(|chart| chart.world_area)
+            .sum::<f32>();
+        assert!((world_area - 6.0).abs() < 1.0e-5);
+
+        // Cube is axis-aligned, so there's no stretch and every chart has the same density.
+        let expected_ratio = 1.0 / (stats.scale * stats.scale);
+        for chart in stats.charts.iter() {
+            assert!((chart.world_to_uv_ratio() / expected_ratio - 1.0).abs() < 1.0e-4);
+        }
+    }
+
+    #[test]
+    fn test_pack_restarts() {
+        let (vertices, triangles) = cube();
+        let generate = |pack_restarts, seed| {
+            super::generate_uvs_with_options(
+                vertices.iter().cloned(),
+                triangles.iter().cloned(),
+                &super::UvGenOptions {
+                    pack_restarts,
+                    seed,
+                    ..Default::default()
+                },
+            )
+            .unwrap()
+        };
+
+        let base = generate(0, 0);
+        let refined = generate(8, 42);
+        assert!(refined.pack_stats.scale >= base.pack_stats.scale);
+
+        // Same seed must give the same results.
+        assert_eq!(generate(8, 42).second_tex_coords, refined.second_tex_coords);
+    }
+
+    #[test]
+    fn test_new_vertex_index() {
+        assert_eq!(super::new_vertex_index(0), Some(0));
+        assert_eq!(super::new_vertex_index(u32::MAX as usize), Some(u32::MAX));
+        assert_eq!(super::new_vertex_index(u32::MAX as usize + 1), None);
+    }
+
+    #[test]
+    fn test_texel_snap() {
+        let (vertices, triangles) = cube();
+        let resolution = 64;
+        let patch = super::generate_uvs_with_options(
+            vertices.into_iter(),
+            triangles.into_iter(),
+            &super::UvGenOptions {
+                texel_snap_resolution: resolution,
+                ..Default::default()
+            },
+        )
+        .unwrap();
+
+        for chart in patch.pack_stats.charts.iter() {
+            let min = chart
+                .triangles
+                .iter()
+                .flat_map(|&triangle| patch.triangles[triangle])
+                .map(|index| patch.second_tex_coords[index as usize])
+                .fold(Vector2::repeat(f32::MAX), |min, uv| min.inf(&uv));
+            let texel = min * resolution as f32 - Vector2::repeat(0.5);
+            assert!((texel.x - texel.x.round()).abs() < 1.0e-3);
+            assert!((texel.y - texel.y.round()).abs() < 1.0e-3);
+        }
+    }
+
+    #[test]
+    fn test_seam_flags() {
+        // Two triangles on different sides of the box, that share the same edge, and a triangle,
+        // that does not share anything.
+        let vertices = [
+            Vector3::new(0.0, 0.0, 0.0),
+            Vector3::new(1.0, 0.0, 0.0),
+            Vector3::new(0.0, 1.0, 0.0),
+            Vector3::new(0.0, 0.0, 1.0),
+            Vector3::new(5.0, 0.0, 0.0),
+            Vector3::new(6.0, 0.0, 0.0),
+            Vector3::new(5.0, 1.0, 0.0),
+        ];
+        let patch = super::generate_uvs(
+            vertices.into_iter(),
+            [[0u32, 1, 2], [0, 3, 1], [4, 5, 6]].into_iter(),
+            0.005,
+        )
+        .unwrap();
+        let flags = patch.seam_flags();
+        assert_eq!(flags.len(), patch.second_tex_coords.len());
+        for (index, flags) in flags.iter().enumerate() {
+            let source = patch.source_vertex(index as u32).unwrap();
+            assert_eq!(flags.added, index >= vertices.len());
+            // Only the shared edge is a seam.
+            assert_eq!(flags.on_seam, source == 0 || source == 1, "{index}");
+        }
+    }
+
+    #[test]
+    fn test_compacted() {
+        // Two triangles on different sides of the box, that share the same edge.
+        let vertices = [
+            Vector3::new(0.0, 0.0, 0.0),
+            Vector3::new(1.0, 0.0, 0.0),
+            Vector3::new(0.0, 1.0, 0.0),
+            Vector3::new(0.0, 0.0, 1.0),
+        ];
+        let mut patch = super::generate_uvs(
+            vertices.into_iter(),
+            [[0u32, 1, 2], [0, 3, 1]].into_iter(),
+            0.005,
+        )
+        .unwrap();
+        assert!(patch.unreferenced_vertices().is_empty());
+
+        // Make a vertex of the second triangle unreferenced.
+        let last = patch.triangles[1][1];
+        patch.triangles[1][1] = patch.triangles[1][0];
+        assert_eq!(patch.unreferenced_vertices(), [last]);
+
+        let compacted = patch.compacted();
+        assert_eq!(compacted.vertices.len(), patch.second_tex_coords.len() - 1);
+        assert!(!compacted.vertices.contains(&last));
+        for (triangle, compacted_triangle) in patch.triangles.iter().zip(compacted.triangles) {
+            for (index, compacted_index) in triangle.iter().zip(compacted_triangle) {
+                assert_eq!(
+                    patch.second_tex_coords[*index as usize],
+                    compacted.second_tex_coords[compacted_index as usize]
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn test_local_frames() {
+        // A cube rotated around Y axis.
+        let (vertices, triangles) = cube();
+        let rotation = nalgebra::Rotation3::from_axis_angle(&Vector3::y_axis(), 0.5);
+        let vertices = vertices.iter().map(|v| rotation * v).collect::<Vec<_>>();
+        let generate = |local_frames| {
+            super::generate_uvs_with_options(
+                vertices.iter().cloned(),
+                triangles.iter().cloned(),
+                &super::UvGenOptions {
+                    local_frames,
+                    ..Default::default()
+                },
+            )
+            .unwrap()
+            .pack_stats
+        };
+        let is_uniform = |stats: &super::PackStats| {
+            let expected_ratio = 1.0 / (stats.scale * stats.scale);
+            stats
+                .charts
+                .iter()
+                .all(|chart| (chart.world_to_uv_ratio() / expected_ratio - 1.0).abs() < 1.0e-3)
+        };
+
+        // Sides of the rotated cube are stretched in the world frame, but not in the local one.
+        assert!(!is_uniform(&generate(false)));
+        assert!(is_uniform(&generate(true)));
+    }
+
+    #[test]
+    fn test_density() {
+        // A wall facing +Z.
+        let vertices = [
+            Vector3::new(0.0, 0.0, 0.0),
+            Vector3::new(1.0, 0.0, 0.0),
+            Vector3::new(1.0, 1.0, 0.0),
+            Vector3::new(0.0, 1.0, 0.0),
+        ];
+        let patch = super::generate_uvs_with_options(
+            vertices.iter().cloned(),
+            [[0u32, 1, 2], [0, 2, 3]].into_iter(),
+            &super::UvGenOptions {
+                density: Vector3::new(1.0, 2.0, 1.0),
+                ..Default::default()
+            },
+        )
+        .unwrap();
+
+        let (min, max) = patch.second_tex_coords.iter().fold(
+            (Vector2::repeat(f32::MAX), Vector2::repeat(-f32::MAX)),
+            |(min, max), uv| (min.inf(uv), max.sup(uv)),
+        );
+        let size = max - min;
+        assert!((size.y - 2.0 * size.x).abs() < 1.0e-5);
+    }
+
+    #[test]
+    fn test_max_stretch() {
+        // A quad on a 45 degree slope, its stretch is sqrt(2).
+        let vertices = [
+            Vector3::new(0.0, 0.0, 0.0),
+            Vector3::new(1.0, 0.0, 0.0),
+            Vector3::new(1.0, 1.0, 1.0),
+            Vector3::new(0.0, 1.0, 1.0),
+        ];
+        let triangles: [[u32; 3]; 2] = [[0, 1, 2], [0, 2, 3]];
+        let generate = |max_stretch| {
+            super::generate_uvs_with_options(
+                vertices.iter().cloned(),
+                triangles.iter().cloned(),
+                &super::UvGenOptions {
+                    max_stretch,
+                    ..Default::default()
+                },
+            )
+            .unwrap()
+        };
+
+        assert_eq!(generate(f32::INFINITY).pack_stats.chart_count, 1);
+
+        let patch = generate(1.1);
+        assert_eq!(patch.pack_stats.chart_count, 2);
+        assert_eq!(patch.additional_vertices.len(), 6);
+    }
+
+    #[test]
+    fn test_chunked() {
+        let (vertices, triangles) = cube();
+        let patch = super::generate_uvs_with_options(
+            vertices.iter().cloned(),
+            triangles.iter().cloned(),
+            &super::UvGenOptions {
+                max_chunk_triangles: 4,
+                ..Default::default()
+            },
+        )
+        .unwrap();
+
+        assert_eq!(patch.triangles.len(), triangles.len());
+        assert_eq!(
+            patch.second_tex_coords.len(),
+            vertices.len() + patch.additional_vertices.len()
+        );
+        // Every triangle must keep its positions.
+        for (triangle, new_triangle) in triangles.iter().zip(patch.triangles.iter()) {
+            for (&index, &new_index) in triangle.iter().zip(new_triangle) {
+                let source = if (new_index as usize) < vertices.len() {
+                    new_index
+                } else {
+                    patch.additional_vertices[new_index as usize - vertices.len()]
+                };
+                assert_eq!(vertices[index as usize], vertices[source as usize]);
+            }
+        }
+        assert!(patch
+            .second_tex_coords
+            .iter()
+            .all(|uv| uv.x >= 0.0 && uv.x <= 1.0 && uv.y >= 0.0 && uv.y <= 1.0));
+    }
+
     #[test]
     fn test_uv_gen() {
         // Create cube geometry.
@@ -502,14 +1900,14 @@ mod test {
                 [3, 2, 0,],
                 [4, 5, 6,],
                 [4, 6, 7,],
-                [12, 10, 8,],
-                [9, 11, 13,],
-                [17, 14, 15,],
-                [18, 16, 19,],
-                [23, 20, 21,],
-                [24, 22, 25,],
-                [27, 26, 29,],
-                [31, 28, 30,],
+                [8, 9, 10,],
+                [10, 11, 8,],
+                [12, 13, 14,],
+                [12, 14, 15,],
+                [16, 17, 18,],
+                [16, 18, 19,],
+                [20, 21, 22,],
+                [23, 20, 22,],
             ]
         );
 
@@ -522,129 +1920,478 @@ mod test {
                 },
                 Vertex {
                     position: Vector3::new(-0.5, 0.5, 0.5),
-                    tex_coord: Vector2::new(0.005, 0.21778576),
+                    tex_coord: Vector2::new(0.005, 0.3257669),
                 },
                 Vertex {
                     position: Vector3::new(0.5, 0.5, 0.5),
-                    tex_coord: Vector2::new(0.21778576, 0.21778576),
+                    tex_coord: Vector2::new(0.3257669, 0.3257669),
                 },
                 Vertex {
                     position: Vector3::new(0.5, -0.5, 0.5),
-                    tex_coord: Vector2::new(0.21778576, 0.005),
+                    tex_coord: Vector2::new(0.3257669, 0.005),
                 },
                 Vertex {
                     position: Vector3::new(-0.5, -0.5, -0.5),
-                    tex_coord: Vector2::new(0.22778577, 0.005),
+                    tex_coord: Vector2::new(0.33576688, 0.005),
                 },
                 Vertex {
                     position: Vector3::new(-0.5, 0.5, -0.5),
-                    tex_coord: Vector2::new(0.44057155, 0.005),
+                    tex_coord: Vector2::new(0.6565338, 0.005),
                 },
                 Vertex {
                     position: Vector3::new(0.5, 0.5, -0.5),
-                    tex_coord: Vector2::new(0.44057155, 0.21778576),
+                    tex_coord: Vector2::new(0.6565338, 0.3257669),
                 },
                 Vertex {
                     position: Vector3::new(0.5, -0.5, -0.5),
-                    tex_coord: Vector2::new(0.22778577, 0.21778576),
-                },
-                Vertex {
-                    position: Vector3::new(0.5, 0.5, 0.5),
-                    tex_coord: Vector2::new(0.21778576, 0.44057155),
+                    tex_coord: Vector2::new(0.33576688, 0.3257669),
                 },
                 Vertex {
-                    position: Vector3::new(0.5, 0.5, 0.5),
-                    tex_coord: Vector2::new(0.6633573, 0.21778576),
+                    position: Vector3::new(0.5, -0.5, -0.5),
+                    tex_coord: Vector2::new(0.005, 0.33576688),
                 },
                 Vertex {
                     position: Vector3::new(0.5, 0.5, -0.5),
-                    tex_coord: Vector2::new(0.21778576, 0.22778577),
+                    tex_coord: Vector2::new(0.3257669, 0.33576688),
                 },
                 Vertex {
-                    position: Vector3::new(0.5, -0.5, 0.5),
-                    tex_coord: Vector2::new(0.45057154, 0.21778576),
+                    position: Vector3::new(0.5, 0.5, 0.5),
+                    tex_coord: Vector2::new(0.3257669, 0.6565338),
                 },
                 Vertex {
-                    position: Vector3::new(0.5, -0.5, -0.5),
-                    tex_coord: Vector2::new(0.005, 0.22778577),
+                    position: Vector3::new(0.5, -0.5, 0.5),
+                    tex_coord: Vector2::new(0.005, 0.6565338),
                 },
                 Vertex {
-                    position: Vector3::new(0.5, -0.5, -0.5),
-                    tex_coord: Vector2::new(0.45057154, 0.005),
+                    position: Vector3::new(-0.5, -0.5, 0.5),
+                    tex_coord: Vector2::new(0.98730063, 0.005),
                 },
                 Vertex {
                     position: Vector3::new(-0.5, 0.5, 0.5),
-                    tex_coord: Vector2::new(0.21778576, 0.6633573),
+                    tex_coord: Vector2::new(0.98730063, 0.3257669),
                 },
                 Vertex {
                     position: Vector3::new(-0.5, 0.5, -0.5),
-                    tex_coord: Vector2::new(0.005, 0.6633573),
-                },
-                Vertex {
-                    position: Vector3::new(-0.5, 0.5, -0.5),
-                    tex_coord: Vector2::new(0.22778577, 0.44057155),
-                },
-                Vertex {
-                    position: Vector3::new(-0.5, -0.5, 0.5),
-                    tex_coord: Vector2::new(0.21778576, 0.45057154),
-                },
-                Vertex {
-                    position: Vector3::new(-0.5, -0.5, 0.5),
-                    tex_coord: Vector2::new(0.44057155, 0.22778577),
+                    tex_coord: Vector2::new(0.66653377, 0.3257669),
                 },
                 Vertex {
                     position: Vector3::new(-0.5, -0.5, -0.5),
-                    tex_coord: Vector2::new(0.22778577, 0.22778577),
+                    tex_coord: Vector2::new(0.66653377, 0.005),
                 },
                 Vertex {
-                    position: Vector3::new(-0.5, 0.5, 0.5),
-                    tex_coord: Vector2::new(0.8861431, 0.005),
+                    position: Vector3::new(-0.5, 0.5, -0.5),
+                    tex_coord: Vector2::new(0.005, 0.66653377),
                 },
                 Vertex {
-                    position: Vector3::new(0.5, 0.5, 0.5),
-                    tex_coord: Vector2::new(0.8861431, 0.21778576),
+                    position: Vector3::new(-0.5, 0.5, 0.5),
+                    tex_coord: Vector2::new(0.3257669, 0.66653377),
                 },
                 Vertex {
                     position: Vector3::new(0.5, 0.5, 0.5),
-                    tex_coord: Vector2::new(0.21778576, 0.8861431),
-                },
-                Vertex {
-                    position: Vector3::new(-0.5, 0.5, -0.5),
-                    tex_coord: Vector2::new(0.6733573, 0.005),
-                },
-                Vertex {
-                    position: Vector3::new(-0.5, 0.5, -0.5),
-                    tex_coord: Vector2::new(0.005, 0.6733573),
+                    tex_coord: Vector2::new(0.3257669, 0.98730063),
                 },
                 Vertex {
                     position: Vector3::new(0.5, 0.5, -0.5),
-                    tex_coord: Vector2::new(0.005, 0.8861431),
-                },
-                Vertex {
-                    position: Vector3::new(-0.5, -0.5, 0.5),
-                    tex_coord: Vector2::new(0.45057154, 0.44057155),
+                    tex_coord: Vector2::new(0.005, 0.98730063),
                 },
                 Vertex {
                     position: Vector3::new(0.5, -0.5, 0.5),
-                    tex_coord: Vector2::new(0.6633573, 0.44057155),
+                    tex_coord: Vector2::new(0.6565338, 0.6565338),
                 },
                 Vertex {
-                    position: Vector3::new(0.5, -0.5, 0.5),
-                    tex_coord: Vector2::new(0.44057155, 0.6633573),
-                },
-                Vertex {
-                    position: Vector3::new(-0.5, -0.5, -0.5),
-                    tex_coord: Vector2::new(0.45057154, 0.22778577),
+                    position: Vector3::new(-0.5, -0.5, 0.5),
+                    tex_coord: Vector2::new(0.33576688, 0.6565338),
                 },
                 Vertex {
                     position: Vector3::new(-0.5, -0.5, -0.5),
-                    tex_coord: Vector2::new(0.22778577, 0.45057154),
+                    tex_coord: Vector2::new(0.33576688, 0.33576688),
                 },
                 Vertex {
                     position: Vector3::new(0.5, -0.5, -0.5),
-                    tex_coord: Vector2::new(0.44057155, 0.45057154),
+                    tex_coord: Vector2::new(0.6565338, 0.33576688),
                 },
             ]
         );
     }
+
+    #[test]
+    fn test_chart_editing() {
+        let (vertices, triangles) = cube();
+        let mut task = super::UnwrapTask::new(
+            vertices.into_iter(),
+            triangles.into_iter(),
+            Default::default(),
+        );
+        assert!(task.charts_mut().is_none());
+        assert!(task.run_until(super::Stage::Packing));
+
+        // Put all the charts in a row and merge them into a single chart.
+        let super::ChartsMut {
+            charts,
+            projections,
+        } = task.charts_mut().unwrap();
+        let mut merged: Option<super::Chart> = None;
+        for mut chart in charts.drain(..) {
+            let offset = merged
+                .as_ref()
+                .map_or(0.0, |merged| merged.uv_max().x + 0.1)
+                - chart.uv_min().x;
+            let triangle_count = chart.triangles().len();
+            chart.transform(
+                projections,
+                &nalgebra::Matrix3::new_translation(&Vector2::new(offset, 0.0)),
+            );
+            assert_eq!(chart.triangles().len(), triangle_count);
+            match merged.as_mut() {
+                Some(merged) => merged.merge(chart),
+                None => merged = Some(chart),
+            }
+        }
+        let merged = merged.unwrap();
+        assert_eq!(merged.triangles().len(), 12);
+        assert!(merged.width() > merged.height());
+        charts.push(merged);
+
+        let patch = task.run().unwrap();
+        assert_eq!(patch.pack_stats.chart_count, 1);
+        assert_eq!(patch.pack_stats.charts[0].triangles.len(), 12);
+    }
+
+    #[test]
+    fn test_repack() {
+        let mesh = crate::test_meshes::uv_sphere(16, 8);
+        let mut task = super::UnwrapTask::new(
+            mesh.vertices.iter().cloned(),
+            mesh.triangles.iter().cloned(),
+            Default::default(),
+        );
+        assert!(task.repack(Default::default()).is_none());
+        assert!(task.run_until(super::Stage::Packing));
+
+        // Repacking gives the same result as the generation from scratch.
+        for spacing in [0.005, 0.02] {
+            let options = super::UvGenOptions {
+                spacing,
+                ..Default::default()
+            };
+            let patch = task.repack(options.clone()).unwrap();
+            let expected = super::generate_uvs_with_options(
+                mesh.vertices.iter().cloned(),
+                mesh.triangles.iter().cloned(),
+                &options,
+            )
+            .unwrap();
+            assert_eq!(patch.second_tex_coords, expected.second_tex_coords);
+            assert_eq!(patch.triangles, expected.triangles);
+        }
+        assert!(task.run().is_some());
+    }
+
+    #[test]
+    fn test_needle_triangles() {
+        // A unit quad and a diagonal needle triangle next to it.
+        let vertices = [
+            Vector3::new(0.0, 0.0, 0.0),
+            Vector3::new(1.0, 0.0, 0.0),
+            Vector3::new(1.0, 1.0, 0.0),
+            Vector3::new(0.0, 1.0, 0.0),
+            Vector3::new(2.0, 0.0, 0.0),
+            Vector3::new(1002.0, 1000.0, 0.0),
+            Vector3::new(1002.0, 1000.001, 0.0),
+        ];
+        let triangles: [[u32; 3]; 3] = [[0, 1, 2], [0, 2, 3], [4, 5, 6]];
+        let generate = |needle_handling| {
+            super::generate_uvs_with_options(
+                vertices.iter().cloned(),
+                triangles.iter().cloned(),
+                &super::UvGenOptions {
+                    needle_ratio: 100.0,
+                    needle_handling,
+                    ..Default::default()
+                },
+            )
+            .unwrap()
+        };
+
+        for handling in [
+            super::NeedleHandling::Keep,
+            super::NeedleHandling::Isolate,
+            super::NeedleHandling::Reproject,
+        ] {
+            let patch = generate(handling);
+            assert_eq!(patch.pack_stats.needle_triangles, [2]);
+            assert_eq!(patch.pack_stats.charts.len(), 2);
+            for uv in patch.second_tex_coords.iter() {
+                assert!(uv.x >= 0.0 && uv.x <= 1.0 && uv.y >= 0.0 && uv.y <= 1.0);
+            }
+        }
+
+        // Re-projected needle has tight bounds, so it occupies much less space.
+        let needle_rect_area = |patch: &super::SurfaceDataPatch| {
+            let chart = patch
+                .pack_stats
+                .charts
+                .iter()
+                .find(|chart| chart.triangles.contains(&2))
+                .unwrap();
+            chart.rect.w() * chart.rect.h()
+        };
+        let keep = generate(super::NeedleHandling::Keep);
+        let reproject = generate(super::NeedleHandling::Reproject);
+        assert!(needle_rect_area(&reproject) < 0.1 * needle_rect_area(&keep));
+    }
+
+    #[test]
+    fn test_repeated_indices() {
+        let (vertices, mut triangles) = cube();
+        let expected =
+            super::generate_uvs(vertices.iter().cloned(), triangles.iter().cloned(), 0.005)
+                .unwrap();
+        triangles.push([0, 0, 2]);
+        let generate = |handling| {
+            super::generate_uvs_with_options(
+                vertices.iter().cloned(),
+                triangles.iter().cloned(),
+                &super::UvGenOptions {
+                    repeated_index_handling: handling,
+                    ..Default::default()
+                },
+            )
+        };
+
+        let skipped = generate(RepeatedIndexHandling::Skip).unwrap();
+        assert_eq!(skipped.second_tex_coords, expected.second_tex_coords);
+        assert_eq!(skipped.triangles[..12], expected.triangles);
+        assert_eq!(skipped.triangles[12], [0, 0, 2]);
+
+        let sentinel = Vector2::new(-1.0, -1.0);
+        let patch = generate(RepeatedIndexHandling::Sentinel(sentinel)).unwrap();
+        let triangle = patch.triangles[12];
+        assert!(triangle
+            .iter()
+            .all(|&i| patch.second_tex_coords[i as usize] == sentinel));
+        assert_eq!(
+            patch.pack_stats.chart_count,
+            expected.pack_stats.chart_count
+        );
+        assert!(patch.triangles[..12]
+            .iter()
+            .flatten()
+            .all(|&i| patch.second_tex_coords[i as usize] != sentinel));
+
+        assert!(generate(RepeatedIndexHandling::Fail).is_none());
+        assert!(generate(RepeatedIndexHandling::Keep).is_some());
+    }
+
+    #[test]
+    fn test_needle_ratio() {
+        let ratio = super::needle_ratio(
+            &Vector3::new(0.0, 0.0, 0.0),
+            &Vector3::new(1.0, 0.0, 0.0),
+            &Vector3::new(0.5, 0.75f32.sqrt(), 0.0),
+        );
+        assert!((ratio - 1.0 / 0.75f32.sqrt()).abs() < 1.0e-5);
+        let projection = super::edge_aligned_map(
+            &Vector3::new(0.0, 0.0, 0.0),
+            &Vector3::new(0.0, 1.0, 0.0),
+            &Vector3::new(10.0, 0.0, 0.0),
+        );
+        // Longest edge goes from the second corner to the third one.
+        assert!(projection[1].y.abs() < 1.0e-5 && projection[2].y.abs() < 1.0e-5);
+        assert!(((projection[2] - projection[1]).norm() - 101.0f32.sqrt()).abs() < 1.0e-4);
+    }
+
+    #[test]
+    fn test_triangle_normals() {
+        // Clockwise triangles with outward normals, like in an engine with the opposite winding.
+        let mesh = crate::test_meshes::cube();
+        let triangles = mesh
+            .triangles
+            .iter()
+            .map(|&[a, b, c]| [a, c, b])
+            .collect::<Vec<_>>();
+        let normals = mesh
+            .triangles
+            .iter()
+            .map(|triangle| {
+                let [a, b, c] = triangle.map(|index| mesh.vertices[index as usize]);
+                (b - a).cross(&(c - a))
+            })
+            .collect::<Vec<_>>();
+
+        let face_triangles = |triangles: &[[u32; 3]], normals: Vec<Vector3<f32>>| {
+            let mut task = crate::UnwrapTask::new(
+                mesh.vertices.iter().cloned(),
+                triangles.iter().cloned(),
+                crate::UvGenOptions {
+                    triangle_normals: normals,
+                    ..Default::default()
+                },
+            );
+            assert!(task.run_until(crate::Stage::Seams));
+            crate::FacePlane::ALL.map(|plane| task.face_triangles(plane).to_vec())
+        };
+        let expected = face_triangles(&mesh.triangles, Vec::new());
+        assert_ne!(face_triangles(&triangles, Vec::new()), expected);
+        assert_eq!(face_triangles(&triangles, normals), expected);
+    }
+
+    #[test]
+    fn test_coordinate_system() {
+        let mesh = crate::test_meshes::uv_sphere(8, 4);
+        let generate = |vertices: &[Vector3<f32>], triangles: &[[u32; 3]], coordinate_system| {
+            let options = crate::UvGenOptions {
+                coordinate_system,
+                ..Default::default()
+            };
+            let planes = crate::face_planes(
+                vertices.iter().cloned(),
+                triangles.iter().cloned(),
+                &options,
+            )
+            .unwrap();
+            let patch = crate::generate_uvs_with_options(
+                vertices.iter().cloned(),
+                triangles.iter().cloned(),
+                &options,
+            )
+            .unwrap();
+            (planes, patch.second_tex_coords)
+        };
+        let expected = generate(
+            &mesh.vertices,
+            &mesh.triangles,
+            crate::CoordinateSystem::YUpRightHanded,
+        );
+
+        // The same mesh, exported from a Z-up tool.
+        let vertices = mesh
+            .vertices
+            .iter()
+            .map(|v| Vector3::new(v.x, -v.z, v.y))
+            .collect::<Vec<_>>();
+        let z_up = generate(
+            &vertices,
+            &mesh.triangles,
+            crate::CoordinateSystem::ZUpRightHanded,
+        );
+        assert_eq!(z_up, expected);
+
+        // Left-handed meshes have mirrored positions and clockwise front faces.
+        let vertices = mesh
+            .vertices
+            .iter()
+            .map(|v| Vector3::new(v.x, v.y, -v.z))
+            .collect::<Vec<_>>();
+        let triangles = mesh
+            .triangles
+            .iter()
+            .map(|&[a, b, c]| [a, c, b])
+            .collect::<Vec<_>>();
+        let (planes, _) = generate(
+            &vertices,
+            &triangles,
+            crate::CoordinateSystem::YUpLeftHanded,
+        );
+        assert_eq!(planes, expected.0);
+    }
+
+    #[test]
+    fn test_uv_bounds() {
+        let mesh = crate::test_meshes::uv_sphere(8, 4);
+        let patch = crate::generate_uvs(
+            mesh.vertices.iter().cloned(),
+            mesh.triangles.iter().cloned(),
+            0.02,
+        )
+        .unwrap();
+        let bounds = patch.uv_bounds(0).unwrap();
+        let (min, max) = (bounds.position, bounds.position + bounds.size);
+        assert!(min.x >= 0.0 && min.y >= 0.0 && max.x <= 1.0 && max.y <= 1.0);
+        for uv in patch.second_tex_coords.iter() {
+            assert!(uv.x >= min.x && uv.y >= min.y && uv.x <= max.x && uv.y <= max.y);
+        }
+        // Spacing around the charts is not included.
+        assert!(min.x >= 0.02 && min.y >= 0.02);
+        assert!(patch.uv_bounds(1).is_none());
+    }
+
+    #[test]
+    fn test_triangle_projections() {
+        let mesh = crate::test_meshes::cube();
+        let patch = crate::generate_uvs_with_options(
+            mesh.vertices.iter().cloned(),
+            mesh.triangles.iter().cloned(),
+            &crate::UvGenOptions {
+                triangle_projections: true,
+                ..Default::default()
+            },
+        )
+        .unwrap();
+        assert_eq!(patch.triangle_projections.len(), patch.triangles.len());
+
+        // Texture coordinates of every chart are its projections scaled and moved.
+        let scale = patch.pack_stats.scale;
+        for chart in patch.pack_stats.charts.iter() {
+            let first = chart.triangles[0];
+            let offset = patch.second_tex_coords[patch.triangles[first][0] as usize]
+                - patch.triangle_projections[first][0] * scale;
+            for &triangle in chart.triangles.iter() {
+                for (&index, projection) in patch.triangles[triangle]
+                    .iter()
+                    .zip(patch.triangle_projections[triangle].iter())
+                {
+                    let uv = projection * scale + offset;
+                    assert!((uv - patch.second_tex_coords[index as usize]).norm() < 1.0e-4);
+                }
+            }
+        }
+
+        let patch = crate::generate_uvs(
+            mesh.vertices.iter().cloned(),
+            mesh.triangles.iter().cloned(),
+            0.005,
+        )
+        .unwrap();
+        assert!(patch.triangle_projections.is_empty());
+    }
+
+    #[test]
+    fn test_progress() {
+        let mesh = crate::test_meshes::uv_sphere(16, 8);
+        let mut reports = Vec::new();
+        let patch = super::generate_uvs_with_progress(
+            mesh.vertices.iter().cloned(),
+            mesh.triangles.iter().cloned(),
+            &Default::default(),
+            |stage, progress| reports.push((stage, progress)),
+        )
+        .unwrap();
+        assert!(patch.pack_stats.chart_count > 0);
+        assert_eq!(reports.first(), Some(&(crate::Stage::BoxMapping, 0.0)));
+        assert_eq!(reports.last(), Some(&(crate::Stage::Done, 1.0)));
+        for pair in reports.windows(2) {
+            let ((stage, progress), (next_stage, next_progress)) = (pair[0], pair[1]);
+            assert!(stage < next_stage || (stage == next_stage && progress < next_progress));
+            assert!((0.0..=1.0).contains(&next_progress));
+        }
+    }
+
+    #[test]
+    fn test_generate_uvs_inplace() {
+        let (mut vertices, mut triangles) = cube();
+        let patch = super::generate_uvs(vertices.iter().cloned(), triangles.iter().cloned(), 0.005)
+            .unwrap();
+        let source_count = vertices.len();
+        let tex_coords =
+            super::generate_uvs_inplace(&mut vertices, &mut triangles, &Default::default())
+                .unwrap();
+        assert_eq!(tex_coords, patch.second_tex_coords);
+        assert_eq!(triangles, patch.triangles);
+        assert_eq!(vertices.len(), tex_coords.len());
+        for (index, vertex) in vertices.iter().enumerate().skip(source_count) {
+            let source = patch.source_vertex(index as u32).unwrap();
+            assert_eq!(*vertex, vertices[source as usize]);
+        }
+    }
 }
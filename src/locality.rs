@@ -0,0 +1,138 @@
+//! Spatial locality of charts in the atlas. See [`crate::UvGenOptions::spatial_locality`] for
+//! more info.
+
+use crate::Chart;
+use nalgebra::Vector3;
+use std::cmp::Reverse;
+
+/// Interleaves lower 10 bits of every coordinate, which gives a position on the Z-order curve.
+fn morton_code(x: u32, y: u32, z: u32) -> u32 {
+    fn spread(mut v: u32) -> u32 {
+        v &= 0x3ff;
+        v = (v | (v << 16)) & 0x030000ff;
+        v = (v | (v << 8)) & 0x0300f00f;
+        v = (v | (v << 4)) & 0x030c30c3;
+        v = (v | (v << 2)) & 0x09249249;
+        v
+    }
+    spread(x) | (spread(y) << 1) | (spread(z) << 2)
+}
+
+/// Calculates world-space centers (average positions of the vertices) of the given meshes.
+pub(crate) fn mesh_centers(
+    meshes: &[Chart],
+    triangles: &[[u32; 3]],
+    position: impl Fn(u32) -> Option<Vector3<f32>>,
+) -> Vec<Vector3<f32>> {
+    meshes
+        .iter()
+        .map(|mesh| {
+            let (sum, count) = mesh
+                .triangles
+                .iter()
+                .filter_map(|&triangle_index| triangles.get(triangle_index))
+                .flatten()
+                .filter_map(|&index| position(index))
+                .fold((Vector3::zeros(), 0usize), |(sum, count), p| {
+                    (sum + p, count + 1)
+                });
+            sum / count.max(1) as f32
+        })
+        .collect()
+}
+
+/// Reorders meshes, that are already sorted by their area, so meshes of similar size (the same
+/// power of two of the area) are ordered along the Z-order curve of their world-space centers.
+/// The packer places subsequent meshes near each other, so charts of spatially adjacent geometry
+/// end up close to each other in the atlas. `position` must return a world-space position of a
+/// vertex with the given index.
+pub(crate) fn sort_by_locality(
+    meshes: &mut Vec<Chart>,
+    triangles: &[[u32; 3]],
+    position: impl Fn(u32) -> Option<Vector3<f32>>,
+) {
+    let centers = mesh_centers(meshes, triangles, position);
+
+    let (min, max) = centers.iter().fold(
+        (Vector3::repeat(f32::MAX), Vector3::repeat(-f32::MAX)),
+        |(min, max), center| (min.inf(center), max.sup(center)),
+    );
+    let size = (max - min).map(|extent| if extent > 0.0 { extent } else { 1.0 });
+
+    let mut keys = meshes
+        .iter()
+        .zip(centers)
+        .enumerate()
+        .map(|(index, (mesh, center))| {
+            let area = mesh.area();
+            let bucket = if area > 0.0 {
+                area.log2().floor() as i32
+            } else {
+                i32::MIN
+            };
+            let cell = (center - min)
+                .component_div(&size)
+                .map(|c| (c * 1023.0).clamp(0.0, 1023.0) as u32);
+            (Reverse(bucket), morton_code(cell.x, cell.y, cell.z), index)
+        })
+        .collect::<Vec<_>>();
+    keys.sort_unstable();
+
+    let mut unsorted = meshes.drain(..).map(Some).collect::<Vec<_>>();
+    meshes.extend(
+        keys.into_iter()
+            .filter_map(|(_, _, index)| unsorted[index].take()),
+    );
+}
+
+#[cfg(test)]
+mod test {
+    use crate::UvGenOptions;
+    use nalgebra::Vector3;
+
+    #[test]
+    fn test_spatial_locality() {
+        // A row of separate quads of the same size, in shuffled order.
+        let mut vertices = Vec::new();
+        let mut triangles = Vec::new();
+        for x in [5, 2, 7, 0, 3, 6, 1, 4] {
+            let base = vertices.len() as u32;
+            let x = x as f32 * 2.0;
+            vertices.extend([
+                Vector3::new(x, 0.0, 0.0),
+                Vector3::new(x + 1.0, 0.0, 0.0),
+                Vector3::new(x + 1.0, 1.0, 0.0),
+                Vector3::new(x, 1.0, 0.0),
+            ]);
+            triangles.extend([[base, base + 1, base + 2], [base, base + 2, base + 3]]);
+        }
+
+        // Charts of the patch are sorted by their keys, so the packing order is checked instead.
+        let mut task = crate::UnwrapTask::new(
+            vertices.iter().cloned(),
+            triangles.iter().cloned(),
+            UvGenOptions {
+                spatial_locality: true,
+                ..Default::default()
+            },
+        );
+        assert!(task.run_until(crate::Stage::Packing));
+
+        let xs = task
+            .charts()
+            .iter()
+            .map(|chart| vertices[triangles[chart.triangles()[0]][0] as usize].x)
+            .collect::<Vec<_>>();
+        assert!(xs.windows(2).all(|pair| pair[0] < pair[1]));
+    }
+
+    #[test]
+    fn test_morton_code() {
+        assert_eq!(super::morton_code(0, 0, 0), 0);
+        assert_eq!(super::morton_code(1, 0, 0), 1);
+        assert_eq!(super::morton_code(0, 1, 0), 2);
+        assert_eq!(super::morton_code(0, 0, 1), 4);
+        assert_eq!(super::morton_code(3, 3, 3), 63);
+        assert_eq!(super::morton_code(1023, 1023, 1023), (1 << 30) - 1);
+    }
+}
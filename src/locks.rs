@@ -0,0 +1,208 @@
+//! Vertices, that must not be duplicated by the generator. See
+//! [`crate::UvGenOptions::locked_vertices`] for more info.
+
+use crate::{
+    face_alignment, face_projection, frames::LocalFrames, measure::find, triangle_normal,
+    SurfaceDataPatch, UvBox,
+};
+use nalgebra::Vector3;
+use std::collections::{BTreeSet, HashMap};
+
+/// Checks whether the vertex with the given index is locked by the given mask.
+pub(crate) fn is_locked(locked_vertices: &[bool], index: u32) -> bool {
+    locked_vertices
+        .get(index as usize)
+        .cloned()
+        .unwrap_or_default()
+}
+
+/// Moves every triangle, that uses a locked vertex, to the same side of the box as the other
+/// triangles around this vertex, so the vertex never ends up at a boundary between the sides and
+/// the seam goes along the neighbouring edges instead. Triangles, that are connected by locked
+/// vertices, go to the side, that is aligned with them the best on average. Isolated triangles
+/// with locked vertices are moved to the sides too. `density`, `normals` and `frames` must be the
+/// same as for the box mapping. Returns `None` if a triangle references a vertex that does not
+/// exist.
+pub(crate) fn gather_locked_triangles(
+    vertices: &[Vector3<f32>],
+    triangles: &[[u32; 3]],
+    uv_box: &mut UvBox,
+    locked_vertices: &[bool],
+    density: &Vector3<f32>,
+    normals: &[Vector3<f32>],
+    frames: Option<&LocalFrames>,
+) -> Option<()> {
+    let mut excluded = vec![false; triangles.len()];
+    for &triangle_index in uv_box.excluded.iter() {
+        if let Some(excluded) = excluded.get_mut(triangle_index) {
+            *excluded = true;
+        }
+    }
+
+    // Groups of triangles, that are connected by locked vertices.
+    let mut parents = (0..triangles.len()).collect::<Vec<_>>();
+    let mut vertex_triangles = HashMap::new();
+    for (triangle_index, triangle) in triangles.iter().enumerate() {
+        if excluded[triangle_index] {
+            continue;
+        }
+        for &index in triangle {
+            if is_locked(locked_vertices, index) {
+                let first = *vertex_triangles.entry(index).or_insert(triangle_index);
+                let (root, other) = (
+                    find(&mut parents, first),
+                    find(&mut parents, triangle_index),
+                );
+                parents[other] = root;
+            }
+        }
+    }
+    if vertex_triangles.is_empty() {
+        return Some(());
+    }
+
+    let mut triangle_faces = vec![None; triangles.len()];
+    for (face, face_triangles) in uv_box.faces.iter().enumerate() {
+        for &triangle_index in face_triangles {
+            triangle_faces[triangle_index] = Some(face);
+        }
+    }
+
+    let mut groups = HashMap::<usize, (Vec<usize>, [f32; 6])>::new();
+    for (triangle_index, triangle) in triangles.iter().enumerate() {
+        if excluded[triangle_index]
+            || !triangle
+                .iter()
+                .any(|&index| is_locked(locked_vertices, index))
+        {
+            continue;
+        }
+        let points = local_points(vertices, triangle, triangle_index, frames)?;
+        let normal = triangle_normal(triangle_index, &points, normals, frames);
+        let (group_triangles, alignments) = groups
+            .entry(find(&mut parents, triangle_index))
+            .or_default();
+        group_triangles.push(triangle_index);
+        for (face, alignment) in alignments.iter_mut().enumerate() {
+            *alignment += face_alignment(face, &normal);
+        }
+    }
+
+    let mut moved = vec![false; triangles.len()];
+    for (group_triangles, alignments) in groups.into_values() {
+        let face = (0..6)
+            .max_by(|&a, &b| alignments[a].total_cmp(&alignments[b]))
+            .unwrap_or_default();
+        for triangle_index in group_triangles {
+            if triangle_faces[triangle_index] == Some(face) {
+                continue;
+            }
+            let points =
+                local_points(vertices, &triangles[triangle_index], triangle_index, frames)?;
+            let [a, b, c] = points.map(|p| p.component_mul(density));
+            uv_box.projections[triangle_index] = face_projection(face, &a, &b, &c);
+            triangle_faces[triangle_index] = Some(face);
+            moved[triangle_index] = true;
+        }
+    }
+
+    uv_box
+        .isolated
+        .retain(|&triangle_index| !moved[triangle_index]);
+    for face_triangles in uv_box.faces.iter_mut() {
+        face_triangles.clear();
+    }
+    for (triangle_index, face) in triangle_faces.into_iter().enumerate() {
+        if let Some(face) = face {
+            uv_box.faces[face].push(triangle_index);
+        }
+    }
+    Some(())
+}
+
+/// Returns the corners of the triangle in its local frame, if any.
+fn local_points(
+    vertices: &[Vector3<f32>],
+    triangle: &[u32; 3],
+    triangle_index: usize,
+    frames: Option<&LocalFrames>,
+) -> Option<[Vector3<f32>; 3]> {
+    let mut points = [Vector3::default(); 3];
+    for (point, &index) in points.iter_mut().zip(triangle) {
+        let vertex = vertices.get(index as usize)?;
+        *point = match frames {
+            Some(frames) => frames.to_local(triangle_index, vertex),
+            None => *vertex,
+        };
+    }
+    Some(points)
+}
+
+/// Fills [`crate::PackStats::split_locked_vertices`] of the patch. Triangles of the patch must
+/// be set already.
+pub(crate) fn find_split_locked_vertices(patch: &mut SurfaceDataPatch, locked_vertices: &[bool]) {
+    if locked_vertices.is_empty() {
+        return;
+    }
+    let mut copies = HashMap::new();
+    let mut split = BTreeSet::new();
+    for &index in patch.triangles.iter().flatten() {
+        let Some(source) = patch.source_vertex(index) else {
+            continue;
+        };
+        if is_locked(locked_vertices, source) && *copies.entry(source).or_insert(index) != index {
+            split.insert(source);
+        }
+    }
+    patch.pack_stats.split_locked_vertices = split.into_iter().collect();
+}
+
+#[cfg(test)]
+mod test {
+    use crate::UvGenOptions;
+
+    #[test]
+    fn test_locked_vertices() {
+        let mesh = crate::test_meshes::cube();
+        let generate = |locked_vertices: Vec<bool>| {
+            crate::generate_uvs_with_options(
+                mesh.vertices.iter().cloned(),
+                mesh.triangles.iter().cloned(),
+                &UvGenOptions {
+                    locked_vertices,
+                    ..Default::default()
+                },
+            )
+            .unwrap()
+        };
+        let copies = |patch: &crate::SurfaceDataPatch, source: u32| {
+            let mut copies = patch
+                .triangles
+                .iter()
+                .flatten()
+                .filter(|&&index| patch.source_vertex(index) == Some(source))
+                .collect::<Vec<_>>();
+            copies.sort_unstable();
+            copies.dedup();
+            copies.len()
+        };
+
+        // Every corner of a cube is split between three sides.
+        let patch = generate(Vec::new());
+        assert!(copies(&patch, 0) > 1);
+
+        let mut locked_vertices = vec![false; mesh.vertices.len()];
+        locked_vertices[0] = true;
+        let patch = generate(locked_vertices);
+        assert_eq!(copies(&patch, 0), 1);
+        assert!(patch.pack_stats.split_locked_vertices.is_empty());
+        // Other corners are still split.
+        assert!(copies(&patch, 6) > 1);
+
+        // Locking every vertex keeps the whole mesh in a single chart, which is folded.
+        let patch = generate(vec![true; mesh.vertices.len()]);
+        assert_eq!(patch.pack_stats.chart_count, 1);
+        assert!(patch.pack_stats.split_locked_vertices.is_empty());
+        assert!(!patch.pack_stats.flipped_triangles.is_empty());
+    }
+}
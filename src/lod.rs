@@ -0,0 +1,130 @@
+//! Chart-level LODs for multiple lightmap resolutions. See [`SurfaceDataPatch::chart_lods`] for
+//! more info.
+
+use crate::SurfaceDataPatch;
+use nalgebra::Vector2;
+
+/// Charts, that become smaller than a texel at a lightmap resolution, see
+/// [`SurfaceDataPatch::chart_lods`].
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct ChartLod {
+    /// Resolution of the (square) lightmap.
+    pub resolution: u32,
+    /// Indices of the charts (see [`crate::PackStats::charts`]), that fit into a single texel at
+    /// the resolution, in ascending order.
+    pub sub_texel_charts: Vec<usize>,
+}
+
+impl SurfaceDataPatch {
+    /// Returns sizes (width and height in UV units) of the bounds of the texture coordinates of
+    /// every chart, excluding the spacing.
+    fn chart_extents(&self) -> Vec<Vector2<f32>> {
+        self.pack_stats
+            .charts
+            .iter()
+            .map(|chart| {
+                let (min, max) = chart
+                    .triangles
+                    .iter()
+                    .filter_map(|&triangle| self.triangles.get(triangle))
+                    .flatten()
+                    .filter_map(|&index| self.second_tex_coords.get(index as usize))
+                    .fold(
+                        (Vector2::repeat(f32::MAX), Vector2::repeat(-f32::MAX)),
+                        |(min, max), uv| (min.inf(uv), max.sup(uv)),
+                    );
+                (max - min).sup(&Vector2::zeros())
+            })
+            .collect()
+    }
+
+    /// Reports the charts, that become sub-texel (their texture coordinates fit into a single
+    /// texel in both directions) at every of the given lightmap resolutions. Such charts get at
+    /// most a few texels of the lightmap, so engines that ship multiple lightmap quality tiers
+    /// could pre-compute a fallback for them (for example, a single color or vertex lighting) and
+    /// drop them from the lower tiers:
+    ///
+    /// ```rust
+    /// let mesh = uvgen::test_meshes::uv_sphere(16, 8);
+    /// let patch = uvgen::generate_uvs(
+    ///     mesh.vertices.iter().cloned(),
+    ///     mesh.triangles.iter().cloned(),
+    ///     0.005,
+    /// )
+    /// .unwrap();
+    /// let lods = patch.chart_lods(&[1024, 1]);
+    /// assert!(lods[0].sub_texel_charts.is_empty());
+    /// assert_eq!(lods[1].sub_texel_charts.len(), patch.pack_stats.charts.len());
+    /// ```
+    ///
+    /// Results are returned in the order of the resolutions. Lower resolutions have more
+    /// sub-texel charts, a chart that is sub-texel at some resolution is also sub-texel at every
+    /// lower one. Charts, that have no area in the atlas (see
+    /// [`crate::PackStats::collapsed_triangles`]), are sub-texel at every resolution.
+    pub fn chart_lods(&self, resolutions: &[u32]) -> Vec<ChartLod> {
+        let extents = self.chart_extents();
+        resolutions
+            .iter()
+            .map(|&resolution| ChartLod {
+                resolution,
+                sub_texel_charts: extents
+                    .iter()
+                    .enumerate()
+                    .filter(|(_, extent)| extent.max() * resolution as f32 <= 1.0)
+                    .map(|(chart_index, _)| chart_index)
+                    .collect(),
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    #[test]
+    fn test_chart_lods() {
+        // A large cube with a tiny one next to it.
+        let cube = crate::test_meshes::cube();
+        let offset = cube.vertices.len() as u32;
+        let vertices = cube
+            .vertices
+            .iter()
+            .cloned()
+            .chain(cube.vertices.iter().map(|v| v * 0.01));
+        let triangles = cube.triangles.iter().cloned().chain(
+            cube.triangles
+                .iter()
+                .map(|t| [t[0] + offset, t[1] + offset, t[2] + offset]),
+        );
+        let patch = crate::generate_uvs(vertices, triangles, 0.005).unwrap();
+        let chart_count = patch.pack_stats.charts.len();
+        let extents = patch.chart_extents();
+
+        let resolutions = [4096, 1024, 256, 64, 16, 1, 0];
+        let lods = patch.chart_lods(&resolutions);
+        assert_eq!(lods.len(), resolutions.len());
+        for (lod, &resolution) in lods.iter().zip(resolutions.iter()) {
+            assert_eq!(lod.resolution, resolution);
+            for (chart_index, extent) in extents.iter().enumerate() {
+                let sub_texel =
+                    extent.x * resolution as f32 <= 1.0 && extent.y * resolution as f32 <= 1.0;
+                assert_eq!(lod.sub_texel_charts.contains(&chart_index), sub_texel);
+            }
+        }
+        // Lower resolutions drop more charts.
+        for pair in lods.windows(2) {
+            assert!(pair[0]
+                .sub_texel_charts
+                .iter()
+                .all(|chart| pair[1].sub_texel_charts.contains(chart)));
+        }
+        assert!(lods[0].sub_texel_charts.is_empty());
+        assert_eq!(lods[5].sub_texel_charts.len(), chart_count);
+        assert_eq!(lods[6].sub_texel_charts.len(), chart_count);
+        // Charts of the tiny cube are dropped before the charts of the large one.
+        assert!(lods.iter().any(
+            |lod| !lod.sub_texel_charts.is_empty() && lod.sub_texel_charts.len() < chart_count
+        ));
+
+        assert!(patch.chart_lods(&[]).is_empty());
+    }
+}
@@ -0,0 +1,144 @@
+//! Cheap estimation of the size of a patch before the generation. See [`measure`] for more info.
+
+use crate::{box_face, triangle_normal, UvGenOptions};
+use nalgebra::Vector3;
+use std::collections::HashMap;
+
+/// Estimated size of a patch, see [`measure`].
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq, Hash)]
+pub struct SizeHints {
+    /// Amount of vertices, that the generator could add at most (see
+    /// [`crate::SurfaceDataPatch::additional_vertices`]). Vertex buffers of the patched mesh
+    /// could be allocated for the source vertices plus this amount.
+    pub added_vertices_upper_bound: usize,
+    /// Approximate amount of charts. It is the amount of connected groups of triangles, that are
+    /// mapped to the same side of the box. Seam reduction and the other options, that change the
+    /// layout of the charts, are not taken into account. Triangles, that share vertices with the
+    /// other sides of the box, could be split apart by the generator, so the actual amount is
+    /// usually larger for meshes with smooth edges.
+    pub chart_count_estimate: usize,
+}
+
+/// Returns the root of the set of the given element in a disjoint-set forest.
+pub(crate) fn find(parents: &mut [usize], mut index: usize) -> usize {
+    while parents[index] != index {
+        let parent = parents[index];
+        // Path halving.
+        parents[index] = parents[parent];
+        index = parent;
+    }
+    index
+}
+
+/// Estimates the size of the patch, that [`crate::generate_uvs`] gives for the same mesh, without
+/// generating it. It is much faster than the generation, so engines could allocate GPU buffers for
+/// the patched mesh and upload them as soon as the patch is ready:
+///
+/// ```rust
+/// let mesh = uvgen::test_meshes::cube();
+/// let hints = uvgen::measure(mesh.vertices.iter().cloned(), mesh.triangles.iter().cloned());
+/// let patch = uvgen::generate_uvs(
+///     mesh.vertices.iter().cloned(),
+///     mesh.triangles.iter().cloned(),
+///     0.005,
+/// )
+/// .unwrap();
+/// assert!(patch.additional_vertices.len() <= hints.added_vertices_upper_bound);
+/// ```
+pub fn measure(
+    vertices: impl Iterator<Item = Vector3<f32>>,
+    triangles: impl Iterator<Item = [u32; 3]>,
+) -> SizeHints {
+    measure_with_options(vertices, triangles, &Default::default())
+}
+
+/// Estimates the size of the patch, that [`crate::generate_uvs_with_options`] gives for the same
+/// mesh and options. See [`measure`] docs for more info.
+pub fn measure_with_options(
+    vertices: impl Iterator<Item = Vector3<f32>>,
+    triangles: impl Iterator<Item = [u32; 3]>,
+    options: &UvGenOptions,
+) -> SizeHints {
+    let vertices = vertices.collect::<Vec<_>>();
+    let triangles = triangles.collect::<Vec<_>>();
+
+    // Every corner of a triangle gets at most one new vertex. Chunks could also clone every
+    // vertex they use, if the vertex was claimed by some other chunk.
+    let corners = triangles.len().saturating_mul(3);
+    let chunked = options.max_chunk_triangles > 0 && triangles.len() > options.max_chunk_triangles;
+    let added_vertices_upper_bound = if chunked {
+        corners.saturating_mul(2)
+    } else {
+        corners
+    };
+
+    // Vertices of the triangles on different sides of the box are different elements, so the
+    // sets are the charts.
+    let mut elements = HashMap::<(u32, usize), usize>::new();
+    let mut parents = Vec::new();
+    for (triangle_index, triangle) in triangles.iter().enumerate() {
+        let Some(points) = triangle
+            .iter()
+            .map(|&index| vertices.get(index as usize))
+            .collect::<Option<Vec<_>>>()
+            .map(|points| [*points[0], *points[1], *points[2]])
+        else {
+            continue;
+        };
+        let normal = triangle_normal(triangle_index, &points, &options.triangle_normals, None);
+        let face = box_face(&normal);
+        let mut root = None;
+        for &index in triangle {
+            let element = *elements.entry((index, face)).or_insert_with(|| {
+                parents.push(parents.len());
+                parents.len() - 1
+            });
+            let element_root = find(&mut parents, element);
+            match root {
+                Some(root) => parents[element_root] = root,
+                None => root = Some(element_root),
+            }
+        }
+    }
+    let chart_count_estimate = (0..parents.len())
+        .filter(|&element| parents[element] == element)
+        .count();
+
+    SizeHints {
+        added_vertices_upper_bound,
+        chart_count_estimate,
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::UvGenOptions;
+
+    #[test]
+    fn test_measure() {
+        let mesh = crate::test_meshes::uv_sphere(16, 8);
+        for max_chunk_triangles in [0, 64] {
+            let options = UvGenOptions {
+                max_chunk_triangles,
+                ..Default::default()
+            };
+            let hints = crate::measure_with_options(
+                mesh.vertices.iter().cloned(),
+                mesh.triangles.iter().cloned(),
+                &options,
+            );
+            let patch = crate::generate_uvs_with_options(
+                mesh.vertices.iter().cloned(),
+                mesh.triangles.iter().cloned(),
+                &options,
+            )
+            .unwrap();
+            assert!(patch.additional_vertices.len() <= hints.added_vertices_upper_bound);
+            assert!(hints.chart_count_estimate >= 6);
+        }
+
+        // Invalid triangles are ignored.
+        let hints = crate::measure([].into_iter(), [[0, 1, 2]].into_iter());
+        assert_eq!(hints.chart_count_estimate, 0);
+    }
+}
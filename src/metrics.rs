@@ -0,0 +1,166 @@
+//! Quality metrics of texture coordinates, that do not depend on the generator that produced them,
+//! so results of different generators (for example, uvgen and xatlas) could be compared
+//! objectively. See [`uv_metrics`] for more info.
+
+use crate::SurfaceDataPatch;
+use nalgebra::{Vector2, Vector3};
+use std::collections::HashMap;
+
+/// Quality metrics of texture coordinates, see [`uv_metrics`].
+#[derive(Copy, Clone, Debug, Default, PartialEq)]
+pub struct UvMetrics {
+    /// Fraction of the [0;1] square covered by triangles in texture space. Triangles are assumed
+    /// not to overlap. Larger is better.
+    pub occupancy: f32,
+    /// Amount of edges of the source mesh, which have different texture coordinates on their
+    /// sides. Smaller is better.
+    pub seam_count: usize,
+    /// Total world-space length of the seam edges. Smaller is better.
+    pub seam_length: f32,
+    /// Area-weighted L2 stretch (Sander et al., "Texture Mapping Progressive Meshes") normalized
+    /// by the total scale of the mapping, so it is `1.0` for a mapping without distortion and
+    /// larger otherwise. Triangles with zero world or texture area are ignored.
+    pub stretch: f32,
+    /// The largest ratio of the singular values of the mapping of a triangle. It is `1.0` if
+    /// every triangle keeps its angles and larger otherwise.
+    pub max_anisotropy: f32,
+}
+
+/// Calculates quality metrics of the texture coordinates of a mesh, which vertices could be split
+/// at seams. `positions` are the positions of the source vertices, `sources` are indices of the
+/// source vertices for every vertex of the (split) mesh, `triangles` and `uvs` are the triangles
+/// and texture coordinates of the split mesh. This layout matches the output of most generators,
+/// for example, xatlas provides `xref` index for every output vertex. Vertices or source vertices
+/// with out-of-range indices are ignored.
+pub fn uv_metrics(
+    positions: &[Vector3<f32>],
+    sources: &[u32],
+    triangles: &[[u32; 3]],
+    uvs: &[Vector2<f32>],
+) -> UvMetrics {
+    let mut metrics = UvMetrics {
+        max_anisotropy: 1.0,
+        ..Default::default()
+    };
+
+    // Every source edge is mapped to texture coordinates of its ends on the first triangle, that
+    // uses it, and a flag, that tells whether any other triangle has different coordinates.
+    let mut edges = HashMap::<(u32, u32), ([Vector2<f32>; 2], bool)>::new();
+    let (mut world_area, mut uv_area, mut stretch) = (0.0f64, 0.0f64, 0.0f64);
+    for triangle in triangles {
+        let Some(corners) = triangle
+            .iter()
+            .map(|&index| {
+                let source = *sources.get(index as usize)?;
+                Some((
+                    source,
+                    *positions.get(source as usize)?,
+                    *uvs.get(index as usize)?,
+                ))
+            })
+            .collect::<Option<Vec<_>>>()
+        else {
+            continue;
+        };
+
+        for (i, j) in [(0, 1), (1, 2), (2, 0)] {
+            let (mut a, mut b) = (corners[i], corners[j]);
+            if a.0 > b.0 {
+                std::mem::swap(&mut a, &mut b);
+            }
+            let entry = edges.entry((a.0, b.0)).or_insert(([a.2, b.2], false));
+            if entry.0 != [a.2, b.2] {
+                entry.1 = true;
+            }
+        }
+
+        let [(_, p0, t0), (_, p1, t1), (_, p2, t2)] = [corners[0], corners[1], corners[2]];
+        let area_3d = (p1 - p0).cross(&(p2 - p0)).norm() * 0.5;
+        let area_2d = (t1 - t0).perp(&(t2 - t0)) * 0.5;
+        metrics.occupancy += area_2d.abs();
+        if area_3d <= f32::EPSILON || area_2d.abs() <= f32::EPSILON {
+            continue;
+        }
+        // Partial derivatives of the world position by the texture coordinates.
+        let ds = (p0 * (t1.y - t2.y) + p1 * (t2.y - t0.y) + p2 * (t0.y - t1.y)) / (2.0 * area_2d);
+        let dt = (p0 * (t2.x - t1.x) + p1 * (t0.x - t2.x) + p2 * (t1.x - t0.x)) / (2.0 * area_2d);
+        let (a, b, c) = (ds.dot(&ds), ds.dot(&dt), dt.dot(&dt));
+        let root = ((a - c) * (a - c) + 4.0 * b * b).sqrt();
+        let max_value = ((a + c + root) * 0.5).sqrt();
+        let min_value = ((a + c - root) * 0.5).max(0.0).sqrt();
+        if min_value > 0.0 {
+            metrics.max_anisotropy = metrics.max_anisotropy.max(max_value / min_value);
+        }
+        stretch += ((a + c) * 0.5) as f64 * area_3d as f64;
+        world_area += area_3d as f64;
+        uv_area += area_2d.abs() as f64;
+    }
+
+    if world_area > 0.0 {
+        metrics.stretch = ((stretch / world_area).sqrt() * (uv_area / world_area).sqrt()) as f32;
+    }
+    for (&(a, b), (_, split)) in edges.iter() {
+        if *split {
+            metrics.seam_count += 1;
+            if let (Some(a), Some(b)) = (positions.get(a as usize), positions.get(b as usize)) {
+                metrics.seam_length += a.metric_distance(b);
+            }
+        }
+    }
+    metrics
+}
+
+impl SurfaceDataPatch {
+    /// Calculates quality metrics (see [`uv_metrics`]) of the patch. `positions` are the
+    /// positions of the source vertices of the mesh, that the patch was generated for.
+    pub fn uv_metrics(&self, positions: &[Vector3<f32>]) -> UvMetrics {
+        let sources = (0..self.second_tex_coords.len() as u32)
+            .map(|index| self.source_vertex(index).unwrap_or(u32::MAX))
+            .collect::<Vec<_>>();
+        uv_metrics(
+            positions,
+            &sources,
+            &self.triangles,
+            &self.second_tex_coords,
+        )
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use nalgebra::{Vector2, Vector3};
+
+    #[test]
+    fn test_uv_metrics() {
+        // A unit quad mapped without distortion, but with a seam along the diagonal.
+        let positions = [
+            Vector3::new(0.0, 0.0, 0.0),
+            Vector3::new(1.0, 0.0, 0.0),
+            Vector3::new(1.0, 1.0, 0.0),
+            Vector3::new(0.0, 1.0, 0.0),
+        ];
+        let sources = [0, 1, 2, 3, 0, 2];
+        let triangles = [[0, 1, 2], [4, 5, 3]];
+        let uvs = [
+            Vector2::new(0.0, 0.0),
+            Vector2::new(0.5, 0.0),
+            Vector2::new(0.5, 0.5),
+            Vector2::new(0.5, 1.0),
+            Vector2::new(0.5, 0.5),
+            Vector2::new(1.0, 1.0),
+        ];
+        let metrics = super::uv_metrics(&positions, &sources, &triangles, &uvs);
+        assert!((metrics.occupancy - 0.25).abs() < 1.0e-6);
+        assert_eq!(metrics.seam_count, 1);
+        assert!((metrics.seam_length - 2.0f32.sqrt()).abs() < 1.0e-6);
+        assert!((metrics.stretch - 1.0).abs() < 1.0e-5);
+        assert!((metrics.max_anisotropy - 1.0).abs() < 1.0e-5);
+
+        // Squashing the first triangle introduces anisotropy.
+        let mut uvs = uvs;
+        uvs[2].y = 0.25;
+        let metrics = super::uv_metrics(&positions, &sources, &triangles, &uvs);
+        assert!(metrics.stretch > 1.0);
+        assert!(metrics.max_anisotropy > 1.5);
+    }
+}
@@ -0,0 +1,96 @@
+//! Padding of charts for mip-mapped lightmaps. See [`mip_paddings`] for more info.
+
+use crate::UvGenOptions;
+
+/// Padding of charts, that prevents bleeding at a mip level, see [`mip_paddings`].
+#[derive(Copy, Clone, Debug, Default, PartialEq)]
+pub struct MipPadding {
+    /// Mip level, `0` is the full resolution.
+    pub level: u32,
+    /// Padding (in texels of the full resolution) on each side of every chart.
+    pub texels: u32,
+    /// The same padding in UV units, it could be used as [`UvGenOptions::spacing`] directly.
+    pub spacing: f32,
+}
+
+/// Returns minimal padding of charts for every mip level of a lightmap with the given resolution,
+/// up to the given amount of mip levels (levels that do not exist for the resolution are
+/// skipped). Texels of mip level `n` cover `2^n` by `2^n` texels of the full resolution, so the
+/// gap between two charts must be `2^(n + 1)` texels wide to contain at least one texel of level
+/// `n`, that does not mix colors of both charts. Every chart has spacing on each side, so the
+/// padding is `2^n` texels:
+///
+/// ```rust
+/// let paddings = uvgen::mip_paddings(1024, 4);
+/// assert_eq!(
+///     paddings.iter().map(|p| p.texels).collect::<Vec<_>>(),
+///     [1, 2, 4, 8]
+/// );
+/// assert_eq!(paddings[3].spacing, 8.0 / 1024.0);
+/// ```
+///
+/// Use [`UvGenOptions::mip_padding_levels`] to enforce the padding during packing.
+pub fn mip_paddings(resolution: u32, mip_count: u32) -> Vec<MipPadding> {
+    let level_count = if resolution == 0 {
+        0
+    } else {
+        resolution.ilog2() + 1
+    };
+    (0..mip_count.min(level_count))
+        .map(|level| {
+            let texels = 1 << level;
+            MipPadding {
+                level,
+                texels,
+                spacing: texels as f32 / resolution as f32,
+            }
+        })
+        .collect()
+}
+
+/// Returns the options with the spacing increased to the padding of the last of
+/// [`UvGenOptions::mip_padding_levels`] mip levels, if it is enabled.
+pub(crate) fn with_mip_padding(mut options: UvGenOptions) -> UvGenOptions {
+    if let Some(padding) = mip_paddings(options.resolution, options.mip_padding_levels).last() {
+        options.spacing = options.spacing.max(padding.spacing);
+    }
+    options
+}
+
+#[cfg(test)]
+mod test {
+    use crate::UvGenOptions;
+
+    #[test]
+    fn test_mip_paddings() {
+        assert!(super::mip_paddings(0, 4).is_empty());
+        // 4x4 texture has only 3 levels: 4x4, 2x2 and 1x1.
+        assert_eq!(super::mip_paddings(4, 8).len(), 3);
+
+        let mesh = crate::test_meshes::cube();
+        let patch = crate::generate_uvs_with_options(
+            mesh.vertices.iter().cloned(),
+            mesh.triangles.iter().cloned(),
+            &UvGenOptions {
+                spacing: 0.0,
+                resolution: 256,
+                mip_padding_levels: 3,
+                ..Default::default()
+            },
+        )
+        .unwrap();
+        // Every chart must be at least 4 texels away from the borders of its rectangle.
+        let spacing = 4.0 / 256.0;
+        for chart in patch.pack_stats.charts.iter() {
+            for &triangle in chart.triangles.iter() {
+                for &vertex in patch.triangles[triangle].iter() {
+                    let uv = patch.second_tex_coords[vertex as usize];
+                    assert!(uv.x >= chart.rect.x() + spacing - 1.0e-6);
+                    assert!(uv.y >= chart.rect.y() + spacing - 1.0e-6);
+                    assert!(uv.x <= chart.rect.x() + chart.rect.w() - spacing + 1.0e-6);
+                    assert!(uv.y <= chart.rect.y() + chart.rect.h() - spacing + 1.0e-6);
+                }
+            }
+        }
+    }
+}
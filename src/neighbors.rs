@@ -0,0 +1,138 @@
+//! Adjacency of the charts in 3D. See [`SurfaceDataPatch::chart_neighbors`] for more info.
+
+use crate::SurfaceDataPatch;
+use std::collections::BTreeMap;
+
+/// A pair of charts, that are adjacent in 3D: they share edges of the source mesh, which were
+/// split into separate edges of the charts. See [`SurfaceDataPatch::chart_neighbors`].
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ChartNeighbors {
+    /// Indices of the charts in [`crate::PackStats::charts`], the first one is smaller.
+    pub charts: [usize; 2],
+    /// Shared edges, sorted by their source vertices. Every item is a pair of edges (as pairs of
+    /// vertex indices of the patched mesh): the first edge belongs to the first chart and the
+    /// second one to the second chart. Vertices at the same positions of both edges are copies of
+    /// the same source vertex, so they have the same position, but different texture
+    /// coordinates.
+    pub edges: Vec<[[u32; 2]; 2]>,
+}
+
+impl SurfaceDataPatch {
+    /// Returns every pair of charts, that share edges of the source mesh, sorted by the indices
+    /// of the charts. This is a graph of the charts, which seam-aware denoisers, chart merging
+    /// tools and lightmap compressors could use instead of rebuilding it from the triangles:
+    ///
+    /// ```rust
+    /// let mesh = uvgen::test_meshes::cube();
+    /// let patch = uvgen::generate_uvs(
+    ///     mesh.vertices.iter().cloned(),
+    ///     mesh.triangles.iter().cloned(),
+    ///     0.005,
+    /// )
+    /// .unwrap();
+    /// // Every side of a cube is a chart, that touches four other sides.
+    /// let neighbors = patch.chart_neighbors();
+    /// assert_eq!(neighbors.len(), 12);
+    /// assert!(neighbors.iter().all(|pair| pair.edges.len() == 1));
+    /// ```
+    ///
+    /// Triangles, that do not belong to any chart, are ignored. If an edge is shared by more than
+    /// two charts, every pair of them is reported.
+    pub fn chart_neighbors(&self) -> Vec<ChartNeighbors> {
+        let mut triangle_charts = vec![None; self.triangles.len()];
+        for (chart_index, chart) in self.pack_stats.charts.iter().enumerate() {
+            for &triangle_index in chart.triangles.iter() {
+                if let Some(triangle_chart) = triangle_charts.get_mut(triangle_index) {
+                    *triangle_chart = Some(chart_index);
+                }
+            }
+        }
+
+        // Users of every source edge along with their actual edges, ordered by source vertices.
+        let mut edges = BTreeMap::<[u32; 2], Vec<(usize, [u32; 2])>>::new();
+        for (triangle, chart) in self.triangles.iter().zip(triangle_charts) {
+            let Some(chart) = chart else {
+                continue;
+            };
+            for (i, j) in [(0, 1), (1, 2), (2, 0)] {
+                let (a, b) = (triangle[i], triangle[j]);
+                let (source_a, source_b) = (
+                    self.source_vertex(a).unwrap_or(a),
+                    self.source_vertex(b).unwrap_or(b),
+                );
+                let (key, actual) = if source_a <= source_b {
+                    ([source_a, source_b], [a, b])
+                } else {
+                    ([source_b, source_a], [b, a])
+                };
+                edges.entry(key).or_default().push((chart, actual));
+            }
+        }
+
+        let mut neighbors = BTreeMap::<[usize; 2], Vec<[[u32; 2]; 2]>>::new();
+        for users in edges.values() {
+            for (i, &(first_chart, first_edge)) in users.iter().enumerate() {
+                for &(second_chart, second_edge) in users[i + 1..].iter() {
+                    if first_chart < second_chart {
+                        neighbors
+                            .entry([first_chart, second_chart])
+                            .or_default()
+                            .push([first_edge, second_edge]);
+                    } else if second_chart < first_chart {
+                        neighbors
+                            .entry([second_chart, first_chart])
+                            .or_default()
+                            .push([second_edge, first_edge]);
+                    }
+                }
+            }
+        }
+        neighbors
+            .into_iter()
+            .map(|(charts, edges)| ChartNeighbors { charts, edges })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    #[test]
+    fn test_chart_neighbors() {
+        let mesh = crate::test_meshes::cube();
+        let patch = crate::generate_uvs(
+            mesh.vertices.iter().cloned(),
+            mesh.triangles.iter().cloned(),
+            0.005,
+        )
+        .unwrap();
+        let neighbors = patch.chart_neighbors();
+        assert_eq!(neighbors.len(), 12);
+
+        let mut degrees = vec![0; patch.pack_stats.chart_count];
+        for pair in neighbors.iter() {
+            assert!(pair.charts[0] < pair.charts[1]);
+            degrees[pair.charts[0]] += 1;
+            degrees[pair.charts[1]] += 1;
+
+            let [first, second] = pair.edges[0];
+            for (a, b) in first.into_iter().zip(second) {
+                assert_ne!(a, b);
+                assert_eq!(patch.source_vertex(a), patch.source_vertex(b));
+            }
+            let chart_vertices = |chart: usize| {
+                patch.pack_stats.charts[chart]
+                    .triangles
+                    .iter()
+                    .flat_map(|&triangle| patch.triangles[triangle])
+                    .collect::<Vec<_>>()
+            };
+            assert!(first
+                .iter()
+                .all(|index| chart_vertices(pair.charts[0]).contains(index)));
+            assert!(second
+                .iter()
+                .all(|index| chart_vertices(pair.charts[1]).contains(index)));
+        }
+        assert!(degrees.iter().all(|&degree| degree == 4));
+    }
+}
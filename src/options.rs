@@ -0,0 +1,572 @@
+//! Options of the UV generator.
+
+use crate::{AnchorLayout, WeldTolerance};
+use nalgebra::{Vector2, Vector3};
+use std::sync::{
+    atomic::{AtomicBool, Ordering},
+    Arc,
+};
+
+/// Options of the UV generator. Use [`Default::default`] to get sensible defaults and then modify
+/// the options you need:
+///
+/// ```rust
+/// let options = uvgen::UvGenOptions {
+///     spacing: 0.01,
+///     ..Default::default()
+/// };
+/// ```
+///
+/// With `serde` feature options could be saved and loaded with any `serde` format, `toml` and
+/// `json` features add `UvGenOptions::to_toml` and `UvGenOptions::to_json` shortcuts. Options,
+/// that hold per-mesh data or runtime state ([`Self::previous_layout`],
+/// [`Self::locked_vertices`], [`Self::triangle_normals`], [`Self::triangle_importance`] and
+/// [`Self::cancellation`]), are skipped. Missing options have their default values, unknown ones
+/// are errors.
+#[derive(Clone, Debug, PartialEq)]
+#[cfg_attr(
+    feature = "serde",
+    derive(serde::Serialize, serde::Deserialize),
+    serde(default, deny_unknown_fields)
+)]
+pub struct UvGenOptions {
+    /// Spacing (in UV units) that will be added to each side of every UV mesh (chart). Spacing
+    /// prevents light "bleeding" between neighbouring charts. Default is `0.005`.
+    ///
+    /// Generation fails with [`crate::UvGenError::PackingFailed`], if the charts do not fit into
+    /// the atlas with this spacing. The error tells the largest spacing that could work in this
+    /// case.
+    pub spacing: f32,
+    /// If `true`, the generated patch will contain a per-vertex RGBA color for every vertex, where
+    /// each chart gets a distinct color. It could be used to visualize chart layout directly on a
+    /// mesh. Default is `false`.
+    pub chart_colors: bool,
+    /// Meshes with less triangles than this threshold are packed using a fast closed-form grid
+    /// layout instead of iterative packing. Grid layout wastes more atlas space, but it is much
+    /// faster, which is useful for batch processing of lots of small meshes (props, decals, etc.).
+    /// Default is `0` (fast path is disabled).
+    pub tiny_mesh_threshold: usize,
+    /// If `true`, the generator will produce bit-identical results regardless of the amount of
+    /// CPU cores and whether the `parallel` feature is enabled or not. This is achieved by using
+    /// fixed-size chunks of work, which are merged in a fixed order. Disabling this option allows
+    /// the generator to split the work evenly between all available cores, but floating-point
+    /// sums may then differ slightly between machines. Default is `true`.
+    pub deterministic: bool,
+    /// Maximum amount of triangles in a chunk. If the mesh has more triangles than this limit,
+    /// it is split into spatially coherent chunks, which are unwrapped independently and then
+    /// packed together into the same atlas. This bounds peak memory usage of the generator
+    /// (and significantly reduces generation time) for very large meshes at the cost of
+    /// additional seams at chunk boundaries. With `parallel` feature chunks are processed
+    /// concurrently. Chunked mode is not supported by [`crate::UnwrapTask`]. Default is `0`
+    /// (chunked mode is disabled).
+    pub max_chunk_triangles: usize,
+    /// Maximum acceptable stretch of a triangle. Stretch is the ratio of the area of a triangle to
+    /// the area of its projection on a side of the box, it is in `[1.0; sqrt(3)]` range for box
+    /// mapping. Triangles with larger stretch (for example, the ones on steep slopes) are split
+    /// into their own charts and projected on their own plane, which removes any stretch at the
+    /// cost of additional seams. Default is [`f32::INFINITY`] (stretch is not limited).
+    #[cfg_attr(feature = "serde", serde(with = "crate::serialization::float"))]
+    pub max_stretch: f32,
+    /// Bias (in `[0; 1]` range) towards shorter seams. Triangles, that are aligned with a side of
+    /// the box just slightly worse than with the best one, are moved to the side that most of
+    /// their neighbours belong to. Larger values give shorter seams (see
+    /// [`crate::PackStats::seam_length`]) at the cost of more stretch. Default is `0.0` (every
+    /// triangle is mapped to the best side).
+    pub seam_bias: f32,
+    /// Per-axis multipliers of lightmap density. Vertex positions are scaled by these values
+    /// before projection, so charts get more atlas space along the axes with larger multipliers.
+    /// For example, `Vector3::new(1.0, 2.0, 1.0)` gives twice as much resolution vertically on
+    /// walls, which usually receive high-frequency shadows. Triangles that were split into their
+    /// own charts (see [`Self::max_stretch`]) are not affected. Default is `(1.0, 1.0, 1.0)`.
+    pub density: Vector3<f32>,
+    /// If `true`, every chart gets a hash of its content (positions of its triangles) and the
+    /// locations of the charts are stored in [`crate::PackStats::anchor_layout`]. Passing this
+    /// layout to the next run (see [`Self::previous_layout`]) keeps unchanged charts at the same
+    /// locations of the atlas, which allows to reuse previously baked lightmap regions after
+    /// small edits of a mesh. Default is `false`.
+    pub stable_anchors: bool,
+    /// A layout of the previous run of the generator (see [`Self::stable_anchors`]). Charts that
+    /// were not changed since the previous run are put at their previous locations with the
+    /// previous scale, the rest of the charts are packed into the remaining space. If they do not
+    /// fit, the layout is built from scratch. Ignored if [`Self::stable_anchors`] is `false`.
+    /// Default is `None`.
+    #[cfg_attr(feature = "serde", serde(skip))]
+    pub previous_layout: Option<AnchorLayout>,
+    /// If `true`, the generated patch will contain adjacency of the triangles (see
+    /// [`crate::SurfaceDataPatch::adjacency`]), which is useful for bakers and seam stitchers.
+    /// Default is `false`.
+    pub adjacency: bool,
+    /// Amount of additional packing attempts with slightly shuffled order of charts. The layout
+    /// with the largest scale (the best texel density) is used. Each attempt is as expensive as
+    /// the regular packing. Default is `0` (no additional attempts).
+    pub pack_restarts: usize,
+    /// Maximum amount of attempts of the iterative packer, which looks for the largest scale, at
+    /// which every chart fits into the atlas. More attempts could make packings of meshes with
+    /// lots of charts tighter at the cost of time. The packing fails with
+    /// [`crate::PackingError::Exhausted`], if none of the attempts succeeds. Zero is treated as
+    /// one. Default is `100`.
+    pub max_packing_iterations: usize,
+    /// Factor, by which the scale of the charts changes after a failed packing attempt. The
+    /// iterative packer grows its empiric scale (so the charts become smaller), packing into
+    /// pages or into free space of an atlas divides the scale by the factor. Larger factors need
+    /// less attempts, but give looser packings. Factors below `1.01` are treated as `1.01`,
+    /// non-finite factors are replaced with the default one. Default is `1.33`.
+    pub scale_growth_factor: f32,
+    /// Seed for every stochastic stage of the generator (see [`Self::pack_restarts`]). The same
+    /// seed gives the same results on every machine, which allows to reproduce and compare the
+    /// results. Default is `0`.
+    pub seed: u64,
+    /// Resolution (in texels) of the lightmap, that is used to snap charts to texel centers. If
+    /// it is non-zero, every chart is moved (by at most half of a texel) so its minimum UV lies
+    /// exactly on a texel center. This stabilizes sampling at chart borders and simplifies
+    /// dilation. Spacing should be at least one texel, otherwise neighbouring charts could
+    /// overlap after snapping. Default is `0` (no snapping).
+    pub texel_snap_resolution: u32,
+    /// If `true`, every connected part of a mesh is box-mapped in its own local frame, which
+    /// axes are aligned with the dominant orientations of the part, instead of the world axes.
+    /// This significantly reduces stretch for meshes, that consist of differently oriented
+    /// parts (for example, a rotated tower on a straight base). Sides of the box (see
+    /// [`crate::face_planes`]) and [`Self::density`] are relative to the local frames in this
+    /// case. Default is `false`.
+    pub local_frames: bool,
+    /// If `true`, charts of spatially adjacent geometry are placed near each other in the atlas
+    /// (as long as they have similar size). This improves compression ratio of lightmaps, since
+    /// similar lighting ends up in nearby blocks, and texture cache behaviour during baking.
+    /// Default is `false`.
+    pub spatial_locality: bool,
+    /// Amount of atlas pages to distribute the charts across. Charts are assigned to pages by
+    /// spatial clustering of their geometry, so every page covers a compact region of the mesh
+    /// (for example, one room of a level), which allows to stream lightmaps page by page. All
+    /// pages share the same scale. See [`crate::SurfaceDataPatch::triangle_pages`] and
+    /// [`crate::ChartInfo::page`] to find out where the triangles went. [`Self::previous_layout`]
+    /// is ignored if there's more than one page. Default is `1`.
+    pub page_count: usize,
+    /// Sizes of additional rectangles, that are not tied to any triangles, but must be packed
+    /// into the atlas along with the charts (for example, to reserve space for baked data of
+    /// non-mesh objects). Sizes are in world units, so the rectangles are scaled the same way as
+    /// the charts. Placements of the rectangles are returned in
+    /// [`crate::PackStats::extra_rects`]. If there are multiple pages, the rectangles are put on
+    /// the first page. Extra rectangles are ignored by [`crate::extend_patch`] and
+    /// [`crate::pack_into_atlas`]. Default is an empty list.
+    pub extra_rects: Vec<Vector2<f32>>,
+    /// Target resolution of the atlas in texels (the atlas is assumed to be square). It is used
+    /// by the options, that work in texels, such as [`Self::min_chart_texel_width`]. Zero means
+    /// that the resolution is unknown. Default is `0`.
+    pub resolution: u32,
+    /// Minimal width of a chart in texels at [`Self::resolution`]. Thinner charts produce
+    /// unusable lightmap data, such charts are flagged in [`crate::ChartInfo::thin`]. Default is
+    /// `2.0`.
+    pub min_chart_texel_width: f32,
+    /// If `true`, charts thinner than [`Self::min_chart_texel_width`] are stretched along their
+    /// thin axis and the atlas is packed again, so every chart has at least the minimal width.
+    /// This sacrifices uniform texel density of such charts. Requires [`Self::resolution`] to be
+    /// set. Default is `false`.
+    pub upscale_thin_charts: bool,
+    /// Optional tolerance of vertex welding. If set, vertices within the tolerance from each
+    /// other are treated as a single vertex, so triangles, that share a position, but not an
+    /// index, end up in the same chart. Welded vertices become unreferenced by the new triangles
+    /// of the patch (see [`crate::SurfaceDataPatch::unreferenced_vertices`]). Default is `None`.
+    #[cfg_attr(feature = "serde", serde(skip_serializing_if = "Option::is_none"))]
+    pub weld: Option<WeldTolerance>,
+    /// Maximal ratio of the longest edge of a triangle to its height, triangles with larger
+    /// ratios are considered as needle triangles. Such triangles have almost no area, but could
+    /// blow up bounds of charts they belong to. Needle triangles are reported in
+    /// [`crate::PackStats::needle_triangles`] and handled according to
+    /// [`Self::needle_handling`]. Default is [`f32::INFINITY`], which disables the detection.
+    #[cfg_attr(feature = "serde", serde(with = "crate::serialization::float"))]
+    pub needle_ratio: f32,
+    /// Defines what to do with needle triangles (see [`Self::needle_ratio`]). Default is
+    /// [`NeedleHandling::Keep`].
+    pub needle_handling: NeedleHandling,
+    /// Defines what to do with triangles, that reference the same vertex more than once (for
+    /// example, `[5, 5, 9]`). Such triangles have zero area and an undefined normal, so they're
+    /// put on a random side of the box and cause useless vertex duplication. Default is
+    /// [`RepeatedIndexHandling::Keep`].
+    pub repeated_index_handling: RepeatedIndexHandling,
+    /// Multiplier of the scale of the charts of the mesh relative to the other meshes of a shared
+    /// atlas, similar to "Scale In Lightmap" parameter of Unity. For example, `2.0` gives the mesh
+    /// twice as much texels along each axis, while `0.5` saves atlas space for unimportant
+    /// objects. It is applied on top of the scale of the atlas by [`crate::pack_into_atlas`] and
+    /// [`crate::extend_patch`], the scale of the atlas itself is not changed. It does not affect
+    /// generation of a standalone atlas, since the charts of a single mesh are always scaled to
+    /// fill it. Must be positive. Default is `1.0`.
+    pub scale_in_lightmap: f32,
+    /// Amount of mip levels of the lightmap, that must not bleed between charts. If it is
+    /// non-zero, [`Self::spacing`] is increased to the padding, that is needed for the last of
+    /// these levels at [`Self::resolution`] (see [`crate::mip_paddings`]). Requires
+    /// [`Self::resolution`] to be set. Default is `0` (spacing is used as is).
+    pub mip_padding_levels: u32,
+    /// Defines what to do with back-to-back triangles: triangles with the same corner positions,
+    /// but the opposite winding, which are typical for double-sided geometry (foliage cards,
+    /// fences, etc.). Such triangles produce two perfectly overlapping charts, that fight for
+    /// atlas space. Default is [`DoubleSidedHandling::Ignore`].
+    pub double_sided_handling: DoubleSidedHandling,
+    /// If `true`, connected parts of a mesh, that are (nearly) cylinders (pipes, columns, rails,
+    /// etc.), are unwrapped around their own axes instead of box mapping. Box mapping splits
+    /// cylinders, that are not aligned with the world axes, into lots of tiny charts, while the
+    /// cylindrical unwrap gives two strips for the side and a chart per cap. A part is considered
+    /// a cylinder, if every its triangle is either on the side or on a cap, the side wraps around
+    /// the axis and covers at least a half of the area of the part. Caps with their own vertices
+    /// (hard edges) are separate parts, that are box mapped as usual. Density (see
+    /// [`Self::density`]) is not applied to cylinders. Default is `false`.
+    pub cylinder_unwrap: bool,
+    /// If `true`, the generated patch will contain texture coordinates of every vertex relative
+    /// to the rectangle of its chart (see [`crate::SurfaceDataPatch::chart_uvs`]). Engines with
+    /// virtual lightmap texturing could relocate charts at runtime by updating a per-chart
+    /// transform (see [`crate::ChartUv::relocate`]), without touching vertex data. Default is
+    /// `false`.
+    pub chart_uvs: bool,
+    /// Size (in texels) of a page of a virtual-texture lightmap, which is streamed page by page.
+    /// If it is non-zero, the atlas of [`Self::resolution`] texels is split into a grid of square
+    /// pages and every chart is kept inside a single page, charts spill into the next pages as
+    /// the previous ones get filled. Index of the page of a chart could be calculated from its
+    /// rectangle (see [`crate::ChartInfo::rect`]). Charts are scaled down, if the largest of them
+    /// does not fit into a page. Requires [`Self::resolution`] to be set, ignored if there's more
+    /// than one atlas page (see [`Self::page_count`]). Default is `0` (charts are not aligned
+    /// with pages).
+    pub virtual_page_size: u32,
+    /// Target texel density of the lightmap in texels per world unit. If it is set along with
+    /// [`Self::virtual_page_size`], charts that are larger than a virtual page at this density
+    /// are split into smaller charts by a uniform grid, so they could be packed into pages
+    /// without shrinking. Triangles are never cut, so a single triangle, that is larger than a
+    /// page, still makes its chart larger than the page. Default is `0.0` (charts are not
+    /// split).
+    pub texel_density: f32,
+    /// If `true`, charts are rasterized at [`Self::resolution`] after packing and pairs of
+    /// charts, that are closer to each other than the spacing allows, are reported in
+    /// [`crate::PackStats::padding_violations`] (see
+    /// [`crate::SurfaceDataPatch::padding_violations`]). Since partially covered texels could
+    /// eat a fraction of a texel on each side, at least `floor(2 * spacing * resolution) - 1`
+    /// texels between charts must be empty. Requires [`Self::resolution`] to be set. Default is
+    /// `false`.
+    pub verify_padding: bool,
+    /// A mask of vertices, that must not be duplicated (for example, because other systems match
+    /// them exactly, like navmesh welds). Vertices with `true` flags keep a single copy: every
+    /// triangle around such vertex is put into the same chart, so seams go along the neighbouring
+    /// edges instead. If a locked vertex is duplicated anyway (for example, when it is shared by
+    /// chunks, see [`Self::max_chunk_triangles`]), it is reported in
+    /// [`crate::PackStats::split_locked_vertices`]. Vertices past the end of the mask are not
+    /// locked. For [`crate::extend_patch`] the mask refers to the new vertices. Default is an
+    /// empty mask.
+    #[cfg_attr(feature = "serde", serde(skip))]
+    pub locked_vertices: Vec<bool>,
+    /// Maximal size of a chart along each axis of its projection, in world units (scaled by
+    /// [`Self::density`]). Larger charts (for example, huge flat ground planes) are cut into tiles
+    /// by a uniform grid before packing, otherwise a single chart could take the whole atlas and
+    /// leave no resolution for the rest of the mesh. Triangles are never cut. Tiles, that share a
+    /// part of the cut, are listed in [`crate::PackStats::tile_adjacency`], so bakers could stitch
+    /// them. Default is `f32::INFINITY` (charts are not split).
+    #[cfg_attr(feature = "serde", serde(with = "crate::serialization::float"))]
+    pub max_chart_size: f32,
+    /// Rounding of the rectangles of the charts in texels (see [`crate::ChartInfo::texel_rect`]),
+    /// which are calculated if [`Self::resolution`] is set. Default is
+    /// [`TexelRounding::Outward`].
+    pub texel_rounding: TexelRounding,
+    /// Precomputed normals of the triangles, indexed by triangle index. If set, they are used to
+    /// pick the sides of the box for the triangles instead of the normals, that are calculated
+    /// from the winding of the triangles, so the sides match the normal convention of the engine
+    /// (handedness, winding), and the cross products are skipped. Normals do not have to be
+    /// normalized. Zero or non-finite normals and triangles past the end of the list fall back
+    /// to the calculated normals. For [`crate::extend_patch`] the list refers to the new
+    /// triangles. Default is an empty list.
+    #[cfg_attr(feature = "serde", serde(skip))]
+    pub triangle_normals: Vec<Vector3<f32>>,
+    /// Coordinate system of the mesh. Projections on the sides of the box (see
+    /// [`crate::face_planes`]) are made in the coordinate system of the generator (Y-up,
+    /// right-handed), meshes in other coordinate systems are converted to it first, so their
+    /// charts are not rotated or mirrored compared to the same mesh exported in the coordinate
+    /// system of the generator. Sides of the box and [`Self::density`] are relative to the
+    /// coordinate system of the generator in this case. Default is
+    /// [`CoordinateSystem::YUpRightHanded`].
+    pub coordinate_system: CoordinateSystem,
+    /// If `true`, the packed charts are uniformly scaled up after packing, so their tight bounds
+    /// (see [`crate::SurfaceDataPatch::uv_bounds`]) fill the atlas, except half-texel margins at
+    /// [`Self::resolution`] (if it is set). This reclaims the border, that the conservative
+    /// packing leaves unused. Spacing is not scaled, so the gaps between the charts only grow.
+    /// Ignored with virtual pages (see [`Self::virtual_page_size`]) and by
+    /// [`crate::extend_patch`]. Default is `false`.
+    pub fill_atlas: bool,
+    /// If `true`, projections of the triangles on the sides of the box (or on their own planes,
+    /// cylinders and so on), from which the final texture coordinates were made, are stored in
+    /// [`crate::SurfaceDataPatch::triangle_projections`]. They're in world units (scaled by
+    /// [`Self::density`]), before the charts are scaled and moved into the atlas: texture
+    /// coordinates of every chart are its projections scaled by [`crate::PackStats::scale`] and
+    /// moved by some offset, so detail textures mapped with the projections align with the
+    /// charts. Charts, that were added by [`crate::extend_patch`], could have a different scale.
+    /// Default is `false`.
+    pub triangle_projections: bool,
+    /// Charts, which both sides would be smaller than this amount of texels at
+    /// [`Self::resolution`], are not packed on their own. Such charts only add spacing overhead
+    /// and noise to the atlas, so their triangles are merged into a single chart, that occupies
+    /// one texel, and every vertex of them gets texture coordinates of the center of that texel
+    /// (which could be filled with a constant value, for example black). Such triangles are
+    /// reported in [`crate::PackStats::collapsed_triangles`]. Requires [`Self::resolution`] to
+    /// be set. Ignored by [`crate::extend_patch`]. Default is `0.0`, which disables collapsing.
+    pub min_chart_texels: f32,
+    /// Optional token, that stops the generation, when it is cancelled from another thread (for
+    /// example, when a user cancels a bake in an editor). The generator checks the token between
+    /// chunks of work, so it stops shortly after the cancellation and returns `None` (or
+    /// [`crate::UvGenError::Cancelled`]). Default is `None`.
+    #[cfg_attr(feature = "serde", serde(skip))]
+    pub cancellation: Option<CancellationToken>,
+    /// If `true`, the generator works in hard real-time mode, which is meant for geometry, that
+    /// is built at runtime (for example, structures built by players in a sandbox game). Every
+    /// stage has bounded complexity: charts are packed by a single pass of a greedy shelf packer,
+    /// which scale is calculated in advance, so there are no packing retries. Options, that need
+    /// unbounded amount of work, are ignored:
+    /// [`Self::max_chunk_triangles`], [`Self::pack_restarts`], [`Self::seam_bias`],
+    /// [`Self::spatial_locality`], [`Self::page_count`], [`Self::stable_anchors`],
+    /// [`Self::virtual_page_size`], [`Self::upscale_thin_charts`], [`Self::fill_atlas`],
+    /// [`Self::verify_padding`] and [`Self::max_chart_size`].
+    ///
+    /// The worst-case time is `O(n log n)` of the amount of triangles (sorting of the charts is
+    /// the only super-linear part), which is about a microsecond per triangle in release builds
+    /// on a desktop CPU. The shelf packer wastes more atlas space, so the charts get smaller
+    /// scale (lower texel density) than with the iterative packer. Default is `false`.
+    pub real_time: bool,
+    /// If `true`, wall time and amount of work of every stage of the generation are measured and
+    /// reported in [`crate::PackStats::stage_timings`]. This helps to find out, which stage
+    /// blows up on a pathological mesh, without a profiler or `trace` feature. Default is
+    /// `false`.
+    pub stage_timings: bool,
+    /// Size of a texture tile in world units. If it is greater than zero, the generator works in
+    /// tiling mode, which is meant for tiling textures (trim sheets, detail maps) rather than
+    /// lightmaps. Charts are not packed: every chart stays at its place on its projection plane,
+    /// scaled to one UV unit per `tiling_size` world units. Texture coordinates could be outside
+    /// of `[0; 1]` range and charts could overlap, the texture is expected to wrap. Charts, that
+    /// cross the borders of the tiles, are flagged in [`crate::ChartInfo::wrap`].
+    ///
+    /// Options of the atlas and of the packer are ignored in this mode:
+    /// [`Self::max_chunk_triangles`], [`Self::stable_anchors`], [`Self::page_count`],
+    /// [`Self::extra_rects`], [`Self::resolution`], [`Self::texel_snap_resolution`],
+    /// [`Self::upscale_thin_charts`], [`Self::virtual_page_size`], [`Self::verify_padding`],
+    /// [`Self::fill_atlas`] and [`Self::min_chart_texels`]. [`crate::extend_patch`] ignores this
+    /// option and packs the new charts into the free space of the atlas. Default is `0.0`
+    /// (regular packing).
+    pub tiling_size: f32,
+    /// Importance of every triangle, which is used along with its area to size the charts. Every
+    /// chart is scaled, so its area in the atlas is proportional to the total area of its
+    /// triangles multiplied by their importance, instead of the area alone. For example,
+    /// importance could be the expected variance of irradiance from a lighting pre-pass, which
+    /// gives more lightmap texels to the regions with sharp shadows without repacking the atlas
+    /// by hand. Triangles past the end of the list, as well as zero, negative and non-finite
+    /// values, have importance `1.0`. For [`crate::extend_patch`] the list refers to the new
+    /// triangles. Default is an empty list (only the area is used).
+    #[cfg_attr(feature = "serde", serde(skip))]
+    pub triangle_importance: Vec<f32>,
+}
+
+/// Defines what to do with needle triangles, see [`UvGenOptions::needle_ratio`].
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq, Hash)]
+#[cfg_attr(
+    feature = "serde",
+    derive(serde::Serialize, serde::Deserialize),
+    serde(rename_all = "snake_case")
+)]
+pub enum NeedleHandling {
+    /// Needle triangles are only reported, they're unwrapped like any other triangle.
+    #[default]
+    Keep,
+    /// Needle triangles are split off their charts and form their own charts.
+    Isolate,
+    /// Needle triangles are split off their charts and projected on their own planes, so their
+    /// longest edges are aligned with the U axis, which gives the tightest bounds.
+    Reproject,
+}
+
+/// Coordinate system of a mesh, see [`UvGenOptions::coordinate_system`]. Meshes of left-handed
+/// coordinate systems are expected to have clockwise front faces, so the cross products of their
+/// edges point outside in their own coordinates.
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq, Hash)]
+#[cfg_attr(
+    feature = "serde",
+    derive(serde::Serialize, serde::Deserialize),
+    serde(rename_all = "snake_case")
+)]
+pub enum CoordinateSystem {
+    /// Y axis points up, right-handed (for example, glTF and OpenGL). This is the coordinate
+    /// system of the generator.
+    #[default]
+    YUpRightHanded,
+    /// Y axis points up, left-handed (for example, Unity and Direct3D). Converted to the
+    /// coordinate system of the generator by flipping the Z axis.
+    YUpLeftHanded,
+    /// Z axis points up, right-handed (for example, Blender and 3ds Max). Converted to the
+    /// coordinate system of the generator by `(x, y, z) -> (x, z, -y)`.
+    ZUpRightHanded,
+    /// Z axis points up, left-handed (for example, Unreal Engine). Converted to the coordinate
+    /// system of the generator by swapping the Y and Z axes.
+    ZUpLeftHanded,
+}
+
+/// Defines how the edges of a rectangle are rounded to texel boundaries, see
+/// [`crate::TexelRect::from_rect`].
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq, Hash)]
+#[cfg_attr(
+    feature = "serde",
+    derive(serde::Serialize, serde::Deserialize),
+    serde(rename_all = "snake_case")
+)]
+pub enum TexelRounding {
+    /// The rectangle grows to the nearest texel boundaries, so it covers every texel, that is
+    /// touched by the original rectangle.
+    #[default]
+    Outward,
+    /// The rectangle shrinks to the nearest texel boundaries, so it covers only the texels, that
+    /// are entirely inside of the original rectangle.
+    Inward,
+    /// Every edge is moved to the nearest texel boundary.
+    Nearest,
+}
+
+/// Defines what to do with triangles with repeated vertex indices, see
+/// [`UvGenOptions::repeated_index_handling`].
+#[derive(Copy, Clone, Debug, Default, PartialEq)]
+#[cfg_attr(
+    feature = "serde",
+    derive(serde::Serialize, serde::Deserialize),
+    serde(rename_all = "snake_case")
+)]
+pub enum RepeatedIndexHandling {
+    /// Such triangles are unwrapped like any other triangle.
+    #[default]
+    Keep,
+    /// Such triangles do not belong to any chart and their vertices are not split. Their
+    /// vertices get texture coordinates of the other triangles, that share them.
+    Skip,
+    /// Such triangles do not belong to any chart and get their own copies of vertices with the
+    /// given texture coordinates, so they could be easily recognized.
+    Sentinel(Vector2<f32>),
+    /// The generation fails, if there's at least one such triangle.
+    Fail,
+}
+
+/// Defines what to do with back-to-back triangles, see
+/// [`UvGenOptions::double_sided_handling`].
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq, Hash)]
+#[cfg_attr(
+    feature = "serde",
+    derive(serde::Serialize, serde::Deserialize),
+    serde(rename_all = "snake_case")
+)]
+pub enum DoubleSidedHandling {
+    /// Back-to-back triangles are not detected, they're unwrapped like any other triangle.
+    #[default]
+    Ignore,
+    /// Back-to-back triangles are unwrapped like any other triangle and reported in
+    /// [`crate::PackStats::double_sided_triangles`].
+    Report,
+    /// The second triangle of every pair does not belong to any chart, it gets its own copies of
+    /// vertices with the texture coordinates of the first triangle, so both sides share the same
+    /// region of the atlas. Pairs are reported in [`crate::PackStats::double_sided_triangles`].
+    Share,
+}
+
+/// A flag, that could be shared between threads to cancel the generation, see
+/// [`UvGenOptions::cancellation`]. Clones of a token share the same flag:
+///
+/// ```rust
+/// let token = uvgen::CancellationToken::new();
+/// let options = uvgen::UvGenOptions {
+///     cancellation: Some(token.clone()),
+///     ..Default::default()
+/// };
+/// token.cancel();
+/// let mesh = uvgen::test_meshes::cube();
+/// let result = uvgen::generate_uvs_with_options(
+///     mesh.vertices.into_iter(),
+///     mesh.triangles.into_iter(),
+///     &options,
+/// );
+/// assert!(result.is_none());
+/// ```
+///
+/// Tokens are equal only if they share the same flag.
+#[derive(Clone, Debug, Default)]
+pub struct CancellationToken(Arc<AtomicBool>);
+
+impl CancellationToken {
+    /// Creates new token, that is not cancelled.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Cancels the generation, that uses this token (or any of its clones).
+    pub fn cancel(&self) {
+        self.0.store(true, Ordering::Relaxed);
+    }
+
+    /// Checks whether the token was cancelled.
+    pub fn is_cancelled(&self) -> bool {
+        self.0.load(Ordering::Relaxed)
+    }
+}
+
+impl PartialEq for CancellationToken {
+    fn eq(&self, other: &Self) -> bool {
+        Arc::ptr_eq(&self.0, &other.0)
+    }
+}
+
+/// Checks whether the generation was cancelled, see [`UvGenOptions::cancellation`].
+pub(crate) fn is_cancelled(options: &UvGenOptions) -> bool {
+    options
+        .cancellation
+        .as_ref()
+        .is_some_and(CancellationToken::is_cancelled)
+}
+
+impl Default for UvGenOptions {
+    fn default() -> Self {
+        Self {
+            spacing: 0.005,
+            chart_colors: false,
+            tiny_mesh_threshold: 0,
+            deterministic: true,
+            max_chunk_triangles: 0,
+            max_stretch: f32::INFINITY,
+            seam_bias: 0.0,
+            density: Vector3::repeat(1.0),
+            stable_anchors: false,
+            previous_layout: None,
+            adjacency: false,
+            pack_restarts: 0,
+            max_packing_iterations: 100,
+            scale_growth_factor: 1.33,
+            seed: 0,
+            texel_snap_resolution: 0,
+            local_frames: false,
+            spatial_locality: false,
+            page_count: 1,
+            extra_rects: Vec::new(),
+            resolution: 0,
+            min_chart_texel_width: 2.0,
+            upscale_thin_charts: false,
+            weld: None,
+            needle_ratio: f32::INFINITY,
+            needle_handling: NeedleHandling::Keep,
+            repeated_index_handling: RepeatedIndexHandling::Keep,
+            scale_in_lightmap: 1.0,
+            mip_padding_levels: 0,
+            double_sided_handling: DoubleSidedHandling::Ignore,
+            cylinder_unwrap: false,
+            chart_uvs: false,
+            virtual_page_size: 0,
+            texel_density: 0.0,
+            verify_padding: false,
+            locked_vertices: Vec::new(),
+            max_chart_size: f32::INFINITY,
+            texel_rounding: TexelRounding::Outward,
+            triangle_normals: Vec::new(),
+            coordinate_system: CoordinateSystem::YUpRightHanded,
+            fill_atlas: false,
+            triangle_projections: false,
+            min_chart_texels: 0.0,
+            cancellation: None,
+            real_time: false,
+            stage_timings: false,
+            tiling_size: 0.0,
+            triangle_importance: Vec::new(),
+        }
+    }
+}
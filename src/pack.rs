@@ -0,0 +1,1273 @@
+//! Packing of UV meshes into the [0;1] atlas.
+
+use crate::{parallel, rng::Rng, stats, virtual_pages, Chart, PackStats, UvGenOptions};
+use nalgebra::{Matrix3, Vector2, Vector3};
+use rectutils::Rect;
+use std::{
+    cmp::Ordering,
+    fmt::{Display, Formatter},
+};
+
+struct PackNode {
+    filled: bool,
+    split: bool,
+    bounds: Rect<f32>,
+    left: usize,
+    right: usize,
+}
+
+impl PackNode {
+    fn new(bounds: Rect<f32>) -> Self {
+        Self {
+            filled: false,
+            split: false,
+            bounds,
+            left: usize::MAX,
+            right: usize::MAX,
+        }
+    }
+}
+
+/// Rectangle packer that packs rectangles into the [0;1] square. It uses the same algorithm as
+/// `rectutils::pack::RectPacker`, but also allows to fetch the space that was left unoccupied.
+#[derive(Default)]
+pub(crate) struct AtlasPacker {
+    nodes: Vec<PackNode>,
+    unvisited: Vec<usize>,
+}
+
+impl AtlasPacker {
+    /// Clears the packer and prepares it for another run.
+    pub fn clear(&mut self) {
+        self.nodes.clear();
+        self.unvisited.clear();
+        self.nodes
+            .push(PackNode::new(Rect::new(0.0, 0.0, 1.0, 1.0)));
+    }
+
+    /// Tries to find free place to put rectangle with given size. Returns None if there
+    /// insufficient space.
+    pub fn find_free(&mut self, w: f32, h: f32) -> Option<Rect<f32>> {
+        // Negative sizes would make the packer split nodes infinitely.
+        if !(w >= 0.0 && h >= 0.0) {
+            return None;
+        }
+
+        if self.unvisited.is_empty() {
+            self.unvisited.push(0);
+        }
+
+        while let Some(node_index) = self.unvisited.pop() {
+            let node = &mut self.nodes[node_index];
+            if node.split {
+                self.unvisited.push(node.right);
+                self.unvisited.push(node.left);
+            } else if !node.filled && node.bounds.w() >= w && node.bounds.h() >= h {
+                if node.bounds.w() == w && node.bounds.h() == h {
+                    node.filled = true;
+                    return Some(node.bounds);
+                }
+
+                // Split and continue.
+                node.split = true;
+
+                let bounds = node.bounds;
+                let (left_bounds, right_bounds) = if bounds.w() - w > bounds.h() - h {
+                    (
+                        Rect::new(bounds.x(), bounds.y(), w, bounds.h()),
+                        Rect::new(bounds.x() + w, bounds.y(), bounds.w() - w, bounds.h()),
+                    )
+                } else {
+                    (
+                        Rect::new(bounds.x(), bounds.y(), bounds.w(), h),
+                        Rect::new(bounds.x(), bounds.y() + h, bounds.w(), bounds.h() - h),
+                    )
+                };
+
+                let left = self.nodes.len();
+                self.nodes.push(PackNode::new(left_bounds));
+                let right = self.nodes.len();
+                self.nodes.push(PackNode::new(right_bounds));
+
+                let node = &mut self.nodes[node_index];
+                node.left = left;
+                node.right = right;
+
+                self.unvisited.push(left);
+            }
+        }
+
+        None
+    }
+
+    /// Returns a list of rectangles that weren't occupied by anything. Rectangles with zero area
+    /// are excluded.
+    pub fn free_rects(&self) -> Vec<Rect<f32>> {
+        self.nodes
+            .iter()
+            .filter(|node| {
+                !node.split && !node.filled && node.bounds.w() > 0.0 && node.bounds.h() > 0.0
+            })
+            .map(|node| node.bounds)
+            .collect()
+    }
+}
+
+/// Result of packing a set of UV meshes into the atlas.
+#[derive(Default)]
+pub(crate) struct Packing {
+    /// Rectangles of packed meshes. Has the same order as the meshes passed to the packer. Could
+    /// contain less rectangles than meshes, if the packer wasn't able to pack everything.
+    pub rects: Vec<Rect<f32>>,
+    /// Scale that was applied to every mesh to fit it into the atlas.
+    pub scale: f32,
+    /// Rectangles of the atlas that are left unoccupied. Only the first page is described, if
+    /// there are multiple pages.
+    pub free_rects: Vec<Rect<f32>>,
+    /// Indices of atlas pages of packed meshes. Empty, if there's only one page.
+    pub pages: Vec<u32>,
+    /// Rectangles of every atlas page that are left unoccupied. Empty, if there's only one page.
+    pub page_free_rects: Vec<Vec<Rect<f32>>>,
+    /// Placements of the extra rectangles, see [`take_extra_rects`].
+    pub extra_rects: Vec<Rect<f32>>,
+    /// Reason why the packing is incomplete, if it is.
+    pub error: Option<PackingError>,
+    /// Amount of attempts of the iterative packer, that were made to find the packing.
+    pub attempts: usize,
+}
+
+impl Packing {
+    /// Returns statistics of the packing.
+    pub fn stats(
+        &self,
+        meshes: &[Chart],
+        triangles: &[[u32; 3]],
+        projections: &[[Vector2<f32>; 3]],
+        position: impl Fn(u32) -> Option<Vector3<f32>>,
+    ) -> PackStats {
+        let (area_histogram, perimeter_ratio_histogram) =
+            stats::chart_histograms(meshes, triangles, projections, self.scale);
+        PackStats {
+            scale: self.scale,
+            free_rects: self.free_rects.clone(),
+            chart_count: meshes.len(),
+            charts: stats::chart_infos(
+                meshes,
+                &self.rects,
+                triangles,
+                projections,
+                &self.pages,
+                self.scale,
+                position,
+            ),
+            area_histogram,
+            perimeter_ratio_histogram,
+            page_free_rects: self.page_free_rects.clone(),
+            extra_rects: self.extra_rects.clone(),
+            packing_error: self.error,
+            collapsed_triangles: meshes
+                .iter()
+                .filter(|mesh| mesh.collapsed)
+                .flat_map(|mesh| mesh.triangles.iter().cloned())
+                .collect(),
+            ..Default::default()
+        }
+    }
+
+    /// Checks whether every mesh got its place in the atlas.
+    pub fn is_complete(&self, meshes: &[Chart]) -> bool {
+        self.rects.len() == meshes.len()
+    }
+
+    /// Calculates final UV coordinates of a projected vertex of the given mesh, that was packed
+    /// into the given rectangle.
+    pub fn map(
+        &self,
+        mesh: &Chart,
+        rect: &Rect<f32>,
+        projection: Vector2<f32>,
+        spacing: f32,
+    ) -> Vector2<f32> {
+        (projection - mesh.uv_min).scale(self.scale)
+            + Vector2::new(spacing, spacing)
+            + rect.position
+    }
+}
+
+/// Adds placeholder charts for the extra rectangles (see [`UvGenOptions::extra_rects`]) to the
+/// given meshes. Returns `true` if anything was added, meshes must be sorted again in this case.
+pub(crate) fn add_extra_charts(meshes: &mut Vec<Chart>, options: &UvGenOptions) -> bool {
+    meshes.extend(
+        options
+            .extra_rects
+            .iter()
+            .enumerate()
+            .map(|(index, size)| Chart {
+                triangles: Vec::new(),
+                uv_max: size.sup(&Vector2::zeros()),
+                uv_min: Vector2::zeros(),
+                extra_rect: Some(index),
+                collapsed: false,
+            }),
+    );
+    !options.extra_rects.is_empty()
+}
+
+/// Reorders the packed meshes (along with their rectangles, pages and hashes, if any) by their
+/// keys (see [`crate::ChartInfo::key`]), so the order of the charts in the patch does not depend
+/// on the packing order. Meshes, that were not packed, stay at the end. Extra rectangles must be
+/// taken already.
+pub(crate) fn sort_by_key(meshes: &mut Vec<Chart>, hashes: &mut Vec<u64>, packing: &mut Packing) {
+    let packed = packing.rects.len().min(meshes.len());
+    let mut order = (0..packed).collect::<Vec<_>>();
+    order.sort_by_key(|&mesh_index| meshes[mesh_index].key());
+    fn permute<T: Clone>(items: &mut Vec<T>, order: &[usize]) {
+        if items.len() >= order.len() {
+            let sorted = order.iter().map(|&i| items[i].clone()).collect::<Vec<_>>();
+            items.splice(..order.len(), sorted);
+        }
+    }
+    permute(meshes, &order);
+    permute(hashes, &order);
+    permute(&mut packing.rects, &order);
+    permute(&mut packing.pages, &order);
+}
+
+/// Uniformly scales the packed meshes up (see [`UvGenOptions::fill_atlas`]), so their tight
+/// bounds fill the atlas, except half-texel margins at [`UvGenOptions::resolution`]. Absolute
+/// spacing stays the same, so the gaps between the meshes only grow. Extra rectangles are moved
+/// and scaled along with the meshes. Free rectangles are moved and scaled too, they are clipped by
+/// the atlas. Does nothing if the meshes can't be scaled up. Extra rectangles must be taken
+/// already.
+pub(crate) fn fill_atlas(meshes: &[Chart], packing: &mut Packing, options: &UvGenOptions) {
+    if !options.fill_atlas || virtual_pages::page_extent(options).is_some() {
+        return;
+    }
+    let spacing = Vector2::repeat(options.spacing);
+    let (mut min, mut max) = (Vector2::repeat(f32::MAX), Vector2::repeat(-f32::MAX));
+    for (mesh, rect) in meshes.iter().zip(packing.rects.iter()) {
+        let origin = rect.position + spacing;
+        min = min.inf(&origin);
+        max = max.sup(&(origin + (mesh.uv_max - mesh.uv_min).scale(packing.scale)));
+    }
+    for rect in packing.extra_rects.iter() {
+        min = min.inf(&rect.position);
+        max = max.sup(&(rect.position + rect.size));
+    }
+    let margin = if options.resolution > 0 {
+        0.5 / options.resolution as f32
+    } else {
+        0.0
+    };
+    let size = max - min;
+    let factor = ((1.0 - 2.0 * margin) / size.x).min((1.0 - 2.0 * margin) / size.y);
+    // Infinite and NaN factors are skipped too.
+    if !(factor > 1.0 && factor.is_finite()) {
+        return;
+    }
+
+    let map = |point: Vector2<f32>| (point - min).scale(factor) + Vector2::repeat(margin);
+    for rect in packing.rects.iter_mut() {
+        *rect = Rect {
+            position: map(rect.position + spacing) - spacing,
+            size: (rect.size - spacing.scale(2.0)).scale(factor) + spacing.scale(2.0),
+        };
+    }
+    for rect in packing.extra_rects.iter_mut() {
+        *rect = Rect {
+            position: map(rect.position),
+            size: rect.size.scale(factor),
+        };
+    }
+    let clip = |rects: &mut Vec<Rect<f32>>| {
+        *rects = rects
+            .iter()
+            .filter_map(|rect| {
+                let position = map(rect.position).sup(&Vector2::zeros());
+                let end = (map(rect.position) + rect.size.scale(factor)).inf(&Vector2::repeat(1.0));
+                (end.x > position.x && end.y > position.y).then(|| Rect {
+                    position,
+                    size: end - position,
+                })
+            })
+            .collect()
+    };
+    clip(&mut packing.free_rects);
+    for rects in packing.page_free_rects.iter_mut() {
+        clip(rects);
+    }
+    packing.scale *= factor;
+}
+
+/// Removes placeholder charts of the extra rectangles from the packed meshes (and their hashes,
+/// if any) and moves their placements (without spacing) to [`Packing::extra_rects`].
+pub(crate) fn take_extra_rects(
+    meshes: &mut Vec<Chart>,
+    hashes: &mut Vec<u64>,
+    packing: &mut Packing,
+    spacing: f32,
+) {
+    let extra = meshes
+        .iter()
+        .map(|mesh| mesh.extra_rect)
+        .collect::<Vec<_>>();
+    if extra.iter().all(Option::is_none) {
+        return;
+    }
+
+    let mut extra_rects = extra
+        .iter()
+        .zip(meshes.iter().zip(packing.rects.iter()))
+        .filter_map(|(extra_rect, (mesh, rect))| {
+            let rect = Rect::new(
+                rect.x() + spacing,
+                rect.y() + spacing,
+                mesh.width() * packing.scale,
+                mesh.height() * packing.scale,
+            );
+            extra_rect.map(|index| (index, rect))
+        })
+        .collect::<Vec<_>>();
+    extra_rects.sort_unstable_by_key(|(index, _)| *index);
+    packing.extra_rects = extra_rects.into_iter().map(|(_, rect)| rect).collect();
+
+    let mut extra_iter = extra.iter();
+    meshes.retain(|_| extra_iter.next().map_or(true, Option::is_none));
+    let mut extra_iter = extra.iter();
+    packing
+        .rects
+        .retain(|_| extra_iter.next().map_or(true, Option::is_none));
+    let mut extra_iter = extra.iter();
+    packing
+        .pages
+        .retain(|_| extra_iter.next().map_or(true, Option::is_none));
+    let mut extra_iter = extra.iter();
+    hashes.retain(|_| extra_iter.next().map_or(true, Option::is_none));
+}
+
+/// Maximum amount of additional packing passes made to up-scale thin charts.
+pub(crate) const MAX_UPSCALE_PASSES: usize = 4;
+
+/// Stretches the charts, that are thinner than [`UvGenOptions::min_chart_texel_width`] texels with
+/// the given scale, along their thin axis. Returns `true` if any chart was changed, meshes must
+/// be sorted and packed again in this case.
+pub(crate) fn upscale_thin_charts(
+    meshes: &mut [Chart],
+    projections: &mut [[Vector2<f32>; 3]],
+    scale: f32,
+    options: &UvGenOptions,
+) -> bool {
+    if !options.upscale_thin_charts || options.resolution == 0 {
+        return false;
+    }
+
+    let texels = scale * options.resolution as f32;
+    let mut changed = false;
+    for mesh in meshes
+        .iter_mut()
+        .filter(|mesh| mesh.extra_rect.is_none() && !mesh.collapsed)
+    {
+        let width = mesh.width() * texels;
+        let height = mesh.height() * texels;
+        let thin = width.min(height);
+        if thin <= 0.0 || thin >= options.min_chart_texel_width {
+            continue;
+        }
+        // Scale of the next packing is slightly smaller, compensate it with a small margin.
+        let factor = 1.1 * options.min_chart_texel_width / thin;
+        let stretch = if width < height {
+            Vector2::new(factor, 1.0)
+        } else {
+            Vector2::new(1.0, factor)
+        };
+        let origin = mesh.uv_min();
+        let transform = Matrix3::new_translation(&origin)
+            * Matrix3::new_nonuniform_scaling(&stretch)
+            * Matrix3::new_translation(&-origin);
+        mesh.transform(projections, &transform);
+        changed = true;
+    }
+    changed
+}
+
+/// Merges the charts, which both sides are smaller than [`UvGenOptions::min_chart_texels`] texels
+/// with the given scale, into a single chart (or into the existing chart of the collapsed charts).
+/// Every triangle of the merged chart is projected into the center of a square, that occupies one
+/// texel with the given scale. Returns `true` if any chart was merged, meshes must be sorted and
+/// packed again in this case.
+pub(crate) fn collapse_tiny_charts(
+    meshes: &mut Vec<Chart>,
+    projections: &mut [[Vector2<f32>; 3]],
+    scale: f32,
+    options: &UvGenOptions,
+) -> bool {
+    if options.min_chart_texels <= 0.0 || options.resolution == 0 {
+        return false;
+    }
+    let texels = scale * options.resolution as f32;
+    let texel = 1.0 / texels;
+    if !(texel > 0.0 && texel.is_finite()) {
+        return false;
+    }
+    let is_tiny = |mesh: &Chart| {
+        mesh.extra_rect.is_none()
+            && !mesh.collapsed
+            && mesh.width().max(mesh.height()) * texels < options.min_chart_texels
+    };
+    if !meshes.iter().any(is_tiny) {
+        return false;
+    }
+
+    let mut collapsed = match meshes.iter().position(|mesh| mesh.collapsed) {
+        Some(index) => meshes.swap_remove(index),
+        None => Chart {
+            triangles: Vec::new(),
+            uv_max: Vector2::zeros(),
+            uv_min: Vector2::zeros(),
+            extra_rect: None,
+            collapsed: true,
+        },
+    };
+    let mut kept = Vec::with_capacity(meshes.len());
+    for mesh in meshes.drain(..) {
+        if is_tiny(&mesh) {
+            collapsed.triangles.extend(mesh.triangles);
+        } else {
+            kept.push(mesh);
+        }
+    }
+    collapsed.triangles.sort_unstable();
+    let center = Vector2::repeat(0.5 * texel);
+    for &triangle_index in collapsed.triangles.iter() {
+        if let Some(projection) = projections.get_mut(triangle_index) {
+            *projection = [center; 3];
+        }
+    }
+    collapsed.uv_min = Vector2::zeros();
+    collapsed.uv_max = Vector2::repeat(texel);
+    kept.push(collapsed);
+    *meshes = kept;
+    true
+}
+
+/// Sorts meshes by their area (largest first), this order gives the best results for the packer.
+pub(crate) fn sort_meshes(meshes: &mut [Chart]) {
+    meshes.sort_unstable_by(|a, b| b.area().total_cmp(&a.area()));
+}
+
+/// Reason why charts could not be packed into the atlas.
+#[derive(Copy, Clone, Debug, PartialEq)]
+#[cfg_attr(
+    feature = "rkyv",
+    derive(rkyv::Archive, rkyv::Serialize, rkyv::Deserialize),
+    archive(check_bytes)
+)]
+pub enum PackingError {
+    /// Spacing between charts is too large for the amount of charts: the packer wasn't able to
+    /// fit charts even when they were scaled down to a fraction of the spacing, so further
+    /// reduction of the scale won't help. `max_spacing` is an estimation of the largest spacing,
+    /// at which the charts of this mesh could fit.
+    SpacingTooLarge {
+        /// Spacing that was requested.
+        spacing: f32,
+        /// Suggested maximum spacing for this mesh.
+        max_spacing: f32,
+    },
+    /// The packer wasn't able to find a scale at which every chart fits after the given amount of
+    /// attempts.
+    Exhausted {
+        /// Amount of packing attempts.
+        attempts: usize,
+    },
+}
+
+impl Display for PackingError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::SpacingTooLarge {
+                spacing,
+                max_spacing,
+            } => write!(
+                f,
+                "spacing {spacing} is too large, charts fit only with spacing below {max_spacing}"
+            ),
+            Self::Exhausted { attempts } => {
+                write!(
+                    f,
+                    "charts did not fit into the atlas after {attempts} attempts"
+                )
+            }
+        }
+    }
+}
+
+impl std::error::Error for PackingError {}
+
+/// Checks whether the given amount of charts could fit into the atlas with the given spacing.
+/// Every chart occupies at least `2 * spacing` by `2 * spacing` square, so the charts can't be
+/// packed at any scale if these squares alone cover the entire atlas.
+pub(crate) fn validate_spacing(chart_count: usize, spacing: f32) -> Result<(), PackingError> {
+    let twice_spacing = 2.0 * spacing;
+    if chart_count == 0 || (chart_count as f32) * twice_spacing * twice_spacing < 1.0 {
+        return Ok(());
+    }
+    // Squares of this size could be arranged in a grid, that fits into the atlas.
+    let side = (chart_count as f32).sqrt().ceil();
+    Err(PackingError::SpacingTooLarge {
+        spacing,
+        max_spacing: 0.5 / side,
+    })
+}
+
+/// Checks whether every mesh got its place in the atlas. Packers give up with an error in
+/// [`Packing::error`], the packing is also treated as exhausted if some meshes are missing
+/// without one.
+pub(crate) fn validate_packing(packing: &Packing, meshes: &[Chart]) -> Result<(), PackingError> {
+    match packing.error {
+        Some(error) => Err(error),
+        None if !packing.is_complete(meshes) => Err(PackingError::Exhausted {
+            attempts: packing.attempts,
+        }),
+        None => Ok(()),
+    }
+}
+
+/// Returns the factor, by which the scale of the charts changes after a failed packing attempt,
+/// see [`UvGenOptions::scale_growth_factor`]. Smaller factors would need too many attempts.
+pub(crate) fn scale_growth_factor(options: &UvGenOptions) -> f32 {
+    let factor = options.scale_growth_factor;
+    if factor.is_finite() {
+        factor.max(1.01)
+    } else {
+        UvGenOptions::default().scale_growth_factor
+    }
+}
+
+/// Result of a single packing attempt.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub(crate) enum PackAttempt {
+    /// Every mesh was packed.
+    Packed,
+    /// Some meshes weren't packed, another attempt with smaller scale is needed.
+    Retry,
+    /// Maximum amount of attempts was reached or the packing can't succeed at any scale, the
+    /// reason is stored in [`Packing::error`].
+    Exhausted,
+}
+
+/// Arranges and scales all meshes on uv map so it fits into [0;1] range. Packing is done
+/// iteratively, one attempt at a time: the scale is decreased until every mesh fits and then it
+/// is refined by a binary search between the last failed and the first successful attempts.
+/// Meshes must be sorted by [`sort_meshes`] first.
+pub(crate) struct MeshPacker {
+    square_side: f32,
+    spacing: f32,
+    empiric_scale: f32,
+    /// The largest empiric scale, at which the packing is known to fail (or can't succeed at
+    /// all, see [`min_empiric_scale`]).
+    failed_scale: f32,
+    /// The best successful packing so far along with its empiric scale.
+    best: Option<(f32, Packing)>,
+    refinements: usize,
+    scale: f32,
+    iteration: usize,
+    rects: Vec<Rect<f32>>,
+    packer: AtlasPacker,
+    max_extent: f32,
+    mesh_count: usize,
+    error: Option<PackingError>,
+    /// Maximum amount of packing attempts, see [`UvGenOptions::max_packing_iterations`].
+    max_iterations: usize,
+    growth_factor: f32,
+}
+
+impl MeshPacker {
+    /// Maximum amount of attempts of the binary search after the first successful attempt.
+    const MAX_REFINEMENTS: usize = 8;
+
+    /// The binary search stops, when the successful scale is within this ratio of the failed one.
+    const TIGHTNESS: f32 = 1.01;
+
+    /// Packing is considered hopeless, if it fails when the largest chart is smaller than this
+    /// fraction of the spacing around it.
+    const SPACING_DOMINANCE: f32 = 0.01;
+
+    pub fn new(meshes: &[Chart], options: &UvGenOptions) -> Self {
+        let spacing = options.spacing;
+        let chunk_len = parallel::chunk_len(meshes.len(), options.deterministic);
+        let area = parallel::map_chunks(meshes, chunk_len, |_, meshes| {
+            meshes.iter().fold(0.0, |area, mesh| area + mesh.area())
+        })
+        .into_iter()
+        .fold(0.0, |area, chunk_area| area + chunk_area);
+        // Some empiric coefficient that large enough to make size big enough for all meshes.
+        // This should be large enough to fit all meshes, but small to prevent losing of space.
+        // We'll use iterative approach to pack everything as tight as possible: at each
+        // iteration scale will be increased until packer is able to pack everything.
+        let empiric_scale = 1.1;
+        // The largest mesh must fit into the atlas at the first attempt, otherwise extremely
+        // elongated meshes (which area is tiny compared to their size) need lots of attempts to
+        // reach the scale at which they fit.
+        let max_extent = meshes.iter().fold(0.0f32, |extent, mesh| {
+            extent.max(mesh.width()).max(mesh.height())
+        });
+        let min_square_side =
+            max_extent / (empiric_scale * (1.0 - 2.0 * spacing).max(f32::EPSILON));
+        let square_side = (area.sqrt() + spacing * meshes.len() as f32).max(min_square_side);
+        let min_empiric_scale = min_empiric_scale(meshes, area, max_extent, spacing, square_side);
+        Self {
+            square_side,
+            spacing,
+            empiric_scale,
+            failed_scale: min_empiric_scale,
+            best: None,
+            refinements: 0,
+            scale: 1.0,
+            iteration: 0,
+            rects: Default::default(),
+            packer: Default::default(),
+            max_extent,
+            mesh_count: meshes.len(),
+            error: validate_spacing(meshes.len(), spacing).err(),
+            max_iterations: options.max_packing_iterations.max(1),
+            growth_factor: scale_growth_factor(options),
+        }
+    }
+
+    /// Returns the fraction of the maximal amount of packing attempts, that were made already.
+    pub fn progress(&self) -> f32 {
+        self.iteration as f32 / self.max_iterations as f32
+    }
+
+    /// Makes a single packing attempt.
+    pub fn try_pack<'a>(&mut self, meshes: impl IntoIterator<Item = &'a Chart>) -> PackAttempt {
+        if self.error.is_some() {
+            return PackAttempt::Exhausted;
+        }
+        if self.iteration >= self.max_iterations {
+            if self.best.is_some() {
+                return self.refine();
+            }
+            self.error = Some(PackingError::Exhausted {
+                attempts: self.iteration,
+            });
+            return PackAttempt::Exhausted;
+        }
+
+        self.iteration += 1;
+
+        self.rects.clear();
+
+        // Calculate size of atlas for packer, we'll scale it later on.
+        let scale = 1.0 / (self.square_side * self.empiric_scale);
+        self.scale = scale;
+
+        let twice_spacing = self.spacing * 2.0;
+
+        // We'll pack into 1.0 square, our UVs must be in [0;1] range, no wrapping is allowed.
+        self.packer.clear();
+        for mesh in meshes {
+            if let Some(rect) = self.packer.find_free(
+                mesh.width() * scale + twice_spacing,
+                mesh.height() * scale + twice_spacing,
+            ) {
+                self.rects.push(rect);
+            } else {
+                trace_event!(
+                    iteration = self.iteration - 1,
+                    scale,
+                    success = false,
+                    "packing iteration"
+                );
+
+                self.failed_scale = self.empiric_scale;
+                if self.best.is_some() {
+                    return self.refine();
+                }
+
+                // When charts are tiny compared to the spacing, rectangles consist mostly of the
+                // spacing and smaller scale won't make them noticeably smaller.
+                if self.max_extent * scale < Self::SPACING_DOMINANCE * twice_spacing {
+                    // Only a fraction of rectangles fit, so the spacing must be reduced at least
+                    // proportionally to the square root of the fraction.
+                    let fraction = self.rects.len() as f32 / self.mesh_count.max(1) as f32;
+                    self.error = Some(PackingError::SpacingTooLarge {
+                        spacing: self.spacing,
+                        max_spacing: self.spacing * fraction.sqrt(),
+                    });
+                    return PackAttempt::Exhausted;
+                }
+
+                // I don't know how to pass this by without iterative approach :(
+                self.empiric_scale *= self.growth_factor;
+                return PackAttempt::Retry;
+            }
+        }
+
+        trace_event!(
+            iteration = self.iteration - 1,
+            scale,
+            success = true,
+            "packing iteration"
+        );
+
+        let packing = Packing {
+            rects: std::mem::take(&mut self.rects),
+            scale,
+            free_rects: self.packer.free_rects(),
+            ..Default::default()
+        };
+        if let Some((_, previous)) = self.best.replace((self.empiric_scale, packing)) {
+            // Reuse the buffer of the previous packing.
+            self.rects = previous.rects;
+        }
+        self.refine()
+    }
+
+    /// Schedules the next attempt of the binary search between the best successful and the last
+    /// failed empiric scales, or finishes the packing, if the best one is tight enough.
+    fn refine(&mut self) -> PackAttempt {
+        let Some(best_scale) = self.best.as_ref().map(|(best_scale, _)| *best_scale) else {
+            return PackAttempt::Exhausted;
+        };
+        if self.refinements < Self::MAX_REFINEMENTS
+            && self.iteration < self.max_iterations
+            && self.failed_scale > 0.0
+            && best_scale / self.failed_scale > Self::TIGHTNESS
+        {
+            self.refinements += 1;
+            self.empiric_scale = (best_scale * self.failed_scale).sqrt();
+            return PackAttempt::Retry;
+        }
+
+        trace_event!(
+            iterations = self.iteration,
+            scale = 1.0 / (self.square_side * best_scale),
+            "packed"
+        );
+        PackAttempt::Packed
+    }
+
+    /// Makes the packer use the given buffers (of some other packer) instead of allocating its
+    /// own ones.
+    pub fn with_buffers(mut self, packer: AtlasPacker, mut rects: Vec<Rect<f32>>) -> Self {
+        rects.clear();
+        self.packer = packer;
+        self.rects = rects;
+        self
+    }
+
+    pub fn finish(self) -> Packing {
+        self.finish_with_buffers().0
+    }
+
+    /// Finishes the packing like [`Self::finish`], but also returns the rectangle packer, so its
+    /// buffers could be reused.
+    pub fn finish_with_buffers(self) -> (Packing, AtlasPacker) {
+        let packing = match self.best {
+            Some((_, packing)) => packing,
+            None => Packing {
+                free_rects: self.packer.free_rects(),
+                rects: self.rects,
+                scale: self.scale,
+                error: self.error,
+                ..Default::default()
+            },
+        };
+        let packing = Packing {
+            attempts: self.iteration,
+            ..packing
+        };
+        (packing, self.packer)
+    }
+}
+
+/// Returns the smallest empiric scale (see [`MeshPacker`]), at which the meshes could fit into
+/// the atlas at all: their rectangles (with spacing) can't be larger than the atlas in total and
+/// the largest mesh must fit into it. Returns zero, if there's no such bound.
+fn min_empiric_scale(
+    meshes: &[Chart],
+    area: f32,
+    max_extent: f32,
+    spacing: f32,
+    square_side: f32,
+) -> f32 {
+    let twice_spacing = 2.0 * spacing;
+    let perimeter = meshes
+        .iter()
+        .map(|mesh| mesh.width().max(0.0) + mesh.height().max(0.0))
+        .sum::<f32>();
+    // Total area of the rectangles is `area * scale^2 + b * scale + c + 1`.
+    let b = twice_spacing * perimeter;
+    let c = meshes.len() as f32 * twice_spacing * twice_spacing - 1.0;
+    let mut max_scale = if area > 0.0 {
+        (-b + (b * b - 4.0 * area * c).sqrt()) / (2.0 * area)
+    } else if b > 0.0 {
+        -c / b
+    } else {
+        f32::INFINITY
+    };
+    if max_extent > 0.0 {
+        max_scale = max_scale.min((1.0 - twice_spacing) / max_extent);
+    }
+    let min_empiric_scale = 1.0 / (square_side * max_scale);
+    if min_empiric_scale.is_finite() && min_empiric_scale > 0.0 {
+        min_empiric_scale
+    } else {
+        0.0
+    }
+}
+
+/// Arranges meshes in a uniform grid, where every mesh occupies a single cell. All meshes are
+/// scaled uniformly so the largest one fits into its cell. Returns `None` if the spacing is too
+/// large for the cells.
+pub(crate) fn grid_pack(meshes: &[Chart], spacing: f32) -> Option<Packing> {
+    let columns = (meshes.len() as f32).sqrt().ceil().max(1.0) as usize;
+    let rows = (meshes.len() + columns - 1) / columns;
+    let cell = 1.0 / columns as f32;
+    let twice_spacing = 2.0 * spacing;
+    if cell <= twice_spacing {
+        return None;
+    }
+
+    let max_extent = meshes.iter().fold(0.0f32, |extent, mesh| {
+        extent.max(mesh.width()).max(mesh.height())
+    });
+    let scale = if max_extent > 0.0 {
+        (cell - twice_spacing) / max_extent
+    } else {
+        1.0
+    };
+
+    let mut rects = Vec::with_capacity(meshes.len());
+    let mut free_rects = Vec::new();
+    for row in 0..rows {
+        for column in 0..columns {
+            let position = Vector2::new(column as f32 * cell, row as f32 * cell);
+            if let Some(mesh) = meshes.get(row * columns + column) {
+                let w = (mesh.width() * scale + twice_spacing).min(cell);
+                let h = (mesh.height() * scale + twice_spacing).min(cell);
+                rects.push(Rect::new(position.x, position.y, w, h));
+                free_rects.push(Rect::new(position.x + w, position.y, cell - w, h));
+                free_rects.push(Rect::new(position.x, position.y + h, cell, cell - h));
+            } else {
+                free_rects.push(Rect::new(position.x, position.y, cell, cell));
+            }
+        }
+    }
+    let grid_height = rows as f32 * cell;
+    free_rects.push(Rect::new(0.0, grid_height, 1.0, 1.0 - grid_height));
+    free_rects.retain(|rect| rect.w() > 0.0 && rect.h() > 0.0);
+
+    Some(Packing {
+        rects,
+        scale,
+        free_rects,
+        ..Default::default()
+    })
+}
+
+/// Packs meshes with the given scale into the [0;1] square, using the same algorithm as
+/// [`MeshPacker`]. Returns `None` if some mesh does not fit.
+pub(crate) fn pack_with_scale<'a>(
+    meshes: impl IntoIterator<Item = &'a Chart>,
+    scale: f32,
+    spacing: f32,
+) -> Option<Packing> {
+    let twice_spacing = 2.0 * spacing;
+    let mut packer = AtlasPacker::default();
+    packer.clear();
+    let rects = meshes
+        .into_iter()
+        .map(|mesh| {
+            packer.find_free(
+                mesh.width() * scale + twice_spacing,
+                mesh.height() * scale + twice_spacing,
+            )
+        })
+        .collect::<Option<Vec<_>>>()?;
+    Some(Packing {
+        rects,
+        scale,
+        free_rects: packer.free_rects(),
+        ..Default::default()
+    })
+}
+
+/// Packs meshes with the given scale into the given free rectangles of an atlas. Every mesh is
+/// put into the smallest rectangle it fits into and the rest of the rectangle is split in two
+/// (the same way as [`AtlasPacker`] does it). Returns `None` if some mesh does not fit. Meshes
+/// must be sorted by [`sort_meshes`] first.
+pub(crate) fn pack_into_free_rects<'a>(
+    meshes: impl IntoIterator<Item = &'a Chart>,
+    free_rects: &[Rect<f32>],
+    scale: f32,
+    spacing: f32,
+) -> Option<Packing> {
+    let twice_spacing = 2.0 * spacing;
+    let mut free_rects = free_rects.to_vec();
+    let mut rects = Vec::new();
+    for mesh in meshes {
+        let w = mesh.width() * scale + twice_spacing;
+        let h = mesh.height() * scale + twice_spacing;
+        let (index, _) = free_rects
+            .iter()
+            .enumerate()
+            .filter(|(_, rect)| rect.w() >= w && rect.h() >= h)
+            .min_by(|(_, a), (_, b)| {
+                (a.w() * a.h())
+                    .partial_cmp(&(b.w() * b.h()))
+                    .unwrap_or(Ordering::Equal)
+            })?;
+        let bounds = free_rects.remove(index);
+        rects.push(Rect::new(bounds.x(), bounds.y(), w, h));
+        let (first, second) = if bounds.w() - w > bounds.h() - h {
+            (
+                Rect::new(bounds.x(), bounds.y() + h, w, bounds.h() - h),
+                Rect::new(bounds.x() + w, bounds.y(), bounds.w() - w, bounds.h()),
+            )
+        } else {
+            (
+                Rect::new(bounds.x() + w, bounds.y(), bounds.w() - w, h),
+                Rect::new(bounds.x(), bounds.y() + h, bounds.w(), bounds.h() - h),
+            )
+        };
+        free_rects.extend(
+            [first, second]
+                .into_iter()
+                .filter(|rect| rect.w() > 0.0 && rect.h() > 0.0),
+        );
+    }
+
+    Some(Packing {
+        rects,
+        scale,
+        free_rects,
+        ..Default::default()
+    })
+}
+
+/// Makes [`UvGenOptions::pack_restarts`] additional packing attempts with slightly shuffled order
+/// of meshes and returns the packing with the largest scale.
+pub(crate) fn refine(meshes: &[Chart], packing: Packing, options: &UvGenOptions) -> Packing {
+    if options.pack_restarts == 0 || meshes.len() < 2 || !packing.is_complete(meshes) {
+        return packing;
+    }
+
+    trace_span!("refine");
+
+    let mut rng = Rng::new(options.seed);
+    let mut attempts = packing.attempts;
+    let mut best = packing;
+    let mut order = (0..meshes.len()).collect::<Vec<_>>();
+    for _ in 0..options.pack_restarts {
+        // Large meshes should still go first, so swap only neighbouring meshes.
+        order.clear();
+        order.extend(0..meshes.len());
+        for _ in 0..(meshes.len() / 4).max(1) {
+            let i = rng.below(meshes.len() - 1);
+            order.swap(i, i + 1);
+        }
+
+        let mut packer = MeshPacker::new(meshes, options);
+        while packer.try_pack(order.iter().map(|&i| &meshes[i])) == PackAttempt::Retry {}
+        let candidate = packer.finish();
+        attempts += candidate.attempts;
+        if candidate.rects.len() == meshes.len() && candidate.scale > best.scale {
+            let mut rects = vec![Rect::default(); meshes.len()];
+            for (&mesh_index, rect) in order.iter().zip(candidate.rects) {
+                rects[mesh_index] = rect;
+            }
+            best = Packing { rects, ..candidate };
+        }
+    }
+    Packing { attempts, ..best }
+}
+
+/// Arranges and scales all meshes on uv map so it fits into [0;1] range. Meshes must be sorted
+/// by [`sort_meshes`] first.
+pub(crate) fn pack_meshes(meshes: &[Chart], options: &UvGenOptions) -> Packing {
+    trace_span!("packing");
+
+    let mut packer = MeshPacker::new(meshes, options);
+    while packer.try_pack(meshes) == PackAttempt::Retry {}
+    refine(meshes, packer.finish(), options)
+}
+
+#[cfg(test)]
+mod test {
+    use super::AtlasPacker;
+    use crate::UvGenOptions;
+    use nalgebra::{Vector2, Vector3};
+
+    #[test]
+    fn test_free_rects() {
+        let mut packer = AtlasPacker::default();
+        packer.clear();
+
+        let rects = [(0.5, 0.5), (0.25, 0.5), (0.3, 0.1)]
+            .iter()
+            .map(|&(w, h)| packer.find_free(w, h).unwrap())
+            .collect::<Vec<_>>();
+
+        let free_rects = packer.free_rects();
+        for free_rect in free_rects.iter() {
+            for rect in rects.iter() {
+                let overlap_w = (free_rect.x() + free_rect.w()).min(rect.x() + rect.w())
+                    - free_rect.x().max(rect.x());
+                let overlap_h = (free_rect.y() + free_rect.h()).min(rect.y() + rect.h())
+                    - free_rect.y().max(rect.y());
+                assert!(overlap_w <= 0.0 || overlap_h <= 0.0);
+            }
+        }
+
+        let total_area = rects
+            .iter()
+            .chain(free_rects.iter())
+            .map(|r| r.w() * r.h())
+            .sum::<f32>();
+        assert!((total_area - 1.0).abs() < 1.0e-6);
+    }
+
+    #[test]
+    fn test_extra_rects() {
+        let vertices = [
+            Vector3::new(0.0, 0.0, 0.0),
+            Vector3::new(1.0, 0.0, 0.0),
+            Vector3::new(1.0, 1.0, 0.0),
+            Vector3::new(0.0, 1.0, 0.0),
+        ];
+        let patch = crate::generate_uvs_with_options(
+            vertices.iter().cloned(),
+            [[0u32, 1, 2], [0, 2, 3]].into_iter(),
+            &UvGenOptions {
+                extra_rects: vec![Vector2::new(2.0, 0.5), Vector2::new(0.5, 0.5)],
+                ..Default::default()
+            },
+        )
+        .unwrap();
+
+        let stats = &patch.pack_stats;
+        assert_eq!(stats.chart_count, 1);
+        assert_eq!(stats.extra_rects.len(), 2);
+        for (rect, size) in stats
+            .extra_rects
+            .iter()
+            .zip([Vector2::new(2.0, 0.5), Vector2::new(0.5, 0.5)])
+        {
+            assert!((rect.w() - size.x * stats.scale).abs() < 1.0e-5);
+            assert!((rect.h() - size.y * stats.scale).abs() < 1.0e-5);
+            assert!(rect.x() >= 0.0 && rect.x() + rect.w() <= 1.0);
+            assert!(rect.y() >= 0.0 && rect.y() + rect.h() <= 1.0);
+            // Must not overlap with the chart.
+            for uv in patch.second_tex_coords.iter() {
+                assert!(
+                    uv.x <= rect.x()
+                        || uv.x >= rect.x() + rect.w()
+                        || uv.y <= rect.y()
+                        || uv.y >= rect.y() + rect.h()
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn test_elongated_mesh_packing() {
+        // A chart, which size is enormous compared to its area, must be packed at once.
+        let projections = [[
+            Vector2::new(0.0, 0.0),
+            Vector2::new(1.0e6, 0.0),
+            Vector2::new(1.0e6, 1.0e-3),
+        ]];
+        let meshes = [crate::Chart::from_triangles(vec![0], &projections)];
+        let mut packer = super::MeshPacker::new(&meshes, &UvGenOptions::default());
+        assert_eq!(packer.try_pack(&meshes), super::PackAttempt::Packed);
+    }
+
+    #[test]
+    fn test_spacing_too_large() {
+        // 64 charts with 0.0624 spacing need almost the entire atlas just for the spacing.
+        let projections = vec![
+            [
+                Vector2::new(0.0, 0.0),
+                Vector2::new(1.0, 0.0),
+                Vector2::new(0.0, 1.0),
+            ];
+            64
+        ];
+        let meshes = (0..projections.len())
+            .map(|i| crate::Chart::from_triangles(vec![i], &projections))
+            .collect::<Vec<_>>();
+        let options = UvGenOptions {
+            spacing: 0.0624,
+            ..Default::default()
+        };
+        let mut packer = super::MeshPacker::new(&meshes, &options);
+        let mut attempts = 1;
+        while packer.try_pack(&meshes) == super::PackAttempt::Retry {
+            attempts += 1;
+        }
+        assert!(attempts < options.max_packing_iterations);
+        let packing = packer.finish();
+        assert!(!packing.is_complete(&meshes));
+        let Some(super::PackingError::SpacingTooLarge { max_spacing, .. }) = packing.error else {
+            panic!("unexpected error {:?}", packing.error);
+        };
+        assert!(max_spacing < 0.0624 && max_spacing > 0.0);
+
+        // Charts must fit with the suggested spacing.
+        let options = UvGenOptions {
+            spacing: max_spacing * 0.9,
+            ..Default::default()
+        };
+        let packing = super::pack_meshes(&meshes, &options);
+        assert!(packing.is_complete(&meshes));
+        assert_eq!(packing.error, None);
+    }
+
+    #[test]
+    fn test_packing_tunables() {
+        let mesh = crate::test_meshes::uv_sphere(16, 8);
+        let mut task = crate::UnwrapTask::new(
+            mesh.vertices.iter().cloned(),
+            mesh.triangles.iter().cloned(),
+            UvGenOptions::default(),
+        );
+        assert!(task.run_until(crate::Stage::Packing));
+        let mut meshes = task.into_charts().meshes;
+        super::sort_meshes(&mut meshes);
+        let pack = |options: &UvGenOptions| {
+            let mut packer = super::MeshPacker::new(&meshes, options);
+            while packer.try_pack(&meshes) == super::PackAttempt::Retry {}
+            packer.finish()
+        };
+
+        let packing = pack(&UvGenOptions::default());
+        assert!(packing.attempts > 2);
+        assert!(packing.is_complete(&meshes));
+
+        // Too few attempts to find a scale, at which every chart fits.
+        let packing = pack(&UvGenOptions {
+            max_packing_iterations: 0,
+            ..Default::default()
+        });
+        assert_eq!(packing.attempts, 1);
+        assert!(!packing.is_complete(&meshes));
+        assert_eq!(
+            packing.error,
+            Some(super::PackingError::Exhausted { attempts: 1 })
+        );
+
+        // Larger factors reach the first successful scale faster.
+        let fast = pack(&UvGenOptions {
+            scale_growth_factor: 4.0,
+            max_packing_iterations: 1000,
+            ..Default::default()
+        });
+        assert!(fast.is_complete(&meshes));
+        let slow = pack(&UvGenOptions {
+            scale_growth_factor: 1.01,
+            max_packing_iterations: 1000,
+            ..Default::default()
+        });
+        assert!(slow.is_complete(&meshes));
+        assert!(fast.attempts < slow.attempts);
+
+        // Invalid factors are replaced.
+        for (factor, expected) in [(f32::NAN, 1.33), (f32::INFINITY, 1.33), (0.5, 1.01)] {
+            let options = UvGenOptions {
+                scale_growth_factor: factor,
+                ..Default::default()
+            };
+            assert_eq!(super::scale_growth_factor(&options), expected);
+        }
+    }
+
+    #[test]
+    fn test_fill_atlas() {
+        let mesh = crate::test_meshes::uv_sphere(16, 8);
+        let generate = |fill_atlas| {
+            crate::generate_uvs_with_options(
+                mesh.vertices.iter().cloned(),
+                mesh.triangles.iter().cloned(),
+                &UvGenOptions {
+                    spacing: 2.0 / 256.0,
+                    resolution: 256,
+                    verify_padding: true,
+                    fill_atlas,
+                    ..Default::default()
+                },
+            )
+            .unwrap()
+        };
+        let conservative = generate(false);
+        let patch = generate(true);
+        assert!(patch.pack_stats.scale > conservative.pack_stats.scale);
+        assert!(patch.pack_stats.padding_violations.is_empty());
+
+        let bounds = patch.uv_bounds(0).unwrap();
+        let margin = 0.5 / 256.0;
+        let end = bounds.position + bounds.size;
+        assert!(bounds.x() >= margin - 1.0e-5 && bounds.y() >= margin - 1.0e-5);
+        assert!(end.x <= 1.0 - margin + 1.0e-5 && end.y <= 1.0 - margin + 1.0e-5);
+        assert!(bounds.w().max(bounds.h()) > 1.0 - 2.0 * margin - 1.0e-4);
+    }
+
+    #[test]
+    fn test_collapse_tiny_charts() {
+        // A large quad and a few tiny triangles around it.
+        let mut vertices = vec![
+            Vector3::new(0.0, 0.0, 0.0),
+            Vector3::new(1.0, 0.0, 0.0),
+            Vector3::new(1.0, 1.0, 0.0),
+            Vector3::new(0.0, 1.0, 0.0),
+        ];
+        let mut triangles = vec![[0, 1, 2], [0, 2, 3]];
+        for i in 0..5 {
+            let origin = Vector3::new(2.0 + i as f32, 0.0, 0.0);
+            let first = vertices.len() as u32;
+            vertices.extend([
+                origin,
+                origin + Vector3::new(0.001, 0.0, 0.0),
+                origin + Vector3::new(0.0, 0.001, 0.0),
+            ]);
+            triangles.push([first, first + 1, first + 2]);
+        }
+        let generate = |min_chart_texels| {
+            crate::generate_uvs_with_options(
+                vertices.iter().cloned(),
+                triangles.iter().cloned(),
+                &UvGenOptions {
+                    spacing: 1.0 / 64.0,
+                    resolution: 64,
+                    min_chart_texels,
+                    ..Default::default()
+                },
+            )
+            .unwrap()
+        };
+
+        let patch = generate(0.0);
+        assert_eq!(patch.pack_stats.chart_count, 6);
+        assert!(patch.pack_stats.collapsed_triangles.is_empty());
+
+        let collapsed = generate(1.0);
+        assert_eq!(collapsed.pack_stats.chart_count, 2);
+        assert_eq!(collapsed.pack_stats.collapsed_triangles, [2, 3, 4, 5, 6]);
+        let uv = collapsed.second_tex_coords[collapsed.triangles[2][0] as usize];
+        for triangle in collapsed.triangles[2..].iter() {
+            for &index in triangle.iter() {
+                assert_eq!(collapsed.second_tex_coords[index as usize], uv);
+            }
+        }
+        // The shared texel is inside the rectangle of its chart.
+        let chart = collapsed
+            .pack_stats
+            .charts
+            .iter()
+            .find(|chart| chart.triangles.contains(&2))
+            .unwrap();
+        let end = chart.rect.position + chart.rect.size;
+        assert!(uv.x > chart.rect.x() && uv.y > chart.rect.y() && uv.x < end.x && uv.y < end.y);
+    }
+}
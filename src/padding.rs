@@ -0,0 +1,287 @@
+//! Verification of padding between charts. See [`SurfaceDataPatch::padding_violations`] for more
+//! info.
+
+use crate::{SurfaceDataPatch, UvGenOptions};
+use nalgebra::Vector2;
+use std::collections::HashMap;
+
+/// Two charts, that are closer to each other than requested, see
+/// [`SurfaceDataPatch::padding_violations`].
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq, Hash)]
+#[cfg_attr(
+    feature = "rkyv",
+    derive(rkyv::Archive, rkyv::Serialize, rkyv::Deserialize),
+    archive(check_bytes)
+)]
+pub struct PaddingViolation {
+    /// Indices of the charts in [`crate::PackStats::charts`], the first one is smaller.
+    pub charts: [usize; 2],
+    /// Index of the atlas page of the charts.
+    pub page: usize,
+    /// Distance between the closest texels of the charts, it is the largest of the distances
+    /// along the axes. `0` means that the charts share a texel, `1` means that they touch each
+    /// other, `n` means that there are `n - 1` empty texels between them.
+    pub distance: u32,
+    /// Coordinates of a texel between the charts (or of a texel of one of them), where the
+    /// violation was found.
+    pub texel: [u32; 2],
+}
+
+/// Touching a texel at its border is not an overlap.
+const EPSILON: f32 = 1.0e-4;
+
+/// Returns the range of texels of the given row, which interiors are overlapped by the triangle
+/// (in texel units).
+fn row_span(triangle: &[Vector2<f32>; 3], row: usize) -> Option<(usize, usize)> {
+    let (y0, y1) = (row as f32 + EPSILON, row as f32 + 1.0 - EPSILON);
+    // The part of the triangle inside the row is a convex polygon, which extreme points are
+    // either corners of the triangle or intersections of its edges with the row bounds.
+    let (mut min, mut max) = (f32::MAX, -f32::MAX);
+    for i in 0..3 {
+        let (p, q) = (triangle[i], triangle[(i + 1) % 3]);
+        if (y0..=y1).contains(&p.y) {
+            (min, max) = (min.min(p.x), max.max(p.x));
+        }
+        for bound in [y0, y1] {
+            if (p.y - bound) * (q.y - bound) < 0.0 {
+                let x = p.x + (q.x - p.x) * (bound - p.y) / (q.y - p.y);
+                (min, max) = (min.min(x), max.max(x));
+            }
+        }
+    }
+    let (first, last) = ((min + EPSILON).floor(), (max - EPSILON).ceil() - 1.0);
+    (first <= last && last >= 0.0).then(|| (first.max(0.0) as usize, last as usize))
+}
+
+/// Returns the amount of atlas pages, that are occupied by the charts of the patch.
+pub(crate) fn page_count(patch: &SurfaceDataPatch) -> usize {
+    patch
+        .pack_stats
+        .charts
+        .iter()
+        .map(|chart| chart.page + 1)
+        .max()
+        .unwrap_or(0)
+}
+
+/// Rasterizes every chart of the given page of the patch conservatively (every texel, that is
+/// overlapped by a triangle, is occupied by its chart) at the given resolution of the (square)
+/// atlas. Returns the index of the chart, that occupies every texel, or `u32::MAX` for empty
+/// texels. `overlap` is called with indices of both charts and coordinates of every texel, that
+/// is occupied by another chart already. `size * size` must not overflow.
+pub(crate) fn rasterize_page(
+    patch: &SurfaceDataPatch,
+    page: usize,
+    size: usize,
+    mut overlap: impl FnMut(usize, usize, [u32; 2]),
+) -> Vec<u32> {
+    let mut owners = vec![u32::MAX; size * size];
+    for (chart_index, chart) in patch.pack_stats.charts.iter().enumerate() {
+        if chart.page != page {
+            continue;
+        }
+        for &triangle_index in chart.triangles.iter() {
+            let Some(triangle) = patch.triangles.get(triangle_index) else {
+                continue;
+            };
+            let Some(uvs) = triangle
+                .iter()
+                .map(|&index| patch.second_tex_coords.get(index as usize))
+                .collect::<Option<Vec<_>>>()
+            else {
+                continue;
+            };
+            let points = [uvs[0], uvs[1], uvs[2]].map(|uv| uv * size as f32);
+            let min = points[0].inf(&points[1]).inf(&points[2]);
+            let max = points[0].sup(&points[1]).sup(&points[2]);
+            if !(min.iter().chain(max.iter()).all(|c| c.is_finite())) {
+                continue;
+            }
+            let rows = (min.y.max(0.0) as usize).min(size)..(max.y.max(0.0) as usize + 1).min(size);
+            for y in rows {
+                let Some((first, last)) = row_span(&points, y) else {
+                    continue;
+                };
+                for x in first..=last.min(size - 1) {
+                    let owner = &mut owners[y * size + x];
+                    if *owner == u32::MAX {
+                        *owner = chart_index as u32;
+                    } else if *owner as usize != chart_index {
+                        overlap(chart_index, *owner as usize, [x as u32, y as u32]);
+                    }
+                }
+            }
+        }
+    }
+    owners
+}
+
+impl SurfaceDataPatch {
+    /// Rasterizes every chart conservatively (every texel, that is overlapped by a triangle, is
+    /// occupied by its chart) at the given resolution of the (square) atlas and returns pairs of
+    /// charts, that have less than `min_gap` empty texels between them. Every pair is reported
+    /// once, with the smallest distance. Charts on different pages never collide. Packing works
+    /// with floating-point rectangles, so rounding could make charts a fraction of a texel closer
+    /// than the spacing, such violations show up as light leaks in baked lightmaps:
+    ///
+    /// ```rust
+    /// let mesh = uvgen::test_meshes::cube();
+    /// let patch = uvgen::generate_uvs(
+    ///     mesh.vertices.iter().cloned(),
+    ///     mesh.triangles.iter().cloned(),
+    ///     // Two texels on each side of every chart.
+    ///     2.0 / 256.0,
+    /// )
+    /// .unwrap();
+    /// // Partially covered texels could eat one texel of the gap on each side.
+    /// assert!(patch.padding_violations(256, 3).is_empty());
+    /// ```
+    pub fn padding_violations(&self, resolution: u32, min_gap: u32) -> Vec<PaddingViolation> {
+        let size = resolution as usize;
+        let Some(texel_count) = size.checked_mul(size) else {
+            return Vec::new();
+        };
+        let page_count = page_count(self);
+        let mut violations = HashMap::<[usize; 2], PaddingViolation>::new();
+        let mut report = |a: usize, b: usize, page: usize, distance: u32, texel: [u32; 2]| {
+            let charts = [a.min(b), a.max(b)];
+            let violation = violations.entry(charts).or_insert(PaddingViolation {
+                charts,
+                page,
+                distance,
+                texel,
+            });
+            if distance < violation.distance {
+                violation.distance = distance;
+                violation.texel = texel;
+            }
+        };
+
+        for page in 0..page_count {
+            let mut owners = rasterize_page(self, page, size, |a, b, texel| {
+                report(a, b, page, 0, texel);
+            });
+            // Amount of growth steps from the chart to every texel, see below.
+            let mut steps = vec![0; texel_count];
+
+            // Charts grow one texel per step in every direction (including diagonals), so the
+            // growth fronts of two charts meet halfway between them. Every texel keeps the chart
+            // that reached it first and the amount of steps it took.
+            let limit = (min_gap + 1) / 2;
+            // Fronts start at the borders of the charts, the closest texel of a chart to anything
+            // outside of it is always on its border.
+            let mut layer = Vec::new();
+            for y in 0..size {
+                let row = &owners[y * size..(y + 1) * size];
+                let above = (y > 0).then(|| &owners[(y - 1) * size..y * size]);
+                let below = owners.get((y + 1) * size..(y + 2) * size);
+                for (x, &owner) in row.iter().enumerate() {
+                    if owner != u32::MAX
+                        && (x == 0
+                            || x + 1 == size
+                            || row[x - 1] != owner
+                            || row[x + 1] != owner
+                            || above.map_or(true, |above| above[x] != owner)
+                            || below.map_or(true, |below| below[x] != owner))
+                    {
+                        layer.push(y * size + x);
+                    }
+                }
+            }
+            let mut step = 0;
+            while !layer.is_empty() {
+                let mut next = Vec::new();
+                for &index in layer.iter() {
+                    let (x, y) = (index % size, index / size);
+                    let owner = owners[index];
+                    for ny in y.saturating_sub(1)..(y + 2).min(size) {
+                        for nx in x.saturating_sub(1)..(x + 2).min(size) {
+                            let neighbour = ny * size + nx;
+                            let other = owners[neighbour];
+                            if other == u32::MAX {
+                                if step < limit {
+                                    owners[neighbour] = owner;
+                                    steps[neighbour] = step + 1;
+                                    next.push(neighbour);
+                                }
+                            } else if other != owner {
+                                let distance = step + steps[neighbour] + 1;
+                                if distance <= min_gap {
+                                    let texel = [x as u32, y as u32];
+                                    report(owner as usize, other as usize, page, distance, texel);
+                                }
+                            }
+                        }
+                    }
+                }
+                layer = next;
+                step += 1;
+            }
+        }
+
+        let mut violations = violations.into_values().collect::<Vec<_>>();
+        violations.sort_unstable_by_key(|violation| violation.charts);
+        violations
+    }
+}
+
+/// Verifies padding between the charts of the patch, if it was requested. Charts must be at least
+/// `2 * spacing` apart, so at least `floor(2 * spacing * resolution) - 1` texels between them must
+/// be empty, since partially covered texels could eat a fraction of a texel on each side. Texture
+/// coordinates, charts and triangles of the patch must be set already.
+pub(crate) fn verify_padding(patch: &mut SurfaceDataPatch, options: &UvGenOptions) {
+    if !options.verify_padding || options.resolution == 0 {
+        return;
+    }
+    let gap = (2.0 * options.spacing * options.resolution as f32).floor();
+    let min_gap = if gap.is_finite() && gap >= 1.0 {
+        (gap - 1.0).min(options.resolution as f32) as u32
+    } else {
+        0
+    };
+    patch.pack_stats.padding_violations = patch.padding_violations(options.resolution, min_gap);
+}
+
+#[cfg(test)]
+mod test {
+    use crate::UvGenOptions;
+    use nalgebra::Vector2;
+
+    #[test]
+    fn test_padding_violations() {
+        let mesh = crate::test_meshes::cube();
+        let options = UvGenOptions {
+            spacing: 2.0 / 64.0,
+            resolution: 64,
+            verify_padding: true,
+            ..Default::default()
+        };
+        let mut patch = crate::generate_uvs_with_options(
+            mesh.vertices.iter().cloned(),
+            mesh.triangles.iter().cloned(),
+            &options,
+        )
+        .unwrap();
+        assert!(patch.pack_stats.padding_violations.is_empty());
+
+        // Move one chart right next to another one.
+        let (first, second) = (&patch.pack_stats.charts[0], &patch.pack_stats.charts[1]);
+        let offset = Vector2::new(first.rect.x() + first.rect.w() - second.rect.x(), 0.0)
+            + Vector2::new(-2.5 / 64.0, first.rect.y() - second.rect.y());
+        let moved = patch.pack_stats.charts[1]
+            .triangles
+            .iter()
+            .flat_map(|&triangle| patch.triangles[triangle])
+            .collect::<std::collections::HashSet<_>>();
+        for index in moved {
+            patch.second_tex_coords[index as usize] += offset;
+        }
+        // Charts are 1.5 texels apart now.
+        let violations = patch.padding_violations(64, 3);
+        let violation = violations
+            .iter()
+            .find(|violation| violation.charts == [0, 1])
+            .unwrap();
+        assert!(violation.distance <= 2);
+    }
+}
@@ -0,0 +1,224 @@
+//! Distribution of charts across multiple atlas pages. See [`crate::UvGenOptions::page_count`]
+//! for more info.
+
+use crate::{
+    locality,
+    pack::{self, MeshPacker, PackAttempt, Packing, PackingError},
+    Chart, UvGenOptions,
+};
+use nalgebra::Vector3;
+use std::ops::Range;
+
+/// Splits the given meshes into groups of spatially close meshes with roughly the same total
+/// area, one group per page. The set is recursively split in two along the longest axis of the
+/// bounds of the mesh centers. Writes page index of every mesh into `out`.
+fn cluster(
+    items: &mut [usize],
+    centers: &[Vector3<f32>],
+    areas: &[f32],
+    pages: Range<u32>,
+    out: &mut [u32],
+) {
+    let page_count = pages.end - pages.start;
+    if page_count <= 1 || items.len() <= 1 {
+        for &item in items.iter() {
+            out[item] = pages.start;
+        }
+        return;
+    }
+
+    let (min, max) = items.iter().fold(
+        (Vector3::repeat(f32::MAX), Vector3::repeat(-f32::MAX)),
+        |(min, max), &item| (min.inf(&centers[item]), max.sup(&centers[item])),
+    );
+    let axis = (max - min).imax();
+    items.sort_unstable_by(|&a, &b| {
+        centers[a][axis]
+            .total_cmp(&centers[b][axis])
+            .then(a.cmp(&b))
+    });
+
+    // Pages are split in two halves, meshes are split proportionally to the amount of pages.
+    let left_pages = page_count / 2;
+    let total_area = items.iter().map(|&item| areas[item]).sum::<f32>();
+    let target_area = total_area * left_pages as f32 / page_count as f32;
+    let mut area = 0.0;
+    let split = items
+        .iter()
+        .position(|&item| {
+            area += areas[item];
+            area >= target_area
+        })
+        .map_or(items.len(), |position| position + 1)
+        .clamp(1, items.len() - 1);
+
+    let (left, right) = items.split_at_mut(split);
+    let middle = pages.start + left_pages;
+    cluster(left, centers, areas, pages.start..middle, out);
+    cluster(right, centers, areas, middle..pages.end, out);
+}
+
+/// Distributes meshes across [`UvGenOptions::page_count`] atlas pages by spatial clustering and
+/// packs every page. Meshes are reordered, so the meshes of every page are contiguous (relative
+/// order of meshes of a page is preserved). Every page has the same scale, so texel density does
+/// not change across pages. `position` must return a world-space position of a vertex with the
+/// given index.
+pub(crate) fn pack_pages(
+    meshes: &mut Vec<Chart>,
+    triangles: &[[u32; 3]],
+    position: impl Fn(u32) -> Option<Vector3<f32>>,
+    options: &UvGenOptions,
+) -> Packing {
+    trace_span!("pages");
+
+    let page_count = options.page_count.clamp(1, u32::MAX as usize) as u32;
+    let centers = locality::mesh_centers(meshes, triangles, position);
+    let areas = meshes.iter().map(Chart::area).collect::<Vec<_>>();
+    let mut items = (0..meshes.len()).collect::<Vec<_>>();
+    let mut mesh_pages = vec![0; meshes.len()];
+    cluster(&mut items, &centers, &areas, 0..page_count, &mut mesh_pages);
+    // Extra rectangles always go to the first page.
+    for (page, mesh) in mesh_pages.iter_mut().zip(meshes.iter()) {
+        if mesh.extra_rect.is_some() {
+            *page = 0;
+        }
+    }
+
+    let mut keys = mesh_pages
+        .into_iter()
+        .enumerate()
+        .map(|(index, page)| (page, index))
+        .collect::<Vec<_>>();
+    keys.sort_unstable();
+    let mut unsorted = meshes.drain(..).map(Some).collect::<Vec<_>>();
+    meshes.extend(keys.iter().filter_map(|&(_, index)| unsorted[index].take()));
+    let pages = keys.into_iter().map(|(page, _)| page).collect::<Vec<_>>();
+    let ranges = (0..page_count)
+        .map(|page| pages.partition_point(|&p| p < page)..pages.partition_point(|&p| p <= page))
+        .collect::<Vec<_>>();
+
+    // Every page is packed independently first and then all of them are packed again with the
+    // smallest scale of the pages.
+    let mut error = None;
+    let mut packer_attempts = 0;
+    let mut scale = ranges
+        .iter()
+        .filter(|range| !range.is_empty())
+        .map(|range| {
+            let page_meshes = &meshes[range.clone()];
+            let mut packer = MeshPacker::new(page_meshes, options);
+            while packer.try_pack(page_meshes) == PackAttempt::Retry {}
+            let packing = packer.finish();
+            packer_attempts += packing.attempts;
+            if packing.is_complete(page_meshes) {
+                packing.scale
+            } else {
+                error = error.or(packing.error);
+                0.0
+            }
+        })
+        .fold(f32::INFINITY, f32::min);
+    if !scale.is_finite() {
+        scale = 1.0;
+    }
+
+    let mut attempts = 0;
+    while scale > f32::EPSILON {
+        attempts += 1;
+        let page_packings = ranges
+            .iter()
+            .map(|range| pack::pack_with_scale(&meshes[range.clone()], scale, options.spacing))
+            .collect::<Option<Vec<_>>>();
+        if let Some(page_packings) = page_packings {
+            let mut packing = Packing {
+                rects: Vec::with_capacity(meshes.len()),
+                scale,
+                pages,
+                attempts: packer_attempts,
+                ..Default::default()
+            };
+            for page_packing in page_packings {
+                packing.rects.extend(page_packing.rects);
+                packing.page_free_rects.push(page_packing.free_rects);
+            }
+            packing.free_rects = packing.page_free_rects[0].clone();
+            return packing;
+        }
+        scale /= pack::scale_growth_factor(options);
+    }
+
+    Packing {
+        scale,
+        pages,
+        error: error.or(Some(PackingError::Exhausted { attempts })),
+        attempts: packer_attempts,
+        ..Default::default()
+    }
+}
+
+/// Returns page index of every triangle, or an empty list if there's only one page.
+pub(crate) fn triangle_pages(
+    meshes: &[Chart],
+    packing: &Packing,
+    triangle_count: usize,
+) -> Vec<u32> {
+    if packing.pages.is_empty() {
+        return Vec::new();
+    }
+    let mut pages = vec![0; triangle_count];
+    for (mesh, &page) in meshes.iter().zip(packing.pages.iter()) {
+        for &triangle in mesh.triangles.iter() {
+            if let Some(triangle_page) = pages.get_mut(triangle) {
+                *triangle_page = page;
+            }
+        }
+    }
+    pages
+}
+
+#[cfg(test)]
+mod test {
+    use crate::UvGenOptions;
+    use nalgebra::Vector3;
+
+    #[test]
+    fn test_pages() {
+        // Two rooms far apart, each of them consists of a few separate quads.
+        let mut vertices = Vec::new();
+        let mut triangles = Vec::new();
+        for x in [0.0, 1.5, 100.0, 3.0, 101.5, 103.0] {
+            let base = vertices.len() as u32;
+            vertices.extend([
+                Vector3::new(x, 0.0, 0.0),
+                Vector3::new(x + 1.0, 0.0, 0.0),
+                Vector3::new(x + 1.0, 1.0, 0.0),
+                Vector3::new(x, 1.0, 0.0),
+            ]);
+            triangles.extend([[base, base + 1, base + 2], [base, base + 2, base + 3]]);
+        }
+
+        let patch = crate::generate_uvs_with_options(
+            vertices.iter().cloned(),
+            triangles.iter().cloned(),
+            &UvGenOptions {
+                page_count: 2,
+                ..Default::default()
+            },
+        )
+        .unwrap();
+
+        let stats = &patch.pack_stats;
+        assert_eq!(stats.page_free_rects.len(), 2);
+        assert_eq!(patch.triangle_pages.len(), patch.triangles.len());
+        for chart in stats.charts.iter() {
+            let x = vertices[triangles[chart.triangles[0]][0] as usize].x;
+            assert_eq!(chart.page, if x < 50.0 { 0 } else { 1 });
+            for &triangle in chart.triangles.iter() {
+                assert_eq!(patch.triangle_pages[triangle] as usize, chart.page);
+            }
+        }
+        for uv in patch.second_tex_coords.iter() {
+            assert!(uv.x >= 0.0 && uv.x <= 1.0 && uv.y >= 0.0 && uv.y <= 1.0);
+        }
+    }
+}
@@ -0,0 +1,102 @@
+//! Helpers for data-parallel processing. The work is spread across the threads of the global
+//! `rayon` pool only if `parallel` feature is enabled, otherwise everything is processed on the
+//! current thread.
+
+/// Length of chunks of work in deterministic mode. It does not depend on the amount of CPU cores,
+/// so the results are the same on any machine.
+const DETERMINISTIC_CHUNK_LEN: usize = 4096;
+
+/// Returns the amount of threads that can be used for processing.
+pub(crate) fn thread_count() -> usize {
+    #[cfg(feature = "parallel")]
+    {
+        rayon::current_num_threads()
+    }
+    #[cfg(not(feature = "parallel"))]
+    {
+        1
+    }
+}
+
+/// Returns length of chunks of work for the given amount of items.
+pub(crate) fn chunk_len(len: usize, deterministic: bool) -> usize {
+    if deterministic {
+        DETERMINISTIC_CHUNK_LEN
+    } else {
+        let threads = thread_count();
+        ((len + threads - 1) / threads).max(1)
+    }
+}
+
+/// Splits the items into chunks of the given length and maps every chunk using the given
+/// function. The function receives the index of the first item of the chunk and the chunk
+/// itself. Results are always returned in the order of the chunks.
+pub(crate) fn map_chunks<T, R>(
+    items: &[T],
+    chunk_len: usize,
+    func: impl Fn(usize, &[T]) -> R + Sync + Send,
+) -> Vec<R>
+where
+    T: Sync,
+    R: Send,
+{
+    let chunk_len = chunk_len.max(1);
+
+    #[cfg(feature = "parallel")]
+    {
+        use rayon::prelude::*;
+
+        if items.len() > chunk_len && thread_count() > 1 {
+            return items
+                .par_chunks(chunk_len)
+                .enumerate()
+                .map(|(i, chunk)| func(i * chunk_len, chunk))
+                .collect();
+        }
+    }
+
+    items
+        .chunks(chunk_len)
+        .enumerate()
+        .map(|(i, chunk)| func(i * chunk_len, chunk))
+        .collect()
+}
+
+/// Maps every item using the given function. Unlike [`map_chunks`], items are handed out to the
+/// threads one by one, so items, that take very different amount of time to process, are
+/// balanced between the threads. Results are always returned in the order of the items.
+pub(crate) fn map_balanced<T, R>(items: &[T], func: impl Fn(&T) -> R + Sync + Send) -> Vec<R>
+where
+    T: Sync,
+    R: Send,
+{
+    #[cfg(feature = "parallel")]
+    {
+        use rayon::prelude::*;
+
+        if items.len() > 1 && thread_count() > 1 {
+            // Every item is a separate job, so idle threads steal them one by one.
+            return items.par_iter().with_max_len(1).map(func).collect();
+        }
+    }
+
+    items.iter().map(func).collect()
+}
+
+#[cfg(test)]
+mod test {
+    #[test]
+    fn test_map_chunks_order() {
+        let items = (0..10000).collect::<Vec<usize>>();
+        for deterministic in [false, true] {
+            let chunk_len = super::chunk_len(items.len(), deterministic);
+            let offsets = super::map_chunks(&items, chunk_len, |offset, chunk| {
+                assert_eq!(chunk[0], offset);
+                offset
+            });
+            assert!(offsets
+                .windows(2)
+                .all(|pair| pair[0] + chunk_len == pair[1]));
+        }
+    }
+}
@@ -0,0 +1,80 @@
+//! Sides of the box, that triangles are projected on, and the projection math.
+
+use crate::{box_map, face_projection};
+use nalgebra::{Vector2, Vector3};
+
+/// A side of the box, that triangles are projected on. See [`crate::face_planes`] for more info.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub enum FacePlane {
+    /// +X side of the box.
+    PositiveX,
+    /// -X side of the box.
+    NegativeX,
+    /// +Y side of the box.
+    PositiveY,
+    /// -Y side of the box.
+    NegativeY,
+    /// +Z side of the box.
+    PositiveZ,
+    /// -Z side of the box.
+    NegativeZ,
+}
+
+impl FacePlane {
+    /// All the sides of the box in the order they're stored internally.
+    pub const ALL: [FacePlane; 6] = [
+        FacePlane::PositiveX,
+        FacePlane::NegativeX,
+        FacePlane::PositiveY,
+        FacePlane::NegativeY,
+        FacePlane::PositiveZ,
+        FacePlane::NegativeZ,
+    ];
+
+    pub(crate) fn index(self) -> usize {
+        match self {
+            FacePlane::PositiveX => 0,
+            FacePlane::NegativeX => 1,
+            FacePlane::PositiveY => 2,
+            FacePlane::NegativeY => 3,
+            FacePlane::PositiveZ => 4,
+            FacePlane::NegativeZ => 5,
+        }
+    }
+}
+
+/// Returns a side of the box, that the triangle is mapped to by the generator.
+pub fn triangle_plane(a: &Vector3<f32>, b: &Vector3<f32>, c: &Vector3<f32>) -> FacePlane {
+    FacePlane::ALL[box_map(a, b, c).0]
+}
+
+/// Projects the triangle on the given side of the box, exactly as the generator does it. The
+/// projection has the same orientation as the charts of the generator, so it could be used to
+/// reproject individual triangles consistently with the lightmap (for example, to generate
+/// runtime decal UVs):
+///
+/// ```rust
+/// # use nalgebra::{Vector2, Vector3};
+/// let (a, b, c) = (
+///     Vector3::new(0.0, 0.0, 0.0),
+///     Vector3::new(1.0, 0.0, 0.0),
+///     Vector3::new(0.0, 1.0, 0.0),
+/// );
+/// let plane = uvgen::triangle_plane(&a, &b, &c);
+/// assert_eq!(plane, uvgen::FacePlane::PositiveZ);
+/// assert_eq!(
+///     uvgen::project_triangle(&a, &b, &c, plane),
+///     [Vector2::new(0.0, 0.0), Vector2::new(1.0, 0.0), Vector2::new(0.0, 1.0)]
+/// );
+/// ```
+///
+/// Note that the generator also applies [`crate::UvGenOptions::density`] to vertex positions
+/// before projection.
+pub fn project_triangle(
+    a: &Vector3<f32>,
+    b: &Vector3<f32>,
+    c: &Vector3<f32>,
+    plane: FacePlane,
+) -> [Vector2<f32>; 3] {
+    face_projection(plane.index(), a, b, c)
+}
@@ -0,0 +1,107 @@
+//! Generation for meshes with `f64` positions. See [`generate_uvs_f64`] for more info.
+
+use crate::{generate_uvs_with_options, SurfaceDataPatch, UvGenOptions, UvIndex};
+use nalgebra::Vector3;
+
+/// Generates UV map for the given vertices with `f64` positions (for example, CAD-scale geometry
+/// far from the origin) and triangles using the given options, like
+/// [`crate::generate_uvs_with_options`]. Converting such positions to `f32` directly loses the
+/// small details of the mesh, since `f32` has only 24 bits of mantissa for both the offset and
+/// the details. The generator is invariant to translation of the mesh, so the positions are moved
+/// to the center of their bounds in `f64` first and only then converted to `f32`. This way, the
+/// precision depends only on the size of the mesh, not on its distance from the origin:
+///
+/// ```rust
+/// # use nalgebra::Vector3;
+/// let mesh = uvgen::test_meshes::cube();
+/// let offset = Vector3::new(1.0e7, -3.0e7, 5.0e6);
+/// let patch = uvgen::generate_uvs_f64(
+///     mesh.vertices.iter().map(|v| v.cast::<f64>() + offset),
+///     mesh.triangles.iter().cloned(),
+///     &Default::default(),
+/// )
+/// .unwrap();
+/// let expected = uvgen::generate_uvs(
+///     mesh.vertices.iter().cloned(),
+///     mesh.triangles.iter().cloned(),
+///     0.005,
+/// )
+/// .unwrap();
+/// assert_eq!(patch.second_tex_coords, expected.second_tex_coords);
+/// ```
+///
+/// Texture coordinates are `f32` as usual. Hashes of the charts (see
+/// [`UvGenOptions::stable_anchors`]) are calculated for the moved positions, so they change if
+/// the bounds of the mesh change.
+pub fn generate_uvs_f64<I: UvIndex>(
+    vertices: impl Iterator<Item = Vector3<f64>>,
+    triangles: impl Iterator<Item = [I; 3]>,
+    options: &UvGenOptions,
+) -> Option<SurfaceDataPatch> {
+    let vertices = vertices.collect::<Vec<_>>();
+    let (min, max) = vertices.iter().fold(
+        (Vector3::repeat(f64::MAX), Vector3::repeat(-f64::MAX)),
+        |(min, max), vertex| (min.inf(vertex), max.sup(vertex)),
+    );
+    let center = if vertices.is_empty() {
+        Vector3::zeros()
+    } else {
+        (min + max) * 0.5
+    };
+    generate_uvs_with_options(
+        vertices
+            .iter()
+            .map(|vertex| (vertex - center).cast::<f32>()),
+        triangles,
+        options,
+    )
+}
+
+#[cfg(test)]
+mod test {
+    use nalgebra::Vector3;
+
+    #[test]
+    fn test_f64_positions() {
+        let mesh = crate::test_meshes::uv_sphere(16, 8);
+        let offset = Vector3::new(2.5e8, 1.0e8, -4.0e8);
+        let vertices = mesh
+            .vertices
+            .iter()
+            .map(|vertex| vertex.cast::<f64>() + offset)
+            .collect::<Vec<_>>();
+
+        let expected = crate::generate_uvs(
+            mesh.vertices.iter().cloned(),
+            mesh.triangles.iter().cloned(),
+            0.005,
+        )
+        .unwrap();
+        let patch = crate::generate_uvs_f64(
+            vertices.iter().cloned(),
+            mesh.triangles.iter().cloned(),
+            &Default::default(),
+        )
+        .unwrap();
+        assert_eq!(
+            patch.pack_stats.chart_count,
+            expected.pack_stats.chart_count
+        );
+        for (a, b) in patch
+            .second_tex_coords
+            .iter()
+            .zip(expected.second_tex_coords.iter())
+        {
+            assert!(a.metric_distance(b) < 1.0e-4);
+        }
+
+        // Positions converted to `f32` directly collapse into a few points.
+        let narrowed = crate::generate_uvs(
+            vertices.iter().map(|vertex| vertex.cast::<f32>()),
+            mesh.triangles.iter().cloned(),
+            0.005,
+        )
+        .unwrap();
+        assert_ne!(narrowed.second_tex_coords, expected.second_tex_coords);
+    }
+}
@@ -0,0 +1,467 @@
+//! Unwrapping of dense meshes through low-poly proxies. See [`generate_uvs_with_proxy`] for more
+//! info.
+
+use crate::{
+    generate_uvs_with_options, index, new_vertex_index, spatial::SpatialHash, ChartInfo, PackStats,
+    SurfaceDataPatch, UvGenOptions, UvIndex,
+};
+use nalgebra::{Vector2, Vector3};
+use std::collections::HashMap;
+
+/// Generates UV map for a dense mesh by unwrapping its low-poly proxy (for example, a decimated
+/// version of the mesh) using the given options and then transferring texture coordinates of the
+/// proxy to the dense mesh (see [`transfer_uvs`]). For very dense meshes this is much faster than
+/// unwrapping them directly and produces smoother charts, since small details of the surface do
+/// not cut the charts:
+///
+/// ```rust
+/// let proxy = uvgen::test_meshes::uv_sphere(12, 6);
+/// let mesh = uvgen::test_meshes::uv_sphere(48, 24);
+/// let patch = uvgen::generate_uvs_with_proxy(
+///     proxy.vertices.iter().cloned(),
+///     proxy.triangles.iter().cloned(),
+///     mesh.vertices.iter().cloned(),
+///     mesh.triangles.iter().cloned(),
+///     &Default::default(),
+/// )
+/// .unwrap();
+/// assert_eq!(patch.triangles.len(), mesh.triangles.len());
+/// ```
+///
+/// The returned patch must be applied to the dense mesh. Returns `None` if either mesh is invalid
+/// or if the proxy could not be unwrapped.
+pub fn generate_uvs_with_proxy<I: UvIndex>(
+    proxy_vertices: impl Iterator<Item = Vector3<f32>>,
+    proxy_triangles: impl Iterator<Item = [I; 3]>,
+    vertices: impl Iterator<Item = Vector3<f32>>,
+    triangles: impl Iterator<Item = [I; 3]>,
+    options: &UvGenOptions,
+) -> Option<SurfaceDataPatch> {
+    trace_span!("generate_uvs_with_proxy");
+
+    let proxy_vertices = proxy_vertices.collect::<Vec<_>>();
+    let proxy_patch =
+        generate_uvs_with_options(proxy_vertices.iter().cloned(), proxy_triangles, options)?;
+    let vertices = vertices.collect::<Vec<_>>();
+    let triangles = index::widen(triangles).ok()?;
+    let patch = transfer_uvs(&proxy_vertices, &proxy_patch, &vertices, &triangles)?;
+    index::check_vertex_count::<I>(patch).ok()
+}
+
+/// Transfers texture coordinates of an unwrapped proxy mesh to another mesh, that has roughly the
+/// same surface (for example, the full-resolution mesh, that the proxy was made of). Every
+/// triangle of the mesh is assigned to the chart of the proxy triangle, that is the nearest one
+/// to its center. Then every vertex of the triangle is projected onto the nearest triangle of
+/// that chart and gets the texture coordinates of the projection. Vertices of triangles from
+/// different charts are duplicated, so the mesh gets the same seams as the proxy.
+///
+/// `proxy_vertices` are the source vertices of the proxy, that `proxy_patch` was generated for.
+/// Triangles of the proxy, that do not belong to any chart, are ignored.
+///
+/// Charts of the returned patch are the charts of the proxy in the same order, but with the
+/// triangles of the mesh. Other packing statistics, which describe individual triangles or the
+/// geometry of the charts, are not transferred. Optional outputs (such as
+/// [`SurfaceDataPatch::chart_uvs`]) are not transferred either, except
+/// [`SurfaceDataPatch::triangle_pages`]. Returns `None` if a triangle of the mesh references a
+/// vertex, that does not exist, or if the proxy has no charts.
+pub fn transfer_uvs(
+    proxy_vertices: &[Vector3<f32>],
+    proxy_patch: &SurfaceDataPatch,
+    vertices: &[Vector3<f32>],
+    triangles: &[[u32; 3]],
+) -> Option<SurfaceDataPatch> {
+    trace_span!("transfer_uvs");
+
+    if triangles
+        .iter()
+        .flatten()
+        .any(|&index| index as usize >= vertices.len())
+    {
+        return None;
+    }
+    let surface = ProxySurface::new(proxy_vertices, proxy_patch)?;
+
+    let mut patch = SurfaceDataPatch {
+        second_tex_coords: vec![Vector2::zeros(); vertices.len()],
+        ..Default::default()
+    };
+    let mut charts = proxy_patch
+        .pack_stats
+        .charts
+        .iter()
+        .map(|chart| ChartInfo {
+            triangles: Vec::new(),
+            key: usize::MAX,
+            ..chart.clone()
+        })
+        .collect::<Vec<_>>();
+    // Copies of the vertices for every chart, that uses them. The first chart, that uses a
+    // vertex, keeps the vertex itself.
+    let mut copies = HashMap::<(u32, usize), u32>::new();
+    let mut used = vec![false; vertices.len()];
+    for (triangle_index, triangle) in triangles.iter().enumerate() {
+        let center = triangle
+            .iter()
+            .map(|&index| vertices[index as usize])
+            .sum::<Vector3<f32>>()
+            / 3.0;
+        let (nearest, _) = surface.nearest(&center, |_| true)?;
+        let chart = surface.triangle_charts[nearest]?;
+
+        let mut new_triangle = [0; 3];
+        for (new_index, &index) in new_triangle.iter_mut().zip(triangle.iter()) {
+            *new_index = match copies.get(&(index, chart)) {
+                Some(&copy) => copy,
+                None => {
+                    let position = &vertices[index as usize];
+                    let (proxy_triangle, barycentric) =
+                        surface.nearest(position, |t| surface.triangle_charts[t] == Some(chart))?;
+                    let tex_coord = surface.tex_coord(proxy_triangle, &barycentric);
+                    let copy = if used[index as usize] {
+                        let copy = new_vertex_index(patch.second_tex_coords.len())?;
+                        patch.additional_vertices.push(index);
+                        patch.second_tex_coords.push(tex_coord);
+                        copy
+                    } else {
+                        used[index as usize] = true;
+                        patch.second_tex_coords[index as usize] = tex_coord;
+                        index
+                    };
+                    copies.insert((index, chart), copy);
+                    copy
+                }
+            };
+        }
+        patch.triangles.push(new_triangle);
+
+        let chart = &mut charts[chart];
+        chart.key = chart.key.min(triangle_index);
+        chart.triangles.push(triangle_index);
+        if !proxy_patch.triangle_pages.is_empty() {
+            patch.triangle_pages.push(chart.page as u32);
+        }
+    }
+
+    let stats = &proxy_patch.pack_stats;
+    patch.pack_stats = PackStats {
+        scale: stats.scale,
+        free_rects: stats.free_rects.clone(),
+        page_free_rects: stats.page_free_rects.clone(),
+        extra_rects: stats.extra_rects.clone(),
+        packing_error: stats.packing_error,
+        chart_count: stats.chart_count,
+        charts,
+        ..Default::default()
+    };
+    Some(patch)
+}
+
+/// Triangles of an unwrapped proxy mesh with a spatial hash over their centers.
+struct ProxySurface<'a> {
+    patch: &'a SurfaceDataPatch,
+    positions: Vec<[Vector3<f32>; 3]>,
+    triangle_charts: Vec<Option<usize>>,
+    hash: SpatialHash,
+    /// The largest distance from the center of a triangle to its vertices.
+    max_radius: f32,
+    center: Vector3<f32>,
+    /// The largest distance from [`Self::center`] to the centers of the triangles.
+    extent: f32,
+}
+
+impl<'a> ProxySurface<'a> {
+    fn new(proxy_vertices: &[Vector3<f32>], patch: &'a SurfaceDataPatch) -> Option<Self> {
+        let mut triangle_charts = vec![None; patch.triangles.len()];
+        for (chart_index, chart) in patch.pack_stats.charts.iter().enumerate() {
+            for &triangle_index in chart.triangles.iter() {
+                if let Some(triangle_chart) = triangle_charts.get_mut(triangle_index) {
+                    *triangle_chart = Some(chart_index);
+                }
+            }
+        }
+        let position = |index: u32| {
+            patch
+                .source_vertex(index)
+                .and_then(|source| proxy_vertices.get(source as usize))
+                .cloned()
+        };
+        let positions = patch
+            .triangles
+            .iter()
+            .map(|triangle| {
+                Some([
+                    position(triangle[0])?,
+                    position(triangle[1])?,
+                    position(triangle[2])?,
+                ])
+            })
+            .collect::<Option<Vec<_>>>()?;
+
+        let centers = positions
+            .iter()
+            .map(|[a, b, c]| (a + b + c) / 3.0)
+            .collect::<Vec<_>>();
+        let (mut max_radius, mut edge_length, mut count) = (0.0f32, 0.0, 0);
+        let (mut min, mut max) = (Vector3::repeat(f32::MAX), Vector3::repeat(f32::MIN));
+        for ((vertices, center), chart) in positions.iter().zip(&centers).zip(&triangle_charts) {
+            if chart.is_none() {
+                continue;
+            }
+            for (i, vertex) in vertices.iter().enumerate() {
+                max_radius = max_radius.max((vertex - center).norm());
+                edge_length += (vertices[(i + 1) % 3] - vertex).norm();
+            }
+            count += 1;
+            min = min.inf(center);
+            max = max.sup(center);
+        }
+        if count == 0 {
+            return None;
+        }
+
+        // Cells of the size of an average edge hold a few triangles each.
+        let mut hash = SpatialHash::new(edge_length / (3 * count) as f32);
+        for (triangle_index, (center, chart)) in centers.iter().zip(&triangle_charts).enumerate() {
+            if chart.is_some() {
+                hash.insert(triangle_index as u32, center);
+            }
+        }
+        let center = (min + max) * 0.5;
+        Some(Self {
+            patch,
+            positions,
+            triangle_charts,
+            hash,
+            max_radius,
+            center,
+            extent: (max - center).norm(),
+        })
+    }
+
+    /// Finds the nearest triangle (among the ones in charts, that pass the given filter) to the
+    /// given point. Returns its index and barycentric coordinates of the nearest point on it.
+    fn nearest(
+        &self,
+        point: &Vector3<f32>,
+        filter: impl Fn(usize) -> bool,
+    ) -> Option<(usize, Vector3<f32>)> {
+        // Radius, at which every triangle is checked.
+        let max_radius = (point - self.center).norm() + self.extent;
+        let mut radius = self.hash.cell_size();
+        loop {
+            let mut nearest: Option<(usize, Vector3<f32>, f32)> = None;
+            // The center of a triangle is at most `max_radius` away from its nearest point.
+            self.hash
+                .query(point, radius + self.max_radius, |triangle_index| {
+                    let triangle_index = triangle_index as usize;
+                    if !filter(triangle_index) {
+                        return;
+                    }
+                    let [a, b, c] = &self.positions[triangle_index];
+                    let barycentric = closest_point(point, a, b, c);
+                    let closest = a * barycentric.x + b * barycentric.y + c * barycentric.z;
+                    let distance = (closest - point).norm();
+                    if nearest.map_or(true, |(index, _, nearest_distance)| {
+                        (distance, triangle_index) < (nearest_distance, index)
+                    }) {
+                        nearest = Some((triangle_index, barycentric, distance));
+                    }
+                });
+            match nearest {
+                // Triangles, which are not farther than the radius, are checked, so there's no
+                // closer triangle.
+                Some((index, barycentric, distance)) if distance <= radius => {
+                    return Some((index, barycentric))
+                }
+                _ if radius >= max_radius || !radius.is_finite() => {
+                    return nearest.map(|(index, barycentric, _)| (index, barycentric))
+                }
+                _ => radius *= 2.0,
+            }
+        }
+    }
+
+    /// Interpolates texture coordinates of the vertices of the given triangle.
+    fn tex_coord(&self, triangle_index: usize, barycentric: &Vector3<f32>) -> Vector2<f32> {
+        self.patch.triangles[triangle_index]
+            .iter()
+            .zip(barycentric.iter())
+            .map(|(&index, &weight)| self.patch.second_tex_coords[index as usize] * weight)
+            .sum()
+    }
+}
+
+/// Returns barycentric coordinates of the point of the triangle, that is the closest one to the
+/// given point. Degenerate triangles are handled as segments or points.
+fn closest_point(
+    point: &Vector3<f32>,
+    a: &Vector3<f32>,
+    b: &Vector3<f32>,
+    c: &Vector3<f32>,
+) -> Vector3<f32> {
+    // "Real-Time Collision Detection" by Christer Ericson, 5.1.5.
+    let ab = b - a;
+    let ac = c - a;
+    let ap = point - a;
+    let d1 = ab.dot(&ap);
+    let d2 = ac.dot(&ap);
+    if d1 <= 0.0 && d2 <= 0.0 {
+        return Vector3::new(1.0, 0.0, 0.0);
+    }
+
+    let bp = point - b;
+    let d3 = ab.dot(&bp);
+    let d4 = ac.dot(&bp);
+    if d3 >= 0.0 && d4 <= d3 {
+        return Vector3::new(0.0, 1.0, 0.0);
+    }
+
+    let vc = d1 * d4 - d3 * d2;
+    if vc <= 0.0 && d1 >= 0.0 && d3 <= 0.0 {
+        let v = d1 / (d1 - d3);
+        return Vector3::new(1.0 - v, v, 0.0);
+    }
+
+    let cp = point - c;
+    let d5 = ab.dot(&cp);
+    let d6 = ac.dot(&cp);
+    if d6 >= 0.0 && d5 <= d6 {
+        return Vector3::new(0.0, 0.0, 1.0);
+    }
+
+    let vb = d5 * d2 - d1 * d6;
+    if vb <= 0.0 && d2 >= 0.0 && d6 <= 0.0 {
+        let w = d2 / (d2 - d6);
+        return Vector3::new(1.0 - w, 0.0, w);
+    }
+
+    let va = d3 * d6 - d5 * d4;
+    if va <= 0.0 && d4 - d3 >= 0.0 && d5 - d6 >= 0.0 {
+        let w = (d4 - d3) / ((d4 - d3) + (d5 - d6));
+        return Vector3::new(0.0, 1.0 - w, w);
+    }
+
+    let denominator = va + vb + vc;
+    if denominator.abs() <= f32::EPSILON * (va.abs() + vb.abs() + vc.abs()) {
+        // Collinear vertices, every edge region was checked above.
+        return Vector3::new(1.0, 0.0, 0.0);
+    }
+    let v = vb / denominator;
+    let w = vc / denominator;
+    Vector3::new(1.0 - v - w, v, w)
+}
+
+#[cfg(test)]
+mod test {
+    use crate::UvGenOptions;
+
+    #[test]
+    fn test_proxy_unwrapping() {
+        let proxy = crate::test_meshes::uv_sphere(12, 6);
+        let mesh = crate::test_meshes::uv_sphere(48, 24);
+        let options = UvGenOptions::default();
+        let proxy_patch = crate::generate_uvs_with_options(
+            proxy.vertices.iter().cloned(),
+            proxy.triangles.iter().cloned(),
+            &options,
+        )
+        .unwrap();
+        let patch = crate::generate_uvs_with_proxy(
+            proxy.vertices.iter().cloned(),
+            proxy.triangles.iter().cloned(),
+            mesh.vertices.iter().cloned(),
+            mesh.triangles.iter().cloned(),
+            &options,
+        )
+        .unwrap();
+
+        assert_eq!(patch.triangles.len(), mesh.triangles.len());
+        assert_eq!(
+            patch.second_tex_coords.len(),
+            mesh.vertices.len() + patch.additional_vertices.len()
+        );
+        for (triangle, new_triangle) in mesh.triangles.iter().zip(patch.triangles.iter()) {
+            for (&index, &new_index) in triangle.iter().zip(new_triangle.iter()) {
+                assert_eq!(patch.source_vertex(new_index), Some(index));
+            }
+        }
+
+        // Charts are the ones of the proxy, every triangle is inside the rectangle of its chart.
+        assert_eq!(
+            patch.pack_stats.chart_count,
+            proxy_patch.pack_stats.chart_count
+        );
+        assert_eq!(
+            patch
+                .pack_stats
+                .charts
+                .iter()
+                .map(|chart| chart.triangles.len())
+                .sum::<usize>(),
+            mesh.triangles.len()
+        );
+        for (chart, proxy_chart) in patch
+            .pack_stats
+            .charts
+            .iter()
+            .zip(proxy_patch.pack_stats.charts.iter())
+        {
+            assert_eq!(chart.rect, proxy_chart.rect);
+            assert_eq!(chart.key, chart.triangles[0]);
+            let rect = chart.rect;
+            for &triangle_index in chart.triangles.iter() {
+                for &index in patch.triangles[triangle_index].iter() {
+                    let tex_coord = patch.second_tex_coords[index as usize];
+                    assert!(tex_coord.x >= rect.x() - 1.0e-5 && tex_coord.y >= rect.y() - 1.0e-5);
+                    assert!(tex_coord.x <= rect.x() + rect.w() + 1.0e-5);
+                    assert!(tex_coord.y <= rect.y() + rect.h() + 1.0e-5);
+                }
+            }
+        }
+
+        // The dense mesh covers roughly the same area of the atlas as the proxy.
+        let uv_area = |patch: &crate::SurfaceDataPatch| {
+            patch
+                .triangles
+                .iter()
+                .map(|triangle| {
+                    let [a, b, c] = triangle.map(|index| patch.second_tex_coords[index as usize]);
+                    (b - a).perp(&(c - a)).abs() * 0.5
+                })
+                .sum::<f32>()
+        };
+        let (area, proxy_area) = (uv_area(&patch), uv_area(&proxy_patch));
+        assert!((area - proxy_area).abs() < proxy_area * 0.1);
+    }
+
+    #[test]
+    fn test_closest_point() {
+        use nalgebra::Vector3;
+
+        let (a, b, c) = (
+            Vector3::new(0.0, 0.0, 0.0),
+            Vector3::new(1.0, 0.0, 0.0),
+            Vector3::new(0.0, 1.0, 0.0),
+        );
+        let closest = |point: Vector3<f32>| super::closest_point(&point, &a, &b, &c);
+        assert_eq!(
+            closest(Vector3::new(-1.0, -1.0, 0.0)),
+            Vector3::new(1.0, 0.0, 0.0)
+        );
+        assert_eq!(
+            closest(Vector3::new(2.0, 0.0, 1.0)),
+            Vector3::new(0.0, 1.0, 0.0)
+        );
+        assert_eq!(
+            closest(Vector3::new(0.5, -1.0, 0.0)),
+            Vector3::new(0.5, 0.5, 0.0)
+        );
+        assert_eq!(
+            closest(Vector3::new(0.25, 0.25, 3.0)),
+            Vector3::new(0.5, 0.25, 0.25)
+        );
+        // Degenerate triangles are handled too.
+        let point = super::closest_point(&Vector3::new(0.5, 1.0, 0.0), &a, &b, &b);
+        assert_eq!(point, Vector3::new(0.5, 0.5, 0.0));
+    }
+}
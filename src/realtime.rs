@@ -0,0 +1,166 @@
+//! Hard real-time mode of the generator. See [`crate::UvGenOptions::real_time`] for more info.
+
+use crate::{
+    pack::{Packing, PackingError},
+    Chart, Rect, UvGenOptions,
+};
+
+/// Disables the options, that need unbounded (or super-linear) amount of work, if real-time mode
+/// is enabled. Returns the options as is otherwise.
+pub(crate) fn bounded_options(mut options: UvGenOptions) -> UvGenOptions {
+    if options.real_time {
+        options.max_chunk_triangles = 0;
+        options.pack_restarts = 0;
+        options.seam_bias = 0.0;
+        options.spatial_locality = false;
+        options.page_count = 1;
+        options.stable_anchors = false;
+        options.virtual_page_size = 0;
+        options.upscale_thin_charts = false;
+        options.fill_atlas = false;
+        options.verify_padding = false;
+        options.max_chart_size = f32::INFINITY;
+    }
+    options
+}
+
+/// Packs the meshes in a single pass of "next fit decreasing height" shelf packer: meshes are
+/// sorted by their height and put in rows (shelves) from left to right, a new row starts when
+/// the next mesh does not fit into the current one. Rows of such packing occupy at most
+/// `2 * area + max_height` of the height of the atlas (where `area` is the total area of the
+/// rectangles of the meshes), so the scale is calculated in advance and the packing never needs
+/// another attempt. It is usually much smaller, than the one of the iterative packer.
+pub(crate) fn shelf_pack(meshes: &[Chart], spacing: f32) -> Packing {
+    trace_span!("shelf_pack");
+
+    let twice_spacing = 2.0 * spacing;
+    let size = |mesh: &Chart| (mesh.width().max(0.0), mesh.height().max(0.0));
+    let (mut area, mut perimeter, mut max_width, mut max_height) = (0.0, 0.0, 0.0f32, 0.0f32);
+    for mesh in meshes {
+        let (w, h) = size(mesh);
+        area += w * h;
+        perimeter += w + h;
+        max_width = max_width.max(w);
+        max_height = max_height.max(h);
+    }
+
+    // Rectangles are `scale * w + 2 * spacing` by `scale * h + 2 * spacing`, so the height of the
+    // packing is bounded by `a * scale^2 + b * scale + c`.
+    let a = 2.0 * area;
+    let b = 2.0 * twice_spacing * perimeter + max_height;
+    let c = 2.0 * meshes.len() as f32 * twice_spacing * twice_spacing + twice_spacing - 1.0;
+    if c >= 0.0 {
+        let count = meshes.len().max(1) as f32;
+        return Packing {
+            error: Some(PackingError::SpacingTooLarge {
+                spacing,
+                max_spacing: ((1.0 + 8.0 * count).sqrt() - 1.0) / (8.0 * count),
+            }),
+            ..Default::default()
+        };
+    }
+    let mut scale = if a > 0.0 {
+        (-b + (b * b - 4.0 * a * c).sqrt()) / (2.0 * a)
+    } else if b > 0.0 {
+        -c / b
+    } else {
+        1.0
+    };
+    if max_width > 0.0 {
+        scale = scale.min((1.0 - twice_spacing) / max_width);
+    }
+    // Leave some room for rounding errors.
+    scale *= 0.999;
+
+    let mut order = (0..meshes.len()).collect::<Vec<_>>();
+    order.sort_by(|&i, &j| size(&meshes[j]).1.total_cmp(&size(&meshes[i]).1));
+
+    let mut rects = vec![Rect::default(); meshes.len()];
+    let mut free_rects = Vec::new();
+    let (mut x, mut y, mut row_height) = (0.0, 0.0, 0.0);
+    for mesh_index in order {
+        let (w, h) = size(&meshes[mesh_index]);
+        let (w, h) = (w * scale + twice_spacing, h * scale + twice_spacing);
+        if x > 0.0 && x + w > 1.0 {
+            free_rects.push(Rect::new(x, y, 1.0 - x, row_height));
+            (x, y, row_height) = (0.0, y + row_height, 0.0);
+        }
+        if row_height == 0.0 {
+            row_height = h;
+        }
+        rects[mesh_index] = Rect::new(x, y, w, h);
+        x += w;
+    }
+    free_rects.push(Rect::new(x, y, 1.0 - x, row_height));
+    free_rects.push(Rect::new(0.0, y + row_height, 1.0, 1.0 - y - row_height));
+    free_rects.retain(|rect| rect.w() > 0.0 && rect.h() > 0.0);
+
+    Packing {
+        rects,
+        scale,
+        free_rects,
+        ..Default::default()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::UvGenOptions;
+
+    #[test]
+    fn test_real_time() {
+        let mesh = crate::test_meshes::uv_sphere(32, 16);
+        let generate = |real_time| {
+            crate::generate_uvs_with_options(
+                mesh.vertices.iter().cloned(),
+                mesh.triangles.iter().cloned(),
+                &UvGenOptions {
+                    real_time,
+                    pack_restarts: 4,
+                    ..Default::default()
+                },
+            )
+            .unwrap()
+        };
+        let (regular, real_time) = (generate(false), generate(true));
+        assert_eq!(
+            real_time.pack_stats.chart_count,
+            regular.pack_stats.chart_count
+        );
+        assert!(real_time.pack_stats.scale > 0.0);
+        assert!(real_time.pack_stats.scale <= regular.pack_stats.scale);
+        assert!(real_time.pack_stats.packing_error.is_none());
+
+        // Every chart is inside the atlas and does not overlap with other charts.
+        let rects = real_time
+            .pack_stats
+            .charts
+            .iter()
+            .map(|chart| chart.rect)
+            .collect::<Vec<_>>();
+        for (i, rect) in rects.iter().enumerate() {
+            assert!(rect.x() >= 0.0 && rect.y() >= 0.0);
+            assert!(rect.x() + rect.w() <= 1.0 && rect.y() + rect.h() <= 1.0);
+            for other in rects[i + 1..].iter() {
+                assert!(
+                    rect.x() + rect.w() <= other.x()
+                        || other.x() + other.w() <= rect.x()
+                        || rect.y() + rect.h() <= other.y()
+                        || other.y() + other.h() <= rect.y()
+                );
+            }
+        }
+        for tex_coord in real_time.second_tex_coords.iter() {
+            assert!((0.0..=1.0).contains(&tex_coord.x) && (0.0..=1.0).contains(&tex_coord.y));
+        }
+
+        // Spacing alone does not fit into the atlas.
+        let chart = crate::Chart::from_triangles(vec![0], &[Default::default()]);
+        let packing = super::shelf_pack(&vec![chart; 4], 0.4);
+        assert!(packing.rects.is_empty());
+        assert!(matches!(
+            packing.error,
+            Some(crate::PackingError::SpacingTooLarge { .. })
+        ));
+    }
+}
@@ -0,0 +1,112 @@
+//! Splitting of vertices at the boundaries between the sides of the box. The splits are collected
+//! first (in parallel, if `parallel` feature is enabled), without modifying the mesh, and then
+//! applied in a single deterministic pass.
+
+use crate::{new_vertex_index, parallel, SurfaceDataPatch};
+use nalgebra::Vector3;
+use std::collections::HashMap;
+
+/// Returns corners of the triangles of every side of the box (as pairs of indices of a triangle
+/// and its corner), which vertices must be replaced with clones. A vertex, that is used by
+/// multiple sides, stays with the last one of them, while every other side gets its own clone.
+/// Corners are listed in the order of the triangles of the sides, so the result does not depend
+/// on the amount of threads. Returns `None` if a triangle references a vertex that does not
+/// exist.
+pub(crate) fn collect_splits(
+    vertex_count: usize,
+    triangles: &[[u32; 3]],
+    faces: &[Vec<usize>; 6],
+    deterministic: bool,
+) -> Option<[Vec<(usize, usize)>; 6]> {
+    // Bit mask of the sides, that use every vertex.
+    let mut vertex_faces = vec![0u8; vertex_count];
+    for (face, face_triangles) in faces.iter().enumerate() {
+        for &triangle_index in face_triangles {
+            for &index in triangles.get(triangle_index)? {
+                *vertex_faces.get_mut(index as usize)? |= 1 << face;
+            }
+        }
+    }
+
+    let mut splits = <[Vec<(usize, usize)>; 6]>::default();
+    for (face, face_triangles) in faces.iter().enumerate() {
+        let later_faces = !((2u8 << face) - 1);
+        let chunk_len = parallel::chunk_len(face_triangles.len(), deterministic);
+        let chunks = parallel::map_chunks(face_triangles, chunk_len, |_, chunk| {
+            let mut chunk_splits = Vec::new();
+            for &triangle_index in chunk {
+                for (corner, &index) in triangles[triangle_index].iter().enumerate() {
+                    if vertex_faces[index as usize] & later_faces != 0 {
+                        chunk_splits.push((triangle_index, corner));
+                    }
+                }
+            }
+            chunk_splits
+        });
+        splits[face] = chunks.into_iter().flatten().collect();
+    }
+    Some(splits)
+}
+
+/// Applies the splits, that were collected by [`collect_splits`]. Triangles of the same side
+/// share a single clone of every split vertex, so the side stays connected. Clones are pushed to
+/// the vertices and their sources to [`SurfaceDataPatch::additional_vertices`]. Returns `None` if
+/// vertex indices overflow or are out of range.
+pub(crate) fn apply_splits(
+    vertices: &mut Vec<Vector3<f32>>,
+    triangles: &mut [[u32; 3]],
+    splits: &[Vec<(usize, usize)>; 6],
+    patch: &mut SurfaceDataPatch,
+) -> Option<()> {
+    for face_splits in splits.iter() {
+        let mut clones = HashMap::new();
+        for &(triangle_index, corner) in face_splits {
+            let vertex_index = triangles.get_mut(triangle_index)?.get_mut(corner)?;
+            *vertex_index = match clones.get(vertex_index) {
+                Some(&clone) => clone,
+                None => {
+                    let clone = new_vertex_index(vertices.len())?;
+                    vertices.push(*vertices.get(*vertex_index as usize)?);
+                    patch.additional_vertices.push(*vertex_index);
+                    clones.insert(*vertex_index, clone);
+                    clone
+                }
+            };
+        }
+    }
+    Some(())
+}
+
+#[cfg(test)]
+mod test {
+    #[test]
+    fn test_seams() {
+        let mesh = crate::test_meshes::cube();
+        let patch = crate::generate_uvs(
+            mesh.vertices.iter().cloned(),
+            mesh.triangles.iter().cloned(),
+            0.005,
+        )
+        .unwrap();
+        // Every corner of the cube is shared by three sides, so it gets two clones, and every
+        // side is a single chart.
+        assert_eq!(patch.additional_vertices.len(), 16);
+        assert_eq!(patch.pack_stats.chart_count, 6);
+        for source in 0..mesh.vertices.len() as u32 {
+            let copies = patch
+                .additional_vertices
+                .iter()
+                .filter(|&&added| added == source)
+                .count();
+            assert_eq!(copies, 2);
+        }
+
+        let faces = [vec![0, 1], vec![2], vec![], vec![], vec![], vec![3]];
+        let triangles = [[0, 1, 2], [0, 2, 3], [0, 3, 4], [3, 4, 5]];
+        let splits = super::collect_splits(6, &triangles, &faces, true).unwrap();
+        assert_eq!(splits[0], [(0, 0), (1, 0), (1, 2)]);
+        assert_eq!(splits[1], [(2, 1), (2, 2)]);
+        assert!(splits[5].is_empty());
+        assert!(super::collect_splits(5, &triangles, &faces, true).is_none());
+    }
+}
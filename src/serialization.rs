@@ -0,0 +1,259 @@
+//! Saving and loading of options with `serde`. Available only with `serde` feature, see
+//! [`crate::UvGenOptions`] docs for more info.
+
+#[cfg(any(feature = "toml", feature = "json"))]
+use crate::UvGenOptions;
+
+/// Serializes floats, that could be infinite (like [`crate::UvGenOptions::max_stretch`]), as
+/// numbers if they're finite and as `"inf"`, `"-inf"` or `"nan"` strings otherwise, because JSON
+/// has no literals for such values. Numbers and TOML `inf` and `nan` literals are read as is.
+pub(crate) mod float {
+    use serde::{de, Deserializer, Serializer};
+    use std::fmt::Formatter;
+
+    pub fn serialize<S: Serializer>(value: &f32, serializer: S) -> Result<S::Ok, S::Error> {
+        if value.is_finite() {
+            serializer.serialize_f32(*value)
+        } else if value.is_nan() {
+            serializer.serialize_str("nan")
+        } else if value.is_sign_positive() {
+            serializer.serialize_str("inf")
+        } else {
+            serializer.serialize_str("-inf")
+        }
+    }
+
+    struct Visitor;
+
+    impl de::Visitor<'_> for Visitor {
+        type Value = f32;
+
+        fn expecting(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+            write!(f, "a number, \"inf\", \"-inf\" or \"nan\"")
+        }
+
+        fn visit_f64<E: de::Error>(self, value: f64) -> Result<f32, E> {
+            Ok(value as f32)
+        }
+
+        fn visit_i64<E: de::Error>(self, value: i64) -> Result<f32, E> {
+            Ok(value as f32)
+        }
+
+        fn visit_u64<E: de::Error>(self, value: u64) -> Result<f32, E> {
+            Ok(value as f32)
+        }
+
+        fn visit_str<E: de::Error>(self, value: &str) -> Result<f32, E> {
+            match value {
+                "inf" => Ok(f32::INFINITY),
+                "-inf" => Ok(f32::NEG_INFINITY),
+                "nan" => Ok(f32::NAN),
+                _ => Err(E::invalid_value(de::Unexpected::Str(value), &self)),
+            }
+        }
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<f32, D::Error> {
+        deserializer.deserialize_any(Visitor)
+    }
+}
+
+/// TOML floats are 64-bit, so `f32` values are written with every digit of their `f64` casts
+/// (`0.004999999888241291` instead of `0.005`). Every float of the options is `f32`, so such
+/// floats are replaced with the shortest ones, that are read back as the same `f32`.
+#[cfg(feature = "toml")]
+fn shorten_floats(value: &mut toml::Value) {
+    match value {
+        toml::Value::Float(float) if float.is_finite() => {
+            *float = (*float as f32).to_string().parse().unwrap_or(*float);
+        }
+        toml::Value::Array(items) => items.iter_mut().for_each(shorten_floats),
+        toml::Value::Table(table) => table
+            .iter_mut()
+            .for_each(|(_, value)| shorten_floats(value)),
+        _ => (),
+    }
+}
+
+#[cfg(feature = "toml")]
+impl UvGenOptions {
+    /// Writes the options as a TOML document, so per-asset settings could be stored in version
+    /// control and loaded by a baking tool with [`Self::from_toml`]. Available only with `toml`
+    /// feature:
+    ///
+    /// ```rust
+    /// let options = uvgen::UvGenOptions {
+    ///     spacing: 0.01,
+    ///     resolution: 512,
+    ///     weld: Some(uvgen::WeldTolerance::Relative(0.001)),
+    ///     ..Default::default()
+    /// };
+    /// let toml = options.to_toml().unwrap();
+    /// assert!(toml.contains("spacing = 0.01\n"));
+    /// assert_eq!(uvgen::UvGenOptions::from_toml(&toml).unwrap(), options);
+    /// ```
+    ///
+    /// Enums are written as snake case strings, or as tables if they have data. Unset
+    /// [`Self::weld`] is omitted. Fails if [`Self::seed`] is larger than `i64::MAX`, because
+    /// TOML integers are signed.
+    pub fn to_toml(&self) -> Result<String, toml::ser::Error> {
+        let mut value = toml::Value::try_from(self)?;
+        shorten_floats(&mut value);
+        toml::to_string(&value)
+    }
+
+    /// Reads the options from a TOML document, for example the one written by [`Self::to_toml`].
+    /// Options, that are missing in the document, have their default values:
+    ///
+    /// ```rust
+    /// let options = uvgen::UvGenOptions::from_toml(
+    ///     r#"
+    ///     ## Lightmap settings of a large building.
+    ///     spacing = 0.002
+    ///     density = [1.0, 2.0, 1.0]
+    ///     needle_handling = "reproject"
+    ///     "#,
+    /// )
+    /// .unwrap();
+    /// assert_eq!(options.spacing, 0.002);
+    /// assert_eq!(options.needle_handling, uvgen::NeedleHandling::Reproject);
+    ///
+    /// assert!(uvgen::UvGenOptions::from_toml("spaceing = 0.002").is_err());
+    /// ```
+    ///
+    /// Unknown and repeated options are errors, so typos do not silently fall back to defaults.
+    pub fn from_toml(toml: &str) -> Result<Self, toml::de::Error> {
+        toml::from_str(toml)
+    }
+}
+
+#[cfg(feature = "json")]
+impl UvGenOptions {
+    /// Writes the options as a pretty-printed JSON object, like [`Self::to_toml`] does for TOML.
+    /// Available only with `json` feature:
+    ///
+    /// ```rust
+    /// let options = uvgen::UvGenOptions {
+    ///     spacing: 0.01,
+    ///     ..Default::default()
+    /// };
+    /// let json = options.to_json().unwrap();
+    /// assert!(json.contains("\"spacing\": 0.01"));
+    /// assert!(json.contains("\"max_stretch\": \"inf\""));
+    /// assert_eq!(uvgen::UvGenOptions::from_json(&json).unwrap(), options);
+    /// ```
+    ///
+    /// Infinite floats are written as `"inf"` strings, because JSON has no literal for them.
+    pub fn to_json(&self) -> Result<String, serde_json::Error> {
+        serde_json::to_string_pretty(self)
+    }
+
+    /// Reads the options from a JSON object, for example the one written by [`Self::to_json`].
+    /// Options, that are missing in the object, have their default values. Unknown and repeated
+    /// options are errors.
+    pub fn from_json(json: &str) -> Result<Self, serde_json::Error> {
+        serde_json::from_str(json)
+    }
+}
+
+#[cfg(all(test, feature = "toml", feature = "json"))]
+mod test {
+    use crate::{
+        CoordinateSystem, DoubleSidedHandling, NeedleHandling, RepeatedIndexHandling,
+        TexelRounding, UvGenOptions, WeldTolerance,
+    };
+    use nalgebra::{Vector2, Vector3};
+
+    #[test]
+    fn test_serialization() {
+        let default = UvGenOptions::default();
+        let options = UvGenOptions {
+            spacing: 1.0 / 3.0,
+            tiny_mesh_threshold: 16,
+            max_stretch: 0.25,
+            density: Vector3::new(1.0, 2.0, 1.0e-10),
+            seed: u64::MAX >> 1,
+            extra_rects: vec![Vector2::new(0.25, 0.5), Vector2::new(0.125, 0.125)],
+            weld: Some(WeldTolerance::Ulps(4)),
+            needle_handling: NeedleHandling::Isolate,
+            repeated_index_handling: RepeatedIndexHandling::Sentinel(Vector2::new(-1.0, 2.0)),
+            double_sided_handling: DoubleSidedHandling::Share,
+            texel_rounding: TexelRounding::Nearest,
+            coordinate_system: CoordinateSystem::ZUpLeftHanded,
+            real_time: true,
+            ..Default::default()
+        };
+
+        for options in [&default, &options] {
+            let toml = options.to_toml().unwrap();
+            assert_eq!(&UvGenOptions::from_toml(&toml).unwrap(), options);
+            let json = options.to_json().unwrap();
+            assert_eq!(&UvGenOptions::from_json(&json).unwrap(), options);
+        }
+        assert_eq!(UvGenOptions::from_toml("").unwrap(), default);
+        assert_eq!(UvGenOptions::from_json("{}").unwrap(), default);
+
+        let toml = options.to_toml().unwrap();
+        assert!(toml.contains("spacing = 0.33333334\n"));
+        assert!(toml.contains("density = [1.0, 2.0, 0.0000000001]\n"));
+        assert!(toml.contains("max_chart_size = \"inf\"\n"));
+        assert!(toml.contains("coordinate_system = \"z_up_left_handed\"\n"));
+        assert!(!default.to_toml().unwrap().contains("weld"));
+
+        // Per-mesh data is neither written nor read.
+        let options = UvGenOptions {
+            triangle_importance: vec![2.0; 4],
+            locked_vertices: vec![true; 4],
+            ..Default::default()
+        };
+        let toml = options.to_toml().unwrap();
+        assert!(!toml.contains("triangle_importance") && !toml.contains("locked_vertices"));
+        assert_eq!(UvGenOptions::from_toml(&toml).unwrap(), default);
+        let options = UvGenOptions {
+            seed: u64::MAX,
+            ..Default::default()
+        };
+        assert!(options.to_toml().is_err());
+        assert_eq!(
+            UvGenOptions::from_json(&options.to_json().unwrap()).unwrap(),
+            options
+        );
+
+        // TOML literals of non-finite floats are read as well.
+        let options = UvGenOptions::from_toml("max_stretch = inf\nneedle_ratio = nan").unwrap();
+        assert_eq!(options.max_stretch, f32::INFINITY);
+        assert!(options.needle_ratio.is_nan());
+        let options = UvGenOptions::from_toml(
+            "weld = { absolute = 0.5 }\nrepeated_index_handling = \"fail\"\nseed = 1_000",
+        )
+        .unwrap();
+        assert_eq!(options.weld, Some(WeldTolerance::Absolute(0.5)));
+        assert_eq!(options.repeated_index_handling, RepeatedIndexHandling::Fail);
+        assert_eq!(options.seed, 1000);
+
+        for invalid in [
+            "spaceing = 0.1",
+            "locked_vertices = []",
+            "triangle_importance = [2.0]",
+            "seed = 1\nseed = 2",
+            "seed = -1",
+            "spacing = \"0.1\"",
+            "density = [1.0, 1.0]",
+            "chart_colors = 1",
+            "needle_handling = \"unknown\"",
+            "max_stretch = \"infinity\"",
+            "weld = { absolute = 0.1, ulps = 1 }",
+        ] {
+            assert!(UvGenOptions::from_toml(invalid).is_err(), "{invalid}");
+        }
+        for invalid in [
+            r#"{"spaceing": 0.1}"#,
+            r#"{"cancellation": null}"#,
+            r#"{"seed": 1, "seed": 2}"#,
+            r#"{"max_chart_size": null}"#,
+        ] {
+            assert!(UvGenOptions::from_json(invalid).is_err(), "{invalid}");
+        }
+    }
+}
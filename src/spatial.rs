@@ -0,0 +1,204 @@
+//! Uniform grid hash over positions and mesh preprocessing utilities built on top of it. This is
+//! the same acceleration structure, that is used by the generator itself (for example, for vertex
+//! welding, see [`crate::UvGenOptions::weld`]), so there's no need to pull another crate for
+//! simple mesh preprocessing.
+
+use crate::{adjacency, WeldTolerance};
+use nalgebra::Vector3;
+use std::collections::HashMap;
+
+pub use crate::weld::weld_vertices;
+
+/// Uniform grid, that stores indices of points in the cells they belong to. Allows to quickly
+/// find points near a given position.
+///
+/// ```rust
+/// # use nalgebra::Vector3;
+/// use uvgen::spatial::SpatialHash;
+///
+/// let points = [
+///     Vector3::new(0.0, 0.0, 0.0),
+///     Vector3::new(0.05, 0.0, 0.0),
+///     Vector3::new(1.0, 0.0, 0.0),
+/// ];
+/// let mut hash = SpatialHash::new(0.1);
+/// for (index, point) in points.iter().enumerate() {
+///     hash.insert(index as u32, point);
+/// }
+/// let mut found = Vec::new();
+/// hash.query_within(&Vector3::new(0.01, 0.0, 0.0), 0.1, &points, |index| {
+///     found.push(index)
+/// });
+/// found.sort();
+/// assert_eq!(found, [0, 1]);
+/// ```
+#[derive(Clone, Debug)]
+pub struct SpatialHash {
+    cell_size: f32,
+    cells: HashMap<[i32; 3], Vec<u32>>,
+}
+
+impl SpatialHash {
+    /// Creates new hash with the given size of cells. The size should be comparable with the
+    /// radius of queries.
+    pub fn new(cell_size: f32) -> Self {
+        Self {
+            cell_size: if cell_size > 0.0 && cell_size.is_finite() {
+                cell_size
+            } else {
+                f32::MIN_POSITIVE
+            },
+            cells: Default::default(),
+        }
+    }
+
+    /// Returns size of the cells.
+    pub fn cell_size(&self) -> f32 {
+        self.cell_size
+    }
+
+    /// Returns total amount of points in the hash.
+    pub fn len(&self) -> usize {
+        self.cells.values().map(Vec::len).sum()
+    }
+
+    /// Returns `true` if there are no points in the hash.
+    pub fn is_empty(&self) -> bool {
+        self.cells.is_empty()
+    }
+
+    /// Removes all the points from the hash.
+    pub fn clear(&mut self) {
+        self.cells.clear();
+    }
+
+    /// Returns the cell that contains the given position. Cells of positions outside of `i32`
+    /// range are clamped.
+    fn cell(&self, position: &Vector3<f32>) -> [i32; 3] {
+        position.map(|c| (c / self.cell_size).floor() as i32).into()
+    }
+
+    /// Adds the point with the given index.
+    pub fn insert(&mut self, index: u32, position: &Vector3<f32>) {
+        self.cells
+            .entry(self.cell(position))
+            .or_default()
+            .push(index);
+    }
+
+    /// Calls the given function for every point, that is possibly closer to the given position
+    /// than the given radius. Actual distance must be checked by the caller (see
+    /// [`Self::query_within`]). Points of a cell are visited in the order of insertion.
+    pub fn query(&self, position: &Vector3<f32>, radius: f32, mut func: impl FnMut(u32)) {
+        let offset = Vector3::repeat(radius.abs());
+        let min = self.cell(&(position - offset));
+        let max = self.cell(&(position + offset));
+        let range_cells = (0..3)
+            .map(|i| max[i] as i64 - min[i] as i64 + 1)
+            .fold(1i64, |count, extent| count.saturating_mul(extent));
+        if range_cells > self.cells.len() as i64 {
+            // The range is larger than the populated part of the grid, it is faster to check
+            // every cell.
+            let mut cells = self
+                .cells
+                .iter()
+                .filter(|(cell, _)| (0..3).all(|i| cell[i] >= min[i] && cell[i] <= max[i]))
+                .collect::<Vec<_>>();
+            // Keep the order of cells the same as in the other branch.
+            cells.sort_unstable_by_key(|(cell, _)| **cell);
+            for (_, indices) in cells {
+                for &index in indices {
+                    func(index);
+                }
+            }
+            return;
+        }
+        for x in min[0]..=max[0] {
+            for y in min[1]..=max[1] {
+                for z in min[2]..=max[2] {
+                    if let Some(cell) = self.cells.get(&[x, y, z]) {
+                        for &index in cell {
+                            func(index);
+                        }
+                    }
+                }
+            }
+        }
+    }
+
+    /// Calls the given function for every point, that is not farther from the given position
+    /// than the given radius. `positions` must contain positions of the points, that were added
+    /// to the hash. Points with indices outside of `positions` are ignored.
+    pub fn query_within(
+        &self,
+        position: &Vector3<f32>,
+        radius: f32,
+        positions: &[Vector3<f32>],
+        mut func: impl FnMut(u32),
+    ) {
+        self.query(position, radius, |index| {
+            if let Some(other) = positions.get(index as usize) {
+                if (other - position).norm() <= radius {
+                    func(index);
+                }
+            }
+        })
+    }
+}
+
+/// Calculates adjacency of the triangles (see [`crate::triangle_adjacency`] for the layout),
+/// where vertices within the given tolerance from each other are considered as the same vertex.
+/// This allows to find neighbours of triangles of meshes with split vertices (for example, at
+/// normal or UV seams).
+pub fn welded_adjacency(
+    vertices: &[Vector3<f32>],
+    triangles: &[[u32; 3]],
+    tolerance: WeldTolerance,
+) -> Vec<[Option<u32>; 3]> {
+    let remap = weld_vertices(vertices, tolerance);
+    adjacency::adjacency_by(triangles, |index| {
+        remap.get(index as usize).cloned().unwrap_or(index)
+    })
+}
+
+#[cfg(test)]
+mod test {
+    use super::SpatialHash;
+    use crate::WeldTolerance;
+    use nalgebra::Vector3;
+
+    #[test]
+    fn test_large_query() {
+        let mut hash = SpatialHash::new(0.001);
+        hash.insert(0, &Vector3::new(0.0, 0.0, 0.0));
+        hash.insert(1, &Vector3::new(1000.0, 0.0, 0.0));
+        hash.insert(2, &Vector3::new(-1000.0, 0.0, 0.0));
+        assert_eq!(hash.len(), 3);
+
+        let mut found = Vec::new();
+        hash.query(&Vector3::zeros(), 1.0e6, |index| found.push(index));
+        assert_eq!(found, [2, 0, 1]);
+    }
+
+    #[test]
+    fn test_welded_adjacency() {
+        // Two triangles of a quad with split vertices along the shared edge.
+        let vertices = [
+            Vector3::new(0.0, 0.0, 0.0),
+            Vector3::new(1.0, 0.0, 0.0),
+            Vector3::new(1.0, 1.0, 0.0),
+            Vector3::new(0.0, 0.0, 0.0),
+            Vector3::new(1.0, 1.0, 0.0),
+            Vector3::new(0.0, 1.0, 0.0),
+        ];
+        let triangles = [[0, 1, 2], [3, 4, 5]];
+        assert_eq!(
+            crate::triangle_adjacency(&triangles),
+            [[None; 3], [None; 3]]
+        );
+        assert_eq!(
+            super::welded_adjacency(&vertices, &triangles, WeldTolerance::Absolute(0.0)),
+            [[None, None, Some(1)], [Some(0), None, None]]
+        );
+    }
+}
@@ -0,0 +1,185 @@
+//! Generation for meshes, that can't be addressed by `u32` indices. See [`generate_uvs_split`]
+//! for more info.
+
+use crate::{chunked, SurfaceDataPatch, UvGenError, UvGenOptions};
+use nalgebra::Vector3;
+use std::collections::HashMap;
+
+/// Upper bound of the amount of triangles of a part. Every triangle of the patched part has its
+/// own vertices in the worst case, so `u32` indices address every vertex of such part.
+const MAX_PART_TRIANGLES: usize = u32::MAX as usize / 3;
+
+/// A part of a mesh, that was unwrapped by [`generate_uvs_split`]. Indices of the patch are local
+/// to the part, use [`Self::vertices`] and [`Self::triangles`] to map them to the source mesh.
+#[derive(Clone, Debug, Default)]
+pub struct MeshPart {
+    /// Indices of the source triangles of the part, in ascending order. Triangle `i` of the patch
+    /// is the source triangle `triangles[i]`.
+    pub triangles: Vec<usize>,
+    /// Indices of the source vertices of the part. Vertex `i` of the part is the source vertex
+    /// `vertices[i]`, use [`SurfaceDataPatch::source_vertex`] to get the vertex of the part for a
+    /// vertex of the patched part.
+    pub vertices: Vec<u64>,
+    /// Patch of the part with its own atlas.
+    pub patch: SurfaceDataPatch,
+}
+
+/// Generates UV maps for a mesh, which vertices can't be addressed by `u32` indices (for example,
+/// merged photogrammetry scans), by splitting it into spatially coherent parts of at most
+/// `max_part_triangles` triangles. Every part is unwrapped independently with its own `u32`
+/// indices and its own atlas:
+///
+/// ```rust
+/// let mesh = uvgen::test_meshes::uv_sphere(16, 8);
+/// let triangles = mesh
+///     .triangles
+///     .iter()
+///     .map(|triangle| triangle.map(u64::from))
+///     .collect::<Vec<_>>();
+/// let parts =
+///     uvgen::generate_uvs_split(&mesh.vertices, &triangles, 64, &Default::default()).unwrap();
+/// assert!(parts.iter().all(|part| part.triangles.len() <= 64));
+/// // Source vertex of the first vertex of the first triangle of a part.
+/// let part = &parts[0];
+/// let local = part.patch.source_vertex(part.patch.triangles[0][0]).unwrap();
+/// assert_eq!(part.vertices[local as usize], triangles[part.triangles[0]][0]);
+/// ```
+///
+/// Parts are limited to `u32::MAX / 3` triangles, so the patched parts always fit into `u32`
+/// indices. Seams are added at the borders of the parts. Failure of any part fails the whole
+/// generation, triangle indices of the errors refer to the source triangles.
+pub fn generate_uvs_split(
+    vertices: &[Vector3<f32>],
+    triangles: &[[u64; 3]],
+    max_part_triangles: usize,
+    options: &UvGenOptions,
+) -> Result<Vec<MeshPart>, UvGenError> {
+    trace_span!("generate_uvs_split");
+
+    let vertex = |index: u64| {
+        usize::try_from(index)
+            .ok()
+            .and_then(|index| vertices.get(index))
+    };
+    let mut centers = Vec::with_capacity(triangles.len());
+    for (triangle_index, triangle) in triangles.iter().enumerate() {
+        let mut center = Vector3::zeros();
+        for &index in triangle {
+            center += vertex(index).ok_or(UvGenError::OutOfBoundsIndex {
+                triangle: triangle_index,
+                index,
+            })?;
+        }
+        centers.push(center.scale(1.0 / 3.0));
+    }
+
+    let max_part_triangles = max_part_triangles.clamp(1, MAX_PART_TRIANGLES);
+    chunked::partition_centers(&centers, max_part_triangles)
+        .into_iter()
+        .map(|part_triangles| {
+            // Local vertices are stored in order of their first appearance.
+            let mut part_vertices = Vec::new();
+            let mut global_to_local = HashMap::new();
+            let local_triangles = part_triangles
+                .iter()
+                .map(|&triangle_index| {
+                    triangles[triangle_index].map(|index| {
+                        *global_to_local.entry(index).or_insert_with(|| {
+                            part_vertices.push(index);
+                            (part_vertices.len() - 1) as u32
+                        })
+                    })
+                })
+                .collect::<Vec<_>>();
+            let patch = crate::try_generate_uvs_with_options(
+                part_vertices
+                    .iter()
+                    .filter_map(|&index| vertex(index).cloned()),
+                local_triangles.into_iter(),
+                options,
+            )
+            .map_err(|error| match error {
+                UvGenError::DegenerateTriangle { triangle } => UvGenError::DegenerateTriangle {
+                    triangle: part_triangles[triangle],
+                },
+                error => error,
+            })?;
+            Ok(MeshPart {
+                triangles: part_triangles,
+                vertices: part_vertices,
+                patch,
+            })
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod test {
+    use crate::{RepeatedIndexHandling, UvGenError, UvGenOptions};
+
+    #[test]
+    fn test_split() {
+        let mesh = crate::test_meshes::uv_sphere(16, 8);
+        let triangles = mesh
+            .triangles
+            .iter()
+            .map(|triangle| triangle.map(u64::from))
+            .collect::<Vec<_>>();
+
+        // Small meshes are unwrapped as a single part.
+        let parts =
+            crate::generate_uvs_split(&mesh.vertices, &triangles, usize::MAX, &Default::default())
+                .unwrap();
+        assert_eq!(parts.len(), 1);
+        assert_eq!(parts[0].triangles, (0..triangles.len()).collect::<Vec<_>>());
+
+        let parts =
+            crate::generate_uvs_split(&mesh.vertices, &triangles, 50, &Default::default()).unwrap();
+        assert!(parts.len() > 1);
+        let mut covered = vec![false; triangles.len()];
+        for part in parts.iter() {
+            assert!(part.triangles.len() <= 50);
+            assert_eq!(part.patch.triangles.len(), part.triangles.len());
+            assert!(part.patch.pack_stats.charts.iter().all(|chart| chart
+                .triangles
+                .iter()
+                .all(|&triangle| triangle < part.triangles.len())));
+            for (patched, &source) in part.patch.triangles.iter().zip(&part.triangles) {
+                assert!(!covered[source]);
+                covered[source] = true;
+                // Every vertex of the patched part maps back to a vertex of the source triangle.
+                for (&index, &expected) in patched.iter().zip(&triangles[source]) {
+                    let local = part.patch.source_vertex(index).unwrap();
+                    assert_eq!(part.vertices[local as usize], expected);
+                    let uv = part.patch.second_tex_coords[index as usize];
+                    assert!((0.0..=1.0).contains(&uv.x) && (0.0..=1.0).contains(&uv.y));
+                }
+            }
+        }
+        assert!(covered.iter().all(|&covered| covered));
+
+        // Indices, that do not fit into `u32`, are reported as is.
+        let mut invalid = triangles.clone();
+        invalid[5][2] = u32::MAX as u64 + 10;
+        assert_eq!(
+            crate::generate_uvs_split(&mesh.vertices, &invalid, 50, &Default::default())
+                .unwrap_err(),
+            UvGenError::OutOfBoundsIndex {
+                triangle: 5,
+                index: u32::MAX as u64 + 10
+            }
+        );
+
+        // Errors of the parts refer to the source triangles.
+        let mut degenerate = triangles;
+        degenerate[40][1] = degenerate[40][0];
+        let options = UvGenOptions {
+            repeated_index_handling: RepeatedIndexHandling::Fail,
+            ..Default::default()
+        };
+        assert_eq!(
+            crate::generate_uvs_split(&mesh.vertices, &degenerate, 50, &options).unwrap_err(),
+            UvGenError::DegenerateTriangle { triangle: 40 }
+        );
+    }
+}
@@ -0,0 +1,927 @@
+//! Statistics of the generation.
+
+use crate::{
+    AnchorLayout, Chart, PackingError, PaddingViolation, Rect, Stage, SurfaceDataPatch,
+    TexelRounding, TileAdjacency, UvGenOptions,
+};
+use nalgebra::{Vector2, Vector3};
+use std::{collections::HashMap, time::Duration};
+
+/// A single bin of a [`Histogram`].
+#[derive(Copy, Clone, Debug, Default, PartialEq)]
+#[cfg_attr(
+    feature = "rkyv",
+    derive(rkyv::Archive, rkyv::Serialize, rkyv::Deserialize),
+    archive(check_bytes)
+)]
+pub struct HistogramBin {
+    /// Lower bound of the bin (inclusive).
+    pub min: f32,
+    /// Upper bound of the bin (exclusive).
+    pub max: f32,
+    /// Amount of values that fell into the bin.
+    pub count: usize,
+}
+
+/// A histogram with logarithmic (power of two) bins.
+#[derive(Clone, Debug, Default, PartialEq)]
+#[cfg_attr(
+    feature = "rkyv",
+    derive(rkyv::Archive, rkyv::Serialize, rkyv::Deserialize),
+    archive(check_bytes)
+)]
+pub struct Histogram {
+    /// Bins of the histogram in ascending order. The first bin collects every value that is less
+    /// than its upper bound and the last bin collects every value that is larger than its lower
+    /// bound.
+    pub bins: Vec<HistogramBin>,
+}
+
+impl Histogram {
+    /// Creates a histogram with bins `[2^min_exponent; 2^(min_exponent + 1))`, ...,
+    /// `[2^(max_exponent - 1); 2^max_exponent)`.
+    fn new(min_exponent: i32, max_exponent: i32) -> Self {
+        Self {
+            bins: (min_exponent..max_exponent)
+                .map(|exponent| HistogramBin {
+                    min: 2.0f32.powi(exponent),
+                    max: 2.0f32.powi(exponent + 1),
+                    count: 0,
+                })
+                .collect(),
+        }
+    }
+
+    fn add(&mut self, value: f32) {
+        let index = self
+            .bins
+            .iter()
+            .position(|bin| value < bin.max)
+            .unwrap_or(self.bins.len().saturating_sub(1));
+        if let Some(bin) = self.bins.get_mut(index) {
+            bin.count += 1;
+        }
+    }
+
+    /// Adds values of the other histogram with the same bins to this one.
+    pub(crate) fn merge(&mut self, other: &Histogram) {
+        if self.bins.is_empty() {
+            self.bins = other.bins.clone();
+        } else {
+            for (bin, other_bin) in self.bins.iter_mut().zip(other.bins.iter()) {
+                bin.count += other_bin.count;
+            }
+        }
+    }
+
+    /// Returns total amount of values in the histogram.
+    pub fn total(&self) -> usize {
+        self.bins.iter().map(|bin| bin.count).sum()
+    }
+}
+
+/// Information about a chart.
+#[derive(Clone, Debug, Default, PartialEq)]
+#[cfg_attr(
+    feature = "rkyv",
+    derive(rkyv::Archive, rkyv::Serialize, rkyv::Deserialize),
+    archive(check_bytes)
+)]
+pub struct ChartInfo {
+    /// Indices of the triangles of the chart.
+    pub triangles: Vec<usize>,
+    /// Stable key of the chart, which is the lowest index of its triangles (in the source mesh).
+    /// Unlike the index of the chart, it does not depend on the packing, so it stays the same
+    /// when options, that affect only the packing, are changed. Charts are sorted by their keys.
+    pub key: usize,
+    /// Rectangle of the atlas occupied by the chart (including spacing).
+    #[cfg_attr(feature = "rkyv", with(crate::archive::RectAsArray))]
+    pub rect: Rect<f32>,
+    /// Total world-space area of the triangles of the chart.
+    pub world_area: f32,
+    /// Total area of the triangles of the chart in the atlas, `1.0` means the entire atlas.
+    pub uv_area: f32,
+    /// Index of the atlas page the chart was put on. Always zero, unless
+    /// [`crate::UvGenOptions::page_count`] is greater than one.
+    pub page: usize,
+    /// Whether the chart is thinner than [`crate::UvGenOptions::min_chart_texel_width`] texels.
+    /// Always `false`, unless [`crate::UvGenOptions::resolution`] is set.
+    pub thin: bool,
+    /// [`Self::rect`] in texels of the atlas, rounded by [`crate::UvGenOptions::texel_rounding`].
+    /// It is `None`, unless [`crate::UvGenOptions::resolution`] is set.
+    pub texel_rect: Option<TexelRect>,
+    /// Whether the texture coordinates of the chart cross the borders of texture tiles along U
+    /// and V axes, so a tiling texture wraps on the chart. Always `false`, unless
+    /// [`crate::UvGenOptions::tiling_size`] is set.
+    pub wrap: [bool; 2],
+}
+
+/// A rectangle of an atlas in texels, see [`ChartInfo::texel_rect`].
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq, Hash)]
+#[cfg_attr(
+    feature = "rkyv",
+    derive(rkyv::Archive, rkyv::Serialize, rkyv::Deserialize),
+    archive(check_bytes)
+)]
+pub struct TexelRect {
+    /// Coordinates of the first texel of the rectangle.
+    pub position: Vector2<u32>,
+    /// Size of the rectangle in texels.
+    pub size: Vector2<u32>,
+}
+
+impl TexelRect {
+    /// Converts the rectangle in UV units to texels of a (square) atlas with the given
+    /// resolution, rounding its edges by the given mode. Edges, that are within a thousandth of a
+    /// texel from a texel boundary, are snapped to it, so rounding errors of the packing do not
+    /// add whole texels. The result is clamped to the atlas:
+    ///
+    /// ```rust
+    /// # use uvgen::{Rect, TexelRect, TexelRounding};
+    /// let rect = Rect::new(0.1, 0.25, 0.2, 0.5);
+    /// let texels = TexelRect::from_rect(&rect, 16, TexelRounding::Outward);
+    /// assert_eq!((texels.position.x, texels.size.x), (1, 4));
+    /// assert_eq!((texels.position.y, texels.size.y), (4, 8));
+    /// let texels = TexelRect::from_rect(&rect, 16, TexelRounding::Inward);
+    /// assert_eq!((texels.position.x, texels.size.x), (2, 2));
+    /// ```
+    pub fn from_rect(rect: &Rect<f32>, resolution: u32, rounding: TexelRounding) -> Self {
+        const EPSILON: f32 = 1.0e-3;
+        let scale = resolution as f32;
+        let to_texels = |value: f32, is_min: bool| {
+            let value = value * scale;
+            let rounded = match (rounding, is_min) {
+                (TexelRounding::Outward, true) | (TexelRounding::Inward, false) => {
+                    (value + EPSILON).floor()
+                }
+                (TexelRounding::Outward, false) | (TexelRounding::Inward, true) => {
+                    (value - EPSILON).ceil()
+                }
+                (TexelRounding::Nearest, _) => value.round(),
+            };
+            // NaN becomes zero.
+            rounded.max(0.0).min(scale) as u32
+        };
+        let min = rect.position.map(|c| to_texels(c, true));
+        let max = (rect.position + rect.size).map(|c| to_texels(c, false));
+        Self {
+            position: min,
+            size: max.sup(&min) - min,
+        }
+    }
+}
+
+impl ChartInfo {
+    /// Returns `world_area / uv_area` ratio, which is the inverse of texel density of the chart.
+    /// Charts with larger ratio than the rest of the charts got less resolution, than they
+    /// should. Returns [`f32::INFINITY`] for charts without any area in the atlas.
+    pub fn world_to_uv_ratio(&self) -> f32 {
+        if self.uv_area > 0.0 {
+            self.world_area / self.uv_area
+        } else {
+            f32::INFINITY
+        }
+    }
+}
+
+/// Packing statistics and the state of the atlas after packing.
+#[derive(Clone, Debug, Default, PartialEq)]
+#[cfg_attr(
+    feature = "rkyv",
+    derive(rkyv::Archive, rkyv::Serialize, rkyv::Deserialize),
+    archive(check_bytes)
+)]
+pub struct PackStats {
+    /// Scale that was applied to the UV meshes to fit them into the atlas.
+    pub scale: f32,
+    /// A list of rectangles (in [0;1] range) of the atlas, that are not occupied by any UV mesh.
+    /// It could be used to put some custom data in the unused space of a lightmap.
+    #[cfg_attr(feature = "rkyv", with(rkyv::with::Map<crate::archive::RectAsArray>))]
+    pub free_rects: Vec<Rect<f32>>,
+    /// Free rectangles of every atlas page. It is empty, unless
+    /// [`crate::UvGenOptions::page_count`] is greater than one, in this case
+    /// [`Self::free_rects`] describes the first page only.
+    #[cfg_attr(
+        feature = "rkyv",
+        with(rkyv::with::Map<rkyv::with::Map<crate::archive::RectAsArray>>)
+    )]
+    pub page_free_rects: Vec<Vec<Rect<f32>>>,
+    /// Placements of the extra rectangles (see [`crate::UvGenOptions::extra_rects`]) in the
+    /// atlas, in the same order as the sizes of the rectangles. Spacing is not included.
+    #[cfg_attr(feature = "rkyv", with(rkyv::with::Map<crate::archive::RectAsArray>))]
+    pub extra_rects: Vec<Rect<f32>>,
+    /// Reason why some charts weren't packed into the atlas. It is always `None` in results of
+    /// the generator, because the generation fails with [`crate::UvGenError::PackingFailed`]
+    /// instead.
+    pub packing_error: Option<PackingError>,
+    /// Total amount of charts (UV meshes).
+    pub chart_count: usize,
+    /// Information about every chart, sorted by [`ChartInfo::key`]. Charts have the same order as
+    /// the colors of charts (see
+    /// [`crate::UvGenOptions::chart_colors`]).
+    pub charts: Vec<ChartInfo>,
+    /// Histogram of areas of charts in the atlas. Area of a chart is a fraction of the atlas
+    /// occupied by its triangles, so `1.0` means the entire atlas.
+    pub area_histogram: Histogram,
+    /// Histogram of `perimeter / sqrt(area)` ratios of charts. This ratio does not depend on the
+    /// size of a chart and it is `~4.0` for square-like charts. Large values mean that there are
+    /// lots of thin or ragged charts.
+    pub perimeter_ratio_histogram: Histogram,
+    /// Total amount of seam edges. A seam edge is an edge of the source mesh, which vertices were
+    /// split, so the edge has different texture coordinates on its sides.
+    pub seam_count: usize,
+    /// Total world-space length of the seam edges. Long seams are where lightmap artifacts
+    /// usually concentrate.
+    pub seam_length: f32,
+    /// Indices of needle triangles (see [`crate::UvGenOptions::needle_ratio`]). It is empty,
+    /// unless the detection is enabled.
+    pub needle_triangles: Vec<usize>,
+    /// Locations of the charts keyed by hashes of their content. It is `None`, unless
+    /// [`crate::UvGenOptions::stable_anchors`] is set.
+    pub anchor_layout: Option<AnchorLayout>,
+    /// Pairs of back-to-back triangles (see [`crate::UvGenOptions::double_sided_handling`]). The
+    /// first triangle of every pair precedes the second one. It is empty, unless the detection is
+    /// enabled.
+    pub double_sided_triangles: Vec<[usize; 2]>,
+    /// Pairs of charts, that are closer to each other than the spacing allows (see
+    /// [`crate::UvGenOptions::verify_padding`]). It is empty, unless the verification is enabled.
+    pub padding_violations: Vec<PaddingViolation>,
+    /// Indices of triangles, that are clockwise in the atlas (their signed area is negative),
+    /// while their front side is visible. Charts are mirrored to keep their triangles
+    /// counter-clockwise, so only triangles of folded charts and back sides of shared
+    /// double-sided triangles (see [`crate::DoubleSidedHandling::Share`]) could get here.
+    pub flipped_triangles: Vec<usize>,
+    /// Locked vertices (see [`crate::UvGenOptions::locked_vertices`]), that were duplicated
+    /// anyway, in ascending order.
+    pub split_locked_vertices: Vec<u32>,
+    /// Pairs of tiles of large charts, that share a part of the cut (see
+    /// [`crate::UvGenOptions::max_chart_size`] and [`crate::UvGenOptions::texel_density`]), sorted
+    /// by the indices of the tiles. It is empty, unless some charts were split.
+    pub tile_adjacency: Vec<TileAdjacency>,
+    /// Indices of triangles of the charts, that were smaller than
+    /// [`crate::UvGenOptions::min_chart_texels`], in ascending order. All of them form a single
+    /// chart, that occupies one texel, so they share the same texture coordinates. It is empty,
+    /// unless some charts were collapsed.
+    pub collapsed_triangles: Vec<usize>,
+    /// The triangle with the lowest texel density in the atlas, which is the blurriest part of
+    /// the lightmap. Collapsed triangles (see [`Self::collapsed_triangles`]) are ignored. It is
+    /// `None`, unless [`crate::UvGenOptions::resolution`] is set.
+    pub min_texel_density: Option<TexelDensity>,
+    /// Wall time and amount of work of every stage of the generation, in the order of the stages.
+    /// It is empty, unless [`crate::UvGenOptions::stage_timings`] is set.
+    pub stage_timings: Vec<StageTiming>,
+}
+
+/// Texel density of a triangle, see [`PackStats::min_texel_density`].
+#[derive(Copy, Clone, Debug, Default, PartialEq)]
+#[cfg_attr(
+    feature = "rkyv",
+    derive(rkyv::Archive, rkyv::Serialize, rkyv::Deserialize),
+    archive(check_bytes)
+)]
+pub struct TexelDensity {
+    /// Amount of texels per world unit at [`crate::UvGenOptions::resolution`], calculated as a
+    /// square root of the ratio of the areas of the triangle in texels and in world units.
+    pub texels_per_unit: f32,
+    /// Index of the triangle.
+    pub triangle: usize,
+    /// Index of the chart of the triangle in [`PackStats::charts`].
+    pub chart: usize,
+}
+
+/// Wall time and amount of work of a stage of the generation, see [`PackStats::stage_timings`].
+#[derive(Copy, Clone, Debug, PartialEq)]
+#[cfg_attr(
+    feature = "rkyv",
+    derive(rkyv::Archive, rkyv::Serialize, rkyv::Deserialize),
+    archive(check_bytes)
+)]
+pub struct StageTiming {
+    /// The stage.
+    pub stage: Stage,
+    /// Total wall time spent on the stage. If the mesh was split into chunks (see
+    /// [`crate::UvGenOptions::max_chunk_triangles`]), times of every chunk are summed, even if
+    /// they were processed in parallel.
+    pub elapsed: Duration,
+    /// Amount of work done at the stage: triangles for [`Stage::BoxMapping`], duplicated
+    /// vertices for [`Stage::Seams`], charts for [`Stage::Charts`] and [`Stage::Assignment`] and
+    /// attempts of the iterative packer for [`Stage::Packing`] (zero if the charts were placed
+    /// without it, for example in real-time mode).
+    pub count: usize,
+}
+
+/// Adds the time to the timing of the stage (or a new timing, if there's none yet) and sets its
+/// amount of work.
+pub(crate) fn record_stage(
+    timings: &mut Vec<StageTiming>,
+    stage: Stage,
+    elapsed: Duration,
+    count: usize,
+) {
+    match timings.iter_mut().find(|timing| timing.stage == stage) {
+        Some(timing) => {
+            timing.elapsed += elapsed;
+            timing.count = count;
+        }
+        None => timings.push(StageTiming {
+            stage,
+            elapsed,
+            count,
+        }),
+    }
+}
+
+/// Adds times and amounts of work of the other timings (of a part of the mesh) to the timings.
+pub(crate) fn merge_stages(timings: &mut Vec<StageTiming>, other: &[StageTiming]) {
+    for other in other {
+        let count = timings
+            .iter()
+            .find(|timing| timing.stage == other.stage)
+            .map_or(0, |timing| timing.count);
+        record_stage(timings, other.stage, other.elapsed, count + other.count);
+    }
+}
+
+/// A group of charts, see [`PackStats::chart_clusters`].
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct ChartCluster {
+    /// Indices of the charts of the cluster (see [`PackStats::charts`]) in ascending order.
+    pub charts: Vec<usize>,
+    /// Total area of the charts of the cluster in the atlas, `1.0` means the entire atlas. Amount
+    /// of texels of the cluster is proportional to this area.
+    pub uv_area: f32,
+}
+
+impl PackStats {
+    /// Splits the charts into the given amount of clusters with roughly equal total area in the
+    /// atlas (and thus amount of texels to bake), so a distributed baker could assign one cluster
+    /// per worker with balanced workloads:
+    ///
+    /// ```rust
+    /// let mesh = uvgen::test_meshes::cube();
+    /// let patch = uvgen::generate_uvs(
+    ///     mesh.vertices.iter().cloned(),
+    ///     mesh.triangles.iter().cloned(),
+    ///     0.005,
+    /// )
+    /// .unwrap();
+    /// let clusters = patch.pack_stats.chart_clusters(3);
+    /// assert_eq!(clusters.len(), 3);
+    /// let chart_count = clusters.iter().map(|cluster| cluster.charts.len()).sum::<usize>();
+    /// assert_eq!(chart_count, patch.pack_stats.chart_count);
+    /// ```
+    ///
+    /// The largest charts are assigned first, every chart goes to the cluster with the smallest
+    /// area so far. Clusters could be empty, if there are less charts than clusters. The result
+    /// is deterministic.
+    pub fn chart_clusters(&self, cluster_count: usize) -> Vec<ChartCluster> {
+        let mut clusters = vec![ChartCluster::default(); cluster_count];
+        let mut order = (0..self.charts.len()).collect::<Vec<_>>();
+        order.sort_by(|&a, &b| {
+            self.charts[b]
+                .uv_area
+                .total_cmp(&self.charts[a].uv_area)
+                .then(a.cmp(&b))
+        });
+        for chart in order {
+            if let Some(lightest) = clusters
+                .iter_mut()
+                .min_by(|a, b| a.uv_area.total_cmp(&b.uv_area))
+            {
+                lightest.charts.push(chart);
+                lightest.uv_area += self.charts[chart].uv_area;
+            }
+        }
+        for cluster in clusters.iter_mut() {
+            cluster.charts.sort_unstable();
+        }
+        clusters
+    }
+}
+
+/// Calculates area and perimeter of the chart in the projection space.
+fn chart_shape(
+    mesh: &Chart,
+    triangles: &[[u32; 3]],
+    projections: &[[Vector2<f32>; 3]],
+    edges: &mut HashMap<(u32, u32), (usize, f32)>,
+) -> (f32, f32) {
+    edges.clear();
+
+    let mut area = 0.0;
+    for &triangle_index in mesh.triangles.iter() {
+        let (Some(triangle), Some([a, b, c])) = (
+            triangles.get(triangle_index),
+            projections.get(triangle_index),
+        ) else {
+            continue;
+        };
+        area += (b - a).perp(&(c - a)).abs() * 0.5;
+
+        for (i, j) in [(0, 1), (1, 2), (2, 0)] {
+            let key = (triangle[i].min(triangle[j]), triangle[i].max(triangle[j]));
+            let length = [a, b, c][i].metric_distance([a, b, c][j]);
+            edges.entry(key).or_insert((0, length)).0 += 1;
+        }
+    }
+
+    // Perimeter is the total length of edges that are used only once.
+    let perimeter = edges
+        .values()
+        .filter(|(count, _)| *count == 1)
+        .map(|(_, length)| length)
+        .sum();
+
+    (area, perimeter)
+}
+
+/// Calculates total amount and length of seam edges. `vertices` are the source vertices of the
+/// mesh, `additional_vertices` and `triangles` are the ones of the patch.
+pub(crate) fn seam_stats(
+    vertices: &[Vector3<f32>],
+    additional_vertices: &[u32],
+    triangles: &[[u32; 3]],
+) -> (usize, f32) {
+    // Additional vertices could be clones of other additional vertices, so resolve them in order.
+    let mut sources = (0..vertices.len() as u32).collect::<Vec<_>>();
+    for &index in additional_vertices {
+        let source = sources.get(index as usize).cloned().unwrap_or(index);
+        sources.push(source);
+    }
+
+    // Every source edge is mapped to the first actual edge that uses it and a flag, that tells
+    // whether there's any other actual edge for it.
+    let mut edges = HashMap::<(u32, u32), ((u32, u32), bool)>::new();
+    for triangle in triangles {
+        for (i, j) in [(0, 1), (1, 2), (2, 0)] {
+            let source = |index: u32| sources.get(index as usize).cloned().unwrap_or(index);
+            let (a, b) = (source(triangle[i]), source(triangle[j]));
+            let key = (a.min(b), a.max(b));
+            let actual = if a <= b {
+                (triangle[i], triangle[j])
+            } else {
+                (triangle[j], triangle[i])
+            };
+            let entry = edges.entry(key).or_insert((actual, false));
+            if entry.0 != actual {
+                entry.1 = true;
+            }
+        }
+    }
+
+    edges
+        .iter()
+        .filter(|(_, (_, split))| *split)
+        .fold((0, 0.0), |(count, length), (&(a, b), _)| {
+            match (vertices.get(a as usize), vertices.get(b as usize)) {
+                (Some(a), Some(b)) => (count + 1, length + a.metric_distance(b)),
+                _ => (count + 1, length),
+            }
+        })
+}
+
+/// Calculates information about the packed charts. `position` must return a world-space
+/// position of a vertex with the given index.
+pub(crate) fn chart_infos(
+    meshes: &[Chart],
+    rects: &[Rect<f32>],
+    triangles: &[[u32; 3]],
+    projections: &[[Vector2<f32>; 3]],
+    pages: &[u32],
+    scale: f32,
+    position: impl Fn(u32) -> Option<Vector3<f32>>,
+) -> Vec<ChartInfo> {
+    meshes
+        .iter()
+        .zip(rects)
+        .enumerate()
+        .map(|(mesh_index, (mesh, rect))| {
+            let mut world_area = 0.0;
+            let mut uv_area = 0.0;
+            for &triangle_index in mesh.triangles.iter() {
+                if let Some(triangle) = triangles.get(triangle_index) {
+                    if let (Some(a), Some(b), Some(c)) = (
+                        position(triangle[0]),
+                        position(triangle[1]),
+                        position(triangle[2]),
+                    ) {
+                        world_area += (b - a).cross(&(c - a)).norm() * 0.5;
+                    }
+                }
+                if let Some([a, b, c]) = projections.get(triangle_index) {
+                    uv_area += (b - a).perp(&(c - a)).abs() * 0.5;
+                }
+            }
+            ChartInfo {
+                triangles: mesh.triangles.clone(),
+                key: mesh.key(),
+                rect: *rect,
+                world_area,
+                uv_area: uv_area * scale * scale,
+                page: pages.get(mesh_index).map_or(0, |&page| page as usize),
+                thin: false,
+                texel_rect: None,
+                wrap: [false; 2],
+            }
+        })
+        .collect()
+}
+
+/// Calculates rectangles of the charts in texels at the target resolution.
+pub(crate) fn fill_texel_rects(charts: &mut [ChartInfo], options: &UvGenOptions) {
+    if options.resolution == 0 {
+        return;
+    }
+    for chart in charts.iter_mut() {
+        chart.texel_rect = Some(TexelRect::from_rect(
+            &chart.rect,
+            options.resolution,
+            options.texel_rounding,
+        ));
+    }
+}
+
+/// Flags charts, that are thinner than the minimal width in texels at the target resolution.
+pub(crate) fn flag_thin_charts(charts: &mut [ChartInfo], options: &UvGenOptions) {
+    if options.resolution == 0 {
+        return;
+    }
+    let resolution = options.resolution as f32;
+    let twice_spacing = 2.0 * options.spacing;
+    for chart in charts.iter_mut() {
+        let width = (chart.rect.w() - twice_spacing).min(chart.rect.h() - twice_spacing);
+        chart.thin = width * resolution < options.min_chart_texel_width;
+    }
+}
+
+/// Finds the triangle of the charts with the lowest texel density at the target resolution.
+/// Triangles and texture coordinates of the patch must be set already. `position` must return a
+/// world-space position of a vertex with the given index.
+pub(crate) fn min_texel_density(
+    patch: &SurfaceDataPatch,
+    options: &UvGenOptions,
+    position: impl Fn(u32) -> Option<Vector3<f32>>,
+) -> Option<TexelDensity> {
+    if options.resolution == 0 {
+        return None;
+    }
+    let resolution = options.resolution as f32;
+    let collapsed = &patch.pack_stats.collapsed_triangles;
+    let mut min = None::<TexelDensity>;
+    for (chart_index, chart) in patch.pack_stats.charts.iter().enumerate() {
+        for &triangle_index in chart.triangles.iter() {
+            if collapsed.binary_search(&triangle_index).is_ok() {
+                continue;
+            }
+            let Some(density) = patch.triangles.get(triangle_index).and_then(|triangle| {
+                let [a, b, c] = [
+                    position(triangle[0])?,
+                    position(triangle[1])?,
+                    position(triangle[2])?,
+                ];
+                let world_area = (b - a).cross(&(c - a)).norm() * 0.5;
+                let [ua, ub, uc] = [
+                    patch.second_tex_coords.get(triangle[0] as usize)?,
+                    patch.second_tex_coords.get(triangle[1] as usize)?,
+                    patch.second_tex_coords.get(triangle[2] as usize)?,
+                ];
+                let uv_area = (ub - ua).perp(&(uc - ua)).abs() * 0.5;
+                // Degenerate triangles do not have any density.
+                (world_area > 0.0).then(|| resolution * (uv_area / world_area).sqrt())
+            }) else {
+                continue;
+            };
+            if min.map_or(true, |min| density < min.texels_per_unit) {
+                min = Some(TexelDensity {
+                    texels_per_unit: density,
+                    triangle: triangle_index,
+                    chart: chart_index,
+                });
+            }
+        }
+    }
+    min
+}
+
+/// Returns indices of needle triangles, if the detection is enabled. `position` must return a
+/// world-space position of a vertex with the given index.
+pub(crate) fn needle_triangles(
+    triangles: &[[u32; 3]],
+    options: &UvGenOptions,
+    position: impl Fn(u32) -> Option<Vector3<f32>>,
+) -> Vec<usize> {
+    if !options.needle_ratio.is_finite() {
+        return Vec::new();
+    }
+    triangles
+        .iter()
+        .enumerate()
+        .filter_map(|(index, triangle)| {
+            let [a, b, c] = [
+                position(triangle[0])?,
+                position(triangle[1])?,
+                position(triangle[2])?,
+            ];
+            (crate::needle_ratio(&a, &b, &c) > options.needle_ratio).then_some(index)
+        })
+        .collect()
+}
+
+/// Calculates histograms of chart areas and perimeter ratios.
+pub(crate) fn chart_histograms(
+    meshes: &[Chart],
+    triangles: &[[u32; 3]],
+    projections: &[[Vector2<f32>; 3]],
+    scale: f32,
+) -> (Histogram, Histogram) {
+    let mut area_histogram = Histogram::new(-24, 0);
+    let mut perimeter_ratio_histogram = Histogram::new(1, 12);
+    let mut edges = HashMap::new();
+    for mesh in meshes {
+        let (area, perimeter) = chart_shape(mesh, triangles, projections, &mut edges);
+        area_histogram.add(area * scale * scale);
+        if area > 0.0 {
+            perimeter_ratio_histogram.add(perimeter / area.sqrt());
+        } else if let Some(last) = perimeter_ratio_histogram.bins.last_mut() {
+            // Degenerate charts are infinitely thin.
+            last.count += 1;
+        }
+    }
+    (area_histogram, perimeter_ratio_histogram)
+}
+
+#[cfg(test)]
+mod test {
+    use crate::{Chart, ChartInfo, Histogram, PackStats, Stage, TexelRounding, UvGenOptions};
+    use nalgebra::{Vector2, Vector3};
+
+    #[test]
+    fn test_chart_histograms() {
+        // Unit square made of two triangles.
+        let triangles = [[0, 1, 2], [0, 2, 3]];
+        let projections = [
+            [
+                Vector2::new(0.0, 0.0),
+                Vector2::new(1.0, 0.0),
+                Vector2::new(1.0, 1.0),
+            ],
+            [
+                Vector2::new(0.0, 0.0),
+                Vector2::new(1.0, 1.0),
+                Vector2::new(0.0, 1.0),
+            ],
+        ];
+        let mut mesh = Chart::new(0);
+        mesh.triangles.push(1);
+
+        let (area, perimeter_ratio) =
+            super::chart_histograms(&[mesh], &triangles, &projections, 0.5);
+        assert_eq!(area.total(), 1);
+        assert_eq!(perimeter_ratio.total(), 1);
+
+        // Area of the square is 1.0, scaled by 0.5 it gives 0.25 of the atlas.
+        let bin = area.bins.iter().find(|bin| bin.count == 1).unwrap();
+        assert!(bin.min <= 0.25 && 0.25 < bin.max);
+
+        // Perimeter of the square is 4.0, so the ratio is 4.0.
+        let bin = perimeter_ratio
+            .bins
+            .iter()
+            .find(|bin| bin.count == 1)
+            .unwrap();
+        assert!(bin.min <= 4.0 && 4.0 < bin.max);
+
+        assert_eq!(Histogram::default().total(), 0);
+    }
+
+    #[test]
+    fn test_chart_clusters() {
+        let stats = PackStats {
+            charts: [0.4, 0.1, 0.3, 0.2, 0.2, 0.1, 0.1]
+                .into_iter()
+                .map(|uv_area| ChartInfo {
+                    uv_area,
+                    ..Default::default()
+                })
+                .collect(),
+            ..Default::default()
+        };
+        let clusters = stats.chart_clusters(2);
+        assert_eq!(clusters.len(), 2);
+        for cluster in clusters.iter() {
+            assert!((cluster.uv_area - 0.7).abs() < 1.0e-6);
+        }
+        let mut charts = clusters
+            .iter()
+            .flat_map(|cluster| cluster.charts.iter().cloned())
+            .collect::<Vec<_>>();
+        charts.sort_unstable();
+        assert_eq!(charts, (0..7).collect::<Vec<_>>());
+        assert!(stats.chart_clusters(0).is_empty());
+    }
+
+    #[test]
+    fn test_thin_charts() {
+        // A large quad and a long thin strip next to it.
+        let vertices = [
+            Vector3::new(0.0, 0.0, 0.0),
+            Vector3::new(4.0, 0.0, 0.0),
+            Vector3::new(4.0, 4.0, 0.0),
+            Vector3::new(0.0, 4.0, 0.0),
+            Vector3::new(0.0, 5.0, 0.0),
+            Vector3::new(4.0, 5.0, 0.0),
+            Vector3::new(4.0, 5.01, 0.0),
+            Vector3::new(0.0, 5.01, 0.0),
+        ];
+        let triangles: [[u32; 3]; 4] = [[0, 1, 2], [0, 2, 3], [4, 5, 6], [4, 6, 7]];
+        let generate = |upscale_thin_charts| {
+            crate::generate_uvs_with_options(
+                vertices.iter().cloned(),
+                triangles.iter().cloned(),
+                &UvGenOptions {
+                    resolution: 64,
+                    upscale_thin_charts,
+                    ..Default::default()
+                },
+            )
+            .unwrap()
+        };
+
+        let patch = generate(false);
+        let thin = patch
+            .pack_stats
+            .charts
+            .iter()
+            .filter(|chart| chart.thin)
+            .collect::<Vec<_>>();
+        assert_eq!(thin.len(), 1);
+        assert_eq!(thin[0].triangles, [2, 3]);
+
+        let patch = generate(true);
+        assert!(patch.pack_stats.charts.iter().all(|chart| !chart.thin));
+    }
+
+    #[test]
+    fn test_texel_rects() {
+        let mesh = crate::test_meshes::cube();
+        let generate = |resolution, texel_rounding| {
+            crate::generate_uvs_with_options(
+                mesh.vertices.iter().cloned(),
+                mesh.triangles.iter().cloned(),
+                &UvGenOptions {
+                    resolution,
+                    texel_rounding,
+                    ..Default::default()
+                },
+            )
+            .unwrap()
+        };
+        let patch = generate(0, TexelRounding::Outward);
+        assert!(patch
+            .pack_stats
+            .charts
+            .iter()
+            .all(|c| c.texel_rect.is_none()));
+
+        let outward = generate(256, TexelRounding::Outward);
+        let inward = generate(256, TexelRounding::Inward);
+        for (outer, inner) in outward
+            .pack_stats
+            .charts
+            .iter()
+            .zip(&inward.pack_stats.charts)
+        {
+            let (outer, inner) = (outer.texel_rect.unwrap(), inner.texel_rect.unwrap());
+            assert!(outer.position <= inner.position);
+            assert!(outer.position + outer.size >= inner.position + inner.size);
+            assert!(outer.position + outer.size <= Vector2::repeat(256));
+        }
+    }
+
+    #[test]
+    fn test_min_texel_density() {
+        let mesh = crate::test_meshes::uv_sphere(16, 8);
+        let generate = |resolution, max_chunk_triangles| {
+            crate::generate_uvs_with_options(
+                mesh.vertices.iter().cloned(),
+                mesh.triangles.iter().cloned(),
+                &UvGenOptions {
+                    resolution,
+                    max_chunk_triangles,
+                    ..Default::default()
+                },
+            )
+            .unwrap()
+        };
+        assert!(generate(0, 0).pack_stats.min_texel_density.is_none());
+
+        for max_chunk_triangles in [0, 64] {
+            let patch = generate(512, max_chunk_triangles);
+            let min = patch.pack_stats.min_texel_density.unwrap();
+            assert!(patch.pack_stats.charts[min.chart]
+                .triangles
+                .contains(&min.triangle));
+            // Box projection only shrinks the triangles, so every triangle of a sphere has lower
+            // density, than the scale of the atlas gives.
+            let max_density = patch.pack_stats.scale * 512.0;
+            assert!(min.texels_per_unit > 0.0 && min.texels_per_unit < max_density);
+
+            let position = |index: u32| mesh.vertices[patch.source_vertex(index).unwrap() as usize];
+            for triangle in patch.triangles.iter() {
+                let [a, b, c] = triangle.map(position);
+                let [ua, ub, uc] = triangle.map(|index| patch.second_tex_coords[index as usize]);
+                let world_area = (b - a).cross(&(c - a)).norm() * 0.5;
+                let uv_area = (ub - ua).perp(&(uc - ua)).abs() * 0.5;
+                let density = 512.0 * (uv_area / world_area).sqrt();
+                assert!(density >= min.texels_per_unit);
+                assert!(density <= max_density * 1.001);
+            }
+        }
+    }
+
+    #[test]
+    fn test_chart_keys() {
+        let mesh = crate::test_meshes::uv_sphere(16, 8);
+        let keys = |spacing: f32, max_chunk_triangles: usize| {
+            let patch = crate::generate_uvs_with_options(
+                mesh.vertices.iter().cloned(),
+                mesh.triangles.iter().cloned(),
+                &UvGenOptions {
+                    spacing,
+                    max_chunk_triangles,
+                    ..Default::default()
+                },
+            )
+            .unwrap();
+            for chart in patch.pack_stats.charts.iter() {
+                assert_eq!(Some(&chart.key), chart.triangles.iter().min());
+            }
+            let keys = patch
+                .pack_stats
+                .charts
+                .iter()
+                .map(|chart| chart.key)
+                .collect::<Vec<_>>();
+            assert!(keys.windows(2).all(|pair| pair[0] < pair[1]));
+            keys
+        };
+        // Spacing changes only the packing, so the charts stay the same.
+        assert_eq!(keys(0.005, 0), keys(0.05, 0));
+        keys(0.005, 64);
+    }
+
+    #[test]
+    fn test_stage_timings() {
+        let mesh = crate::test_meshes::uv_sphere(16, 8);
+        let generate = |options: UvGenOptions| {
+            crate::generate_uvs_with_options(
+                mesh.vertices.iter().cloned(),
+                mesh.triangles.iter().cloned(),
+                &options,
+            )
+            .unwrap()
+        };
+        assert!(generate(Default::default())
+            .pack_stats
+            .stage_timings
+            .is_empty());
+
+        let stages = [
+            Stage::BoxMapping,
+            Stage::Seams,
+            Stage::Charts,
+            Stage::Packing,
+            Stage::Assignment,
+        ];
+        for max_chunk_triangles in [0, 64] {
+            let patch = generate(UvGenOptions {
+                stage_timings: true,
+                max_chunk_triangles,
+                ..Default::default()
+            });
+            let timings = &patch.pack_stats.stage_timings;
+            assert_eq!(
+                timings
+                    .iter()
+                    .map(|timing| timing.stage)
+                    .collect::<Vec<_>>(),
+                stages
+            );
+            assert_eq!(timings[0].count, mesh.triangles.len());
+            // Vertices could also be split by the later stages.
+            assert!(timings[1].count > 0);
+            assert!(timings[1].count <= patch.additional_vertices.len());
+            assert_eq!(timings[2].count, patch.pack_stats.chart_count);
+            assert!(timings[3].count > 0);
+            assert_eq!(timings[4].count, patch.pack_stats.charts.len());
+        }
+
+        // The shelf packer places the charts in a single pass.
+        let patch = generate(UvGenOptions {
+            stage_timings: true,
+            real_time: true,
+            ..Default::default()
+        });
+        assert_eq!(patch.pack_stats.stage_timings[3].count, 0);
+    }
+}
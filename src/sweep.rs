@@ -0,0 +1,89 @@
+//! Resolution sweep - packing of the same set of meshes for multiple lightmap resolutions.
+
+use crate::{pack, Stage, UnwrapTask, UvGenOptions};
+use nalgebra::Vector3;
+
+/// Layout of UV meshes for a particular lightmap resolution. See [`resolution_sweep`] for more
+/// info.
+#[derive(Clone, Debug, PartialEq)]
+pub struct ResolutionLayout {
+    /// Resolution of the lightmap (in texels) this layout was calculated for.
+    pub resolution: u32,
+    /// Spacing (in UV units) that was used to achieve requested gutter at this resolution.
+    pub spacing: f32,
+    /// Scale that was applied to every mesh to fit it into the atlas. Larger values mean that
+    /// more texels will be used per unit of surface.
+    pub scale: f32,
+    /// Fraction of the atlas area, that is occupied by the meshes (excluding spacing). It is in
+    /// [0;1] range.
+    pub occupancy: f32,
+    /// `true` if every mesh was packed with the requested gutter, `false` - otherwise.
+    pub feasible: bool,
+}
+
+/// Builds UV meshes once and then calculates their layout for every given resolution. Every layout
+/// uses such spacing, that there will be at least `gutter` texels between any two meshes. It could
+/// be used to pick the smallest lightmap that meets the minimum gutter requirement:
+///
+/// ```rust
+/// # use nalgebra::Vector3;
+/// # let vertices = [
+/// #     Vector3::new(0.0, 0.0, 0.0),
+/// #     Vector3::new(1.0, 0.0, 0.0),
+/// #     Vector3::new(0.0, 1.0, 0.0),
+/// # ];
+/// # let triangles = [[0, 1, 2]];
+/// let layouts = uvgen::resolution_sweep(
+///     vertices.iter().cloned(),
+///     triangles.iter().cloned(),
+///     2.0,
+///     &[256, 512, 1024],
+/// )
+/// .unwrap();
+///
+/// let smallest = layouts.iter().find(|layout| layout.feasible).unwrap();
+/// assert_eq!(smallest.resolution, 256);
+/// ```
+///
+/// Returns `None` if the input data is invalid (for example, a triangle references a vertex that
+/// does not exist).
+pub fn resolution_sweep(
+    vertices: impl Iterator<Item = Vector3<f32>>,
+    triangles: impl Iterator<Item = [u32; 3]>,
+    gutter: f32,
+    resolutions: &[u32],
+) -> Option<Vec<ResolutionLayout>> {
+    let mut task = UnwrapTask::new(vertices, triangles, Default::default());
+    if !task.run_until(Stage::Packing) {
+        return None;
+    }
+    let meshes = task.charts();
+
+    Some(
+        resolutions
+            .iter()
+            .map(|&resolution| {
+                // Spacing is added to each side of a mesh, so the gutter between two meshes is
+                // twice as large as the spacing.
+                let spacing = gutter / (2.0 * resolution as f32);
+                let packing = pack::pack_meshes(
+                    meshes,
+                    &UvGenOptions {
+                        spacing,
+                        ..Default::default()
+                    },
+                );
+                let occupied_area = meshes.iter().fold(0.0, |area, mesh| {
+                    area + mesh.area() * packing.scale * packing.scale
+                });
+                ResolutionLayout {
+                    resolution,
+                    spacing,
+                    scale: packing.scale,
+                    occupancy: occupied_area,
+                    feasible: packing.is_complete(meshes),
+                }
+            })
+            .collect(),
+    )
+}
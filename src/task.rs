@@ -0,0 +1,1072 @@
+//! Resumable UV generation. See [`UnwrapTask`] docs for more info.
+
+use crate::{
+    adjacency::{self, VertexTriangles},
+    anchor, assign_sentinel_uvs, assign_uvs, box_face, chart_split, chart_uvs,
+    cylinders::{self, CylinderMapping},
+    double_sided, edge_aligned_map, face_projection,
+    frames::{self, LocalFrames},
+    has_repeated_indices, importance, isolate_triangle, locality, locks, mips, needle_ratio,
+    options,
+    pack::{self, AtlasPacker, MeshPacker, PackAttempt, Packing, PackingError},
+    padding, pages, parallel, planar_map, prepare_patch, projection_stretch, realtime,
+    reduce_seams, seams,
+    stats::{self, StageTiming},
+    tiling, triangle_normal, virtual_pages, weld, winding, Chart, CoordinateSystem, FacePlane,
+    NeedleHandling, PartialResult, RepeatedIndexHandling, SurfaceDataPatch, UvBox, UvGenError,
+    UvGenOptions,
+};
+use nalgebra::{Vector2, Vector3};
+use rectutils::Rect;
+use std::{
+    task::Poll,
+    time::{Duration, Instant},
+};
+
+/// Stage of the UV generation.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+#[cfg_attr(
+    feature = "rkyv",
+    derive(rkyv::Archive, rkyv::Serialize, rkyv::Deserialize),
+    archive(check_bytes)
+)]
+pub enum Stage {
+    /// Every triangle is mapped to a side of a box.
+    BoxMapping,
+    /// Vertices are split at the boundaries between the sides of the box.
+    Seams,
+    /// Sets of connected triangles (charts) are gathered.
+    Charts,
+    /// Charts are packed into the atlas.
+    Packing,
+    /// Final texture coordinates are assigned to the vertices.
+    Assignment,
+    /// Generation is finished.
+    Done,
+}
+
+enum State {
+    BoxMapping {
+        triangle: usize,
+    },
+    Seams,
+    Charts {
+        seed: usize,
+        // A chart that is currently growing and index of its next triangle to check.
+        chart: Option<(Chart, usize)>,
+    },
+    // Packer is `None` until the first packing attempt, charts could be edited at this point.
+    Packing(Option<MeshPacker>),
+    Assignment {
+        packing: Packing,
+        mesh: usize,
+    },
+    Done,
+}
+
+/// Charts of a task with all the data needed to calculate texture coordinates for them.
+pub(crate) struct TaskCharts {
+    /// Total amount of vertices, including the ones that were added at seams.
+    pub vertex_count: usize,
+    pub meshes: Vec<Chart>,
+    pub projections: Vec<[Vector2<f32>; 3]>,
+    /// Triangles with split vertices.
+    pub triangles: Vec<[u32; 3]>,
+    pub additional_vertices: Vec<u32>,
+    /// Pairs of vertices and their clones at the cuts of large charts.
+    pub cut_vertices: Vec<[u32; 2]>,
+    /// Timings of the stages, that were done, see [`UvGenOptions::stage_timings`].
+    pub stage_timings: Vec<StageTiming>,
+}
+
+/// Mutable access to the charts of a task, see [`UnwrapTask::charts_mut`].
+pub struct ChartsMut<'a> {
+    /// Charts of the task.
+    pub charts: &'a mut Vec<Chart>,
+    /// Projections of the triangles, indexed by triangle index.
+    pub projections: &'a mut [[Vector2<f32>; 3]],
+}
+
+/// Resumable UV generation. The task splits the generation into small chunks of work, which
+/// allows to spread the generation across multiple frames (for example, to keep an editor
+/// responsive) without a need for threads or any async runtime:
+///
+/// ```rust
+/// # use nalgebra::Vector3;
+/// # use std::{task::Poll, time::Duration};
+/// # let vertices = [
+/// #     Vector3::new(0.0, 0.0, 0.0),
+/// #     Vector3::new(1.0, 0.0, 0.0),
+/// #     Vector3::new(0.0, 1.0, 0.0),
+/// # ];
+/// # let triangles = [[0, 1, 2]];
+/// let mut task = uvgen::UnwrapTask::new(
+///     vertices.iter().cloned(),
+///     triangles.iter().cloned(),
+///     Default::default(),
+/// );
+///
+/// // Do a piece of work each frame.
+/// let patch = loop {
+///     if let Poll::Ready(patch) = task.step(Duration::from_millis(2)) {
+///         break patch;
+///     }
+/// };
+/// assert!(patch.is_some());
+/// ```
+pub struct UnwrapTask {
+    options: UvGenOptions,
+    vertices: Vec<Vector3<f32>>,
+    triangles: Vec<[u32; 3]>,
+    uv_box: UvBox,
+    patch: SurfaceDataPatch,
+    meshes: Vec<Chart>,
+    removed_triangles: Vec<bool>,
+    // Hashes of content of the charts, calculated only if stable anchors are enabled.
+    chart_hashes: Vec<u64>,
+    // Local frames of connected parts of the mesh, calculated only if local frames are enabled.
+    frames: Option<LocalFrames>,
+    // Flags of back-to-back triangles, that share texture coordinates of their twins, see
+    // `UvGenOptions::double_sided_handling`.
+    shared_twins: Vec<bool>,
+    // Flags of triangles, that must be unwrapped. Empty if every triangle must be unwrapped.
+    subset: Vec<bool>,
+    // Projections of the triangles of cylinders, calculated only if cylinder unwrap is enabled.
+    cylinders: Vec<Option<CylinderMapping>>,
+    // Pairs of vertices and their clones, that were made by splitting of large charts.
+    cut_vertices: Vec<[u32; 2]>,
+    // Triangles around every vertex, that are used to grow the charts.
+    vertex_triangles: VertexTriangles,
+    // Whether the charts were (possibly) modified by the user before packing.
+    charts_edited: bool,
+    // Amount of packing passes made to up-scale thin charts.
+    upscale_passes: usize,
+    // Total amount of attempts of the iterative packer in every packing pass.
+    packing_attempts: usize,
+    // Timings of the stages, measured only if `UvGenOptions::stage_timings` is enabled.
+    stage_timings: Vec<StageTiming>,
+    packing_error: Option<PackingError>,
+    // Stage of the chunk of work, that is being done.
+    active_stage: Stage,
+    // Stage, at which the generation has failed, if it has (and the partial result wasn't taken
+    // yet).
+    failed_stage: Option<Stage>,
+    // Reason of the failure, if the generation has failed.
+    failure: Option<UvGenError>,
+    // Buffers of the packer, that are kept between packing attempts and tasks.
+    atlas_packer: AtlasPacker,
+    spare_rects: Vec<Rect<f32>>,
+    state: State,
+}
+
+/// Buffers of a task, that could be reused by another task to avoid allocations, see
+/// [`crate::UvGenContext`].
+#[derive(Default)]
+pub(crate) struct TaskBuffers {
+    vertices: Vec<Vector3<f32>>,
+    uv_box: UvBox,
+    removed_triangles: Vec<bool>,
+    meshes: Vec<Chart>,
+    atlas_packer: AtlasPacker,
+    rects: Vec<Rect<f32>>,
+}
+
+impl UnwrapTask {
+    // Amount of triangles mapped at once at box mapping stage.
+    const BOX_MAPPING_CHUNK: usize = if cfg!(feature = "parallel") {
+        65536
+    } else {
+        1024
+    };
+
+    /// Creates new task for the given vertices and triangles. No work is done until [`Self::step`]
+    /// is called.
+    pub fn new(
+        vertices: impl Iterator<Item = Vector3<f32>>,
+        triangles: impl Iterator<Item = [u32; 3]>,
+        options: UvGenOptions,
+    ) -> Self {
+        Self::with_buffers(vertices, triangles, options, Default::default())
+    }
+
+    /// Creates new task like [`Self::new`], but uses the given buffers of some other task.
+    pub(crate) fn with_buffers(
+        vertices: impl Iterator<Item = Vector3<f32>>,
+        triangles: impl Iterator<Item = [u32; 3]>,
+        options: UvGenOptions,
+        mut buffers: TaskBuffers,
+    ) -> Self {
+        buffers.vertices.clear();
+        buffers.vertices.extend(vertices);
+        buffers.uv_box.clear();
+        buffers.removed_triangles.clear();
+        buffers.meshes.clear();
+        Self {
+            options: tiling::tiling_options(realtime::bounded_options(mips::with_mip_padding(
+                options,
+            ))),
+            vertices: buffers.vertices,
+            triangles: triangles.collect(),
+            uv_box: buffers.uv_box,
+            patch: Default::default(),
+            meshes: buffers.meshes,
+            removed_triangles: buffers.removed_triangles,
+            chart_hashes: Default::default(),
+            frames: None,
+            shared_twins: Default::default(),
+            subset: Default::default(),
+            cylinders: Default::default(),
+            cut_vertices: Default::default(),
+            vertex_triangles: Default::default(),
+            charts_edited: false,
+            upscale_passes: 0,
+            packing_attempts: 0,
+            stage_timings: Vec::new(),
+            packing_error: None,
+            active_stage: Stage::BoxMapping,
+            failed_stage: None,
+            failure: None,
+            atlas_packer: buffers.atlas_packer,
+            spare_rects: buffers.rects,
+            state: State::BoxMapping { triangle: 0 },
+        }
+    }
+
+    /// Restricts the generation to the given triangles, see [`crate::generate_uvs_for_subset`].
+    /// Must be called before the first step. Indices out of range are ignored.
+    pub(crate) fn set_subset(&mut self, subset: impl Iterator<Item = usize>) {
+        self.subset = vec![false; self.triangles.len()];
+        for triangle_index in subset {
+            if let Some(flag) = self.subset.get_mut(triangle_index) {
+                *flag = true;
+            }
+        }
+    }
+
+    /// Returns current stage of the generation.
+    pub fn stage(&self) -> Stage {
+        match self.state {
+            State::BoxMapping { .. } => Stage::BoxMapping,
+            State::Seams => Stage::Seams,
+            State::Charts { .. } => Stage::Charts,
+            State::Packing(_) => Stage::Packing,
+            State::Assignment { .. } => Stage::Assignment,
+            State::Done => Stage::Done,
+        }
+    }
+
+    /// Returns an estimated progress of the current stage in `[0; 1]` range. The progress of
+    /// box mapping, chart gathering and assignment is proportional to the amount of processed
+    /// triangles or charts, while the progress of packing is the fraction of the maximal amount
+    /// of packing attempts, so packing usually finishes early. Seams are split in a single step.
+    pub fn progress(&self) -> f32 {
+        let fraction = |done: usize, total: usize| {
+            if total == 0 {
+                1.0
+            } else {
+                (done as f32 / total as f32).min(1.0)
+            }
+        };
+        match &self.state {
+            State::BoxMapping { triangle } => fraction(*triangle, self.triangles.len()),
+            State::Seams | State::Packing(None) => 0.0,
+            State::Charts { seed, .. } => fraction(*seed, self.triangles.len()),
+            State::Packing(Some(packer)) => packer.progress(),
+            State::Assignment { mesh, .. } => fraction(*mesh, self.meshes.len()),
+            State::Done => 1.0,
+        }
+    }
+
+    /// Continues the generation for (approximately) the given amount of time. At least one chunk
+    /// of work is done on each call, so the task always makes progress even with zero budget.
+    /// Returns [`Poll::Pending`] if there's more work to do, or [`Poll::Ready`] with the result
+    /// of the generation. The result is `None` if the input data is invalid or if the generation
+    /// was already finished.
+    pub fn step(&mut self, budget: Duration) -> Poll<Option<SurfaceDataPatch>> {
+        let start = Instant::now();
+        loop {
+            if let Poll::Ready(result) = self.advance() {
+                return Poll::Ready(result);
+            }
+            if start.elapsed() >= budget {
+                return Poll::Pending;
+            }
+        }
+    }
+
+    /// Runs the generation until it is finished.
+    pub fn run(mut self) -> Option<SurfaceDataPatch> {
+        loop {
+            if let Poll::Ready(result) = self.advance() {
+                return result;
+            }
+        }
+    }
+
+    /// Runs the generation until it reaches the given stage. Returns `false` if the generation
+    /// has failed. For example, `run_until(Stage::Packing)` stops right before packing, so the
+    /// charts could be edited using [`Self::charts_mut`].
+    pub fn run_until(&mut self, stage: Stage) -> bool {
+        while self.stage() < stage {
+            if let Poll::Ready(result) = self.advance() {
+                return result.is_some();
+            }
+        }
+        true
+    }
+
+    /// Takes the data, that was calculated before the generation has failed, so it could be
+    /// inspected or visualized. Returns `None` if the generation hasn't failed or if the data was
+    /// taken already. See [`PartialResult`] for more info.
+    pub fn take_partial_result(&mut self) -> Option<PartialResult> {
+        let stage = self.failed_stage.take()?;
+        Some(PartialResult {
+            stage,
+            vertices: std::mem::take(&mut self.vertices),
+            triangles: std::mem::take(&mut self.triangles),
+            projections: std::mem::take(&mut self.uv_box.projections),
+            charts: std::mem::take(&mut self.meshes),
+        })
+    }
+
+    /// Returns indices of triangles, that were mapped to the given side of the box. Triangles are
+    /// known only after [`Stage::BoxMapping`] stage, the list is empty before that. This is
+    /// debug data, see [`crate::face_planes`] for more info.
+    pub fn face_triangles(&self, plane: FacePlane) -> &[usize] {
+        &self.uv_box.faces[plane.index()]
+    }
+
+    /// Returns indices of triangles, that do not belong to any side of the box and form their
+    /// own charts (see [`UvGenOptions::max_stretch`]). Triangles are known only after
+    /// [`Stage::BoxMapping`] stage, the list is empty before that.
+    pub fn isolated_triangles(&self) -> &[usize] {
+        &self.uv_box.isolated
+    }
+
+    /// Returns total amount of triangles of the mesh.
+    pub fn triangle_count(&self) -> usize {
+        self.triangles.len()
+    }
+
+    /// Returns the reason why the charts could not be packed. The generation fails at the packing
+    /// stage in this case (see [`Self::failure`]). If the spacing is too large for the charts,
+    /// the error tells the largest spacing that could work.
+    pub fn packing_error(&self) -> Option<PackingError> {
+        self.packing_error
+    }
+
+    /// Returns the reason why the generation has failed, or `None` if it hasn't failed (yet).
+    pub fn failure(&self) -> Option<UvGenError> {
+        self.failure
+    }
+
+    /// Returns charts of the task. Charts are valid only after [`Stage::Charts`] stage.
+    pub fn charts(&self) -> &[Chart] {
+        &self.meshes
+    }
+
+    /// Returns charts of the task along with projections of all triangles (indexed by triangle
+    /// index), so the charts could be merged, split or transformed before packing. Every triangle
+    /// must belong to exactly one chart, triangles that are not in any chart won't get texture
+    /// coordinates. Returns `None` if the charts are not built yet or if the packing has already
+    /// started, see [`Self::run_until`].
+    pub fn charts_mut(&mut self) -> Option<ChartsMut<'_>> {
+        if let State::Packing(None) = self.state {
+            self.charts_edited = true;
+            Some(ChartsMut {
+                charts: &mut self.meshes,
+                projections: &mut self.uv_box.projections,
+            })
+        } else {
+            None
+        }
+    }
+
+    /// Packs the charts of the task with the given options and returns the patch, leaving the task
+    /// intact, so the same charts could be packed again with different spacing, resolution and so
+    /// on (for example, to pick the best layout):
+    ///
+    /// ```rust
+    /// let mesh = uvgen::test_meshes::cube();
+    /// let mut task = uvgen::UnwrapTask::new(
+    ///     mesh.vertices.iter().cloned(),
+    ///     mesh.triangles.iter().cloned(),
+    ///     Default::default(),
+    /// );
+    /// assert!(task.run_until(uvgen::Stage::Packing));
+    /// for spacing in [0.005, 0.01, 0.02] {
+    ///     let options = uvgen::UvGenOptions {
+    ///         spacing,
+    ///         ..Default::default()
+    ///     };
+    ///     let patch = task.repack(options).unwrap();
+    ///     assert_eq!(patch.pack_stats.chart_count, task.charts().len());
+    /// }
+    /// ```
+    ///
+    /// Options, that affect the charts (box mapping, seams, splitting of charts), have no effect,
+    /// since the charts are built already. Returns `None` if the charts are not built yet or if
+    /// the packing has already started (see [`Self::charts_mut`]), or if the packing has failed.
+    pub fn repack(&self, options: UvGenOptions) -> Option<SurfaceDataPatch> {
+        let State::Packing(None) = self.state else {
+            return None;
+        };
+        let task = Self {
+            options: tiling::tiling_options(realtime::bounded_options(mips::with_mip_padding(
+                options,
+            ))),
+            vertices: self.vertices.clone(),
+            triangles: self.triangles.clone(),
+            uv_box: self.uv_box.clone(),
+            patch: self.patch.clone(),
+            meshes: self.meshes.clone(),
+            removed_triangles: self.removed_triangles.clone(),
+            chart_hashes: Default::default(),
+            // Frames and cylinders are used only by the box mapping.
+            frames: None,
+            shared_twins: self.shared_twins.clone(),
+            subset: self.subset.clone(),
+            cylinders: Default::default(),
+            cut_vertices: self.cut_vertices.clone(),
+            // Charts are built already.
+            vertex_triangles: Default::default(),
+            // Meshes must be sorted for the new options.
+            charts_edited: true,
+            upscale_passes: 0,
+            packing_attempts: 0,
+            stage_timings: self.stage_timings.clone(),
+            packing_error: None,
+            active_stage: Stage::Packing,
+            failed_stage: None,
+            failure: None,
+            atlas_packer: Default::default(),
+            spare_rects: Default::default(),
+            state: State::Packing(None),
+        };
+        task.run()
+    }
+
+    /// Sorts meshes in the order, that gives the best results for the packer.
+    fn sort_meshes(&mut self) {
+        pack::sort_meshes(&mut self.meshes);
+        if self.options.spatial_locality {
+            let vertices = &self.vertices;
+            locality::sort_by_locality(&mut self.meshes, &self.triangles, |index| {
+                vertices.get(index as usize).cloned()
+            });
+        }
+    }
+
+    /// Finishes the generation with the given reason of the failure.
+    fn fail(&mut self, error: UvGenError) -> Poll<Option<SurfaceDataPatch>> {
+        self.failure = Some(error);
+        self.finish(None)
+    }
+
+    /// Returns the reason of a failure of a stage, that adds vertices: either their indices
+    /// overflow or the input references vertices, that do not exist.
+    fn index_failure(&self) -> UvGenError {
+        if u32::try_from(self.vertices.len()).is_err() {
+            UvGenError::TooManyVertices
+        } else {
+            UvGenError::DegenerateInput
+        }
+    }
+
+    fn finish(&mut self, result: Option<()>) -> Poll<Option<SurfaceDataPatch>> {
+        if result.is_none() {
+            self.failed_stage = Some(self.active_stage);
+        }
+        self.state = State::Done;
+        Poll::Ready(result.map(|_| std::mem::take(&mut self.patch)))
+    }
+
+    /// Starts assignment of texture coordinates, or fails if the packer gave up.
+    fn begin_assignment(&mut self, mut packing: Packing) -> Result<State, PackingError> {
+        self.packing_attempts += packing.attempts;
+        if let Err(error) = pack::validate_packing(&packing, &self.meshes) {
+            self.packing_error = Some(error);
+            return Err(error);
+        }
+        pack::take_extra_rects(
+            &mut self.meshes,
+            &mut self.chart_hashes,
+            &mut packing,
+            self.options.spacing,
+        );
+        if pack::collapse_tiny_charts(
+            &mut self.meshes,
+            &mut self.uv_box.projections,
+            packing.scale,
+            &self.options,
+        ) {
+            self.charts_edited = true;
+            return Ok(State::Packing(None));
+        }
+        if self.upscale_passes < pack::MAX_UPSCALE_PASSES
+            && pack::upscale_thin_charts(
+                &mut self.meshes,
+                &mut self.uv_box.projections,
+                packing.scale,
+                &self.options,
+            )
+        {
+            self.upscale_passes += 1;
+            self.charts_edited = true;
+            return Ok(State::Packing(None));
+        }
+        pack::sort_by_key(&mut self.meshes, &mut self.chart_hashes, &mut packing);
+        pack::fill_atlas(&self.meshes, &mut packing, &self.options);
+        prepare_patch(&mut self.patch, self.vertices.len(), &self.options);
+        Ok(State::Assignment { packing, mesh: 0 })
+    }
+
+    /// Consumes the task and returns its buffers, so they could be reused by another task.
+    pub(crate) fn into_buffers(self) -> TaskBuffers {
+        TaskBuffers {
+            vertices: self.vertices,
+            uv_box: self.uv_box,
+            removed_triangles: self.removed_triangles,
+            meshes: self.meshes,
+            atlas_packer: self.atlas_packer,
+            rects: self.spare_rects,
+        }
+    }
+
+    /// Consumes the task and returns its charts. Must be called only after [`Stage::Charts`]
+    /// stage.
+    pub(crate) fn into_charts(self) -> TaskCharts {
+        TaskCharts {
+            vertex_count: self.vertices.len(),
+            meshes: self.meshes,
+            projections: self.uv_box.projections,
+            triangles: self.triangles,
+            additional_vertices: self.patch.additional_vertices,
+            cut_vertices: self.cut_vertices,
+            stage_timings: self.stage_timings,
+        }
+    }
+
+    // Does a single chunk of work and measures its time, if stage timings are enabled.
+    fn advance(&mut self) -> Poll<Option<SurfaceDataPatch>> {
+        let stage = self.stage();
+        trace_span!("stage", ?stage);
+        if !self.options.stage_timings || stage == Stage::Done {
+            return self.advance_stage();
+        }
+        let start = Instant::now();
+        let mut result = self.advance_stage();
+        let elapsed = start.elapsed();
+        let count = match stage {
+            Stage::BoxMapping => self.triangles.len(),
+            Stage::Seams => self.patch.additional_vertices.len(),
+            Stage::Packing => self.packing_attempts,
+            Stage::Charts | Stage::Assignment | Stage::Done => self.meshes.len(),
+        };
+        stats::record_stage(&mut self.stage_timings, stage, elapsed, count);
+        if let Poll::Ready(Some(patch)) = &mut result {
+            patch.pack_stats.stage_timings = std::mem::take(&mut self.stage_timings);
+        }
+        result
+    }
+
+    // Does a single chunk of work.
+    fn advance_stage(&mut self) -> Poll<Option<SurfaceDataPatch>> {
+        self.active_stage = self.stage();
+        if !matches!(self.state, State::Done) && options::is_cancelled(&self.options) {
+            return self.fail(UvGenError::Cancelled);
+        }
+        self.state = match std::mem::replace(&mut self.state, State::Done) {
+            State::BoxMapping { triangle: start } => {
+                let end = (start + Self::BOX_MAPPING_CHUNK).min(self.triangles.len());
+                if start == 0 {
+                    if let Some(tolerance) = self.options.weld {
+                        weld::weld(&self.vertices, &mut self.triangles, tolerance);
+                    }
+                }
+                if start == 0 {
+                    self.shared_twins =
+                        double_sided::shared_twins(&self.vertices, &self.triangles, &self.options);
+                }
+                if start == 0 && self.options.cylinder_unwrap {
+                    let Some(cylinders) =
+                        cylinders::cylinder_mappings(&self.vertices, &self.triangles)
+                    else {
+                        return self.fail(UvGenError::DegenerateInput);
+                    };
+                    self.cylinders = cylinders;
+                }
+                if start == 0 && self.options.local_frames {
+                    self.frames = frames::local_frames(
+                        &self.vertices,
+                        &self.triangles,
+                        self.options.coordinate_system,
+                    );
+                    if self.frames.is_none() {
+                        return self.fail(UvGenError::DegenerateInput);
+                    }
+                } else if start == 0
+                    && self.options.coordinate_system != CoordinateSystem::default()
+                {
+                    self.frames = Some(frames::convention_frames(
+                        self.triangles.len(),
+                        self.options.coordinate_system,
+                    ));
+                }
+                let vertices = &self.vertices;
+                let frames = self.frames.as_ref();
+                let max_stretch = self.options.max_stretch;
+                let max_needle_ratio = self.options.needle_ratio;
+                let needle_handling = self.options.needle_handling;
+                let repeated_handling = self.options.repeated_index_handling;
+                let density = self.options.density;
+                let normals = &self.options.triangle_normals;
+                let anisotropic = density != Vector3::repeat(1.0);
+                let chunk_len = parallel::chunk_len(end - start, self.options.deterministic);
+                let chunks = parallel::map_chunks(
+                    &self.triangles[start..end],
+                    chunk_len,
+                    |offset, triangles| {
+                        triangles
+                            .iter()
+                            .enumerate()
+                            .map(|(i, triangle)| {
+                                let repeated = repeated_handling != RepeatedIndexHandling::Keep
+                                    && has_repeated_indices(triangle);
+                                if repeated && repeated_handling == RepeatedIndexHandling::Fail {
+                                    return None;
+                                }
+                                let triangle_index = start + offset + i;
+                                let mut a = *vertices.get(triangle[0] as usize)?;
+                                let mut b = *vertices.get(triangle[1] as usize)?;
+                                let mut c = *vertices.get(triangle[2] as usize)?;
+                                if let Some(frames) = frames {
+                                    [a, b, c] =
+                                        [a, b, c].map(|p| frames.to_local(triangle_index, &p));
+                                }
+                                let normal =
+                                    triangle_normal(triangle_index, &[a, b, c], normals, frames);
+                                let face = box_face(&normal);
+                                let projection = face_projection(face, &a, &b, &c);
+                                let needle = max_needle_ratio.is_finite()
+                                    && needle_ratio(&a, &b, &c) > max_needle_ratio;
+                                let face = if repeated
+                                    || needle && needle_handling != NeedleHandling::Keep
+                                {
+                                    None
+                                } else {
+                                    Some(face)
+                                };
+                                if repeated {
+                                    Some((None, projection, true))
+                                } else if needle && needle_handling == NeedleHandling::Reproject {
+                                    Some((None, edge_aligned_map(&a, &b, &c), false))
+                                } else if projection_stretch(&a, &b, &c, &projection) > max_stretch
+                                {
+                                    Some((None, planar_map(&a, &b, &c), false))
+                                } else if let (Some(face), true) = (face, anisotropic) {
+                                    let [a, b, c] = [a, b, c].map(|p| p.component_mul(&density));
+                                    Some((Some(face), face_projection(face, &a, &b, &c), false))
+                                } else {
+                                    Some((face, projection, false))
+                                }
+                            })
+                            .collect::<Option<Vec<_>>>()
+                    },
+                );
+                let mut index = start;
+                for chunk in chunks {
+                    let Some(chunk) = chunk else {
+                        return self.fail(UvGenError::DegenerateInput);
+                    };
+                    for (mut face, projection, mut excluded) in chunk {
+                        if !self.subset.get(index).cloned().unwrap_or(true) {
+                            // Triangles outside of the subset keep their vertices untouched.
+                            self.uv_box.projections.push(projection);
+                            self.uv_box.excluded.push(index);
+                            index += 1;
+                            continue;
+                        }
+                        if self.shared_twins.get(index).cloned().unwrap_or_default() {
+                            face = None;
+                            excluded = true;
+                        }
+                        match face {
+                            Some(face) => self.uv_box.push(index, face, projection),
+                            None => {
+                                // Skipped triangles keep their vertices shared.
+                                if !excluded || repeated_handling != RepeatedIndexHandling::Skip {
+                                    self.uv_box.isolated.push(index);
+                                }
+                                self.uv_box.projections.push(projection);
+                            }
+                        }
+                        if excluded {
+                            self.uv_box.excluded.push(index);
+                        }
+                        index += 1;
+                    }
+                }
+
+                if end == self.triangles.len() {
+                    if self.options.seam_bias > 0.0 {
+                        reduce_seams(
+                            &self.vertices,
+                            &self.triangles,
+                            &mut self.uv_box,
+                            self.options.seam_bias,
+                            &self.options.density,
+                            &self.options.triangle_normals,
+                            self.frames.as_ref(),
+                        );
+                    }
+                    cylinders::apply(&mut self.uv_box, &self.cylinders);
+                    if locks::gather_locked_triangles(
+                        &self.vertices,
+                        &self.triangles,
+                        &mut self.uv_box,
+                        &self.options.locked_vertices,
+                        &self.options.density,
+                        &self.options.triangle_normals,
+                        self.frames.as_ref(),
+                    )
+                    .is_none()
+                    {
+                        return self.fail(UvGenError::DegenerateInput);
+                    }
+
+                    trace_event!(triangles = self.triangles.len(), "box mapped");
+
+                    State::Seams
+                } else {
+                    State::BoxMapping { triangle: end }
+                }
+            }
+            State::Seams => {
+                // Step 1. Split vertices at boundary between each face. This step multiplies the
+                // number of vertices at boundary so we'll get separate texture coordinates at
+                // seams. Splits are collected first, so the mesh is modified in a single pass.
+                let Some(splits) = seams::collect_splits(
+                    self.vertices.len(),
+                    &self.triangles,
+                    &self.uv_box.faces,
+                    self.options.deterministic,
+                ) else {
+                    return self.fail(UvGenError::DegenerateInput);
+                };
+                if seams::apply_splits(
+                    &mut self.vertices,
+                    &mut self.triangles,
+                    &splits,
+                    &mut self.patch,
+                )
+                .is_none()
+                {
+                    return self.fail(self.index_failure());
+                }
+
+                // Isolated triangles do not belong to any face, so they're split from every
+                // other triangle.
+                for &triangle_index in self.uv_box.isolated.iter() {
+                    if isolate_triangle(
+                        &mut self.vertices,
+                        &mut self.triangles[triangle_index],
+                        &mut self.patch,
+                    )
+                    .is_none()
+                    {
+                        return self.fail(self.index_failure());
+                    }
+                }
+
+                trace_event!(
+                    duplicated_vertices = self.patch.additional_vertices.len(),
+                    "seams created"
+                );
+
+                self.removed_triangles.clear();
+                self.removed_triangles.resize(self.triangles.len(), false);
+                for &triangle_index in self.uv_box.excluded.iter() {
+                    self.removed_triangles[triangle_index] = true;
+                }
+                self.vertex_triangles = VertexTriangles::new(self.vertices.len(), &self.triangles);
+                State::Charts {
+                    seed: 0,
+                    chart: None,
+                }
+            }
+            State::Charts {
+                seed,
+                chart: Some((mut mesh, i)),
+            } => {
+                if i < mesh.triangles.len() {
+                    let triangle = &self.triangles[mesh.triangles[i]];
+                    // Push all adjacent triangles into mesh. Triangles around every vertex are
+                    // added at once, so every vertex is expanded only once.
+                    let start = mesh.triangles.len();
+                    for &index in triangle {
+                        for &other_triangle_index in self.vertex_triangles.expand(index) {
+                            if !self.removed_triangles[other_triangle_index] {
+                                mesh.triangles.push(other_triangle_index);
+                                self.removed_triangles[other_triangle_index] = true;
+                            }
+                        }
+                    }
+                    // Keep the triangles in the order of their indices, so the charts do not
+                    // depend on the order of the corners.
+                    mesh.triangles[start..].sort_unstable();
+                    State::Charts {
+                        seed,
+                        chart: Some((mesh, i + 1)),
+                    }
+                } else {
+                    // Bounds are calculated for every chart at once, when all of them are built.
+                    self.meshes.push(mesh);
+                    State::Charts { seed, chart: None }
+                }
+            }
+            State::Charts { seed, chart: None } => {
+                // Step 2. Find separate "meshes" on uv map. After box mapping we will most likely
+                // end up with set of faces, some of them may form meshes and each such mesh must
+                // be moved with all faces it has.
+                if seed < self.triangles.len() {
+                    let chart = if self.removed_triangles[seed] {
+                        None
+                    } else {
+                        // Start off random triangle and continue gather adjacent triangles one
+                        // by one.
+                        self.removed_triangles[seed] = true;
+                        Some((Chart::new(seed), 0))
+                    };
+                    State::Charts {
+                        seed: seed + 1,
+                        chart,
+                    }
+                } else {
+                    // Calculate bounds of the charts.
+                    let projections = &self.uv_box.projections;
+                    let chunk_len =
+                        parallel::chunk_len(self.meshes.len(), self.options.deterministic);
+                    let bounds = parallel::map_chunks(&self.meshes, chunk_len, |_, meshes| {
+                        meshes
+                            .iter()
+                            .map(|mesh| mesh.projection_bounds(projections))
+                            .collect::<Vec<_>>()
+                    });
+                    for (mesh, (uv_min, uv_max)) in
+                        self.meshes.iter_mut().zip(bounds.into_iter().flatten())
+                    {
+                        mesh.uv_min = uv_min;
+                        mesh.uv_max = uv_max;
+                    }
+                    winding::orient_charts(&mut self.meshes, &mut self.uv_box.projections);
+                    importance::weight_charts(
+                        &mut self.meshes,
+                        &mut self.uv_box.projections,
+                        &self.options,
+                    );
+                    if let Some(max_extent) = chart_split::max_chart_extent(&self.options) {
+                        if chart_split::split_large_charts(
+                            &mut self.meshes,
+                            &mut self.vertices,
+                            &mut self.triangles,
+                            &mut self.patch.additional_vertices,
+                            &mut self.cut_vertices,
+                            &self.uv_box.projections,
+                            &self.options.locked_vertices,
+                            max_extent,
+                        )
+                        .is_none()
+                        {
+                            return self.fail(self.index_failure());
+                        }
+                    }
+
+                    trace_event!(charts = self.meshes.len(), "charts built");
+
+                    self.sort_meshes();
+                    State::Packing(None)
+                }
+            }
+            State::Packing(None) => {
+                // Step 3. Arrange and scale all meshes on uv map so it fits into [0;1] range.
+                let extra = pack::add_extra_charts(&mut self.meshes, &self.options);
+                if self.charts_edited || extra {
+                    self.sort_meshes();
+                }
+                if let Err(error) = pack::validate_spacing(self.meshes.len(), self.options.spacing)
+                {
+                    self.packing_error = Some(error);
+                    return self.fail(UvGenError::PackingFailed(error));
+                }
+                let mut packing = None;
+                if self.options.tiling_size > 0.0 {
+                    packing = Some(tiling::tiling_packing(&self.meshes, &self.options));
+                } else if self.options.page_count > 1 {
+                    let vertices = &self.vertices;
+                    packing = Some(pages::pack_pages(
+                        &mut self.meshes,
+                        &self.triangles,
+                        |index| vertices.get(index as usize).cloned(),
+                        &self.options,
+                    ));
+                } else if let Some(extent) = virtual_pages::page_extent(&self.options) {
+                    packing = Some(virtual_pages::pack_virtual_pages(
+                        &self.meshes,
+                        extent,
+                        &self.options,
+                    ));
+                }
+                if self.options.stable_anchors {
+                    let vertices = &self.vertices;
+                    let Some(hashes) = self
+                        .meshes
+                        .iter()
+                        .map(|mesh| {
+                            anchor::chart_hash(mesh, &self.triangles, |index| {
+                                vertices.get(index as usize).cloned()
+                            })
+                        })
+                        .collect::<Option<Vec<_>>>()
+                    else {
+                        return self.fail(UvGenError::DegenerateInput);
+                    };
+                    self.chart_hashes = hashes;
+
+                    if let Some(layout) = self
+                        .options
+                        .previous_layout
+                        .as_ref()
+                        .filter(|_| packing.is_none())
+                    {
+                        packing = anchor::pack_anchored(
+                            &self.meshes,
+                            &self.chart_hashes,
+                            layout,
+                            &self.options,
+                        );
+                    }
+                }
+                if packing.is_none() && self.options.real_time {
+                    packing = Some(realtime::shelf_pack(&self.meshes, self.options.spacing));
+                }
+                if packing.is_none() && self.triangles.len() < self.options.tiny_mesh_threshold {
+                    packing = pack::grid_pack(&self.meshes, self.options.spacing);
+                }
+                match packing {
+                    Some(packing) => match self.begin_assignment(packing) {
+                        Ok(state) => state,
+                        Err(error) => return self.fail(UvGenError::PackingFailed(error)),
+                    },
+                    None => State::Packing(Some(
+                        MeshPacker::new(&self.meshes, &self.options).with_buffers(
+                            std::mem::take(&mut self.atlas_packer),
+                            std::mem::take(&mut self.spare_rects),
+                        ),
+                    )),
+                }
+            }
+            State::Packing(Some(mut packer)) => {
+                if packer.try_pack(&self.meshes) == PackAttempt::Retry {
+                    State::Packing(Some(packer))
+                } else {
+                    let (packing, atlas_packer) = packer.finish_with_buffers();
+                    self.atlas_packer = atlas_packer;
+                    let packing = pack::refine(&self.meshes, packing, &self.options);
+                    match self.begin_assignment(packing) {
+                        Ok(state) => state,
+                        Err(error) => return self.fail(UvGenError::PackingFailed(error)),
+                    }
+                }
+            }
+            State::Assignment { packing, mesh } => {
+                // Step 4. Calculate final texture coordinates.
+                let (Some(uv_mesh), Some(rect)) = (self.meshes.get(mesh), packing.rects.get(mesh))
+                else {
+                    self.patch.pack_stats = packing.stats(
+                        &self.meshes,
+                        &self.triangles,
+                        &self.uv_box.projections,
+                        |index| self.vertices.get(index as usize).cloned(),
+                    );
+                    stats::flag_thin_charts(&mut self.patch.pack_stats.charts, &self.options);
+                    stats::fill_texel_rects(&mut self.patch.pack_stats.charts, &self.options);
+                    tiling::flag_wrapping_charts(
+                        &mut self.patch.pack_stats.charts,
+                        &self.meshes,
+                        &self.options,
+                    );
+                    self.patch.pack_stats.needle_triangles =
+                        stats::needle_triangles(&self.triangles, &self.options, |index| {
+                            self.vertices.get(index as usize).cloned()
+                        });
+                    (
+                        self.patch.pack_stats.seam_count,
+                        self.patch.pack_stats.seam_length,
+                    ) = stats::seam_stats(
+                        &self.vertices
+                            [..self.vertices.len() - self.patch.additional_vertices.len()],
+                        &self.patch.additional_vertices,
+                        &self.triangles,
+                    );
+                    if self.options.stable_anchors {
+                        self.patch.pack_stats.anchor_layout =
+                            Some(anchor::anchor_layout(&packing, &self.chart_hashes));
+                    }
+                    self.patch.triangle_pages =
+                        pages::triangle_pages(&self.meshes, &packing, self.triangles.len());
+                    self.patch.triangles = std::mem::take(&mut self.triangles);
+                    if self.options.triangle_projections {
+                        self.patch.triangle_projections =
+                            std::mem::take(&mut self.uv_box.projections);
+                    }
+                    assign_sentinel_uvs(&mut self.patch, &self.options);
+                    chart_uvs::fill_chart_uvs(&mut self.patch, &self.options);
+                    let source_count = self.vertices.len() - self.patch.additional_vertices.len();
+                    double_sided::handle_double_sided(
+                        &mut self.patch,
+                        &self.vertices[..source_count],
+                        &self.options,
+                    );
+                    adjacency::fill_adjacency(&mut self.patch, &self.options);
+                    padding::verify_padding(&mut self.patch, &self.options);
+                    winding::find_flipped_triangles(&mut self.patch);
+                    self.patch.pack_stats.min_texel_density =
+                        stats::min_texel_density(&self.patch, &self.options, |index| {
+                            self.vertices.get(index as usize).cloned()
+                        });
+                    locks::find_split_locked_vertices(
+                        &mut self.patch,
+                        &self.options.locked_vertices,
+                    );
+                    chart_split::fill_tile_adjacency(&mut self.patch, &self.cut_vertices);
+                    self.spare_rects = packing.rects;
+                    return self.finish(Some(()));
+                };
+
+                if assign_uvs(
+                    &mut self.patch,
+                    mesh,
+                    uv_mesh,
+                    rect,
+                    &packing,
+                    &self.triangles,
+                    &self.uv_box.projections,
+                    &self.options,
+                )
+                .is_none()
+                {
+                    return self.fail(UvGenError::DegenerateInput);
+                }
+
+                State::Assignment {
+                    packing,
+                    mesh: mesh + 1,
+                }
+            }
+            State::Done => return Poll::Ready(None),
+        };
+
+        Poll::Pending
+    }
+}
@@ -0,0 +1,304 @@
+//! Procedural meshes for tests. Every generator returns the same mesh for the same arguments, so
+//! the meshes could be used in golden tests (see [`crate::compare_golden`]).
+//!
+//! ```rust
+//! let mesh = uvgen::test_meshes::torus(1.0, 0.25, 32, 16);
+//! let patch = uvgen::generate_uvs(
+//!     mesh.vertices.iter().cloned(),
+//!     mesh.triangles.iter().cloned(),
+//!     0.005,
+//! );
+//! assert!(patch.is_some());
+//! ```
+
+use crate::rng::Rng;
+use nalgebra::Vector3;
+use std::f32::consts::{PI, TAU};
+
+/// Vertex positions and triangles of a mesh.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct TestMesh {
+    /// Positions of the vertices.
+    pub vertices: Vec<Vector3<f32>>,
+    /// Triangles of the mesh, counter-clockwise when looking from the outside.
+    pub triangles: Vec<[u32; 3]>,
+}
+
+impl TestMesh {
+    /// Adds a quad with the given corners (in counter-clockwise order) as two triangles.
+    fn quad(&mut self, corners: [Vector3<f32>; 4]) {
+        let first = self.vertices.len() as u32;
+        self.vertices.extend(corners);
+        self.triangles
+            .extend([[first, first + 1, first + 2], [first, first + 2, first + 3]]);
+    }
+}
+
+/// A cube with the side of `1.0` centered at the origin. Vertices are shared between faces.
+pub fn cube() -> TestMesh {
+    TestMesh {
+        vertices: vec![
+            Vector3::new(-0.5, -0.5, 0.5),
+            Vector3::new(-0.5, 0.5, 0.5),
+            Vector3::new(0.5, 0.5, 0.5),
+            Vector3::new(0.5, -0.5, 0.5),
+            Vector3::new(-0.5, -0.5, -0.5),
+            Vector3::new(-0.5, 0.5, -0.5),
+            Vector3::new(0.5, 0.5, -0.5),
+            Vector3::new(0.5, -0.5, -0.5),
+        ],
+        triangles: vec![
+            [2, 1, 0],
+            [3, 2, 0],
+            [4, 5, 6],
+            [4, 6, 7],
+            [7, 6, 2],
+            [2, 3, 7],
+            [0, 1, 5],
+            [0, 5, 4],
+            [5, 1, 2],
+            [5, 2, 6],
+            [3, 0, 4],
+            [7, 3, 4],
+        ],
+    }
+}
+
+/// A sphere with the radius of `1.0` centered at the origin, made of `segments` slices around the
+/// Y axis and `rings` stacks from pole to pole. Poles are single vertices. At least 3 segments and
+/// 2 rings are used.
+pub fn uv_sphere(segments: usize, rings: usize) -> TestMesh {
+    let (segments, rings) = (segments.max(3), rings.max(2));
+    let mut mesh = TestMesh::default();
+    mesh.vertices.push(Vector3::new(0.0, 1.0, 0.0));
+    for ring in 1..rings {
+        let theta = PI * ring as f32 / rings as f32;
+        for segment in 0..segments {
+            let phi = TAU * segment as f32 / segments as f32;
+            mesh.vertices.push(Vector3::new(
+                theta.sin() * phi.cos(),
+                theta.cos(),
+                theta.sin() * phi.sin(),
+            ));
+        }
+    }
+    let bottom = mesh.vertices.len() as u32;
+    mesh.vertices.push(Vector3::new(0.0, -1.0, 0.0));
+
+    let index =
+        |ring: usize, segment: usize| (1 + (ring - 1) * segments + segment % segments) as u32;
+    for segment in 0..segments {
+        mesh.triangles
+            .push([0, index(1, segment + 1), index(1, segment)]);
+        mesh.triangles.push([
+            bottom,
+            index(rings - 1, segment),
+            index(rings - 1, segment + 1),
+        ]);
+    }
+    for ring in 1..rings - 1 {
+        for segment in 0..segments {
+            let (a, b) = (index(ring, segment), index(ring, segment + 1));
+            let (c, d) = (index(ring + 1, segment + 1), index(ring + 1, segment));
+            mesh.triangles.extend([[a, b, c], [a, c, d]]);
+        }
+    }
+    mesh
+}
+
+/// A torus around the Y axis centered at the origin. At least 3 segments are used in both
+/// directions.
+pub fn torus(
+    major_radius: f32,
+    minor_radius: f32,
+    major_segments: usize,
+    minor_segments: usize,
+) -> TestMesh {
+    let (major_segments, minor_segments) = (major_segments.max(3), minor_segments.max(3));
+    let mut mesh = TestMesh::default();
+    for i in 0..major_segments {
+        let phi = TAU * i as f32 / major_segments as f32;
+        for j in 0..minor_segments {
+            let theta = TAU * j as f32 / minor_segments as f32;
+            let radius = major_radius + minor_radius * theta.cos();
+            mesh.vertices.push(Vector3::new(
+                radius * phi.cos(),
+                minor_radius * theta.sin(),
+                radius * phi.sin(),
+            ));
+        }
+    }
+    let index =
+        |i: usize, j: usize| ((i % major_segments) * minor_segments + j % minor_segments) as u32;
+    for i in 0..major_segments {
+        for j in 0..minor_segments {
+            let (a, b) = (index(i, j), index(i + 1, j));
+            let (c, d) = (index(i + 1, j + 1), index(i, j + 1));
+            mesh.triangles.extend([[a, d, c], [a, c, b]]);
+        }
+    }
+    mesh
+}
+
+/// A closed cylinder around the Y axis centered at the origin, made of `segments` slices. Caps are
+/// fans around their center vertices. The edges between the side and the caps are hard: the caps
+/// have their own copies of the vertices of the rims, as it usually is for exported meshes. At
+/// least 3 segments are used.
+pub fn cylinder(radius: f32, height: f32, segments: usize) -> TestMesh {
+    let segments = segments.max(3);
+    let mut mesh = TestMesh::default();
+    // Rims of the side and then rims of the caps.
+    for _ in 0..2 {
+        for y in [-0.5 * height, 0.5 * height] {
+            for i in 0..segments {
+                let phi = TAU * i as f32 / segments as f32;
+                mesh.vertices
+                    .push(Vector3::new(radius * phi.cos(), y, radius * phi.sin()));
+            }
+        }
+    }
+    let bottom_center = mesh.vertices.len() as u32;
+    mesh.vertices.push(Vector3::new(0.0, -0.5 * height, 0.0));
+    mesh.vertices.push(Vector3::new(0.0, 0.5 * height, 0.0));
+    let top_center = bottom_center + 1;
+    let bottom = |i: usize| (i % segments) as u32;
+    let top = |i: usize| (segments + i % segments) as u32;
+    let cap = 2 * segments as u32;
+    for i in 0..segments {
+        let (a, b, c, d) = (bottom(i), bottom(i + 1), top(i + 1), top(i));
+        mesh.triangles.extend([
+            [a, d, c],
+            [a, c, b],
+            [top_center, cap + c, cap + d],
+            [bottom_center, cap + a, cap + b],
+        ]);
+    }
+    mesh
+}
+
+/// A closed staircase with the given amount of steps. Each step is `1.0` wide, `0.2` high and
+/// `0.3` deep. Every face of the staircase has its own vertices.
+pub fn stairs(steps: usize) -> TestMesh {
+    let (height, depth) = (0.2, 0.3);
+    let mut mesh = TestMesh::default();
+    for step in 0..steps {
+        let (z0, z1) = (step as f32 * depth, (step + 1) as f32 * depth);
+        let (y0, y1) = (step as f32 * height, (step + 1) as f32 * height);
+        // Riser and tread.
+        mesh.quad([
+            Vector3::new(0.0, y0, z0),
+            Vector3::new(0.0, y1, z0),
+            Vector3::new(1.0, y1, z0),
+            Vector3::new(1.0, y0, z0),
+        ]);
+        mesh.quad([
+            Vector3::new(0.0, y1, z0),
+            Vector3::new(0.0, y1, z1),
+            Vector3::new(1.0, y1, z1),
+            Vector3::new(1.0, y1, z0),
+        ]);
+        // Side walls of the step column.
+        for x in [0.0, 1.0] {
+            let corners = [
+                Vector3::new(x, 0.0, z0),
+                Vector3::new(x, 0.0, z1),
+                Vector3::new(x, y1, z1),
+                Vector3::new(x, y1, z0),
+            ];
+            if x == 0.0 {
+                mesh.quad(corners);
+            } else {
+                mesh.quad([corners[3], corners[2], corners[1], corners[0]]);
+            }
+        }
+    }
+    if steps > 0 {
+        let (length, top) = (steps as f32 * depth, steps as f32 * height);
+        // Back wall and bottom.
+        mesh.quad([
+            Vector3::new(0.0, 0.0, length),
+            Vector3::new(1.0, 0.0, length),
+            Vector3::new(1.0, top, length),
+            Vector3::new(0.0, top, length),
+        ]);
+        mesh.quad([
+            Vector3::new(0.0, 0.0, 0.0),
+            Vector3::new(1.0, 0.0, 0.0),
+            Vector3::new(1.0, 0.0, length),
+            Vector3::new(0.0, 0.0, length),
+        ]);
+    }
+    mesh
+}
+
+/// The given amount of unconnected triangles with random vertices in the [0;1] cube. The same
+/// seed always gives the same triangles.
+pub fn random_soup(seed: u64, triangle_count: usize) -> TestMesh {
+    let mut rng = Rng::new(seed);
+    let mut next = || (rng.next_u64() >> 40) as f32 / (1u64 << 24) as f32;
+    let vertices = (0..triangle_count * 3)
+        .map(|_| Vector3::new(next(), next(), next()))
+        .collect();
+    let triangles = (0..triangle_count as u32)
+        .map(|i| [3 * i, 3 * i + 1, 3 * i + 2])
+        .collect();
+    TestMesh {
+        vertices,
+        triangles,
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::TestMesh;
+
+    /// Checks that every edge of a closed mesh is shared by exactly two triangles, going in
+    /// opposite directions, so the mesh is watertight and consistently oriented.
+    fn is_closed(mesh: &TestMesh) -> bool {
+        let mut edges = std::collections::HashMap::new();
+        for triangle in mesh.triangles.iter() {
+            for i in 0..3 {
+                *edges
+                    .entry((triangle[i], triangle[(i + 1) % 3]))
+                    .or_insert(0) += 1;
+            }
+        }
+        edges
+            .iter()
+            .all(|(&(a, b), &count)| count == 1 && edges.get(&(b, a)) == Some(&1))
+    }
+
+    #[test]
+    fn test_meshes() {
+        assert!(is_closed(&super::cube()));
+        let sphere = super::uv_sphere(8, 4);
+        assert_eq!(sphere.vertices.len(), 2 + 8 * 3);
+        assert_eq!(sphere.triangles.len(), 2 * 8 * 3);
+        assert!(is_closed(&sphere));
+        let torus = super::torus(1.0, 0.25, 8, 6);
+        assert_eq!(torus.triangles.len(), 2 * 8 * 6);
+        assert!(is_closed(&torus));
+        let cylinder = super::cylinder(0.5, 2.0, 8);
+        assert_eq!(cylinder.vertices.len(), 4 * 8 + 2);
+        assert_eq!(cylinder.triangles.len(), 4 * 8);
+        assert_eq!(super::stairs(3).triangles.len(), 2 * (4 * 3 + 2));
+        assert_eq!(super::random_soup(1, 10), super::random_soup(1, 10));
+        assert_ne!(super::random_soup(1, 10), super::random_soup(2, 10));
+
+        for mesh in [
+            super::cube(),
+            sphere,
+            torus,
+            cylinder,
+            super::stairs(3),
+            super::random_soup(0, 16),
+        ] {
+            assert!(crate::generate_uvs(
+                mesh.vertices.into_iter(),
+                mesh.triangles.into_iter(),
+                0.005
+            )
+            .is_some());
+        }
+    }
+}
@@ -0,0 +1,141 @@
+//! Tiling mode of the generator, where charts are not packed. See
+//! [`crate::UvGenOptions::tiling_size`] for more info.
+
+use crate::{pack::Packing, Chart, ChartInfo, Rect, UvGenOptions};
+
+/// Tolerance (in UV units) of the check whether a chart crosses the border of a tile, so charts,
+/// that end right at the border, are not flagged because of rounding errors.
+const EPSILON: f32 = 1.0e-5;
+
+/// Disables the options, that work with the atlas, if tiling mode is enabled. Returns the options
+/// as is otherwise.
+pub(crate) fn tiling_options(mut options: UvGenOptions) -> UvGenOptions {
+    if options.tiling_size > 0.0 {
+        options.max_chunk_triangles = 0;
+        options.stable_anchors = false;
+        options.page_count = 1;
+        options.extra_rects.clear();
+        options.resolution = 0;
+        options.texel_snap_resolution = 0;
+        options.upscale_thin_charts = false;
+        options.virtual_page_size = 0;
+        options.verify_padding = false;
+        options.fill_atlas = false;
+        options.min_chart_texels = 0.0;
+    }
+    options
+}
+
+/// Places every mesh at its own projection, scaled to one UV unit per tile, instead of packing.
+/// Rectangles of the meshes include spacing, as usual, but they could overlap and they could be
+/// anywhere outside of the atlas.
+pub(crate) fn tiling_packing(meshes: &[Chart], options: &UvGenOptions) -> Packing {
+    let scale = 1.0 / options.tiling_size;
+    let spacing = options.spacing;
+    Packing {
+        rects: meshes
+            .iter()
+            .map(|mesh| {
+                let position = mesh.uv_min.scale(scale);
+                Rect::new(
+                    position.x - spacing,
+                    position.y - spacing,
+                    mesh.width().max(0.0) * scale + 2.0 * spacing,
+                    mesh.height().max(0.0) * scale + 2.0 * spacing,
+                )
+            })
+            .collect(),
+        scale,
+        ..Default::default()
+    }
+}
+
+/// Flags the charts, which texture coordinates cross the borders of the tiles, if tiling mode is
+/// enabled. Charts must be in the same order as the meshes.
+pub(crate) fn flag_wrapping_charts(
+    charts: &mut [ChartInfo],
+    meshes: &[Chart],
+    options: &UvGenOptions,
+) {
+    if options.tiling_size <= 0.0 {
+        return;
+    }
+    let scale = 1.0 / options.tiling_size;
+    for (chart, mesh) in charts.iter_mut().zip(meshes) {
+        let (min, max) = (mesh.uv_min.scale(scale), mesh.uv_max.scale(scale));
+        chart.wrap = [0, 1].map(|axis| (min[axis] + EPSILON).floor() + 1.0 < max[axis] - EPSILON);
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::UvGenOptions;
+    use nalgebra::Vector3;
+
+    #[test]
+    fn test_tiling() {
+        // A horizontal strip three units long and one unit wide.
+        let vertices = [
+            Vector3::new(0.0, 0.0, 0.0),
+            Vector3::new(3.0, 0.0, 0.0),
+            Vector3::new(3.0, 0.0, 1.0),
+            Vector3::new(0.0, 0.0, 1.0),
+        ];
+        let triangles: [[u32; 3]; 2] = [[0, 2, 1], [0, 3, 2]];
+        let generate = |tiling_size| {
+            crate::generate_uvs_with_options(
+                vertices.iter().cloned(),
+                triangles.iter().cloned(),
+                &UvGenOptions {
+                    tiling_size,
+                    resolution: 256,
+                    extra_rects: vec![[0.5, 0.5].into()],
+                    ..Default::default()
+                },
+            )
+            .unwrap()
+        };
+
+        for (tiling_size, wrap) in [(1.0, 1), (0.5, 2), (4.0, 0)] {
+            let patch = generate(tiling_size);
+            assert_eq!(patch.pack_stats.scale, 1.0 / tiling_size);
+            assert!(patch.pack_stats.free_rects.is_empty());
+            assert!(patch.pack_stats.extra_rects.is_empty());
+            assert_eq!(patch.pack_stats.charts.len(), 1);
+            let chart = &patch.pack_stats.charts[0];
+            assert!(chart.texel_rect.is_none());
+            assert_eq!(chart.wrap.iter().filter(|&&wrap| wrap).count(), wrap);
+
+            // Distances in the atlas are distances in the world divided by the size of a tile.
+            let uv = |index: usize| patch.second_tex_coords[index];
+            let length = (uv(1) - uv(0)).norm();
+            let width = (uv(3) - uv(0)).norm();
+            assert!((length - 3.0 / tiling_size).abs() < 1.0e-5);
+            assert!((width - 1.0 / tiling_size).abs() < 1.0e-5);
+        }
+
+        // Charts, that fit into a tile, do not wrap, even if they are outside of the first one.
+        let options = UvGenOptions {
+            tiling_size: 1.0,
+            ..Default::default()
+        };
+        let mesh = crate::test_meshes::cube();
+        let patch = crate::generate_uvs_with_options(
+            mesh.vertices
+                .iter()
+                .map(|v| v + Vector3::new(10.5, 0.5, -5.5)),
+            mesh.triangles.iter().cloned(),
+            &options,
+        )
+        .unwrap();
+        assert!(patch
+            .pack_stats
+            .charts
+            .iter()
+            .all(|chart| chart.wrap == [false; 2]));
+        assert!(patch
+            .second_tex_coords
+            .iter()
+            .any(|uv| !(0.0..=1.0).contains(&uv.x) || !(0.0..=1.0).contains(&uv.y)));
+    }
+}
@@ -0,0 +1,276 @@
+//! Changes of the topology of a mesh, separated from the texture coordinates. See
+//! [`TopologyEdit`] for more info.
+
+use crate::{CornerRemap, SurfaceDataPatch};
+use std::{
+    collections::BTreeMap,
+    fmt::{Display, Formatter},
+};
+
+/// Changes of the topology of a mesh, that were made by the generator: clones of the vertices at
+/// the seams and corners of the triangles, that were moved to the clones. Unlike
+/// [`SurfaceDataPatch`], the edit does not contain any texture coordinates, so it could be applied
+/// to any per-vertex attribute (positions, normals, skin weights and so on) and to every buffer,
+/// that shares the vertices of the source mesh (for example, index buffers of LODs are patched
+/// with the remaps of their own edits, while the shared vertex buffers are patched once):
+///
+/// ```rust
+/// let mesh = uvgen::test_meshes::cube();
+/// let patch = uvgen::generate_uvs(
+///     mesh.vertices.iter().cloned(),
+///     mesh.triangles.iter().cloned(),
+///     0.005,
+/// )
+/// .unwrap();
+/// let edit = patch.topology_edit();
+///
+/// let (mut positions, mut triangles) = (mesh.vertices.clone(), mesh.triangles.clone());
+/// let mut colors = vec![[255u8; 4]; mesh.vertices.len()];
+/// assert!(edit.apply_to_vertices(&mut positions));
+/// assert!(edit.apply_to_vertices(&mut colors));
+/// assert!(edit.apply_to_triangles(&mut triangles));
+/// assert_eq!(triangles, patch.triangles);
+///
+/// // Edits could be saved as text and loaded back.
+/// let text = edit.to_string();
+/// assert_eq!(uvgen::TopologyEdit::parse(&text), Some(edit));
+/// ```
+///
+/// Welding of the vertices (see [`crate::UvGenOptions::weld`]) is not a part of the edit, since
+/// it moves the corners to other source vertices instead of clones.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct TopologyEdit {
+    /// Amount of vertices of the source mesh.
+    pub source_vertex_count: usize,
+    /// Indices of the vertices, that must be cloned and pushed to the end of the vertices one by
+    /// one, see [`SurfaceDataPatch::additional_vertices`].
+    pub additional_vertices: Vec<u32>,
+    /// Corners of the triangles, that were moved to the clones, sorted by triangle and corner.
+    pub corner_remaps: Vec<CornerRemap>,
+}
+
+impl TopologyEdit {
+    const HEADER: &'static str = "uvgen-topology 1";
+
+    /// Returns amount of vertices of the edited mesh.
+    pub fn vertex_count(&self) -> usize {
+        self.source_vertex_count + self.additional_vertices.len()
+    }
+
+    /// Pushes the clones of the vertices to the given per-vertex attribute of the source mesh.
+    /// Returns `false` and leaves the attribute intact, if it does not have exactly
+    /// [`Self::source_vertex_count`] items or if the edit references a missing vertex.
+    pub fn apply_to_vertices<T: Clone>(&self, attribute: &mut Vec<T>) -> bool {
+        if attribute.len() != self.source_vertex_count
+            || self
+                .additional_vertices
+                .iter()
+                .enumerate()
+                .any(|(i, &source)| source as usize >= self.source_vertex_count + i)
+        {
+            return false;
+        }
+        attribute.reserve(self.additional_vertices.len());
+        // Clones could be clones of earlier clones, so they're pushed one by one.
+        for &source in self.additional_vertices.iter() {
+            let value = attribute[source as usize].clone();
+            attribute.push(value);
+        }
+        true
+    }
+
+    /// Moves the corners of the given triangles of the source mesh to the clones. Returns `false`
+    /// and leaves the triangles intact, if some corner does not exist or does not reference the
+    /// expected source vertex.
+    pub fn apply_to_triangles(&self, triangles: &mut [[u32; 3]]) -> bool {
+        let valid = self.corner_remaps.iter().all(|remap| {
+            triangles
+                .get(remap.triangle)
+                .and_then(|triangle| triangle.get(remap.corner))
+                == Some(&remap.source)
+        });
+        if valid {
+            for remap in self.corner_remaps.iter() {
+                triangles[remap.triangle][remap.corner] = remap.clone;
+            }
+        }
+        valid
+    }
+
+    /// Combines this edit with the other one, that was made for the mesh edited by this edit
+    /// (for example, by [`crate::extend_patch`] or by another pass of some tool). Applying the
+    /// result to the source mesh gives the same mesh as applying both edits one after another.
+    /// Returns `None` if the other edit was made for a mesh with different amount of vertices or
+    /// if its remaps do not match this edit.
+    pub fn merge(&self, other: &TopologyEdit) -> Option<TopologyEdit> {
+        if other.source_vertex_count != self.vertex_count() {
+            return None;
+        }
+        let mut remaps = self
+            .corner_remaps
+            .iter()
+            .map(|remap| ((remap.triangle, remap.corner), *remap))
+            .collect::<BTreeMap<_, _>>();
+        for remap in other.corner_remaps.iter() {
+            match remaps.get_mut(&(remap.triangle, remap.corner)) {
+                Some(existing) if existing.clone == remap.source => existing.clone = remap.clone,
+                Some(_) => return None,
+                // The corner still references its source vertex.
+                None if (remap.source as usize) < self.source_vertex_count => {
+                    remaps.insert((remap.triangle, remap.corner), *remap);
+                }
+                None => return None,
+            }
+        }
+        Some(TopologyEdit {
+            source_vertex_count: self.source_vertex_count,
+            additional_vertices: self
+                .additional_vertices
+                .iter()
+                .chain(other.additional_vertices.iter())
+                .cloned()
+                .collect(),
+            corner_remaps: remaps.into_values().collect(),
+        })
+    }
+
+    /// Parses the edit from the text, that was produced by [`Display`] implementation. Returns
+    /// `None` if the text is malformed.
+    pub fn parse(text: &str) -> Option<Self> {
+        let mut lines = text.lines().map(str::trim).filter(|line| !line.is_empty());
+        if lines.next()? != Self::HEADER {
+            return None;
+        }
+
+        let mut source_vertex_count = None;
+        let mut edit = TopologyEdit::default();
+        for line in lines {
+            let mut tokens = line.split_whitespace();
+            match tokens.next()? {
+                "vertices" => source_vertex_count = Some(tokens.next()?.parse().ok()?),
+                "additional" => edit.additional_vertices.push(tokens.next()?.parse().ok()?),
+                "remap" => {
+                    let mut next = || tokens.next()?.parse::<usize>().ok();
+                    let (triangle, corner) = (next()?, next()?);
+                    let (source, clone) = (next()?.try_into().ok()?, next()?.try_into().ok()?);
+                    edit.corner_remaps.push(CornerRemap {
+                        triangle,
+                        corner,
+                        source,
+                        clone,
+                    });
+                }
+                _ => return None,
+            }
+        }
+        edit.source_vertex_count = source_vertex_count?;
+        Some(edit)
+    }
+}
+
+impl Display for TopologyEdit {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        writeln!(f, "{}", Self::HEADER)?;
+        writeln!(f, "vertices {}", self.source_vertex_count)?;
+        for source in self.additional_vertices.iter() {
+            writeln!(f, "additional {source}")?;
+        }
+        for remap in self.corner_remaps.iter() {
+            writeln!(
+                f,
+                "remap {} {} {} {}",
+                remap.triangle, remap.corner, remap.source, remap.clone
+            )?;
+        }
+        Ok(())
+    }
+}
+
+impl SurfaceDataPatch {
+    /// Returns the changes of the topology of the source mesh, that were made by the generator,
+    /// see [`TopologyEdit`] for more info.
+    pub fn topology_edit(&self) -> TopologyEdit {
+        TopologyEdit {
+            source_vertex_count: self.source_vertex_count(),
+            additional_vertices: self.additional_vertices.clone(),
+            corner_remaps: self.corner_remaps(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::{CornerRemap, TopologyEdit};
+
+    #[test]
+    fn test_topology_edit() {
+        let mesh = crate::test_meshes::cube();
+        let patch = crate::generate_uvs(
+            mesh.vertices.iter().cloned(),
+            mesh.triangles.iter().cloned(),
+            0.005,
+        )
+        .unwrap();
+        let edit = patch.topology_edit();
+        assert_eq!(edit.vertex_count(), patch.second_tex_coords.len());
+
+        let mut indices = (0..mesh.vertices.len() as u32).collect::<Vec<_>>();
+        assert!(edit.apply_to_vertices(&mut indices));
+        for (index, source) in indices.iter().enumerate() {
+            assert_eq!(patch.source_vertex(index as u32), Some(*source));
+        }
+        // The attribute is patched already.
+        assert!(!edit.apply_to_vertices(&mut indices));
+
+        // A corner, that does not reference the expected vertex, rejects the whole edit.
+        let remap = edit.corner_remaps[0];
+        let mut triangles = mesh.triangles.clone();
+        triangles[remap.triangle][remap.corner] = remap.clone;
+        assert!(!edit.apply_to_triangles(&mut triangles));
+        assert_eq!(
+            &triangles[remap.triangle + 1..],
+            &mesh.triangles[remap.triangle + 1..]
+        );
+
+        // The second edit clones a clone and a source vertex.
+        let (triangle, corner) = (0..mesh.triangles.len())
+            .flat_map(|triangle| (0..3).map(move |corner| (triangle, corner)))
+            .find(|&(triangle, corner)| {
+                !edit
+                    .corner_remaps
+                    .iter()
+                    .any(|remap| remap.triangle == triangle && remap.corner == corner)
+            })
+            .unwrap();
+        let source = mesh.triangles[triangle][corner];
+        let count = edit.vertex_count() as u32;
+        let second = TopologyEdit {
+            source_vertex_count: edit.vertex_count(),
+            additional_vertices: vec![remap.clone, source],
+            corner_remaps: vec![
+                CornerRemap {
+                    source: remap.clone,
+                    clone: count,
+                    ..remap
+                },
+                CornerRemap {
+                    triangle,
+                    corner,
+                    source,
+                    clone: count + 1,
+                },
+            ],
+        };
+        let merged = edit.merge(&second).unwrap();
+        let (mut sequential, mut combined) = (mesh.triangles.clone(), mesh.triangles.clone());
+        assert!(edit.apply_to_triangles(&mut sequential));
+        assert!(second.apply_to_triangles(&mut sequential));
+        assert!(merged.apply_to_triangles(&mut combined));
+        assert_eq!(sequential, combined);
+        assert_eq!(merged.vertex_count(), edit.vertex_count() + 2);
+        assert!(second.merge(&edit).is_none());
+
+        assert_eq!(TopologyEdit::parse(&merged.to_string()), Some(merged));
+        assert!(TopologyEdit::parse("uvgen-topology 1\nadditional 1").is_none());
+    }
+}
@@ -0,0 +1,104 @@
+//! Writing of the texture coordinates of a patch directly into vertex buffers. See
+//! [`SurfaceDataPatch::write_uvs_interleaved`] for more info.
+
+use crate::SurfaceDataPatch;
+use nalgebra::Vector2;
+
+/// Size of a texture coordinate in a vertex buffer: two `f32` values.
+const UV_SIZE: usize = 2 * std::mem::size_of::<f32>();
+
+impl SurfaceDataPatch {
+    /// Copies texture coordinates of every vertex of the patched mesh (see
+    /// [`Self::second_tex_coords`]) to the beginning of the given slice, which could be a part of
+    /// a mapped vertex buffer. Returns `false` and writes nothing, if the slice is too short.
+    pub fn write_uvs(&self, out: &mut [Vector2<f32>]) -> bool {
+        let Some(out) = out.get_mut(..self.second_tex_coords.len()) else {
+            return false;
+        };
+        out.copy_from_slice(&self.second_tex_coords);
+        true
+    }
+
+    /// Writes texture coordinates of every vertex of the patched mesh into a buffer with
+    /// interleaved vertex attributes, where every vertex takes `stride` bytes and its texture
+    /// coordinates are `offset` bytes from its start. Coordinates are written as two `f32` values
+    /// in native byte order, other bytes are left untouched. This allows to write the results
+    /// directly into a persistently mapped GPU buffer, without an intermediate copy:
+    ///
+    /// ```rust
+    /// let mesh = uvgen::test_meshes::cube();
+    /// let patch = uvgen::generate_uvs(
+    ///     mesh.vertices.iter().cloned(),
+    ///     mesh.triangles.iter().cloned(),
+    ///     0.005,
+    /// )
+    /// .unwrap();
+    /// // Position (12 bytes) followed by the second texture coordinates (8 bytes).
+    /// let stride = 20;
+    /// let mut buffer = vec![0u8; patch.second_tex_coords.len() * stride];
+    /// assert!(patch.write_uvs_interleaved(&mut buffer, 12, stride));
+    /// let u = f32::from_ne_bytes(buffer[stride + 12..stride + 16].try_into().unwrap());
+    /// assert_eq!(u, patch.second_tex_coords[1].x);
+    /// ```
+    ///
+    /// Returns `false` and writes nothing, if the texture coordinates do not fit into `stride`
+    /// after `offset` or if the buffer is too short.
+    pub fn write_uvs_interleaved(&self, buffer: &mut [u8], offset: usize, stride: usize) -> bool {
+        let count = self.second_tex_coords.len();
+        let required = match count.checked_sub(1) {
+            Some(last) => last
+                .checked_mul(stride)
+                .and_then(|start| start.checked_add(offset + UV_SIZE)),
+            None => Some(0),
+        };
+        if offset.saturating_add(UV_SIZE) > stride || required.map_or(true, |r| r > buffer.len()) {
+            return false;
+        }
+        for (vertex, uv) in buffer.chunks_mut(stride).zip(self.second_tex_coords.iter()) {
+            vertex[offset..offset + 4].copy_from_slice(&uv.x.to_ne_bytes());
+            vertex[offset + 4..offset + UV_SIZE].copy_from_slice(&uv.y.to_ne_bytes());
+        }
+        true
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use nalgebra::Vector2;
+
+    #[test]
+    fn test_write_uvs() {
+        let mesh = crate::test_meshes::cube();
+        let patch = crate::generate_uvs(
+            mesh.vertices.iter().cloned(),
+            mesh.triangles.iter().cloned(),
+            0.005,
+        )
+        .unwrap();
+        let count = patch.second_tex_coords.len();
+
+        let mut uvs = vec![Vector2::repeat(-1.0); count + 1];
+        assert!(!patch.write_uvs(&mut uvs[..count - 1]));
+        assert!(uvs.iter().all(|uv| *uv == Vector2::repeat(-1.0)));
+        assert!(patch.write_uvs(&mut uvs));
+        assert_eq!(uvs[..count], patch.second_tex_coords);
+        assert_eq!(uvs[count], Vector2::repeat(-1.0));
+
+        let (offset, stride) = (4, 16);
+        // The last vertex does not need the whole stride.
+        let mut buffer = vec![0xAB; (count - 1) * stride + offset + 8];
+        assert!(!patch.write_uvs_interleaved(&mut buffer, 12, stride));
+        assert!(!patch.write_uvs_interleaved(&mut buffer[1..], offset, stride));
+        assert!(buffer.iter().all(|&byte| byte == 0xAB));
+        assert!(patch.write_uvs_interleaved(&mut buffer, offset, stride));
+        for (i, uv) in patch.second_tex_coords.iter().enumerate() {
+            let vertex = &buffer[i * stride..];
+            let read = |at: usize| f32::from_ne_bytes(vertex[at..at + 4].try_into().unwrap());
+            assert_eq!(Vector2::new(read(offset), read(offset + 4)), *uv);
+            assert!(vertex[..offset].iter().all(|&byte| byte == 0xAB));
+            if i + 1 < count {
+                assert!(vertex[offset + 8..stride].iter().all(|&byte| byte == 0xAB));
+            }
+        }
+    }
+}
@@ -0,0 +1,111 @@
+//! Packing of charts for virtual-texture lightmaps. See
+//! [`crate::UvGenOptions::virtual_page_size`] for more info.
+
+use crate::{
+    pack::{self, MeshPacker, PackAttempt, Packing, PackingError},
+    Chart, Rect, UvGenOptions,
+};
+
+/// Returns the size of a virtual page in UV units, or `None` if virtual pages are disabled or
+/// the whole atlas fits into a single page.
+pub(crate) fn page_extent(options: &UvGenOptions) -> Option<f32> {
+    if options.virtual_page_size == 0 || options.virtual_page_size >= options.resolution {
+        return None;
+    }
+    Some(options.virtual_page_size as f32 / options.resolution as f32)
+}
+
+/// Returns rectangles of every virtual page of the atlas, row by row. Texels at the right and
+/// bottom edges of the atlas, that do not form a full page, are not used.
+fn page_rects(extent: f32) -> Vec<Rect<f32>> {
+    let count = (1.0 / extent) as usize;
+    (0..count)
+        .flat_map(|row| {
+            (0..count).map(move |column| {
+                Rect::new(column as f32 * extent, row as f32 * extent, extent, extent)
+            })
+        })
+        .collect()
+}
+
+/// Packs meshes, so every mesh is inside a single virtual page. Meshes fill the pages one by one,
+/// so the atlas is occupied as tightly as possible. The scale is the largest one, at which every
+/// mesh fits into a page and all of them fit into the atlas. Meshes must be sorted by
+/// [`pack::sort_meshes`] first.
+pub(crate) fn pack_virtual_pages(meshes: &[Chart], extent: f32, options: &UvGenOptions) -> Packing {
+    trace_span!("virtual_pages");
+
+    // Start from the scale of the unconstrained packing, it could only be smaller.
+    let mut packer = MeshPacker::new(meshes, options);
+    while packer.try_pack(meshes) == PackAttempt::Retry {}
+    let packing = packer.finish();
+    let mut scale = if packing.is_complete(meshes) && packing.scale.is_finite() {
+        packing.scale
+    } else {
+        1.0
+    };
+    // The largest mesh must fit into a page.
+    let max_extent = meshes.iter().fold(0.0f32, |max_extent, mesh| {
+        max_extent.max(mesh.width()).max(mesh.height())
+    });
+    if max_extent > 0.0 {
+        scale = scale.min((extent - 2.0 * options.spacing) / max_extent);
+    }
+
+    let pages = page_rects(extent);
+    let mut attempts = 0;
+    while scale > f32::EPSILON {
+        attempts += 1;
+        if let Some(found) = pack::pack_into_free_rects(meshes, &pages, scale, options.spacing) {
+            return Packing {
+                attempts: packing.attempts,
+                ..found
+            };
+        }
+        scale /= pack::scale_growth_factor(options);
+    }
+
+    Packing {
+        scale,
+        error: packing.error.or(Some(PackingError::Exhausted { attempts })),
+        attempts: packing.attempts,
+        ..Default::default()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::UvGenOptions;
+
+    #[test]
+    fn test_virtual_pages() {
+        let mesh = crate::test_meshes::stairs(4);
+        let options = UvGenOptions {
+            resolution: 512,
+            virtual_page_size: 128,
+            ..Default::default()
+        };
+        let patch = crate::generate_uvs_with_options(
+            mesh.vertices.iter().cloned(),
+            mesh.triangles.iter().cloned(),
+            &options,
+        )
+        .unwrap();
+        assert!(patch.pack_stats.packing_error.is_none());
+
+        let extent = 128.0 / 512.0;
+        let mut pages = Vec::new();
+        for chart in patch.pack_stats.charts.iter() {
+            let rect = chart.rect;
+            let page = rect.position.map(|c| (c / extent + 1.0e-4).floor());
+            let end = rect.position + rect.size;
+            assert!(end.x <= (page.x + 1.0) * extent + 1.0e-5);
+            assert!(end.y <= (page.y + 1.0) * extent + 1.0e-5);
+            if !pages.contains(&page) {
+                pages.push(page);
+            }
+        }
+        // Charts do not fit into a single page.
+        assert!(pages.len() > 1);
+    }
+}
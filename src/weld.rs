@@ -0,0 +1,184 @@
+//! Vertex welding pre-pass. See [`crate::UvGenOptions::weld`] for more info.
+
+use crate::spatial::SpatialHash;
+use nalgebra::Vector3;
+
+/// Tolerance of vertex welding. Assets of different scales need different tolerances, so the
+/// tolerance could be set either in world units or relative to the mesh.
+#[derive(Copy, Clone, Debug, PartialEq)]
+#[cfg_attr(
+    feature = "serde",
+    derive(serde::Serialize, serde::Deserialize),
+    serde(rename_all = "snake_case")
+)]
+pub enum WeldTolerance {
+    /// Vertices closer to each other than the given distance in world units are welded.
+    Absolute(f32),
+    /// Vertices closer to each other than the given fraction of the diagonal of the bounding box
+    /// of the mesh are welded. For example, `0.001` means 0.1% of the size of the mesh.
+    Relative(f32),
+    /// Vertices, which coordinates differ by at most the given amount of units in the last place
+    /// (ULPs), are welded. This tolerance scales with the magnitude of the coordinates, so it is
+    /// suitable for meshes placed far away from the origin.
+    Ulps(u32),
+}
+
+/// Maps a float to an integer, so the difference between two integers is the distance between
+/// the floats in ULPs. Both zeros are mapped to zero.
+fn ordered(value: f32) -> i64 {
+    let bits = value.to_bits() as i32;
+    if bits < 0 {
+        -((bits & i32::MAX) as i64)
+    } else {
+        bits as i64
+    }
+}
+
+/// Returns the distance between the given float and the next representable float away from zero.
+fn ulp(value: f32) -> f32 {
+    let value = value.abs();
+    if value.is_finite() {
+        f32::from_bits(value.to_bits() + 1) - value
+    } else {
+        0.0
+    }
+}
+
+/// Finds vertices, that are within the given tolerance from another vertex, and returns index
+/// of the vertex every vertex is welded to (vertices, that are not welded to anything, map to
+/// themselves). Vertices are welded greedily in the order of their indices, so every vertex is
+/// welded to the first vertex within the tolerance. Vertices with non-finite coordinates are
+/// never welded.
+///
+/// ```rust
+/// # use nalgebra::Vector3;
+/// let vertices = [
+///     Vector3::new(0.0, 0.0, 0.0),
+///     Vector3::new(1.0, 0.0, 0.0),
+///     Vector3::new(0.0, 0.0, 0.001),
+/// ];
+/// let remap = uvgen::spatial::weld_vertices(&vertices, uvgen::WeldTolerance::Absolute(0.01));
+/// assert_eq!(remap, [0, 1, 0]);
+/// ```
+pub fn weld_vertices(vertices: &[Vector3<f32>], tolerance: WeldTolerance) -> Vec<u32> {
+    trace_span!("weld");
+
+    let mut remap = (0..vertices.len() as u32).collect::<Vec<_>>();
+    let finite = || vertices.iter().filter(|v| v.iter().all(|c| c.is_finite()));
+    let (min, max) = finite().fold(
+        (Vector3::repeat(f32::MAX), Vector3::repeat(-f32::MAX)),
+        |(min, max), v| (min.inf(v), max.sup(v)),
+    );
+    if min.x > max.x {
+        return remap;
+    }
+
+    let max_ulp = ulp(min.abs().sup(&max.abs()).max());
+    let distance = match tolerance {
+        WeldTolerance::Absolute(distance) => distance,
+        WeldTolerance::Relative(fraction) => fraction * (max - min).norm(),
+        WeldTolerance::Ulps(ulps) => {
+            // Coordinates within the given amount of ULPs are never farther than this.
+            ulps as f32 * max_ulp * 3.0f32.sqrt()
+        }
+    };
+    if distance.is_nan() || distance < 0.0 {
+        return remap;
+    }
+    let within = |a: &Vector3<f32>, b: &Vector3<f32>| match tolerance {
+        WeldTolerance::Ulps(ulps) => a
+            .iter()
+            .zip(b.iter())
+            .all(|(&a, &b)| (ordered(a) - ordered(b)).unsigned_abs() <= ulps as u64),
+        _ => (a - b).norm() <= distance,
+    };
+
+    // Cells must not be smaller than the precision of the coordinates.
+    let cell_size = distance.max(max_ulp);
+    let mut hash = SpatialHash::new(cell_size);
+    for (index, vertex) in vertices.iter().enumerate() {
+        if !vertex.iter().all(|c| c.is_finite()) {
+            continue;
+        }
+        let mut target = None;
+        hash.query(vertex, cell_size, |other| {
+            if target.map_or(true, |target| other < target)
+                && within(vertex, &vertices[other as usize])
+            {
+                target = Some(other);
+            }
+        });
+        match target {
+            Some(target) => remap[index] = target,
+            None => hash.insert(index as u32, vertex),
+        }
+    }
+    remap
+}
+
+/// Replaces indices of the welded vertices (see [`weld_vertices`]) of the triangles with the
+/// indices of the vertices they're welded to. Vertices themselves are left untouched, welded
+/// vertices just become unreferenced.
+pub(crate) fn weld(
+    vertices: &[Vector3<f32>],
+    triangles: &mut [[u32; 3]],
+    tolerance: WeldTolerance,
+) {
+    let remap = weld_vertices(vertices, tolerance);
+    for triangle in triangles.iter_mut() {
+        for index in triangle.iter_mut() {
+            if let Some(&target) = remap.get(*index as usize) {
+                *index = target;
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::WeldTolerance;
+    use nalgebra::Vector3;
+
+    #[test]
+    fn test_weld() {
+        // A quad split into two triangles with duplicated vertices along the diagonal, placed
+        // far from the origin. Duplicates are one ULP (~0.0078) away from the originals.
+        let base = 100000.0f32;
+        let next = f32::from_bits(base.to_bits() + 1);
+        let vertices = [
+            Vector3::new(base, base, base),
+            Vector3::new(base + 1.0, base, base),
+            Vector3::new(base + 1.0, base + 1.0, base),
+            Vector3::new(base, base, next),
+            Vector3::new(base + 1.0, base + 1.0, next),
+            Vector3::new(base, base + 1.0, base),
+        ];
+        let triangles = [[0, 1, 2], [3, 4, 5]];
+
+        let welded = |tolerance| {
+            let mut triangles = triangles;
+            super::weld(&vertices, &mut triangles, tolerance);
+            triangles
+        };
+        assert_eq!(welded(WeldTolerance::Absolute(0.0001)), triangles);
+        assert_eq!(
+            welded(WeldTolerance::Absolute(0.01)),
+            [[0, 1, 2], [0, 2, 5]]
+        );
+        assert_eq!(
+            welded(WeldTolerance::Relative(0.01)),
+            [[0, 1, 2], [0, 2, 5]]
+        );
+        assert_eq!(welded(WeldTolerance::Relative(0.0001)), triangles);
+        assert_eq!(welded(WeldTolerance::Ulps(1)), [[0, 1, 2], [0, 2, 5]]);
+        assert_eq!(welded(WeldTolerance::Ulps(0)), triangles);
+    }
+
+    #[test]
+    fn test_ordered() {
+        assert_eq!(super::ordered(0.0), super::ordered(-0.0));
+        assert_eq!(super::ordered(f32::from_bits(1)), 1);
+        assert_eq!(super::ordered(-f32::from_bits(1)), -1);
+        assert!(super::ordered(-1.0) < super::ordered(1.0));
+    }
+}
@@ -0,0 +1,93 @@
+//! Winding of triangles in the atlas. Every triangle is expected to be counter-clockwise (to have
+//! positive signed area) in the atlas when its front side is visible, so UV-space winding gives
+//! the same geometric normals as the source mesh. See [`crate::PackStats::flipped_triangles`].
+
+use crate::{Chart, SurfaceDataPatch};
+use nalgebra::{Matrix3, Vector2};
+
+/// Returns doubled signed area of the triangle, it is positive for counter-clockwise triangles.
+fn signed_area([a, b, c]: &[Vector2<f32>; 3]) -> f32 {
+    (b - a).perp(&(c - a))
+}
+
+/// Mirrors every chart, which triangles are mostly clockwise, along the U axis. This makes the
+/// whole chart counter-clockwise, unless it is folded, and keeps it connected. Projections could
+/// be clockwise after cylindrical unwrapping of inner surfaces of pipes or with negative
+/// [`crate::UvGenOptions::density`].
+pub(crate) fn orient_charts(meshes: &mut [Chart], projections: &mut [[Vector2<f32>; 3]]) {
+    let mirror = Matrix3::new(-1.0, 0.0, 0.0, 0.0, 1.0, 0.0, 0.0, 0.0, 1.0);
+    for mesh in meshes.iter_mut() {
+        let area = mesh
+            .triangles
+            .iter()
+            .filter_map(|&triangle_index| projections.get(triangle_index))
+            .map(signed_area)
+            .sum::<f32>();
+        if area < 0.0 {
+            mesh.transform(projections, &mirror);
+        }
+    }
+}
+
+/// Fills [`crate::PackStats::flipped_triangles`] of the patch. Texture coordinates and triangles of
+/// the patch must be set already.
+pub(crate) fn find_flipped_triangles(patch: &mut SurfaceDataPatch) {
+    patch.pack_stats.flipped_triangles = patch
+        .triangles
+        .iter()
+        .enumerate()
+        .filter(|(_, triangle)| {
+            let uvs = triangle.map(|index| {
+                patch
+                    .second_tex_coords
+                    .get(index as usize)
+                    .cloned()
+                    .unwrap_or_default()
+            });
+            signed_area(&uvs) < 0.0
+        })
+        .map(|(triangle_index, _)| triangle_index)
+        .collect();
+}
+
+#[cfg(test)]
+mod test {
+    use crate::UvGenOptions;
+    use nalgebra::Vector3;
+
+    #[test]
+    fn test_winding() {
+        // Mirrored density makes every projection clockwise.
+        let mesh = crate::test_meshes::cube();
+        let patch = crate::generate_uvs_with_options(
+            mesh.vertices.iter().cloned(),
+            mesh.triangles.iter().cloned(),
+            &UvGenOptions {
+                density: Vector3::new(-1.0, 1.0, 1.0),
+                ..Default::default()
+            },
+        )
+        .unwrap();
+        assert!(patch.pack_stats.flipped_triangles.is_empty());
+        for triangle in patch.triangles.iter() {
+            let [a, b, c] = triangle.map(|index| patch.second_tex_coords[index as usize]);
+            assert!((b - a).perp(&(c - a)) > 0.0);
+        }
+
+        // A pipe, that is visible from the inside.
+        let mut pipe = crate::test_meshes::cylinder(1.0, 4.0, 16);
+        for triangle in pipe.triangles.iter_mut() {
+            triangle.swap(1, 2);
+        }
+        let patch = crate::generate_uvs_with_options(
+            pipe.vertices.iter().cloned(),
+            pipe.triangles.iter().cloned(),
+            &UvGenOptions {
+                cylinder_unwrap: true,
+                ..Default::default()
+            },
+        )
+        .unwrap();
+        assert!(patch.pack_stats.flipped_triangles.is_empty());
+    }
+}